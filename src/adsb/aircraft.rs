@@ -0,0 +1,280 @@
+// Aircraft tracking for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Collects decoded messages by aircraft address into what is known about
+// each aircraft, resolving CPR positions from pairs of messages or from a
+// nearby reference, and forgets aircraft which have not been heard lately.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use super::{Content, Message};
+use super::cpr::{self, Cpr};
+
+/// Time after which an aircraft not heard from is forgotten.
+pub const EXPIRY: Duration = Duration::from_secs(60);
+
+/// Longest time between an even and odd airborne position for a global
+/// decode.
+const AIRBORNE_PAIR: Duration = Duration::from_secs(10);
+
+/// Longest time between an even and odd surface position for a global
+/// decode; surface aircraft move slowly, so this may be longer.
+const SURFACE_PAIR: Duration = Duration::from_secs(50);
+
+/// Age beyond which a position is not trusted as a local reference.
+const REFERENCE_AGE: Duration = Duration::from_secs(60);
+
+/// Furthest a receiver is assumed to hear an aircraft (in metres), for
+/// decoding relative to the receiver and rejecting bad global decodes.
+const RANGE: f64 = 400_000.0;
+
+/// What is known about one aircraft.
+#[derive(Clone, Debug)]
+pub struct Aircraft {
+    /// 24 bit address.
+    pub icao: u32,
+    pub callsign: Option<String>,
+    /// Emitter category, as in `Content::Identification`.
+    pub category: Option<u8>,
+    pub squawk: Option<u16>,
+    /// Barometric altitude (in feet).
+    pub altitude: Option<i32>,
+    /// GNSS height (in feet).
+    pub gnss_altitude: Option<i32>,
+    /// Latitude and longitude (in degrees).
+    pub position: Option<(f64, f64)>,
+    /// Ground speed (in knots).
+    pub speed: Option<f64>,
+    /// Track over the ground, or heading if that is all that is known (in
+    /// degrees).
+    pub track: Option<f64>,
+    /// Climb rate (in feet per minute).
+    pub vertical_rate: Option<i32>,
+    pub on_ground: bool,
+    /// Number of messages received.
+    pub messages: u64,
+    /// Time of the first message.
+    pub first_seen: SystemTime,
+    /// Time of the latest message.
+    pub last_seen: SystemTime,
+    /// Time the position was last updated.
+    pub position_time: Option<SystemTime>,
+    /// Signal level of the latest message, where 1 is full scale.
+    pub signal: f64,
+    even: Option<(Cpr, SystemTime)>,
+    odd: Option<(Cpr, SystemTime)>
+}
+
+impl Aircraft {
+    fn new(icao: u32, now: SystemTime) -> Aircraft {
+        Aircraft {
+            icao, callsign: None, category: None, squawk: None,
+            altitude: None, gnss_altitude: None, position: None,
+            speed: None, track: None, vertical_rate: None,
+            on_ground: false, messages: 0, first_seen: now, last_seen: now,
+            position_time: None, signal: 0.0, even: None, odd: None
+        }
+    }
+
+    /// Time since the latest message, at `now`.
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.last_seen).unwrap_or_default()
+    }
+
+    /// Resolve a new CPR position, trying a global decode from the latest
+    /// pair of even and odd messages and falling back to a local decode
+    /// relative to the last position or the receiver.
+    fn update_position(&mut self, cpr: Cpr, surface: bool, now: SystemTime,
+                       receiver: Option<(f64, f64)>) {
+        if cpr.odd {
+            self.odd = Some((cpr, now));
+        } else {
+            self.even = Some((cpr, now));
+        }
+        let reference = match (self.position, self.position_time) {
+            (Some(p), Some(t))
+                if now.duration_since(t).unwrap_or_default() < REFERENCE_AGE
+                => Some(p),
+            _ => None
+        };
+
+        let mut position = None;
+        if let (Some((even, te)), Some((odd, to))) = (self.even, self.odd) {
+            let gap = now.duration_since(te.min(to)).unwrap_or_default();
+            let limit = if surface { SURFACE_PAIR } else { AIRBORNE_PAIR };
+            if gap <= limit {
+                position = if surface {
+                    reference.or(receiver).and_then(|(lat, lon)| {
+                        cpr::global_surface(&even, &odd, cpr.odd, lat, lon)
+                    })
+                } else {
+                    cpr::global_airborne(&even, &odd, cpr.odd)
+                };
+            }
+        }
+        // A global decode far from the receiver is from corrupt messages.
+        if let (Some((lat, lon)), Some((rlat, rlon))) = (position, receiver)
+           && cpr::distance(lat, lon, rlat, rlon) > RANGE {
+            position = None;
+        }
+
+        if position.is_none() {
+            // Airborne positions relative to the receiver are only
+            // unambiguous within half a zone, which is about the range of
+            // a receiver; surface zones are too small for that.
+            let local = match (reference, receiver) {
+                (Some(r), _) => Some(r),
+                (None, Some(r)) if !surface => Some(r),
+                _ => None
+            };
+            position = local.map(|(lat, lon)| cpr::local(&cpr, surface,
+                                                          lat, lon));
+        }
+
+        if let Some(p) = position {
+            self.position = Some(p);
+            self.position_time = Some(now);
+        }
+    }
+}
+
+/// Aircraft heard recently, by address.
+pub struct AircraftTable {
+    aircraft: HashMap<u32, Aircraft>,
+    receiver: Option<(f64, f64)>,
+    expiry: Duration,
+    messages: u64
+}
+
+impl AircraftTable {
+    pub fn new() -> AircraftTable {
+        AircraftTable {
+            aircraft: HashMap::new(), receiver: None, expiry: EXPIRY,
+            messages: 0
+        }
+    }
+
+    /// Set the receiver position (latitude and longitude in degrees), used
+    /// to decode positions from single messages and surface positions.
+    pub fn set_receiver(&mut self, lat: f64, lon: f64) {
+        self.receiver = Some((lat, lon));
+    }
+
+    pub fn receiver(&self) -> Option<(f64, f64)> {
+        self.receiver
+    }
+
+    /// Set how long an aircraft not heard from is kept.
+    pub fn set_expiry(&mut self, expiry: Duration) {
+        self.expiry = expiry;
+    }
+
+    /// Whether an address belongs to an aircraft heard recently, for
+    /// checking messages whose parity is overlaid with the address.
+    pub fn known(&self, icao: u32) -> bool {
+        self.aircraft.contains_key(&icao)
+    }
+
+    /// Add a message received at `now`, returning the updated aircraft.
+    ///
+    /// Only messages with their own parity (DF11, DF17 and DF18) add new
+    /// aircraft; others just update aircraft already known.
+    pub fn update(&mut self, msg: &Message, now: SystemTime)
+                  -> Option<&Aircraft> {
+        let receiver = self.receiver;
+        let a = match msg.df {
+            11 | 17 | 18 => self.aircraft.entry(msg.icao)
+                                .or_insert_with(|| Aircraft::new(msg.icao,
+                                                                 now)),
+            _ => self.aircraft.get_mut(&msg.icao)?
+        };
+        self.messages += 1;
+        a.messages += 1;
+        a.last_seen = now;
+        a.signal = msg.frame.signal;
+
+        match msg.content {
+            Content::AllCall { .. } | Content::Other { .. } => (),
+            Content::Altitude { altitude } => {
+                if altitude.is_some() {
+                    a.altitude = altitude;
+                }
+            },
+            Content::Squawk { squawk } => a.squawk = Some(squawk),
+            Content::Identification { category, ref callsign } => {
+                a.category = Some(category);
+                a.callsign = Some(callsign.clone());
+            },
+            Content::AirbornePosition { altitude, gnss, cpr } => {
+                if a.on_ground {
+                    a.even = None;
+                    a.odd = None;
+                }
+                a.on_ground = false;
+                if gnss {
+                    a.gnss_altitude = altitude;
+                } else {
+                    a.altitude = altitude;
+                }
+                a.update_position(cpr, false, now, receiver);
+            },
+            Content::SurfacePosition { speed, track, cpr } => {
+                // Surface and airborne pairs cannot be mixed.
+                if !a.on_ground {
+                    a.even = None;
+                    a.odd = None;
+                }
+                a.on_ground = true;
+                a.altitude = None;
+                a.speed = speed.or(a.speed);
+                a.track = track.or(a.track);
+                a.update_position(cpr, true, now, receiver);
+            },
+            Content::Velocity(ref v) => {
+                a.speed = v.ground_speed.or(v.airspeed).or(a.speed);
+                a.track = v.track.or(v.heading).or(a.track);
+                a.vertical_rate = v.vertical_rate;
+            }
+        }
+        Some(a)
+    }
+
+    /// Forget aircraft not heard from for longer than the expiry time, at
+    /// `now`. Returns the number forgotten.
+    pub fn expire(&mut self, now: SystemTime) -> usize {
+        let before = self.aircraft.len();
+        let expiry = self.expiry;
+        self.aircraft.retain(|_, a| a.age(now) <= expiry);
+        before - self.aircraft.len()
+    }
+
+    /// Number of messages accepted since the table was created.
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+
+    pub fn get(&self, icao: u32) -> Option<&Aircraft> {
+        self.aircraft.get(&icao)
+    }
+
+    /// Every aircraft, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Aircraft> {
+        self.aircraft.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.aircraft.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aircraft.is_empty()
+    }
+}
+
+impl Default for AircraftTable {
+    fn default() -> AircraftTable {
+        AircraftTable::new()
+    }
+}
@@ -0,0 +1,161 @@
+// Compact Position Reporting for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Positions are sent as 17 bit fractions of a latitude zone and of a
+// longitude zone whose width depends on latitude. Even and odd messages use
+// slightly different zone sizes, so a pair of them fixes the position
+// globally; a single message only fixes it relative to a nearby reference.
+
+/// Number of latitude zones between the equator and a pole.
+const NZ: f64 = 15.0;
+
+/// Scale of the 17 bit encoded values.
+const SCALE: f64 = 131072.0;
+
+/// One encoded position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cpr {
+    /// Odd rather than even format.
+    pub odd: bool,
+    /// Encoded latitude.
+    pub lat: u32,
+    /// Encoded longitude.
+    pub lon: u32
+}
+
+/// Number of longitude zones at latitude `lat` (in degrees).
+pub fn nl(lat: f64) -> u32 {
+    let lat = lat.abs();
+    if lat == 0.0 {
+        return 59;
+    } else if lat == 87.0 {
+        return 2;
+    } else if lat > 87.0 {
+        return 1;
+    }
+    let pi = std::f64::consts::PI;
+    let a = 1.0 - (pi / (2.0 * NZ)).cos();
+    let b = (pi / 180.0 * lat).cos().powi(2);
+    (2.0 * pi / (1.0 - a / b).acos()).floor() as u32
+}
+
+/// Positive remainder.
+fn modulo(x: f64, y: f64) -> f64 {
+    x - y * (x / y).floor()
+}
+
+/// Latitude zone size (in degrees) for a format.
+fn dlat(odd: bool, surface: bool) -> f64 {
+    let span = if surface { 90.0 } else { 360.0 };
+    span / if odd { 4.0 * NZ - 1.0 } else { 4.0 * NZ }
+}
+
+/// Latitudes (in degrees) from an even and odd pair, before wrapping.
+fn pair_lats(even: &Cpr, odd: &Cpr, surface: bool) -> (f64, f64) {
+    let (lat0, lat1) = (even.lat as f64 / SCALE, odd.lat as f64 / SCALE);
+    let j = (59.0 * lat0 - 60.0 * lat1 + 0.5).floor();
+    (dlat(false, surface) * (modulo(j, 60.0) + lat0),
+     dlat(true, surface) * (modulo(j, 59.0) + lat1))
+}
+
+/// Longitude (in degrees, from 0 to `span`) at latitude `lat`, from a pair,
+/// using the format of the newer message.
+fn pair_lon(even: &Cpr, odd: &Cpr, lat: f64, newer_odd: bool, span: f64)
+            -> f64 {
+    let (lon0, lon1) = (even.lon as f64 / SCALE, odd.lon as f64 / SCALE);
+    let nl = nl(lat) as f64;
+    let m = (lon0 * (nl - 1.0) - lon1 * nl + 0.5).floor();
+    let ni = if newer_odd { nl - 1.0 } else { nl }.max(1.0);
+    let frac = if newer_odd { lon1 } else { lon0 };
+    (span / ni) * (modulo(m, ni) + frac)
+}
+
+/// Decode an airborne position (latitude, longitude in degrees) from an
+/// even and odd pair, at the position of the newer one. None if the pair
+/// straddles a zone boundary and so can't be decoded.
+pub fn global_airborne(even: &Cpr, odd: &Cpr, newer_odd: bool)
+                       -> Option<(f64, f64)> {
+    let (mut lat0, mut lat1) = pair_lats(even, odd, false);
+    if lat0 >= 270.0 {
+        lat0 -= 360.0;
+    }
+    if lat1 >= 270.0 {
+        lat1 -= 360.0;
+    }
+    if nl(lat0) != nl(lat1) || lat0.abs() > 90.0 || lat1.abs() > 90.0 {
+        return None;
+    }
+    let lat = if newer_odd { lat1 } else { lat0 };
+    let mut lon = pair_lon(even, odd, lat, newer_odd, 360.0);
+    if lon >= 180.0 {
+        lon -= 360.0;
+    }
+    Some((lat, lon))
+}
+
+/// Decode a surface position from an even and odd pair, at the position of
+/// the newer one. Surface zones are a quarter the size of airborne ones,
+/// so the quadrant is resolved using a reference position within a few
+/// hundred miles, such as the receiver's.
+pub fn global_surface(even: &Cpr, odd: &Cpr, newer_odd: bool,
+                      ref_lat: f64, ref_lon: f64) -> Option<(f64, f64)> {
+    let (lat0, lat1) = pair_lats(even, odd, true);
+    // The pair gives a latitude from 0 to 90; it may also be 90 degrees
+    // south of that.
+    let nearest = |lat: f64| if (lat - 90.0 - ref_lat).abs() <
+                                (lat - ref_lat).abs() { lat - 90.0 }
+                             else { lat };
+    let (lat0, lat1) = (nearest(lat0), nearest(lat1));
+    if nl(lat0) != nl(lat1) {
+        return None;
+    }
+    let lat = if newer_odd { lat1 } else { lat0 };
+    let lon = pair_lon(even, odd, lat, newer_odd, 90.0);
+    // Likewise the longitude is only known modulo 90 degrees.
+    let lon = (0..4).map(|k| lon + 90.0 * k as f64 - 180.0)
+                    .min_by(|a, b| {
+                        angle_diff(*a, ref_lon).total_cmp(&angle_diff(*b,
+                                                                     ref_lon))
+                    })?;
+    Some((lat, lon))
+}
+
+/// Decode a single message (latitude, longitude in degrees) relative to a
+/// reference position within half a zone, about 180 nautical miles for
+/// airborne positions or 45 for surface.
+pub fn local(cpr: &Cpr, surface: bool, ref_lat: f64, ref_lon: f64)
+             -> (f64, f64) {
+    let (lat_f, lon_f) = (cpr.lat as f64 / SCALE, cpr.lon as f64 / SCALE);
+    let dlat = dlat(cpr.odd, surface);
+    let j = (ref_lat / dlat).floor() +
+            (0.5 + modulo(ref_lat, dlat) / dlat - lat_f).floor();
+    let lat = dlat * (j + lat_f);
+    let span = if surface { 90.0 } else { 360.0 };
+    let ni = (nl(lat) as f64 - if cpr.odd { 1.0 } else { 0.0 }).max(1.0);
+    let dlon = span / ni;
+    let m = (ref_lon / dlon).floor() +
+            (0.5 + modulo(ref_lon, dlon) / dlon - lon_f).floor();
+    let mut lon = dlon * (m + lon_f);
+    if lon >= 180.0 {
+        lon -= 360.0;
+    }
+    (lat, lon)
+}
+
+/// Absolute difference between two longitudes (in degrees), allowing for
+/// wrap around.
+fn angle_diff(a: f64, b: f64) -> f64 {
+    let d = modulo(a - b, 360.0);
+    d.min(360.0 - d)
+}
+
+/// Great circle distance (in metres) between two positions (in degrees).
+pub fn distance(lat0: f64, lon0: f64, lat1: f64, lon1: f64) -> f64 {
+    let (p0, p1) = (lat0.to_radians(), lat1.to_radians());
+    let dp = p1 - p0;
+    let dl = (lon1 - lon0).to_radians();
+    let a = (dp / 2.0).sin().powi(2) +
+            p0.cos() * p1.cos() * (dl / 2.0).sin().powi(2);
+    2.0 * 6_371_000.0 * a.sqrt().min(1.0).asin()
+}
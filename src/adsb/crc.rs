@@ -0,0 +1,98 @@
+// Mode S parity for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Every Mode S message ends in 24 parity bits. The CRC is linear, so the
+// syndrome of a damaged message is the sum of the syndromes of its bit
+// errors, and a single flipped bit can be found from the syndrome alone.
+
+/// The Mode S generator polynomial, without its x^24 term.
+const POLY: u32 = 0xfff409;
+
+/// Longest message, in bits.
+const LONG_BITS: usize = 112;
+
+/// CRC of each byte value, for bytewise computation.
+const TABLE: [u32; 256] = make_table();
+
+/// Syndrome of an error in the bit `d` places from the end of a message.
+const BIT_SYNDROMES: [u32; LONG_BITS] = make_bit_syndromes();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = (i as u32) << 16;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 0x800000 != 0 { (c << 1) ^ POLY } else { c << 1 };
+            k += 1;
+        }
+        table[i] = c & 0xffffff;
+        i += 1;
+    }
+    table
+}
+
+const fn make_bit_syndromes() -> [u32; LONG_BITS] {
+    // An error in the parity bits is its own syndrome; further back, each
+    // place multiplies by x modulo the generator.
+    let mut s = [0u32; LONG_BITS];
+    let mut d = 0;
+    while d < LONG_BITS {
+        s[d] = if d < 24 {
+            1 << d
+        } else if s[d - 1] & 0x800000 != 0 {
+            ((s[d - 1] << 1) ^ POLY) & 0xffffff
+        } else {
+            s[d - 1] << 1
+        };
+        d += 1;
+    }
+    s
+}
+
+/// The 24 bit Mode S CRC of `data`.
+pub fn crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |c, &b| {
+        ((c << 8) ^ TABLE[((c >> 16) as u8 ^ b) as usize]) & 0xffffff
+    })
+}
+
+/// The CRC of a message's data XORed with its parity bits: zero for an
+/// intact message with plain parity, or the address for address/parity
+/// messages.
+pub fn syndrome(msg: &[u8]) -> u32 {
+    let n = msg.len();
+    if n < 3 {
+        return 0;
+    }
+    let parity = (msg[n - 3] as u32) << 16 | (msg[n - 2] as u32) << 8 |
+                 msg[n - 1] as u32;
+    crc(&msg[..n - 3]) ^ parity
+}
+
+/// Find the bit whose error gives `syndrome` in a message of `bits` bits,
+/// counting from the first bit. The first five bits (the downlink format)
+/// are never blamed, so a repair can't change the message type.
+pub fn error_bit(syndrome: u32, bits: usize) -> Option<usize> {
+    if syndrome == 0 {
+        return None;
+    }
+    let bits = bits.min(LONG_BITS);
+    BIT_SYNDROMES[..bits - 5].iter().position(|&s| s == syndrome)
+                             .map(|d| bits - 1 - d)
+}
+
+/// Flip bit `bit` of `msg`, counting from the first bit.
+pub fn flip_bit(msg: &mut [u8], bit: usize) {
+    msg[bit / 8] ^= 0x80 >> (bit % 8);
+}
+
+/// Repair a single bit error in a message whose syndrome should be zero,
+/// returning the bit fixed.
+pub fn fix_single_bit(msg: &mut [u8]) -> Option<usize> {
+    let bit = error_bit(syndrome(msg), msg.len() * 8)?;
+    flip_bit(msg, bit);
+    Some(bit)
+}
@@ -0,0 +1,225 @@
+// Mode S demodulation for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Mode S replies are pulse position modulated at 1Mbit/s: each bit is a
+// 0.5us pulse in the first or second half of its microsecond. At 2MS/s
+// there is one sample per half bit, so each bit is sliced by comparing its
+// two samples. Messages start with an 8us preamble of pulses at 0, 1, 3.5
+// and 4.5us.
+
+use crate::samples::IqPairs;
+use super::crc;
+
+/// Sample rate the demodulator expects (in Hz).
+pub const SAMPLE_RATE: u32 = 2_000_000;
+
+/// Samples in the preamble.
+const PREAMBLE: usize = 16;
+
+/// Bits in a long message.
+pub const LONG_BITS: usize = 112;
+
+/// Bits in a short message.
+pub const SHORT_BITS: usize = 56;
+
+/// Samples in the preamble and the longest message.
+const FRAME: usize = PREAMBLE + 2 * LONG_BITS;
+
+/// Magnitude corresponding to full scale.
+const FULL_SCALE: f64 = 127.5 * 360.0;
+
+/// Bits which may be ambiguous (equal halves) before a frame is rejected.
+const MAX_WEAK_BITS: usize = 10;
+
+/// A received message, before decoding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The message, 7 or 14 bytes.
+    pub data: Vec<u8>,
+    /// Start of the message, in ticks of a 12MHz clock counted from the
+    /// first sample processed, as used by Beast receivers for
+    /// multilateration.
+    pub timestamp: u64,
+    /// Mean power of the preamble pulses, where 1 is full scale.
+    pub signal: f64,
+    /// Bit repaired using the parity, if any.
+    pub corrected: Option<usize>
+}
+
+impl Frame {
+    /// Downlink format, from the first five bits.
+    pub fn df(&self) -> u8 {
+        self.data[0] >> 3
+    }
+
+    /// Message length in bits.
+    pub fn bits(&self) -> usize {
+        self.data.len() * 8
+    }
+}
+
+/// Message length in bits for a downlink format.
+pub fn message_bits(df: u8) -> usize {
+    if df & 0x10 != 0 { LONG_BITS } else { SHORT_BITS }
+}
+
+/// Finds and slices Mode S messages in 2MS/s I/Q, keeping state between
+/// blocks.
+///
+/// Messages whose parity can be checked directly (DF11, DF17 and DF18) are
+/// only passed on if it is correct, after repairing single bit errors.
+/// Other formats overlay the parity with the aircraft address, so they are
+/// passed on for the decoder to check against known aircraft.
+pub struct Demodulator {
+    /// |I + jQ| scaled by 360, indexed by I << 8 | Q.
+    magnitude: Vec<u16>,
+    /// Samples not yet searched, which may start a message.
+    pending: Vec<u16>,
+    /// Index of the first pending sample since the start.
+    sample: u64,
+    iq: IqPairs,
+    fix_errors: bool
+}
+
+impl Demodulator {
+    pub fn new() -> Demodulator {
+        let magnitude = (0..65536u32).map(|x| {
+            let i = (x >> 8) as f64 - 127.5;
+            let q = (x & 0xff) as f64 - 127.5;
+            ((i * i + q * q).sqrt() * 360.0).round() as u16
+        }).collect();
+        Demodulator { magnitude, pending: Vec::new(), sample: 0,
+                      iq: IqPairs::new(), fix_errors: true }
+    }
+
+    /// Enable or disable single bit error correction. Default enabled.
+    pub fn set_fix_errors(&mut self, enabled: bool) {
+        self.fix_errors = enabled;
+    }
+
+    /// Forget any partial message and restart the timestamp clock.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.sample = 0;
+        self.iq.reset();
+    }
+
+    /// Process interleaved u8 I/Q, returning the messages found.
+    pub fn process_u8(&mut self, buf: &[u8]) -> Vec<Frame> {
+        for (i, q) in self.iq.pairs(buf) {
+            let m = self.mag(i, q);
+            self.pending.push(m);
+        }
+        self.search()
+    }
+
+    fn mag(&self, i: u8, q: u8) -> u16 {
+        self.magnitude[(i as usize) << 8 | q as usize]
+    }
+
+    /// Search the pending samples, keeping any too close to the end to
+    /// hold a whole message.
+    fn search(&mut self) -> Vec<Frame> {
+        let m = std::mem::take(&mut self.pending);
+        let mut frames = Vec::new();
+        let mut j = 0;
+        while j + FRAME <= m.len() {
+            match self.try_frame(&m[j..j + FRAME]) {
+                Some(mut frame) => {
+                    frame.timestamp = (self.sample + j as u64) * 6;
+                    let checked = frame.df() == 11 || frame.df() == 17 ||
+                                  frame.df() == 18;
+                    let skip = if checked {
+                        PREAMBLE + 2 * frame.bits()
+                    } else {
+                        1
+                    };
+                    frames.push(frame);
+                    j += skip;
+                },
+                None => j += 1
+            }
+        }
+        self.sample += j as u64;
+        self.pending = m[j..].to_vec();
+        frames
+    }
+
+    /// Check for a preamble at the start of `m` and slice the message after
+    /// it.
+    fn try_frame(&self, m: &[u16]) -> Option<Frame> {
+        // Pulses at samples 0, 2, 7 and 9, with gaps between.
+        if !(m[0] > m[1] && m[1] < m[2] && m[2] > m[3] && m[3] < m[0] &&
+             m[4] < m[0] && m[5] < m[0] && m[6] < m[0] && m[7] > m[8] &&
+             m[8] < m[9] && m[9] > m[6]) {
+            return None;
+        }
+        let high = (m[0] as u32 + m[2] as u32 + m[7] as u32 + m[9] as u32)
+                   / 6;
+        if m[4] as u32 >= high || m[5] as u32 >= high {
+            return None;
+        }
+        if m[11..PREAMBLE].iter().any(|&x| x as u32 >= high) {
+            return None;
+        }
+
+        let mut data = [0u8; LONG_BITS / 8];
+        let mut weak = 0;
+        let mut bits = LONG_BITS;
+        let mut last = false;
+        for b in 0..LONG_BITS {
+            if b == bits {
+                break;
+            }
+            let (a, c) = (m[PREAMBLE + 2 * b], m[PREAMBLE + 2 * b + 1]);
+            let bit = if a == c {
+                weak += 1;
+                last
+            } else {
+                a > c
+            };
+            if bit {
+                data[b / 8] |= 0x80 >> (b % 8);
+            }
+            last = bit;
+            if b == 4 {
+                bits = message_bits(data[0] >> 3);
+            }
+        }
+        if weak > MAX_WEAK_BITS {
+            return None;
+        }
+
+        let mut data = data[..bits / 8].to_vec();
+        let df = data[0] >> 3;
+        let corrected = match df {
+            17 | 18 | 11 => {
+                let syndrome = crc::syndrome(&data);
+                // DF11 replies to interrogators with an identifier, which
+                // is overlaid on the low bits of the parity.
+                let valid = syndrome == 0 ||
+                            (df == 11 && syndrome & !0x7f == 0);
+                if valid {
+                    None
+                } else if self.fix_errors {
+                    Some(crc::fix_single_bit(&mut data)?)
+                } else {
+                    return None;
+                }
+            },
+            0 | 4 | 5 | 16 | 20 | 21 => None,
+            _ => return None
+        };
+
+        let pulses = (m[0] as f64 + m[2] as f64 + m[7] as f64 +
+                      m[9] as f64) / (4.0 * FULL_SCALE);
+        Some(Frame { data, timestamp: 0, signal: pulses * pulses, corrected })
+    }
+}
+
+impl Default for Demodulator {
+    fn default() -> Demodulator {
+        Demodulator::new()
+    }
+}
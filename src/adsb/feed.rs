@@ -0,0 +1,314 @@
+// Mode S network feeds for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Publishes frames and aircraft in the formats dump1090 offers, so existing
+// tools can consume them: Beast binary frames, SBS-1 BaseStation text
+// lines, and an aircraft.json snapshot served over HTTP.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::RTLSDRError;
+use crate::json::Value;
+use crate::timestamp::UtcTime;
+use super::aircraft::{Aircraft, AircraftTable};
+use super::demod::Frame;
+use super::{Content, Message};
+
+/// Port dump1090 serves Beast output on.
+pub const BEAST_PORT: u16 = 30005;
+
+/// Port dump1090 serves SBS-1 BaseStation output on.
+pub const SBS_PORT: u16 = 30003;
+
+/// Port aircraft.json is served on by default.
+pub const JSON_PORT: u16 = 8080;
+
+/// Escape byte which starts each Beast frame.
+const ESCAPE: u8 = 0x1a;
+
+/// Messages queued for a client before new ones are dropped.
+const MAX_QUEUED: usize = 1024;
+
+/// Encode a frame in Beast binary format: an escape byte, a type byte ('2'
+/// for short or '3' for long messages), a 48 bit 12MHz timestamp, a signal
+/// level byte and the message, with any escape byte after the first
+/// doubled.
+pub fn beast(frame: &Frame) -> Vec<u8> {
+    let kind = if frame.data.len() == 7 { b'2' } else { b'3' };
+    let level = (frame.signal.max(0.0).sqrt() * 255.0).round().min(255.0);
+    let mut body = Vec::with_capacity(21);
+    body.extend_from_slice(&frame.timestamp.to_be_bytes()[2..]);
+    body.push(level as u8);
+    body.extend_from_slice(&frame.data);
+
+    let mut out = Vec::with_capacity(2 + 2 * body.len());
+    out.push(ESCAPE);
+    out.push(kind);
+    for &b in &body {
+        if b == ESCAPE {
+            out.push(ESCAPE);
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// Format a message as an SBS-1 BaseStation line (ending in CRLF), filling
+/// in position and other fields from `aircraft` where the message alone
+/// doesn't give them. Returns None for messages BaseStation has no type
+/// for.
+pub fn sbs(msg: &Message, aircraft: Option<&Aircraft>, time: SystemTime)
+           -> Option<String> {
+    let text = |v: Option<i32>| v.map_or(String::new(), |v| v.to_string());
+    let round = |v: Option<f64>| {
+        v.map_or(String::new(), |v| format!("{:.0}", v))
+    };
+    let flag = |b: bool| if b { "-1" } else { "0" }.to_string();
+
+    // Fields are callsign, altitude, ground speed, track, latitude,
+    // longitude, vertical rate, squawk, alert, emergency, SPI and on
+    // ground, each left empty when the message doesn't give it.
+    let mut f: [String; 12] = Default::default();
+    let position = |f: &mut [String; 12]| {
+        if let Some((lat, lon)) = aircraft.and_then(|a| a.position) {
+            f[4] = format!("{:.5}", lat);
+            f[5] = format!("{:.5}", lon);
+        }
+    };
+    let kind = match msg.content {
+        Content::Identification { ref callsign, .. } => {
+            f[0] = callsign.clone();
+            1
+        },
+        Content::SurfacePosition { speed, track, .. } => {
+            f[2] = round(speed);
+            f[3] = round(track);
+            position(&mut f);
+            f[11] = flag(true);
+            2
+        },
+        Content::AirbornePosition { altitude, .. } => {
+            f[1] = text(altitude);
+            position(&mut f);
+            f[11] = flag(false);
+            3
+        },
+        Content::Velocity(ref v) => {
+            f[2] = round(v.ground_speed.or(v.airspeed));
+            f[3] = round(v.track.or(v.heading));
+            f[6] = text(v.vertical_rate);
+            4
+        },
+        Content::Altitude { altitude } => {
+            f[1] = text(altitude);
+            // Surveillance replies, or else air to air.
+            if msg.df == 4 || msg.df == 20 { 5 } else { 7 }
+        },
+        Content::Squawk { squawk } => {
+            f[1] = text(aircraft.and_then(|a| a.altitude));
+            f[7] = format!("{:04}", squawk);
+            f[8] = flag(false);
+            f[9] = flag(matches!(squawk, 7500 | 7600 | 7700));
+            f[10] = flag(false);
+            6
+        },
+        Content::AllCall { .. } => 8,
+        Content::Other { .. } => return None
+    };
+
+    let t = UtcTime::from_system(time);
+    let stamp = format!("{:04}/{:02}/{:02},{}.{:03}", t.year, t.month, t.day,
+                        t.time(), t.millis);
+    Some(format!("MSG,{},1,1,{:06X},1,{},{},{}\r\n", kind, msg.icao, stamp,
+                 stamp, f.join(",")))
+}
+
+/// Describe every aircraft in `table` at `now` as dump1090's aircraft.json.
+pub fn aircraft_json(table: &AircraftTable, now: SystemTime) -> Value {
+    let seconds = |t: SystemTime| {
+        now.duration_since(t).unwrap_or_default().as_secs_f64()
+    };
+    let mut list: Vec<&Aircraft> = table.iter().collect();
+    list.sort_by_key(|a| a.icao);
+    let aircraft: Vec<Value> = list.into_iter().map(|a| {
+        let mut v = Value::object().with("hex", format!("{:06x}", a.icao));
+        if let Some(ref callsign) = a.callsign {
+            v.set("flight", callsign.as_str());
+        }
+        if a.on_ground {
+            v.set("alt_baro", "ground");
+        } else if let Some(altitude) = a.altitude {
+            v.set("alt_baro", altitude);
+        }
+        // Fields which aren't known are left out, as dump1090 does.
+        if let Some(altitude) = a.gnss_altitude {
+            v.set("alt_geom", altitude);
+        }
+        if let Some(speed) = a.speed {
+            v.set("gs", speed);
+        }
+        if let Some(track) = a.track {
+            v.set("track", track);
+        }
+        if let Some(rate) = a.vertical_rate {
+            v.set("baro_rate", rate);
+        }
+        if let Some(squawk) = a.squawk {
+            v.set("squawk", format!("{:04}", squawk));
+        }
+        if let Some(c) = a.category {
+            v.set("category", format!("{}{}", (b'A' + (c >> 3)) as char,
+                                      c & 7));
+        }
+        if let (Some((lat, lon)), Some(t)) = (a.position, a.position_time) {
+            v.set("lat", lat);
+            v.set("lon", lon);
+            v.set("seen_pos", seconds(t));
+        }
+        v.with("messages", a.messages)
+         .with("seen", seconds(a.last_seen))
+         .with("rssi", 10.0 * a.signal.max(1e-10).log10())
+    }).collect();
+    let epoch = now.duration_since(SystemTime::UNIX_EPOCH)
+                   .unwrap_or_default().as_secs_f64();
+    Value::object().with("now", epoch)
+                   .with("messages", table.messages())
+                   .with("aircraft", aircraft)
+}
+
+/// Queues of messages for each connected client.
+type Clients = Arc<Mutex<Vec<SyncSender<Arc<[u8]>>>>>;
+
+/// Streams data to every connected TCP client, as for the Beast and SBS
+/// outputs.
+///
+/// Clients are accepted on a background thread, and each has a writer
+/// thread and a bounded queue, so a slow client never stalls the receiver;
+/// its messages are dropped instead while its queue is full.
+pub struct FeedServer {
+    addr: SocketAddr,
+    clients: Clients
+}
+
+impl FeedServer {
+    /// Listen on `addr`.
+    pub fn bind<A: ToSocketAddrs>(addr: A)
+                                  -> Result<FeedServer, RTLSDRError> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = clients.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (tx, rx) = mpsc::sync_channel(MAX_QUEUED);
+                std::thread::spawn(move || write_messages(stream, rx));
+                accepted.lock().unwrap().push(tx);
+            }
+        });
+        Ok(FeedServer { addr, clients })
+    }
+
+    /// Address being listened on, useful after binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Number of clients connected.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// Queue `data` for every client, forgetting clients which have gone.
+    pub fn send(&self, data: &[u8]) {
+        let data: Arc<[u8]> = Arc::from(data);
+        self.clients.lock().unwrap().retain(|tx| {
+            match tx.try_send(data.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false
+            }
+        });
+    }
+}
+
+/// Send queued messages until the client goes away.
+fn write_messages(mut stream: TcpStream, rx: mpsc::Receiver<Arc<[u8]>>) {
+    let _ = stream.set_nodelay(true);
+    while let Ok(data) = rx.recv() {
+        if stream.write_all(&data).is_err() {
+            break;
+        }
+    }
+}
+
+/// Serves the latest snapshot of a JSON document, such as aircraft.json, to
+/// HTTP clients.
+pub struct JsonServer {
+    addr: SocketAddr,
+    snapshot: Arc<Mutex<String>>
+}
+
+impl JsonServer {
+    /// Listen on `addr`, serving `path` (such as "/data/aircraft.json").
+    /// The root path is served too.
+    pub fn bind<A: ToSocketAddrs>(addr: A, path: &str)
+                                  -> Result<JsonServer, RTLSDRError> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let snapshot = Arc::new(Mutex::new("{}".to_string()));
+        let shared = snapshot.clone();
+        let path: Arc<str> = Arc::from(path);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // Each request on its own thread, so an idle client can't
+                // hold up the others.
+                let (path, shared) = (path.clone(), shared.clone());
+                std::thread::spawn(move || respond(stream, &path, &shared));
+            }
+        });
+        Ok(JsonServer { addr, snapshot })
+    }
+
+    /// Address being listened on, useful after binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Replace the document served.
+    pub fn set(&self, value: &Value) {
+        *self.snapshot.lock().unwrap() = value.to_compact();
+    }
+}
+
+/// Answer one HTTP request, ignoring everything but the path.
+fn respond(mut stream: TcpStream, path: &str, snapshot: &Mutex<String>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf) {
+            Ok(n) if n > 0 && request.len() < 16_384 =>
+                request.extend_from_slice(&buf[..n]),
+            _ => return
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let target = request.split_whitespace().nth(1).unwrap_or("");
+    let target = target.split('?').next().unwrap_or("");
+    let (status, body) = if target == path || target == "/" {
+        ("200 OK", snapshot.lock().unwrap().clone())
+    } else {
+        ("404 Not Found", "{}".to_string())
+    };
+    let header = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\n\
+                          Content-Length: {}\r\n\
+                          Access-Control-Allow-Origin: *\r\n\
+                          Cache-Control: no-cache\r\n\
+                          Connection: close\r\n\r\n", status, body.len());
+    let _ = stream.write_all(header.as_bytes());
+    let _ = stream.write_all(body.as_bytes());
+}
@@ -0,0 +1,298 @@
+// ADS-B and Mode S for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Aircraft transponders reply on 1090MHz. Extended squitters (DF17, and
+// DF18 from non-transponder devices) broadcast identity, position and
+// velocity unprompted; other replies carry altitude or squawk with the
+// parity overlaid by the aircraft address. Sample at 2MS/s and feed the
+// Demodulator, then decode its frames and keep an AircraftTable.
+
+pub mod aircraft;
+pub mod cpr;
+pub mod crc;
+pub mod demod;
+pub mod feed;
+
+use crate::bitfield::bits;
+use self::cpr::Cpr;
+use self::demod::Frame;
+
+/// Frequency of Mode S replies (in Hz).
+pub const FREQUENCY: u64 = 1_090_000_000;
+
+/// Characters of the 6 bit identification alphabet; '#' is unused.
+const CHARSET: &[u8; 64] =
+    b"#ABCDEFGHIJKLMNOPQRSTUVWXYZ##### ###############0123456789######";
+
+/// Velocity from an airborne velocity message.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Velocity {
+    /// Speed over the ground (in knots).
+    pub ground_speed: Option<f64>,
+    /// Track over the ground (in degrees from true north).
+    pub track: Option<f64>,
+    /// Airspeed (in knots).
+    pub airspeed: Option<f64>,
+    /// True rather than indicated airspeed.
+    pub true_airspeed: bool,
+    /// Heading (in degrees from magnetic north).
+    pub heading: Option<f64>,
+    /// Climb rate (in feet per minute).
+    pub vertical_rate: Option<i32>,
+    /// The climb rate is from GNSS rather than barometric altitude.
+    pub gnss_rate: bool
+}
+
+/// What a message says.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content {
+    /// All call reply (DF11).
+    AllCall { capability: u8 },
+    /// Surveillance or Comm-B altitude reply (DF0, DF4, DF16, DF20), with
+    /// barometric altitude (in feet).
+    Altitude { altitude: Option<i32> },
+    /// Surveillance or Comm-B identity reply (DF5, DF21).
+    Squawk { squawk: u16 },
+    /// Aircraft identification (ES type codes 1 to 4), with the emitter
+    /// category as its set (0 for A to 3 for D) times 8 plus the number
+    /// within the set, so A3 is 3 and B2 is 10.
+    Identification { category: u8, callsign: String },
+    /// Airborne position (ES type codes 9 to 18 and 20 to 22), with
+    /// altitude (in feet) that is barometric, or GNSS height if `gnss`.
+    AirbornePosition { altitude: Option<i32>, gnss: bool, cpr: Cpr },
+    /// Surface position (ES type codes 5 to 8), with ground speed (in
+    /// knots) and track (in degrees).
+    SurfacePosition { speed: Option<f64>, track: Option<f64>, cpr: Cpr },
+    /// Airborne velocity (ES type code 19).
+    Velocity(Velocity),
+    /// An extended squitter of another type code.
+    Other { type_code: u8 }
+}
+
+/// A decoded message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    /// Downlink format.
+    pub df: u8,
+    /// 24 bit aircraft address.
+    pub icao: u32,
+    /// The frame the message was decoded from.
+    pub frame: Frame,
+    pub content: Content
+}
+
+/// Convert a 13 bit identity field, with bits C1 A1 C2 A2 C4 A4 X B1 D1 B2
+/// D2 B4 D4, to Gillham code with one nibble for each of A, B, C and D.
+fn gillham(field: u32) -> u32 {
+    const MAP: [(u32, u32); 12] = [
+        (0x1000, 0x0010), (0x0800, 0x1000), (0x0400, 0x0020),
+        (0x0200, 0x2000), (0x0100, 0x0040), (0x0080, 0x4000),
+        (0x0020, 0x0100), (0x0010, 0x0001), (0x0008, 0x0200),
+        (0x0004, 0x0002), (0x0002, 0x0400), (0x0001, 0x0004)
+    ];
+    MAP.iter().filter(|&&(f, _)| field & f != 0).fold(0, |g, &(_, b)| g | b)
+}
+
+/// Decode a squawk code from a 13 bit identity field, as four octal
+/// digits read as decimal (so 7700 is 7700).
+pub fn decode_squawk(field: u32) -> u16 {
+    let g = gillham(field);
+    ((g >> 12 & 7) * 1000 + (g >> 8 & 7) * 100 + (g >> 4 & 7) * 10 +
+     (g & 7)) as u16
+}
+
+/// Decode a Gillham coded altitude (in feet), as sent by older transponders
+/// in 100 foot steps.
+fn gillham_altitude(field: u32) -> Option<i32> {
+    let g = gillham(field);
+    // D1 is never used, and at least one C bit is always set.
+    if g & 0xffff_8889 != 0 || g & 0xf0 == 0 {
+        return None;
+    }
+    let mut hundreds = 0i32;
+    if g & 0x0010 != 0 { hundreds ^= 7; }
+    if g & 0x0020 != 0 { hundreds ^= 3; }
+    if g & 0x0040 != 0 { hundreds ^= 1; }
+    if hundreds & 5 == 5 {
+        hundreds ^= 2;
+    }
+    if hundreds > 5 {
+        return None;
+    }
+    let mut fives = 0i32;
+    for &(bit, mask) in &[(0x0002, 0xff), (0x0004, 0x7f), (0x1000, 0x3f),
+                          (0x2000, 0x1f), (0x4000, 0x0f), (0x0100, 0x07),
+                          (0x0200, 0x03), (0x0400, 0x01)] {
+        if g & bit != 0 {
+            fives ^= mask;
+        }
+    }
+    if fives & 1 != 0 {
+        hundreds = 6 - hundreds;
+    }
+    Some((fives * 5 + hundreds - 13) * 100)
+}
+
+/// Decode a 13 bit altitude field (in feet), as in surveillance replies.
+pub fn decode_ac13(field: u32) -> Option<i32> {
+    if field == 0 {
+        return None;
+    }
+    let m = field & 0x40 != 0;
+    let q = field & 0x10 != 0;
+    if m {
+        // Metric altitudes are not used in practice.
+        None
+    } else if q {
+        let n = (field & 0x1f80) >> 2 | (field & 0x20) >> 1 | field & 0xf;
+        Some(n as i32 * 25 - 1000)
+    } else {
+        gillham_altitude(field)
+    }
+}
+
+/// Decode a 12 bit altitude field (in feet), as in airborne positions.
+pub fn decode_ac12(field: u32) -> Option<i32> {
+    // The same as the 13 bit field with the M bit removed.
+    decode_ac13((field & 0xfc0) << 1 | field & 0x3f)
+}
+
+/// Decode surface movement to ground speed (in knots).
+fn decode_movement(mov: u32) -> Option<f64> {
+    let mov = mov as f64;
+    match mov as u32 {
+        0 | 125..=127 => None,
+        1 => Some(0.0),
+        2..=8 => Some(0.125 + (mov - 2.0) * 0.125),
+        9..=12 => Some(1.0 + (mov - 9.0) * 0.25),
+        13..=38 => Some(2.0 + (mov - 13.0) * 0.5),
+        39..=93 => Some(15.0 + (mov - 39.0)),
+        94..=108 => Some(70.0 + (mov - 94.0) * 2.0),
+        109..=123 => Some(100.0 + (mov - 109.0) * 5.0),
+        _ => Some(175.0)
+    }
+}
+
+/// Decode the velocity message in `data`.
+fn decode_velocity(data: &[u8]) -> Content {
+    let subtype = bits(data, 37, 3);
+    let mut v = Velocity::default();
+    let scale = if subtype == 2 || subtype == 4 { 4.0 } else { 1.0 };
+    match subtype {
+        1 | 2 => {
+            let (ew, ns) = (bits(data, 46, 10), bits(data, 57, 10));
+            if ew != 0 && ns != 0 {
+                let sign = |s: u32| if s != 0 { -1.0 } else { 1.0 };
+                let vx = sign(bits(data, 45, 1)) * (ew - 1) as f64 * scale;
+                let vy = sign(bits(data, 56, 1)) * (ns - 1) as f64 * scale;
+                v.ground_speed = Some(vx.hypot(vy));
+                let track = vx.atan2(vy).to_degrees();
+                v.track = Some(if track < 0.0 { track + 360.0 } else { track });
+            }
+        },
+        3 | 4 => {
+            if bits(data, 45, 1) != 0 {
+                v.heading = Some(bits(data, 46, 10) as f64 * 360.0 / 1024.0);
+            }
+            v.true_airspeed = bits(data, 56, 1) != 0;
+            let speed = bits(data, 57, 10);
+            if speed != 0 {
+                v.airspeed = Some((speed - 1) as f64 * scale);
+            }
+        },
+        _ => return Content::Other { type_code: 19 }
+    }
+    v.gnss_rate = bits(data, 67, 1) == 0;
+    let rate = bits(data, 69, 9);
+    if rate != 0 {
+        let sign = if bits(data, 68, 1) != 0 { -1 } else { 1 };
+        v.vertical_rate = Some(sign * (rate as i32 - 1) * 64);
+    }
+    Content::Velocity(v)
+}
+
+/// Decode the ME field of an extended squitter.
+fn decode_extended(data: &[u8]) -> Content {
+    let tc = bits(data, 32, 5) as u8;
+    let cpr = || Cpr { odd: bits(data, 53, 1) != 0, lat: bits(data, 54, 17),
+                       lon: bits(data, 71, 17) };
+    match tc {
+        1..=4 => {
+            let callsign: String = (0..8).map(|i| {
+                CHARSET[bits(data, 40 + 6 * i, 6) as usize] as char
+            }).collect();
+            Content::Identification {
+                category: (4 - tc) << 3 | bits(data, 37, 3) as u8,
+                callsign: callsign.trim_end().to_string()
+            }
+        },
+        5..=8 => Content::SurfacePosition {
+            speed: decode_movement(bits(data, 37, 7)),
+            track: if bits(data, 44, 1) != 0 {
+                Some(bits(data, 45, 7) as f64 * 360.0 / 128.0)
+            } else {
+                None
+            },
+            cpr: cpr()
+        },
+        9..=18 => Content::AirbornePosition {
+            altitude: decode_ac12(bits(data, 40, 12)),
+            gnss: false,
+            cpr: cpr()
+        },
+        20..=22 => {
+            let height = bits(data, 40, 12);
+            Content::AirbornePosition {
+                altitude: if height == 0 {
+                    None
+                } else {
+                    Some((height as f64 * 3.28084).round() as i32)
+                },
+                gnss: true,
+                cpr: cpr()
+            }
+        },
+        19 => decode_velocity(data),
+        _ => Content::Other { type_code: tc }
+    }
+}
+
+/// Decode a frame from the Demodulator.
+///
+/// Formats whose parity is overlaid with the aircraft address are only
+/// accepted if `known` says the address recovered from the parity belongs
+/// to an aircraft already seen, since otherwise noise would be accepted.
+pub fn decode<F: Fn(u32) -> bool>(frame: &Frame, known: F)
+                                  -> Option<Message> {
+    let data = &frame.data;
+    let df = frame.df();
+    let (icao, content) = match df {
+        11 => (bits(data, 8, 24),
+               Content::AllCall { capability: bits(data, 5, 3) as u8 }),
+        17 | 18 => {
+            // DF18 with control field other than 0 or 1 carries TIS-B and
+            // ADS-R rebroadcasts, which we decode the same way.
+            (bits(data, 8, 24), decode_extended(data))
+        },
+        0 | 4 | 16 | 20 => {
+            let icao = crc::syndrome(data);
+            if !known(icao) {
+                return None;
+            }
+            (icao, Content::Altitude {
+                altitude: decode_ac13(bits(data, 19, 13))
+            })
+        },
+        5 | 21 => {
+            let icao = crc::syndrome(data);
+            if !known(icao) {
+                return None;
+            }
+            (icao, Content::Squawk { squawk: decode_squawk(bits(data, 19,
+                                                                13)) })
+        },
+        _ => return None
+    };
+    Some(Message { df, icao, frame: frame.clone(), content })
+}
@@ -0,0 +1,306 @@
+// AIS demodulation for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Each channel is shifted to baseband, filtered and decimated to about
+// 48kHz, five samples per bit, then FM demodulated and averaged over a bit.
+// A clock which is nudged towards the zero crossings samples each bit
+// midway between them, and bits are NRZI decoded, no change being a one.
+// HDLC frames lie between 0x7e flags, have a zero stuffed after every five
+// ones, are sent least significant bit first, and end in a CRC-16 frame
+// check sequence.
+
+use std::collections::VecDeque;
+
+use crate::{RTLSDRError, rtlsdr_error};
+use crate::channelizer::USABLE_BANDWIDTH;
+use crate::demod;
+use crate::dsp::Complex;
+use crate::samples::{IqPairs, u8_to_f32};
+use super::Channel;
+
+/// Bit rate of AIS (in bits per second).
+pub const BAUD: f64 = 9600.0;
+
+/// Rate each channel is demodulated at, at least (in Hz).
+const CHANNEL_RATE: f64 = 48_000.0;
+
+/// Bandwidth selected for each channel (in Hz), a little over the signal's
+/// to allow for frequency error.
+const BANDWIDTH: f64 = 16_000.0;
+
+/// Fraction of its timing error the clock is corrected by at each zero
+/// crossing.
+const CLOCK_GAIN: f64 = 0.15;
+
+/// Smoothing of the signal power, the noise floor, and the discriminator's
+/// offset due to frequency error, per sample. The offset is mostly the
+/// receiver's own error, shared by every signal, so is learnt slowly while
+/// signals are present; correct large errors with the tuner instead.
+const LEVEL_ALPHA: f32 = 1.0 / 16.0;
+const NOISE_ALPHA: f32 = 1.0 / 48_000.0;
+const OFFSET_ALPHA: f32 = 1.0 / 4096.0;
+
+/// Power over the noise floor above which the frequency offset is tracked.
+const SIGNAL_RATIO: f32 = 4.0;
+
+/// Shortest frame accepted (in bytes, excluding the frame check sequence),
+/// the size of the smallest messages.
+const MIN_BYTES: usize = 9;
+
+/// Bits after which a frame is abandoned, more than a five slot message.
+const MAX_BITS: usize = 1280;
+
+/// A received frame which passed its frame check sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub channel: Channel,
+    /// The payload, without the frame check sequence.
+    pub data: Vec<u8>,
+    /// Index of the sample at which the frame ended, counted from the first
+    /// sample processed.
+    pub sample: u64,
+    /// Power of the channel at the end of the frame (in dBFS).
+    pub level: f64
+}
+
+/// Compute the frame check sequence of `data`, the complement of its
+/// CRC-16-CCITT, least significant bit first.
+pub fn fcs(data: &[u8]) -> u16 {
+    let crc = data.iter().fold(0xffffu16, |mut crc, &b| {
+        crc ^= b as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { crc >> 1 ^ 0x8408 } else { crc >> 1 };
+        }
+        crc
+    });
+    !crc
+}
+
+/// Collects the bits between flags, removing stuffed zeros, and checks
+/// each frame found.
+#[derive(Clone, Debug, Default)]
+struct Deframer {
+    bits: Vec<bool>,
+    /// Ones received in a row.
+    ones: usize,
+    /// A flag has been seen, so bits belong to a frame.
+    in_frame: bool
+}
+
+impl Deframer {
+    /// Add a bit, returning the payload of any valid frame it ends.
+    fn push(&mut self, bit: bool) -> Option<Vec<u8>> {
+        if bit {
+            self.ones += 1;
+            if self.ones > 6 {
+                // Seven ones abort the frame.
+                self.in_frame = false;
+                self.bits.clear();
+            } else if self.in_frame {
+                self.bits.push(true);
+            }
+            return None;
+        }
+        let ones = std::mem::replace(&mut self.ones, 0);
+        match ones {
+            // A zero after five ones was stuffed.
+            5 => None,
+            6 => {
+                // A flag, 01111110, whose first seven bits were taken as
+                // data.
+                let frame = if self.in_frame && self.bits.len() > 7 {
+                    self.check(self.bits.len() - 7)
+                } else {
+                    None
+                };
+                self.bits.clear();
+                self.in_frame = true;
+                frame
+            },
+            _ => {
+                if self.in_frame {
+                    self.bits.push(false);
+                    if self.bits.len() > MAX_BITS {
+                        self.in_frame = false;
+                        self.bits.clear();
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    /// Pack the first `len` bits into bytes and check the frame check
+    /// sequence, returning the payload if it is good.
+    fn check(&self, len: usize) -> Option<Vec<u8>> {
+        if !len.is_multiple_of(8) || len / 8 < MIN_BYTES + 2 {
+            return None;
+        }
+        let mut data: Vec<u8> = self.bits[..len].chunks(8).map(|byte| {
+            byte.iter().rev().fold(0, |b, &bit| b << 1 | bit as u8)
+        }).collect();
+        let n = data.len() - 2;
+        let sent = data[n] as u16 | (data[n + 1] as u16) << 8;
+        data.truncate(n);
+        if fcs(&data) == sent { Some(data) } else { None }
+    }
+}
+
+/// Demodulates one channel.
+struct ChannelDemod {
+    channel: Channel,
+    select: demod::Channel,
+    /// The previous sample, for the discriminator.
+    last: Complex,
+    /// Signal power and noise floor.
+    level: f32,
+    noise: f32,
+    /// Discriminator output due to frequency error rather than data.
+    offset: f32,
+    /// The last bit's worth of discriminator outputs, to be averaged.
+    window: VecDeque<f32>,
+    /// Time since the last bit was sampled (in bits), and its increment
+    /// per sample.
+    clock: f64,
+    step: f64,
+    /// The previous discriminator output, less the offset.
+    prev: f32,
+    /// The previous symbol, for NRZI decoding.
+    symbol: bool,
+    deframer: Deframer,
+    /// Samples output by the channel filter.
+    outputs: u64
+}
+
+impl ChannelDemod {
+    fn new(channel: Channel, rate: u32, center: u64) -> ChannelDemod {
+        let offset = channel.frequency() as f64 - center as f64;
+        let select = demod::Channel::with_min_rate(rate, offset, BANDWIDTH,
+                                                   CHANNEL_RATE);
+        let step = BAUD / select.rate();
+        let window = VecDeque::from(vec![0.0; (1.0 / step).round() as usize]);
+        ChannelDemod {
+            channel, select, last: Complex::default(), level: 0.0,
+            noise: 1.0, offset: 0.0, window, clock: 0.0, step, prev: 0.0,
+            symbol: false, deframer: Deframer::default(), outputs: 0
+        }
+    }
+
+    fn reset(&mut self) {
+        self.select.reset();
+        self.last = Complex::default();
+        self.level = 0.0;
+        self.noise = 1.0;
+        self.offset = 0.0;
+        self.window.iter_mut().for_each(|d| *d = 0.0);
+        self.clock = 0.0;
+        self.prev = 0.0;
+        self.symbol = false;
+        self.deframer = Deframer::default();
+        self.outputs = 0;
+    }
+
+    /// Demodulate a block of the capture, adding frames found to `frames`.
+    fn process(&mut self, samples: &[Complex], frames: &mut Vec<Frame>) {
+        let decimation = self.select.decimation() as u64;
+        for s in self.select.process(samples) {
+            self.outputs += 1;
+            let power = s.norm_sqr();
+            self.level += (power - self.level) * LEVEL_ALPHA;
+            if self.level < self.noise {
+                self.noise = self.level;
+            } else {
+                self.noise += (self.level - self.noise) * NOISE_ALPHA;
+            }
+
+            let d = (s * self.last.conj()).arg();
+            self.last = s;
+            if self.level > SIGNAL_RATIO * self.noise {
+                self.offset += (d - self.offset) * OFFSET_ALPHA;
+            }
+            self.window.pop_front();
+            self.window.push_back(d);
+            let x = self.window.iter().sum::<f32>() /
+                    self.window.len() as f32 - self.offset;
+
+            let before = self.clock;
+            self.clock += self.step;
+            if (x > 0.0) != (self.prev > 0.0) {
+                // Interpolate the crossing, which should fall midway
+                // between bits.
+                let t = (self.prev / (self.prev - x)) as f64;
+                let at = before + t * self.step;
+                self.clock -= CLOCK_GAIN * (at - at.floor() - 0.5);
+            }
+            self.prev = x;
+
+            // Sample at whichever sample is nearest the middle of the bit.
+            if self.clock < 1.0 - self.step / 2.0 {
+                continue;
+            }
+            self.clock -= 1.0;
+            let symbol = x > 0.0;
+            let bit = symbol == self.symbol;
+            self.symbol = symbol;
+            if let Some(data) = self.deframer.push(bit) {
+                frames.push(Frame {
+                    channel: self.channel,
+                    data,
+                    sample: self.outputs * decimation,
+                    level: 10.0 * (self.level as f64).log10()
+                });
+            }
+        }
+    }
+}
+
+/// Finds frames on both AIS channels in I/Q, keeping state between blocks.
+pub struct Demodulator {
+    channels: [ChannelDemod; 2],
+    iq: IqPairs
+}
+
+impl Demodulator {
+    /// Receive both channels from a capture at `rate` Hz centred on
+    /// `center` Hz, which must hold them both, as SAMPLE_RATE and
+    /// CENTER_FREQ do.
+    pub fn new(rate: u32, center: u64) -> Result<Demodulator, RTLSDRError> {
+        let half = USABLE_BANDWIDTH * rate as f64 / 2.0;
+        for channel in [Channel::A, Channel::B] {
+            let offset = channel.frequency() as f64 - center as f64;
+            if offset.abs() + BANDWIDTH / 2.0 > half ||
+               (rate as f64) < CHANNEL_RATE {
+                return Err(rtlsdr_error(-1, "AIS channels are outside the \
+                                             capture"));
+            }
+        }
+        Ok(Demodulator {
+            channels: [ChannelDemod::new(Channel::A, rate, center),
+                       ChannelDemod::new(Channel::B, rate, center)],
+            iq: IqPairs::new()
+        })
+    }
+
+    /// Forget any partial frames and restart the sample count.
+    pub fn reset(&mut self) {
+        for c in self.channels.iter_mut() {
+            c.reset();
+        }
+        self.iq.reset();
+    }
+
+    /// Process interleaved u8 I/Q, returning the frames found in the order
+    /// they ended.
+    pub fn process_u8(&mut self, buf: &[u8]) -> Vec<Frame> {
+        let samples: Vec<Complex> = self.iq.pairs(buf)
+            .map(|(i, q)| Complex::new(u8_to_f32(i), u8_to_f32(q)))
+            .collect();
+        let mut frames = Vec::new();
+        for c in self.channels.iter_mut() {
+            c.process(&samples, &mut frames);
+        }
+        frames.sort_by_key(|f| f.sample);
+        frames
+    }
+}
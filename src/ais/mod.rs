@@ -0,0 +1,353 @@
+// AIS for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Ships and shore stations broadcast their identity, position and voyage
+// on two VHF channels, 161.975MHz and 162.025MHz, as GMSK at 9600 baud in
+// HDLC frames. Tune between the two and feed the Demodulator, which
+// receives both at once, then decode its frames or pass them on as NMEA
+// !AIVDM sentences for other software.
+
+pub mod demod;
+pub mod nmea;
+
+use super::bitfield::bits;
+use super::json::Value;
+
+/// Frequencies of the two AIS channels (in Hz).
+pub const FREQUENCY_A: u64 = 161_975_000;
+pub const FREQUENCY_B: u64 = 162_025_000;
+
+/// Centre frequency which receives both channels (in Hz).
+pub const CENTER_FREQ: u64 = 162_000_000;
+
+/// Sample rate which suits the Demodulator (in Hz), a multiple of the 48kHz
+/// each channel is demodulated at.
+pub const SAMPLE_RATE: u32 = 288_000;
+
+/// Characters of the 6 bit text alphabet, in which '@' pads unused ones.
+const CHARSET: &[u8; 64] =
+    b"@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_ !\"#$%&'()*+,-./0123456789:;<=>?";
+
+/// One of the two AIS channels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    A,
+    B
+}
+
+impl Channel {
+    /// Frequency of the channel (in Hz).
+    pub fn frequency(self) -> u64 {
+        match self {
+            Channel::A => FREQUENCY_A,
+            Channel::B => FREQUENCY_B
+        }
+    }
+
+    /// Letter naming the channel in NMEA sentences.
+    pub fn letter(self) -> char {
+        match self {
+            Channel::A => 'A',
+            Channel::B => 'B'
+        }
+    }
+}
+
+/// Position report from a class A (message types 1 to 3) or class B
+/// (message type 18) transponder. Fields are None when not available.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    /// Navigational status, such as 0 under way using engine, 1 at anchor
+    /// or 5 moored; always None from class B.
+    pub status: Option<u8>,
+    /// Rate of turn (in degrees per minute, positive to starboard), which
+    /// is only reported as +/-720 when turning faster than 5 degrees in 30
+    /// seconds without a turn indicator.
+    pub turn: Option<f64>,
+    /// Speed over the ground (in knots).
+    pub speed: Option<f64>,
+    /// The position is from a DGNSS fix, better than 10m.
+    pub accurate: bool,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// Course over the ground (in degrees from true north).
+    pub course: Option<f64>,
+    /// True heading (in degrees).
+    pub heading: Option<u16>,
+    /// UTC second at which the report was made.
+    pub second: Option<u8>
+}
+
+/// Size of a vessel, as distances from its GNSS antenna (in m).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dimensions {
+    pub bow: u16,
+    pub stern: u16,
+    pub port: u8,
+    pub starboard: u8
+}
+
+impl Dimensions {
+    /// Length of the vessel (in m), if known.
+    pub fn length(&self) -> Option<u16> {
+        let length = self.bow + self.stern;
+        if length == 0 { None } else { Some(length) }
+    }
+
+    /// Beam of the vessel (in m), if known.
+    pub fn beam(&self) -> Option<u16> {
+        let beam = self.port as u16 + self.starboard as u16;
+        if beam == 0 { None } else { Some(beam) }
+    }
+}
+
+/// Static and voyage related data from a class A transponder (message
+/// type 5). Text fields are None when blank.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaticVoyage {
+    pub imo: Option<u32>,
+    pub callsign: Option<String>,
+    pub name: Option<String>,
+    /// Type of ship and cargo, such as 30 for fishing or 70 for cargo.
+    pub ship_type: u8,
+    pub dimensions: Dimensions,
+    /// Estimated time of arrival as (month, day, hour, minute) UTC, with no
+    /// year.
+    pub eta: Option<(u8, u8, u8, u8)>,
+    /// Draught (in m).
+    pub draught: Option<f64>,
+    pub destination: Option<String>
+}
+
+/// Static data from a class B transponder (message type 24), which is
+/// sent in two parts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StaticB {
+    A { name: Option<String> },
+    B {
+        ship_type: u8,
+        vendor: Option<String>,
+        callsign: Option<String>,
+        dimensions: Dimensions
+    }
+}
+
+/// What a message says.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content {
+    /// Class A (message types 1 to 3) or class B (type 18) position.
+    Position(Position),
+    /// Class A static and voyage data (type 5).
+    StaticVoyage(StaticVoyage),
+    /// Class B static data (type 24).
+    StaticB(StaticB),
+    /// A message of another type, or one too short for its type.
+    Other
+}
+
+/// A decoded message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub msg_type: u8,
+    /// Times the message has been repeated by a base station, up to 3.
+    pub repeat: u8,
+    /// Maritime Mobile Service Identity of the sender.
+    pub mmsi: u32,
+    pub content: Content
+}
+
+/// Read `len` bits as a two's complement signed number.
+fn signed(data: &[u8], start: usize, len: usize) -> i32 {
+    let v = bits(data, start, len);
+    (v << (32 - len)) as i32 >> (32 - len)
+}
+
+/// Read `chars` characters of 6 bit text, with padding and trailing spaces
+/// removed, or None if that leaves nothing.
+fn text(data: &[u8], start: usize, chars: usize) -> Option<String> {
+    let s: String = (0..chars).map(|i| {
+        CHARSET[bits(data, start + 6 * i, 6) as usize] as char
+    }).collect();
+    let s = s.split('@').next().unwrap_or("").trim_end();
+    if s.is_empty() { None } else { Some(s.to_string()) }
+}
+
+/// Decode a 30 bit dimensions field.
+fn dimensions(data: &[u8], start: usize) -> Dimensions {
+    Dimensions {
+        bow: bits(data, start, 9) as u16,
+        stern: bits(data, start + 9, 9) as u16,
+        port: bits(data, start + 18, 6) as u8,
+        starboard: bits(data, start + 24, 6) as u8
+    }
+}
+
+/// Decode a position from a longitude field of 28 bits and a latitude
+/// field of 27 bits which follows it, both in 1/10000 minutes.
+fn lat_lon(data: &[u8], start: usize) -> (Option<f64>, Option<f64>) {
+    let lon = signed(data, start, 28) as f64 / 600_000.0;
+    let lat = signed(data, start + 28, 27) as f64 / 600_000.0;
+    // 91 and 181 degrees mean not available.
+    (if lat.abs() <= 90.0 { Some(lat) } else { None },
+     if lon.abs() <= 180.0 { Some(lon) } else { None })
+}
+
+/// Decode speed, course, heading and timestamp fields, which are the same
+/// in class A and class B positions except for where they start.
+fn motion(data: &[u8], sog: usize, cog: usize, status: Option<u8>,
+          turn: Option<f64>) -> Position {
+    let speed = bits(data, sog, 10);
+    let (lat, lon) = lat_lon(data, sog + 11);
+    let course = bits(data, cog, 12);
+    let heading = bits(data, cog + 12, 9);
+    let second = bits(data, cog + 21, 6);
+    Position {
+        status,
+        turn,
+        speed: if speed == 1023 { None } else { Some(speed as f64 / 10.0) },
+        accurate: bits(data, sog + 10, 1) != 0,
+        lat,
+        lon,
+        course: if course >= 3600 { None } else { Some(course as f64 / 10.0) },
+        heading: if heading >= 360 { None } else { Some(heading as u16) },
+        second: if second >= 60 { None } else { Some(second as u8) }
+    }
+}
+
+/// Decode a rate of turn field, which is scaled as 4.733 times the square
+/// root of the rate (in degrees per minute).
+fn turn(raw: i32) -> Option<f64> {
+    match raw {
+        -128 => None,
+        127 | -127 => Some(raw.signum() as f64 * 720.0),
+        r => {
+            let rate = (r as f64 / 4.733).powi(2);
+            Some(if r < 0 { -rate } else { rate })
+        }
+    }
+}
+
+/// Decode the message in the payload of a frame from the Demodulator, or
+/// None if it is too short to hold even the MMSI.
+pub fn decode(data: &[u8]) -> Option<Message> {
+    let len = data.len() * 8;
+    if len < 38 {
+        return None;
+    }
+    let msg_type = bits(data, 0, 6) as u8;
+    let content = match msg_type {
+        1..=3 if len >= 168 => Content::Position(motion(
+            data, 50, 116, Some(bits(data, 38, 4) as u8),
+            turn(signed(data, 42, 8)))),
+        18 if len >= 168 =>
+            Content::Position(motion(data, 46, 112, None, None)),
+        5 if len >= 420 => {
+            let imo = bits(data, 40, 30);
+            let (month, day) = (bits(data, 274, 4), bits(data, 278, 5));
+            let (hour, minute) = (bits(data, 283, 5), bits(data, 288, 6));
+            let draught = bits(data, 294, 8);
+            Content::StaticVoyage(StaticVoyage {
+                imo: if imo == 0 { None } else { Some(imo) },
+                callsign: text(data, 70, 7),
+                name: text(data, 112, 20),
+                ship_type: bits(data, 232, 8) as u8,
+                dimensions: dimensions(data, 240),
+                eta: if month == 0 || day == 0 || hour >= 24 ||
+                        minute >= 60 {
+                    None
+                } else {
+                    Some((month as u8, day as u8, hour as u8, minute as u8))
+                },
+                draught: if draught == 0 {
+                    None
+                } else {
+                    Some(draught as f64 / 10.0)
+                },
+                destination: text(data, 302, 20)
+            })
+        },
+        24 if len >= 160 => match bits(data, 38, 2) {
+            0 => Content::StaticB(StaticB::A { name: text(data, 40, 20) }),
+            1 if len >= 162 => Content::StaticB(StaticB::B {
+                ship_type: bits(data, 40, 8) as u8,
+                vendor: text(data, 48, 3),
+                callsign: text(data, 90, 7),
+                dimensions: dimensions(data, 132)
+            }),
+            _ => Content::Other
+        },
+        _ => Content::Other
+    };
+    Some(Message {
+        msg_type,
+        repeat: bits(data, 6, 2) as u8,
+        mmsi: bits(data, 8, 30),
+        content
+    })
+}
+
+/// Set `key` to `value` if it is available.
+fn set_some<T: Into<Value>>(obj: &mut Value, key: &str, value: Option<T>) {
+    if let Some(v) = value {
+        obj.set(key, v);
+    }
+}
+
+/// Add the fields of `dimensions` to `obj`.
+fn set_dimensions(obj: &mut Value, dimensions: &Dimensions) {
+    obj.set("to_bow", dimensions.bow as u32);
+    obj.set("to_stern", dimensions.stern as u32);
+    obj.set("to_port", dimensions.port as u32);
+    obj.set("to_starboard", dimensions.starboard as u32);
+}
+
+impl Message {
+    /// The message as a JSON object, with the field names used by gpsd.
+    pub fn to_json(&self) -> Value {
+        let mut obj = Value::object()
+            .with("type", self.msg_type as u32)
+            .with("repeat", self.repeat as u32)
+            .with("mmsi", self.mmsi);
+        match self.content {
+            Content::Position(ref p) => {
+                set_some(&mut obj, "status", p.status.map(u32::from));
+                set_some(&mut obj, "turn", p.turn);
+                set_some(&mut obj, "speed", p.speed);
+                obj.set("accuracy", p.accurate);
+                set_some(&mut obj, "lat", p.lat);
+                set_some(&mut obj, "lon", p.lon);
+                set_some(&mut obj, "course", p.course);
+                set_some(&mut obj, "heading", p.heading.map(u32::from));
+                set_some(&mut obj, "second", p.second.map(u32::from));
+            },
+            Content::StaticVoyage(ref s) => {
+                set_some(&mut obj, "imo", s.imo);
+                set_some(&mut obj, "callsign", s.callsign.clone());
+                set_some(&mut obj, "shipname", s.name.clone());
+                obj.set("shiptype", s.ship_type as u32);
+                set_dimensions(&mut obj, &s.dimensions);
+                if let Some((month, day, hour, minute)) = s.eta {
+                    obj.set("eta", format!("{:02}-{:02}T{:02}:{:02}Z", month,
+                                           day, hour, minute));
+                }
+                set_some(&mut obj, "draught", s.draught);
+                set_some(&mut obj, "destination", s.destination.clone());
+            },
+            Content::StaticB(StaticB::A { ref name }) => {
+                obj.set("partno", 0u32);
+                set_some(&mut obj, "shipname", name.clone());
+            },
+            Content::StaticB(StaticB::B { ship_type, ref vendor,
+                                          ref callsign, ref dimensions }) => {
+                obj.set("partno", 1u32);
+                obj.set("shiptype", ship_type as u32);
+                set_some(&mut obj, "vendorid", vendor.clone());
+                set_some(&mut obj, "callsign", callsign.clone());
+                set_dimensions(&mut obj, dimensions);
+            },
+            Content::Other => ()
+        }
+        obj
+    }
+}
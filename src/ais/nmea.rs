@@ -0,0 +1,145 @@
+// AIS NMEA output for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Programs exchange AIS as NMEA 0183 !AIVDM sentences, which carry a
+// frame's payload six bits to a character. Payloads too long for one
+// sentence are split over several, tied together by a sequential message
+// ID. Vessel tracking services and plotters take the same sentences as UDP
+// datagrams.
+
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::{RTLSDRError, rtlsdr_error};
+use super::Channel;
+
+/// Most payload characters put in one sentence, which keeps sentences
+/// within NMEA's 82 characters.
+pub const MAX_PAYLOAD: usize = 60;
+
+/// Convert `data` to payload characters, returning them and the number of
+/// fill bits added to complete the last character.
+pub fn armor(data: &[u8]) -> (String, u8) {
+    let len = data.len() * 8;
+    let payload = (0..len).step_by(6).map(|start| {
+        let v = (start..start + 6).fold(0u8, |v, b| {
+            let bit = if b < len { data[b / 8] >> (7 - b % 8) & 1 } else { 0 };
+            v << 1 | bit
+        });
+        (if v < 40 { v + 48 } else { v + 56 }) as char
+    }).collect();
+    (payload, ((6 - len % 6) % 6) as u8)
+}
+
+/// Convert payload characters back to bytes, dropping `fill` bits from the
+/// end, or None if any character is invalid. A last partial byte is padded
+/// with zeros.
+pub fn dearmor(payload: &str, fill: u8) -> Option<Vec<u8>> {
+    let len = (payload.len() * 6).checked_sub(fill as usize)?;
+    let mut data = vec![0u8; len.div_ceil(8)];
+    for (i, c) in payload.bytes().enumerate() {
+        let v = match c {
+            48..=87 => c - 48,
+            96..=119 => c - 56,
+            _ => return None
+        };
+        for b in 0..6 {
+            let n = 6 * i + b;
+            if n < len && v >> (5 - b) & 1 != 0 {
+                data[n / 8] |= 0x80 >> (n % 8);
+            }
+        }
+    }
+    Some(data)
+}
+
+/// Compute the checksum of a sentence, the XOR of the characters between
+/// the '!' and the '*'.
+pub fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |c, b| c ^ b)
+}
+
+/// Formats frames as !AIVDM sentences, numbering multi-sentence messages.
+#[derive(Clone, Debug, Default)]
+pub struct Encoder {
+    next_id: u8
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder { next_id: 0 }
+    }
+
+    /// Format the payload `data` of a frame received on `channel` as one
+    /// or more sentences, without line endings.
+    pub fn encode(&mut self, data: &[u8], channel: Channel) -> Vec<String> {
+        let (payload, fill) = armor(data);
+        let parts: Vec<&str> = payload.as_bytes().chunks(MAX_PAYLOAD)
+            .map(|p| std::str::from_utf8(p).unwrap())
+            .collect();
+        let count = parts.len().max(1);
+        let id = if count > 1 {
+            let id = self.next_id;
+            self.next_id = (id + 1) % 10;
+            id.to_string()
+        } else {
+            String::new()
+        };
+        (0..count).map(|i| {
+            let part = parts.get(i).copied().unwrap_or("");
+            let fill = if i == count - 1 { fill } else { 0 };
+            let body = format!("AIVDM,{},{},{},{},{},{}", count, i + 1, id,
+                               channel.letter(), part, fill);
+            format!("!{}*{:02X}", body, checksum(&body))
+        }).collect()
+    }
+}
+
+/// Sends sentences as UDP datagrams to any number of destinations, such
+/// as a vessel tracking service's feeder address.
+///
+/// Each message's sentences go in one datagram, each ended by CR LF.
+/// Datagrams which can't be sent are dropped, as they may be on the way.
+#[derive(Debug, Default)]
+pub struct UdpFeed {
+    sockets: Vec<UdpSocket>
+}
+
+impl UdpFeed {
+    pub fn new() -> UdpFeed {
+        UdpFeed { sockets: Vec::new() }
+    }
+
+    /// Add a destination, such as "127.0.0.1:10110".
+    pub fn add<A: ToSocketAddrs>(&mut self, addr: A)
+                                 -> Result<(), RTLSDRError> {
+        let addr = addr.to_socket_addrs()?.next()
+            .ok_or_else(|| rtlsdr_error(-1, "Address not found"))?;
+        let local = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        self.sockets.push(socket);
+        Ok(())
+    }
+
+    /// Number of destinations.
+    pub fn len(&self) -> usize {
+        self.sockets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sockets.is_empty()
+    }
+
+    /// Send the sentences of one message to every destination.
+    pub fn send(&self, sentences: &[String]) {
+        let mut datagram = String::new();
+        for s in sentences {
+            datagram.push_str(s);
+            datagram.push_str("\r\n");
+        }
+        for socket in self.sockets.iter() {
+            let _ = socket.send(datagram.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// ADS-B receiver, in the manner of dump1090
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::time::{Duration, SystemTime};
+
+use rtlsdr::adsb::aircraft::AircraftTable;
+use rtlsdr::adsb::demod::{Demodulator, SAMPLE_RATE};
+use rtlsdr::adsb::feed::{self, FeedServer, JsonServer};
+use rtlsdr::adsb::{self, Content, Message};
+use rtlsdr::playback::FileSource;
+use rtlsdr::source::Source;
+
+/// Bytes read at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_adsb [options] [recording]");
+    eprintln!("  Receives from a device at 1090MHz, or from a SigMF or raw");
+    eprintln!("  recording at 2MS/s if one is given.");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default maximum)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -P lat:lon   receiver position in degrees");
+    eprintln!("  -v           print each message rather than a table");
+    eprintln!("  -t seconds   table and JSON refresh interval (default 1)");
+    eprintln!("  -E           don't repair single bit errors");
+    eprintln!("  -N           serve Beast, SBS and JSON on their usual ports");
+    eprintln!("  -B port      serve Beast binary frames (usually 30005)");
+    eprintln!("  -S port      serve SBS-1 BaseStation text (usually 30003)");
+    eprintln!("  -J port      serve /data/aircraft.json over HTTP");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn show<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or(String::new(), |v| v.to_string())
+}
+
+/// Print a message as hex followed by what it says.
+fn print_message(msg: &Message) {
+    let hex: String = msg.frame.data.iter().map(|b| format!("{:02x}", b))
+                                    .collect();
+    let fixed = if msg.frame.corrected.is_some() { " (fixed)" } else { "" };
+    println!("*{}; DF{} {:06X}{}", hex, msg.df, msg.icao, fixed);
+    match msg.content {
+        Content::AllCall { capability } =>
+            println!("  All call, capability {}", capability),
+        Content::Altitude { altitude } =>
+            println!("  Altitude {}ft", show(altitude)),
+        Content::Squawk { squawk } => println!("  Squawk {:04}", squawk),
+        Content::Identification { category, ref callsign } =>
+            println!("  Identification {} category {}{}", callsign,
+                     (b'A' + (category >> 3)) as char, category & 7),
+        Content::AirbornePosition { altitude, gnss, cpr } =>
+            println!("  Airborne position {}ft{} CPR {} {} {}",
+                     show(altitude), if gnss { " GNSS" } else { "" },
+                     if cpr.odd { "odd" } else { "even" }, cpr.lat, cpr.lon),
+        Content::SurfacePosition { speed, track, cpr } =>
+            println!("  Surface position {}kt {}deg CPR {} {} {}",
+                     show(speed), show(track.map(|t| t.round())),
+                     if cpr.odd { "odd" } else { "even" }, cpr.lat, cpr.lon),
+        Content::Velocity(ref v) =>
+            println!("  Velocity {}kt {}deg {}ft/min",
+                     show(v.ground_speed.or(v.airspeed).map(|s| s.round())),
+                     show(v.track.or(v.heading).map(|t| t.round())),
+                     show(v.vertical_rate)),
+        Content::Other { type_code } =>
+            println!("  Extended squitter type {}", type_code)
+    }
+}
+
+/// Clear the terminal and print every aircraft.
+fn print_table(table: &AircraftTable, now: SystemTime) {
+    print!("\x1b[H\x1b[2J");
+    println!("{:6} {:8} {:6} {:6} {:5} {:4} {:6} {:9} {:10} {:6} {:4} {:6}",
+             "Hex", "Flight", "Squawk", "Alt", "Speed", "Trk", "VRate",
+             "Lat", "Lon", "Msgs", "Age", "dBFS");
+    let mut aircraft: Vec<_> = table.iter().collect();
+    aircraft.sort_by_key(|a| a.icao);
+    for a in aircraft {
+        let altitude = if a.on_ground {
+            "ground".to_string()
+        } else {
+            show(a.altitude)
+        };
+        let (lat, lon) = match a.position {
+            Some((lat, lon)) => (format!("{:.4}", lat), format!("{:.4}", lon)),
+            None => (String::new(), String::new())
+        };
+        println!("{:06X} {:8} {:6} {:>6} {:>5} {:>4} {:>6} {:>9} {:>10} \
+                  {:>6} {:>4} {:>6.1}",
+                 a.icao, show(a.callsign.as_ref()),
+                 show(a.squawk.map(|s| format!("{:04}", s))), altitude,
+                 show(a.speed.map(|s| s.round())),
+                 show(a.track.map(|t| t.round())), show(a.vertical_rate),
+                 lat, lon, a.messages, a.age(now).as_secs(),
+                 10.0 * a.signal.max(1e-10).log10());
+    }
+}
+
+/// Network outputs, each optional.
+struct Outputs {
+    beast: Option<FeedServer>,
+    sbs: Option<FeedServer>,
+    json: Option<JsonServer>
+}
+
+impl Outputs {
+    /// Start a server for each port given.
+    fn bind(beast: Option<u16>, sbs: Option<u16>, json: Option<u16>)
+            -> Outputs {
+        let feed = |port: u16, name: &str| {
+            let server = FeedServer::bind(("0.0.0.0", port))
+                                    .unwrap_or_else(|e| fail(&e.to_string()));
+            eprintln!("Serving {} on port {}", name,
+                      server.local_addr().port());
+            server
+        };
+        Outputs {
+            beast: beast.map(|p| feed(p, "Beast")),
+            sbs: sbs.map(|p| feed(p, "SBS")),
+            json: json.map(|port| {
+                let path = "/data/aircraft.json";
+                let server = JsonServer::bind(("0.0.0.0", port), path)
+                                        .unwrap_or_else(|e| {
+                                            fail(&e.to_string())
+                                        });
+                eprintln!("Serving {} on port {}", path,
+                          server.local_addr().port());
+                server
+            })
+        }
+    }
+}
+
+fn run<S: Source>(source: &mut S, demod: &mut Demodulator,
+                  table: &mut AircraftTable, outputs: &Outputs,
+                  verbose: bool, interval: Duration) {
+    let mut shown = SystemTime::now();
+    loop {
+        let buf = match source.read_sync(BLOCK) {
+            Ok(buf) => buf,
+            Err(e) => { eprintln!("{}", e); break; }
+        };
+        let now = SystemTime::now();
+        for frame in demod.process_u8(&buf) {
+            let msg = match adsb::decode(&frame, |icao| table.known(icao)) {
+                Some(msg) => msg,
+                None => continue
+            };
+            let aircraft = table.update(&msg, now);
+            if let Some(ref server) = outputs.beast {
+                server.send(&feed::beast(&msg.frame));
+            }
+            if let Some(ref server) = outputs.sbs
+               && let Some(line) = feed::sbs(&msg, aircraft, now) {
+                server.send(line.as_bytes());
+            }
+            if verbose {
+                print_message(&msg);
+            }
+        }
+        table.expire(now);
+        if now.duration_since(shown).unwrap_or_default() >= interval {
+            if let Some(ref server) = outputs.json {
+                server.set(&feed::aircraft_json(table, now));
+            }
+            if !verbose {
+                print_table(table, now);
+            }
+            shown = now;
+        }
+    }
+    if !verbose {
+        print_table(table, SystemTime::now());
+    }
+}
+
+fn main() {
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut receiver: Option<(f64, f64)> = None;
+    let mut verbose = false;
+    let mut interval = 1.0;
+    let mut fix_errors = true;
+    let mut beast: Option<u16> = None;
+    let mut sbs: Option<u16> = None;
+    let mut json: Option<u16> = None;
+    let mut input: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-P" => {
+                let position: String = parse(args.next());
+                receiver = match position.split_once(':') {
+                    Some((lat, lon)) => Some((parse(Some(lat.to_string())),
+                                              parse(Some(lon.to_string())))),
+                    None => usage()
+                };
+            },
+            "-v" => verbose = true,
+            "-t" => interval = parse(args.next()),
+            "-E" => fix_errors = false,
+            "-N" => {
+                beast = beast.or(Some(feed::BEAST_PORT));
+                sbs = sbs.or(Some(feed::SBS_PORT));
+                json = json.or(Some(feed::JSON_PORT));
+            },
+            "-B" => beast = Some(parse(args.next())),
+            "-S" => sbs = Some(parse(args.next())),
+            "-J" => json = Some(parse(args.next())),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => usage()
+        }
+    }
+
+    let mut demod = Demodulator::new();
+    demod.set_fix_errors(fix_errors);
+    let mut table = AircraftTable::new();
+    if let Some((lat, lon)) = receiver {
+        table.set_receiver(lat, lon);
+    }
+    let interval = Duration::from_secs_f64(interval);
+    let outputs = Outputs::bind(beast, sbs, json);
+
+    match input {
+        Some(path) => {
+            // Anything which isn't a SigMF recording is taken as raw.
+            let mut source = FileSource::open_sigmf(&path)
+                .or_else(|_| FileSource::open_raw(&path, SAMPLE_RATE,
+                                                  adsb::FREQUENCY))
+                .unwrap_or_else(|e| fail(&e.to_string()));
+            if source.get_sample_rate().unwrap() != SAMPLE_RATE {
+                fail("Recording must be sampled at 2MS/s");
+            }
+            run(&mut source, &mut demod, &mut table, &outputs, verbose,
+                interval);
+        },
+        None => {
+            let mut dev = rtlsdr::open(index).unwrap();
+            dev.set_sample_rate(SAMPLE_RATE).unwrap();
+            dev.set_center_freq(adsb::FREQUENCY).unwrap();
+            if ppm != 0 {
+                dev.set_freq_correction(ppm).unwrap();
+            }
+            // Automatic gain is poor at following short pulses, so default
+            // to the highest gain.
+            let gain = gain.unwrap_or_else(|| {
+                dev.get_tuner_gains().unwrap().into_iter().max().unwrap_or(0)
+            });
+            dev.set_tuner_gain_mode(true).unwrap();
+            dev.set_tuner_gain(gain).unwrap();
+            dev.reset_buffer().unwrap();
+            eprintln!("Tuned to {}Hz, gain {}dB", adsb::FREQUENCY,
+                      gain as f64 / 10.0);
+            run(&mut dev, &mut demod, &mut table, &outputs, verbose,
+                interval);
+            dev.close().unwrap();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// AIS receiver and feeder
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::Write;
+
+use rtlsdr::ais::{self, demod::Demodulator};
+use rtlsdr::ais::nmea::{Encoder, UdpFeed};
+use rtlsdr::playback::FileSource;
+use rtlsdr::source::Source;
+
+/// Bytes read at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_ais [options] [recording]");
+    eprintln!("  Receives both AIS channels from a device or from a SigMF");
+    eprintln!("  or raw recording, printing !AIVDM sentences and sending");
+    eprintln!("  them to any UDP destinations given.");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -s rate      sample rate in Hz (default 288000)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -u host:port send sentences over UDP (may be repeated)");
+    eprintln!("  -j           print decoded messages as JSON instead");
+    eprintln!("  -q           print nothing");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// What to print for each frame.
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Nmea,
+    Json,
+    Quiet
+}
+
+fn run<S: Source>(source: &mut S, demod: &mut Demodulator, feed: &UdpFeed,
+                  output: Output) {
+    let mut encoder = Encoder::new();
+    let stdout = std::io::stdout();
+    loop {
+        let buf = match source.read_sync(BLOCK) {
+            Ok(buf) => buf,
+            Err(e) => { eprintln!("{}", e); break; }
+        };
+        let mut out = stdout.lock();
+        for frame in demod.process_u8(&buf) {
+            let sentences = encoder.encode(&frame.data, frame.channel);
+            feed.send(&sentences);
+            let written = match output {
+                Output::Nmea => sentences.iter()
+                    .try_for_each(|s| writeln!(out, "{}", s)),
+                Output::Json => match ais::decode(&frame.data) {
+                    Some(msg) => {
+                        let level = (frame.level * 100.0).round() / 100.0;
+                        let json = msg.to_json()
+                            .with("channel", frame.channel.letter()
+                                                  .to_string())
+                            .with("level", level);
+                        writeln!(out, "{}", json.to_compact())
+                    },
+                    None => Ok(())
+                },
+                Output::Quiet => Ok(())
+            };
+            if written.is_err() {
+                return;
+            }
+        }
+        if out.flush().is_err() {
+            return;
+        }
+    }
+}
+
+fn main() {
+    let mut index = 0;
+    let mut rate = ais::SAMPLE_RATE;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut feed = UdpFeed::new();
+    let mut output = Output::Nmea;
+    let mut input: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" => index = parse(args.next()),
+            "-s" => rate = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-u" => {
+                let addr: String = parse(args.next());
+                feed.add(addr.as_str()).unwrap_or_else(|e| {
+                    fail(&format!("{}: {}", addr, e))
+                });
+            },
+            "-j" => output = Output::Json,
+            "-q" => output = Output::Quiet,
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => usage()
+        }
+    }
+
+    match input {
+        Some(path) => {
+            // Anything which isn't a SigMF recording is taken as raw.
+            let mut source = FileSource::open_sigmf(&path)
+                .or_else(|_| FileSource::open_raw(&path, rate,
+                                                  ais::CENTER_FREQ))
+                .unwrap_or_else(|e| fail(&e.to_string()));
+            let rate = source.get_sample_rate().unwrap();
+            let center = source.get_center_freq().unwrap();
+            let mut demod = Demodulator::new(rate, center)
+                .unwrap_or_else(|e| fail(&e.to_string()));
+            run(&mut source, &mut demod, &feed, output);
+        },
+        None => {
+            let mut demod = Demodulator::new(rate, ais::CENTER_FREQ)
+                .unwrap_or_else(|e| fail(&e.to_string()));
+            let mut dev = rtlsdr::open(index).unwrap();
+            dev.set_sample_rate(rate).unwrap();
+            dev.set_center_freq(ais::CENTER_FREQ).unwrap();
+            if ppm != 0 {
+                dev.set_freq_correction(ppm).unwrap();
+            }
+            match gain {
+                Some(g) => {
+                    dev.set_tuner_gain_mode(true).unwrap();
+                    dev.set_tuner_gain(g).unwrap();
+                },
+                None => dev.set_tuner_gain_mode(false).unwrap()
+            }
+            dev.reset_buffer().unwrap();
+            eprintln!("Tuned to {}Hz, sampling at {}Hz", ais::CENTER_FREQ,
+                      rate);
+            run(&mut dev, &mut demod, &feed, output);
+            dev.close().unwrap();
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// Pulse analyser for unknown ISM band devices
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use rtlsdr::ism::{self, analyze};
+use rtlsdr::ism::analyze::{Analysis, Bin, Keying, Segmenter};
+use rtlsdr::ism::bits::BitRow;
+use rtlsdr::ism::pulse::PulseConfig;
+use rtlsdr::ism::slicer::LineCode;
+use rtlsdr::playback::FileSource;
+use rtlsdr::source::Source;
+
+/// Bytes read at a time.
+const BLOCK: usize = 262_144;
+
+/// Width of the longest histogram bar (in characters).
+const BAR: usize = 40;
+
+/// Rows printed for each line code, counting repeats once.
+const MAX_ROWS: usize = 16;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_analyze [options] [recording]");
+    eprintln!("  Splits a device or a SigMF or raw recording into bursts,");
+    eprintln!("  and prints the modulation, symbol rate, pulse and gap");
+    eprintln!("  widths, and bits sliced by likely line codes for each.");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -f freq      frequency in Hz (default 433920000)");
+    eprintln!("  -s rate      sample rate in Hz (default 250000)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -l dB        level over the noise to detect (default 10)");
+    eprintln!("  -G us        gap which ends a burst (default 20000)");
+    eprintln!("  -n count     stop after this many bursts");
+    eprintln!("  -b           print bits in binary as well as hex");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn print_bins(name: &str, bins: &[Bin]) {
+    let total: usize = bins.iter().map(|b| b.count).sum();
+    println!("  {} ({}):", name, total);
+    let most = bins.iter().map(|b| b.count).max().unwrap_or(1);
+    for b in bins {
+        println!("    {:>8.0}us {:>8.0}-{:<8.0} {:>5} {}", b.mean, b.min,
+                 b.max, b.count, "#".repeat((b.count * BAR).div_ceil(most)));
+    }
+}
+
+fn describe(code: &LineCode) -> String {
+    match *code {
+        LineCode::Pcm { short, long, reset } if short == long =>
+            format!("PCM (NRZ) {:.0}us, reset {:.0}us", long, reset),
+        LineCode::Pcm { short, long, reset } =>
+            format!("PCM (RZ) {:.0}us pulses every {:.0}us, reset {:.0}us",
+                    short, long, reset),
+        LineCode::Ppm { short, long, gap_limit, .. } =>
+            format!("PPM gaps {:.0}/{:.0}us, gap limit {:.0}us", short,
+                    long, gap_limit),
+        LineCode::Pwm { short, long, gap_limit, .. } =>
+            format!("PWM pulses {:.0}/{:.0}us, gap limit {:.0}us", short,
+                    long, gap_limit),
+        LineCode::Manchester { half, reset } =>
+            format!("Manchester {:.0}us half bits, reset {:.0}us", half,
+                    reset)
+    }
+}
+
+fn print_row(row: &BitRow, repeats: usize, binary: bool) {
+    let hex: String = row.bytes().iter().map(|b| format!("{:02x}", b))
+                         .collect();
+    let times = if repeats > 1 { format!(" x{}", repeats) } else {
+        String::new()
+    };
+    println!("    {{{}}} {}{}", row.len(), hex, times);
+    if binary {
+        let bits: String = (0..row.len()).map(|i| {
+            if row.get(i) { '1' } else { '0' }
+        }).collect();
+        println!("         {}", bits);
+    }
+}
+
+fn print_analysis(n: usize, a: &Analysis, binary: bool) {
+    let p = &a.pulses;
+    let start = p.start as f64 / p.sample_rate as f64;
+    let end = p.pulses.iter().sum::<usize>() +
+              p.gaps[..p.len() - 1].iter().sum::<usize>();
+    println!("Burst {} at {:.6}s, {:.2}ms, {:.1}dBFS, SNR {:.1}dB", n, start,
+             p.to_us(end) / 1000.0, a.rssi, a.snr);
+    let khz: Vec<String> = a.tones.iter().map(|f| format!("{:+.1}kHz",
+                                                          f / 1000.0))
+                                  .collect();
+    match a.keying {
+        Keying::Ook => println!("  OOK, carrier {}", khz.join(" ")),
+        Keying::Ask => println!("  ASK, carrier {}", khz.join(" ")),
+        Keying::Fsk => println!("  2-FSK, tones {}", khz.join(" / "))
+    }
+    match a.symbol_period {
+        Some(t) => println!("  Symbol period {:.1}us, {:.0} baud", t,
+                            1e6 / t),
+        None => println!("  Symbol period unknown")
+    }
+    print_bins("Pulses", &a.pulse_bins);
+    print_bins("Gaps", &a.gap_bins);
+    for (code, bits) in a.candidates.iter() {
+        println!("  {}:", describe(code));
+        let rows: Vec<&BitRow> = bits.rows().collect();
+        let mut i = 0;
+        let mut printed = 0;
+        while i < rows.len() {
+            if printed == MAX_ROWS {
+                println!("    ... {} more rows", rows.len() - i);
+                break;
+            }
+            let same = rows[i..].iter().take_while(|&&r| r == rows[i])
+                                .count();
+            print_row(rows[i], same, binary);
+            i += same;
+            printed += 1;
+        }
+    }
+    println!();
+}
+
+fn run<S: Source>(source: &mut S, segmenter: &mut Segmenter,
+                  config: &PulseConfig, count: Option<usize>, binary: bool) {
+    let mut n = 0;
+    let mut ended = false;
+    while !ended {
+        let bursts = match source.read_sync(BLOCK) {
+            Ok(buf) => segmenter.process_u8(&buf),
+            Err(e) => {
+                eprintln!("{}", e);
+                // A burst still open at the end is cut short there.
+                ended = true;
+                segmenter.finish().into_iter().collect()
+            }
+        };
+        for burst in bursts {
+            if let Some(a) = analyze::analyze(&burst, config) {
+                n += 1;
+                print_analysis(n, &a, binary);
+                if count.is_some_and(|c| n >= c) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn main() {
+    let mut index = 0;
+    let mut freq = ism::FREQUENCY_433;
+    let mut rate = ism::SAMPLE_RATE;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut config = PulseConfig::new();
+    let mut count: Option<usize> = None;
+    let mut binary = false;
+    let mut input: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" => index = parse(args.next()),
+            "-f" => freq = parse(args.next()),
+            "-s" => rate = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-l" => config.min_snr = parse(args.next()),
+            "-G" => config.max_gap = parse(args.next()),
+            "-n" => count = Some(parse(args.next())),
+            "-b" => binary = true,
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => usage()
+        }
+    }
+
+    match input {
+        Some(path) => {
+            // Anything which isn't a SigMF recording is taken as raw.
+            let mut source = FileSource::open_sigmf(&path)
+                .or_else(|_| FileSource::open_raw(&path, rate, freq))
+                .unwrap_or_else(|e| fail(&e.to_string()));
+            let rate = source.get_sample_rate().unwrap();
+            let mut segmenter = Segmenter::new(rate, config);
+            run(&mut source, &mut segmenter, &config, count, binary);
+        },
+        None => {
+            let mut dev = rtlsdr::open(index).unwrap();
+            dev.set_sample_rate(rate).unwrap();
+            dev.set_center_freq(freq).unwrap();
+            if ppm != 0 {
+                dev.set_freq_correction(ppm).unwrap();
+            }
+            match gain {
+                Some(g) => {
+                    dev.set_tuner_gain_mode(true).unwrap();
+                    dev.set_tuner_gain(g).unwrap();
+                },
+                None => dev.set_tuner_gain_mode(false).unwrap()
+            }
+            dev.reset_buffer().unwrap();
+            eprintln!("Tuned to {}Hz, sampling at {}Hz", freq, rate);
+            let mut segmenter = Segmenter::new(rate, config);
+            run(&mut dev, &mut segmenter, &config, count, binary);
+            dev.close().unwrap();
+        }
+    }
+}
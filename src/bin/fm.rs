@@ -0,0 +1,141 @@
+// rtl_fm style receiver for FM, AM, SSB and CW
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::prelude::*;
+
+use rtlsdr::DirectSampling;
+use rtlsdr::demod::{Demodulator, Mode, new_demodulator};
+use rtlsdr::demod::fm::{FmConfig, FmDemod, DEEMPHASIS_EU, DEEMPHASIS_US};
+use rtlsdr::wav::{WavWriter, to_pcm16};
+
+/// Bytes read from the device at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_fm [options] -f <frequency Hz>");
+    eprintln!("  -M mode      wbfm, fm, am, sam, usb, lsb, cw (default wbfm)");
+    eprintln!("  -s rate      capture sample rate (default 1200000 for wbfm,");
+    eprintln!("               otherwise 240000)");
+    eprintln!("  -r rate      audio sample rate (default 48000)");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -l squelch   squelch level in dBFS (default off)");
+    eprintln!("  -E deemp     wbfm de-emphasis: 50, 75 or none (default 50)");
+    eprintln!("  -D branch    direct sampling from the I or Q branch, for HF");
+    eprintln!("  -w           write a WAV header rather than raw s16le");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn main() {
+    let mut freq: Option<u64> = None;
+    let mut mode = "wbfm".to_string();
+    let mut rate: Option<u32> = None;
+    let mut audio_rate = 48_000;
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut squelch: Option<f64> = None;
+    let mut deemphasis: Option<String> = None;
+    let mut wav = false;
+    let mut direct = DirectSampling::Disabled;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" => freq = Some(parse(args.next())),
+            "-M" => mode = parse(args.next()),
+            "-s" => rate = Some(parse(args.next())),
+            "-r" => audio_rate = parse(args.next()),
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-l" => squelch = Some(parse(args.next())),
+            "-E" => deemphasis = Some(parse(args.next())),
+            "-w" => wav = true,
+            "-D" => direct = match args.next().as_deref() {
+                Some("I") | Some("i") => DirectSampling::I,
+                Some("Q") | Some("q") => DirectSampling::Q,
+                _ => usage()
+            },
+            _ => usage()
+        }
+    }
+    let freq = freq.unwrap_or_else(|| usage());
+
+    let mode = Mode::from_name(&mode).unwrap_or_else(|| usage());
+    let rate = rate.unwrap_or(match mode {
+        Mode::WidebandFm => 1_200_000,
+        _ => 240_000
+    });
+    // Tune a quarter of the sample rate away to keep the channel clear of
+    // the DC spike.
+    let offset = rate / 4;
+    let mut demod: Box<dyn Demodulator> = match (mode, deemphasis.as_deref()) {
+        (Mode::WidebandFm, Some(d)) => {
+            let mut config = FmConfig::broadcast(rate);
+            config.offset = -(offset as f64);
+            config.audio_rate = audio_rate;
+            config.squelch = squelch;
+            config.deemphasis = match d {
+                "50" => Some(DEEMPHASIS_EU),
+                "75" => Some(DEEMPHASIS_US),
+                "none" => None,
+                _ => usage()
+            };
+            Box::new(FmDemod::new(&config))
+        },
+        (_, Some(_)) => usage(),
+        (_, None) => new_demodulator(mode, rate, -(offset as f64),
+                                     audio_rate, squelch)
+    };
+
+    let mut dev = rtlsdr::open(index).unwrap();
+    dev.set_direct_sampling(direct).unwrap();
+    dev.set_sample_rate(rate).unwrap();
+    dev.set_center_freq(freq + offset as u64).unwrap();
+    if ppm != 0 {
+        dev.set_freq_correction(ppm).unwrap();
+    }
+    match gain {
+        Some(g) => {
+            dev.set_tuner_gain_mode(true).unwrap();
+            dev.set_tuner_gain(g).unwrap();
+        },
+        None => dev.set_tuner_gain_mode(false).unwrap()
+    }
+    dev.reset_buffer().unwrap();
+    eprintln!("Tuned to {}Hz, sampling at {}Hz, audio at {}Hz", freq,
+              rate, audio_rate);
+
+    let mut out = std::io::stdout().lock();
+    if wav {
+        // The header is streamed with unknown lengths, after which WAV data
+        // is plain s16le.
+        WavWriter::new(&mut out, audio_rate, 1).unwrap();
+    }
+    loop {
+        let samples = match dev.read_iq(BLOCK) {
+            Ok(s) => s,
+            Err(e) => { eprintln!("{}", e); break; }
+        };
+        let audio = demod.process(&samples);
+        // Stop quietly when whoever is reading the audio goes away.
+        if out.write_all(&to_pcm16(&audio)).and_then(|_| out.flush())
+              .is_err() {
+            break;
+        }
+    }
+
+    dev.close().unwrap();
+}
@@ -0,0 +1,139 @@
+// ISM band device receiver, in the manner of rtl_433
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::Write;
+
+use rtlsdr::ism::{self, Receiver};
+use rtlsdr::json::Value;
+use rtlsdr::playback::FileSource;
+use rtlsdr::source::Source;
+
+/// Bytes read at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_ism [options] [recording]");
+    eprintln!("  Prints a line of JSON for each device message received,");
+    eprintln!("  from a device or from a SigMF or raw recording.");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -f freq      frequency in Hz (default 433920000)");
+    eprintln!("  -s rate      sample rate in Hz (default 250000)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -R name      disable a decoder (may be repeated)");
+    eprintln!("  -L           list the decoders and exit");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Print each event as a line of JSON, returning false once stdout has
+/// gone.
+fn print(events: &[Value]) -> bool {
+    let mut out = std::io::stdout().lock();
+    for event in events {
+        if writeln!(out, "{}", event.to_compact()).is_err() {
+            return false;
+        }
+    }
+    out.flush().is_ok()
+}
+
+fn run<S: Source>(source: &mut S, receiver: &mut Receiver) {
+    loop {
+        let buf = match source.read_sync(BLOCK) {
+            Ok(buf) => buf,
+            Err(e) => { eprintln!("{}", e); break; }
+        };
+        if !print(&receiver.process_u8(&buf)) {
+            return;
+        }
+    }
+    // A message just before the end is still waiting for its final gap.
+    print(&receiver.flush());
+}
+
+fn main() {
+    let mut index = 0;
+    let mut freq = ism::FREQUENCY_433;
+    let mut rate = ism::SAMPLE_RATE;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut disabled: Vec<String> = Vec::new();
+    let mut list = false;
+    let mut input: Option<String> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" => index = parse(args.next()),
+            "-f" => freq = parse(args.next()),
+            "-s" => rate = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-R" => disabled.push(parse(args.next())),
+            "-L" => list = true,
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => usage()
+        }
+    }
+
+    let mut source = input.map(|path| {
+        // Anything which isn't a SigMF recording is taken as raw.
+        FileSource::open_sigmf(&path)
+            .or_else(|_| FileSource::open_raw(&path, rate, freq))
+            .unwrap_or_else(|e| fail(&e.to_string()))
+    });
+    if let Some(ref mut s) = source {
+        rate = s.get_sample_rate().unwrap();
+    }
+
+    let mut receiver = Receiver::new(rate);
+    for name in disabled.iter() {
+        if !receiver.table_mut().remove(name) {
+            fail(&format!("No decoder called {}", name));
+        }
+    }
+    if list {
+        for name in receiver.table().names() {
+            println!("{}", name);
+        }
+        return;
+    }
+
+    match source {
+        Some(ref mut source) => run(source, &mut receiver),
+        None => {
+            let mut dev = rtlsdr::open(index).unwrap();
+            dev.set_sample_rate(rate).unwrap();
+            dev.set_center_freq(freq).unwrap();
+            if ppm != 0 {
+                dev.set_freq_correction(ppm).unwrap();
+            }
+            match gain {
+                Some(g) => {
+                    dev.set_tuner_gain_mode(true).unwrap();
+                    dev.set_tuner_gain(g).unwrap();
+                },
+                None => dev.set_tuner_gain_mode(false).unwrap()
+            }
+            dev.reset_buffer().unwrap();
+            eprintln!("Tuned to {}Hz, sampling at {}Hz", freq, rate);
+            run(&mut dev, &mut receiver);
+            dev.close().unwrap();
+        }
+    }
+}
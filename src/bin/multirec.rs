@@ -0,0 +1,242 @@
+// Multichannel squelched recorder, in the manner of rtlsdr-airband
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::SystemTime;
+
+use rtlsdr::channelizer::{Channelizer, ExtractorBank, PfbChannelizer,
+                          PfbConfig, USABLE_BANDWIDTH, plan_center};
+use rtlsdr::dsp::Complex;
+use rtlsdr::demod::{Demodulator, Mode, new_demodulator};
+use rtlsdr::timestamp::UtcTime;
+use rtlsdr::wav::WavWriter;
+
+/// Bytes read from the device at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_multirec [options] <frequency Hz>...");
+    eprintln!("  Writes a WAV file for each transmission on each channel.");
+    eprintln!("  -M mode      fm, am, sam, usb, lsb or cw (default am)");
+    eprintln!("  -b width     channel bandwidth in Hz (default 12500)");
+    eprintln!("  -S spacing   channel grid in Hz, using a filter bank rather");
+    eprintln!("               than separate filters (sets the bandwidth)");
+    eprintln!("  -s rate      capture sample rate (default 2400000)");
+    eprintln!("  -r rate      audio sample rate (default 16000)");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -l squelch   squelch level in dBFS (default -40)");
+    eprintln!("  -H seconds   keep recording after squelch closes (default 1)");
+    eprintln!("  -T seconds   stop after this long (default run forever)");
+    eprintln!("  -o dir       output directory (default .)");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// A transmission being written to disk.
+struct Recording {
+    wav: WavWriter<BufWriter<File>>,
+    path: String,
+    /// Audio samples written since the squelch last closed.
+    quiet: usize
+}
+
+/// One monitored channel.
+struct Monitor {
+    frequency: f64,
+    demod: Box<dyn Demodulator>,
+    audio_rate: u32,
+    recording: Option<Recording>
+}
+
+impl Monitor {
+    /// Demodulate a block of channel samples, starting, continuing or
+    /// ending a recording as the squelch dictates.
+    fn process(&mut self, samples: &[Complex], dir: &str, hang: usize) {
+        let audio = self.demod.process(samples);
+        let open = self.demod.squelch().is_open();
+        if open && self.recording.is_none() {
+            let time = UtcTime::from_system(SystemTime::now());
+            let path = format!("{}/{}_{}.wav", dir, self.frequency.round(),
+                               time.compact());
+            let file = File::create(&path)
+                            .unwrap_or_else(|e| fail(&e.to_string()));
+            let wav = WavWriter::new(BufWriter::new(file), self.audio_rate, 1)
+                                .unwrap_or_else(|e| fail(&e.to_string()));
+            eprintln!("{} {}Hz opened ({:.1}dBFS)", time.iso8601(),
+                      self.frequency.round(), self.demod.squelch().power());
+            self.recording = Some(Recording { wav, path, quiet: 0 });
+        }
+        let done = match self.recording {
+            Some(ref mut rec) => {
+                rec.wav.write(&audio).unwrap_or_else(|e| fail(&e.to_string()));
+                rec.quiet = if open { 0 } else { rec.quiet + audio.len() };
+                rec.quiet >= hang
+            },
+            None => false
+        };
+        if done {
+            self.finish();
+        }
+    }
+
+    /// Close any recording in progress.
+    fn finish(&mut self) {
+        if let Some(rec) = self.recording.take() {
+            let seconds = rec.wav.data_len() as f64 / 2.0 /
+                          self.audio_rate as f64;
+            rec.wav.finalize().unwrap_or_else(|e| fail(&e.to_string()));
+            let time = UtcTime::from_system(SystemTime::now());
+            eprintln!("{} {}Hz closed, {:.1}s to {}", time.iso8601(),
+                      self.frequency.round(), seconds, rec.path);
+        }
+    }
+}
+
+fn main() {
+    let mut mode = "am".to_string();
+    let mut bandwidth = 12_500.0;
+    let mut spacing: Option<f64> = None;
+    let mut rate = 2_400_000;
+    let mut audio_rate = 16_000;
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut squelch = -40.0;
+    let mut hang = 1.0;
+    let mut duration: Option<f64> = None;
+    let mut dir = ".".to_string();
+    let mut frequencies: Vec<f64> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-M" => mode = parse(args.next()),
+            "-b" => bandwidth = parse(args.next()),
+            "-S" => spacing = Some(parse(args.next())),
+            "-s" => rate = parse(args.next()),
+            "-r" => audio_rate = parse(args.next()),
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-l" => squelch = parse(args.next()),
+            "-H" => hang = parse(args.next()),
+            "-T" => duration = Some(parse(args.next())),
+            "-o" => dir = parse(args.next()),
+            _ => frequencies.push(parse(Some(arg)))
+        }
+    }
+    let mode = match Mode::from_name(&mode) {
+        Some(Mode::WidebandFm) | None => usage(),
+        Some(m) => m
+    };
+    if let Some(s) = spacing {
+        bandwidth = s;
+    }
+
+    let mut center = plan_center(&frequencies, bandwidth, rate)
+                         .unwrap_or_else(|e| fail(&e.to_string()));
+    let mut channelizer: Box<dyn Channelizer> = match spacing {
+        Some(s) => {
+            // Filter bank channels sit on a grid through the centre, so
+            // move the centre onto the grid of the requested channels.
+            let first = frequencies[0];
+            let steps = ((center as f64 - first) / s).round();
+            center = (first + steps * s).round() as u64;
+            let half = USABLE_BANDWIDTH * rate as f64 / 2.0;
+            for &f in &frequencies {
+                let offset = f - center as f64;
+                if (offset / s - (offset / s).round()).abs() > 1e-6 {
+                    fail(&format!("{}Hz is not on the channel grid", f));
+                }
+                if offset.abs() + s / 2.0 > half {
+                    fail("Channels do not fit in the capture");
+                }
+            }
+            let config = PfbConfig::new(rate as f64, s);
+            let mut pfb = PfbChannelizer::new(config)
+                              .unwrap_or_else(|e| fail(&e.to_string()));
+            pfb.set_center_freq(center);
+            let selected: Vec<usize> = frequencies.iter().map(|&f| {
+                pfb.channel_for(f).unwrap()
+            }).collect();
+            pfb.select(&selected);
+            Box::new(pfb)
+        },
+        None => {
+            let mut bank = ExtractorBank::new(rate as f64);
+            bank.set_center_freq(center);
+            for &f in &frequencies {
+                bank.add(f, bandwidth)
+                    .unwrap_or_else(|e| fail(&e.to_string()));
+            }
+            Box::new(bank)
+        }
+    };
+
+    let mut dev = rtlsdr::open(index).unwrap();
+    dev.set_sample_rate(rate).unwrap();
+    dev.set_center_freq(center).unwrap();
+    if ppm != 0 {
+        dev.set_freq_correction(ppm).unwrap();
+    }
+    match gain {
+        Some(g) => {
+            dev.set_tuner_gain_mode(true).unwrap();
+            dev.set_tuner_gain(g).unwrap();
+        },
+        None => dev.set_tuner_gain_mode(false).unwrap()
+    }
+    dev.set_dc_correction(true);
+    dev.reset_buffer().unwrap();
+    eprintln!("Tuned to {}Hz, sampling at {}Hz, {} channels", center, rate,
+              frequencies.len());
+
+    // Each channel is demodulated at its own rate, which the channelizer
+    // reports with the first block.
+    let hang = (hang * audio_rate as f64) as usize;
+    let mut monitors: Vec<Monitor> = Vec::new();
+    let mut remaining = duration.map(|d| (d * rate as f64) as usize * 2);
+    while remaining != Some(0) {
+        let len = remaining.map_or(BLOCK, |r| r.min(BLOCK));
+        let samples = match dev.read_iq(len) {
+            Ok(s) => s,
+            Err(e) => { eprintln!("{}", e); break; }
+        };
+        remaining = remaining.map(|r| r.saturating_sub(len));
+        let channels = channelizer.process(&samples);
+        if monitors.is_empty() {
+            monitors = channels.iter().map(|c| Monitor {
+                frequency: c.frequency,
+                demod: new_demodulator(mode, c.rate.round() as u32, 0.0,
+                                       audio_rate, Some(squelch)),
+                audio_rate,
+                recording: None
+            }).collect();
+        }
+        for (m, c) in monitors.iter_mut().zip(&channels) {
+            m.process(&c.samples, &dir, hang);
+        }
+    }
+
+    for m in monitors.iter_mut() {
+        m.finish();
+    }
+    dev.close().unwrap();
+}
@@ -0,0 +1,113 @@
+// rtl_power style wideband power scanner
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::prelude::*;
+
+use rtlsdr::source::Source;
+use rtlsdr::sweep::{HopPlan, Scanner};
+use rtlsdr::tcp::DEFAULT_PORT;
+use rtlsdr::tcp::client::TcpDevice;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_power [options] <start Hz> <stop Hz> <bin Hz>");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -r rate      sample rate (default 2400000)");
+    eprintln!("  -c crop      fraction of each hop to discard (default 0.25)");
+    eprintln!("  -a averages  FFTs averaged per hop (default 16)");
+    eprintln!("  -n sweeps    number of sweeps, 0 to run forever (default 1)");
+    eprintln!("  -R host      read from an rtl_tcp server (host or host:port)");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr, S: AsRef<str>>(arg: Option<S>) -> T {
+    match arg.map(|a| a.as_ref().parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn scan<S: Source>(source: &mut S, scanner: &Scanner, sweeps: usize,
+                   gain: Option<i32>, ppm: i32) {
+    if ppm != 0 {
+        source.set_freq_correction(ppm).unwrap();
+    }
+    match gain {
+        Some(g) => {
+            source.set_tuner_gain_mode(true).unwrap();
+            source.set_tuner_gain(g).unwrap();
+        },
+        None => source.set_tuner_gain_mode(false).unwrap()
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let mut n = 0;
+    while sweeps == 0 || n < sweeps {
+        let sweep = scanner.sweep(source).unwrap();
+        sweep.write_csv(&mut out).unwrap();
+        out.flush().unwrap();
+        n += 1;
+    }
+}
+
+fn main() {
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut rate = 2_400_000;
+    let mut crop = 0.25;
+    let mut averages = 16;
+    let mut sweeps = 1;
+    let mut remote: Option<String> = None;
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-r" => rate = parse(args.next()),
+            "-c" => crop = parse(args.next()),
+            "-a" => averages = parse(args.next()),
+            "-n" => sweeps = parse(args.next()),
+            "-R" => remote = Some(parse(args.next())),
+            _ => positional.push(arg)
+        }
+    }
+    if positional.len() != 3 {
+        usage();
+    }
+    let start: u64 = parse(positional.first());
+    let stop: u64 = parse(positional.get(1));
+    let bin: f64 = parse(positional.get(2));
+
+    let plan = match HopPlan::new(start, stop, bin, rate, crop) {
+        Ok(plan) => plan,
+        Err(e) => { eprintln!("{}", e); std::process::exit(1); }
+    };
+    eprintln!("{} hops of {} bins of {:.2}Hz", plan.hops.len(),
+              plan.fft_size, plan.bin_width());
+    let scanner = Scanner::new(plan, averages);
+
+    match remote {
+        Some(host) => {
+            let mut dev = match host.contains(':') {
+                true => TcpDevice::connect(host.as_str()),
+                false => TcpDevice::connect((host.as_str(), DEFAULT_PORT))
+            }.unwrap();
+            scan(&mut dev, &scanner, sweeps, gain, ppm);
+            dev.close().unwrap();
+        },
+        None => {
+            let mut dev = rtlsdr::open(index).unwrap();
+            scan(&mut dev, &scanner, sweeps, gain, ppm);
+            dev.close().unwrap();
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// rtl_tcp compatible I/Q server
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use rtlsdr::source::{SimulatedSource, Source};
+use rtlsdr::tcp::DEFAULT_PORT;
+use rtlsdr::tcp::server::{DropPolicy, Server, ServerOptions, SessionStats};
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_tcp [options]");
+    eprintln!("  -a address   listen address (default 127.0.0.1)");
+    eprintln!("  -P port      listen port (default 1234)");
+    eprintln!("  -f freq      initial frequency in Hz (default 100000000)");
+    eprintln!("  -s rate      initial sample rate (default 2048000)");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -b bytes     bytes per block (default 262144)");
+    eprintln!("  -n blocks    blocks queued per client (default 64)");
+    eprintln!("  -x policy    when a client falls behind: oldest, newest or");
+    eprintln!("               disconnect (default oldest)");
+    eprintln!("  -T           enable the bias tee");
+    eprintln!("  -S           serve simulated samples rather than a device");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn report(stats: &SessionStats) {
+    eprintln!("{} disconnected: {} bytes sent, {} of {} blocks dropped{}",
+              stats.peer, stats.bytes_sent, stats.blocks_dropped,
+              stats.blocks_read,
+              if stats.overflowed { " (too slow)" } else { "" });
+}
+
+fn serve<S: Source>(server: &Server, source: &mut S, freq: u64, rate: u32,
+                    gain: Option<i32>, ppm: i32) {
+    source.set_sample_rate(rate).unwrap();
+    source.set_center_freq(freq).unwrap();
+    if ppm != 0 {
+        source.set_freq_correction(ppm).unwrap();
+    }
+    match gain {
+        Some(g) => {
+            source.set_tuner_gain_mode(true).unwrap();
+            source.set_tuner_gain(g).unwrap();
+        },
+        None => source.set_tuner_gain_mode(false).unwrap()
+    }
+    eprintln!("Listening on {}", server.local_addr().unwrap());
+    if let Err(e) = server.run(source, report) {
+        eprintln!("{}", e);
+    }
+}
+
+fn main() {
+    let mut address = "127.0.0.1".to_string();
+    let mut port = DEFAULT_PORT;
+    let mut freq = 100_000_000;
+    let mut rate = 2_048_000;
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut bias_tee = false;
+    let mut simulate = false;
+    let mut options = ServerOptions::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" => address = parse(args.next()),
+            "-P" => port = parse(args.next()),
+            "-f" => freq = parse(args.next()),
+            "-s" => rate = parse(args.next()),
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-b" => options.block_size = parse(args.next()),
+            "-n" => options.max_queued = parse(args.next()),
+            "-x" => options.drop_policy = match args.next().as_deref() {
+                Some("oldest") => DropPolicy::Oldest,
+                Some("newest") => DropPolicy::Newest,
+                Some("disconnect") => DropPolicy::Disconnect,
+                _ => usage()
+            },
+            "-T" => bias_tee = true,
+            "-S" => simulate = true,
+            _ => usage()
+        }
+    }
+
+    let server = match Server::bind((address.as_str(), port), options) {
+        Ok(server) => server,
+        Err(e) => { eprintln!("{}", e); std::process::exit(1); }
+    };
+
+    if simulate {
+        let mut sim = SimulatedSource::new();
+        sim.add_signal(freq + 100_000, 0.3);
+        serve(&server, &mut sim, freq, rate, gain, ppm);
+    } else {
+        let mut dev = rtlsdr::open(index).unwrap();
+        if bias_tee {
+            dev.set_bias_tee(true).unwrap();
+        }
+        serve(&server, &mut dev, freq, rate, gain, ppm);
+        dev.close().unwrap();
+    }
+}
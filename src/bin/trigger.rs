@@ -0,0 +1,139 @@
+// Triggered I/Q recorder with pre-trigger history
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::BufRead;
+
+use rtlsdr::detect::DetectorConfig;
+use rtlsdr::sigmf::Metadata;
+use rtlsdr::trigger::{Format, Trigger, TriggeredRecorder};
+
+/// Bytes read from the device at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_trigger [options] -f <frequency Hz>");
+    eprintln!("  -s rate      sample rate (default 2048000)");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -b seconds   history kept before each trigger (default 5)");
+    eprintln!("  -a seconds   recorded after the last trigger (default 5)");
+    eprintln!("  -l level     trigger on block power in dBFS");
+    eprintln!("  -D snr       trigger on a detected signal of this SNR in dB");
+    eprintln!("  -F low:high  only detect signals between these frequencies");
+    eprintln!("  -i           trigger on each line read from stdin");
+    eprintln!("  -R           write raw .cu8 rather than SigMF");
+    eprintln!("  -o prefix    output filename prefix (default trigger)");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+fn main() {
+    let mut freq: Option<u64> = None;
+    let mut rate = 2_048_000;
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut pre = 5.0;
+    let mut post = 5.0;
+    let mut level: Option<f64> = None;
+    let mut snr: Option<f64> = None;
+    let mut band: Option<(f64, f64)> = None;
+    let mut stdin = false;
+    let mut format = Format::Sigmf;
+    let mut prefix = "trigger".to_string();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" => freq = Some(parse(args.next())),
+            "-s" => rate = parse(args.next()),
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-b" => pre = parse(args.next()),
+            "-a" => post = parse(args.next()),
+            "-l" => level = Some(parse(args.next())),
+            "-D" => snr = Some(parse(args.next())),
+            "-F" => {
+                let range: String = parse(args.next());
+                band = match range.split_once(':') {
+                    Some((l, h)) => Some((parse(Some(l.to_string())),
+                                          parse(Some(h.to_string())))),
+                    None => usage()
+                };
+            },
+            "-i" => stdin = true,
+            "-R" => format = Format::Raw,
+            "-o" => prefix = parse(args.next()),
+            _ => usage()
+        }
+    }
+    let freq = freq.unwrap_or_else(|| usage());
+    if level.is_none() && snr.is_none() && !stdin {
+        eprintln!("No triggers given");
+        usage();
+    }
+
+    let mut dev = rtlsdr::open(index).unwrap();
+    dev.set_sample_rate(rate).unwrap();
+    dev.set_center_freq(freq).unwrap();
+    if ppm != 0 {
+        dev.set_freq_correction(ppm).unwrap();
+    }
+    match gain {
+        Some(g) => {
+            dev.set_tuner_gain_mode(true).unwrap();
+            dev.set_tuner_gain(g).unwrap();
+        },
+        None => dev.set_tuner_gain_mode(false).unwrap()
+    }
+
+    let meta = Metadata::from_device(&mut dev).unwrap();
+    let mut rec = TriggeredRecorder::new(meta, pre, post, &prefix, format);
+    if let Some(l) = level {
+        rec.add_trigger(Trigger::Power(l));
+    }
+    if let Some(s) = snr {
+        let mut config = DetectorConfig::new();
+        config.threshold = s;
+        rec.add_trigger(Trigger::detector(config, 1024, rate as f64, band));
+    }
+    if stdin {
+        let handle = rec.external_trigger();
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lock().lines() {
+                handle.fire();
+            }
+        });
+    }
+
+    dev.reset_buffer().unwrap();
+    eprintln!("Tuned to {}Hz, sampling at {}Hz, keeping {}s of history",
+              freq, rate, pre);
+    loop {
+        let recording = rec.recording().is_some();
+        match rec.read(&mut dev, BLOCK) {
+            Ok(Some(path)) => eprintln!("Saved {}", path),
+            Ok(None) => (),
+            Err(e) => { eprintln!("{}", e); break; }
+        }
+        if let (false, Some(path)) = (recording, rec.recording()) {
+            eprintln!("Triggered, recording to {}", path);
+        }
+    }
+
+    if let Some(path) = rec.finish().unwrap() {
+        eprintln!("Saved {}", path);
+    }
+    dev.close().unwrap();
+}
@@ -0,0 +1,147 @@
+// Waterfall PNG renderer for recordings and live devices
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use rtlsdr::playback::FileSource;
+use rtlsdr::source::Source;
+use rtlsdr::waterfall::{Colormap, Waterfall, WaterfallConfig};
+
+/// Bytes read at a time.
+const BLOCK: usize = 262_144;
+
+fn usage() -> ! {
+    eprintln!("Usage: rtlsdr_waterfall [options] -o <out.png> [recording]");
+    eprintln!("  Renders a SigMF or raw cu8 recording, or a live device if no");
+    eprintln!("  recording is given.");
+    eprintln!("  -f freq      centre frequency in Hz (device or raw file)");
+    eprintln!("  -s rate      sample rate (default 2048000)");
+    eprintln!("  -d index     device index (default 0)");
+    eprintln!("  -g gain      tuner gain in tenths of dB (default auto)");
+    eprintln!("  -p ppm       frequency correction (default 0)");
+    eprintln!("  -T seconds   length to render (default 10, or whole file)");
+    eprintln!("  -n size      FFT size (default 1024)");
+    eprintln!("  -t seconds   time per row (default 0.1)");
+    eprintln!("  -c map       viridis, inferno, jet or gray (default viridis)");
+    eprintln!("  -m dB        bottom of the colour scale (default auto)");
+    eprintln!("  -M dB        top of the colour scale (default auto)");
+    eprintln!("  -L           omit axes and labels");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: Option<String>) -> T {
+    match arg.map(|a| a.parse()) {
+        Some(Ok(v)) => v,
+        _ => usage()
+    }
+}
+
+/// Feed up to `duration` seconds (or everything, if None) into `waterfall`.
+fn render<S: Source>(source: &mut S, waterfall: &mut Waterfall, rate: u32,
+                     duration: Option<f64>) {
+    let mut remaining = duration.map(|d| (d * rate as f64) as usize * 2);
+    while remaining != Some(0) {
+        let len = remaining.map_or(BLOCK, |r| r.min(BLOCK));
+        match source.read_sync(len) {
+            Ok(buf) => {
+                waterfall.process_u8(&buf);
+                remaining = remaining.map(|r| r.saturating_sub(buf.len()));
+            },
+            Err(_) if duration.is_none() => break,
+            Err(e) => { eprintln!("{}", e); break; }
+        }
+    }
+}
+
+fn main() {
+    let mut freq: Option<u64> = None;
+    let mut rate: Option<u32> = None;
+    let mut index = 0;
+    let mut gain: Option<i32> = None;
+    let mut ppm = 0;
+    let mut duration: Option<f64> = None;
+    let mut output: Option<String> = None;
+    let mut input: Option<String> = None;
+    let mut config = WaterfallConfig::new(1024);
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" => freq = Some(parse(args.next())),
+            "-s" => rate = Some(parse(args.next())),
+            "-d" => index = parse(args.next()),
+            "-g" => gain = Some(parse(args.next())),
+            "-p" => ppm = parse(args.next()),
+            "-T" => duration = Some(parse(args.next())),
+            "-n" => config.fft_size = parse(args.next()),
+            "-t" => config.row_time = parse(args.next()),
+            "-c" => config.colormap = args.next().as_deref()
+                .and_then(Colormap::from_name).unwrap_or_else(|| usage()),
+            "-m" => config.min_db = Some(parse(args.next())),
+            "-M" => config.max_db = Some(parse(args.next())),
+            "-L" => config.labels = false,
+            "-o" => output = Some(parse(args.next())),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => usage()
+        }
+    }
+    let output = output.unwrap_or_else(|| usage());
+    if !config.fft_size.is_power_of_two() {
+        usage();
+    }
+
+    let waterfall = match input {
+        Some(path) => {
+            let opened = match (freq, rate) {
+                (None, None) => FileSource::open(&path),
+                _ => FileSource::open_raw(&path, rate.unwrap_or(2_048_000),
+                                          freq.unwrap_or(0))
+            };
+            let mut source = match opened {
+                Ok(source) => source,
+                Err(e) => { eprintln!("{}", e); std::process::exit(1); }
+            };
+            let rate = source.get_sample_rate().unwrap();
+            let center = source.get_center_freq().unwrap();
+            let mut waterfall = Waterfall::new(config, rate, center);
+            if let Some(time) = source.start_time() {
+                waterfall.set_start_time(time);
+            }
+            render(&mut source, &mut waterfall, rate, duration);
+            waterfall
+        },
+        None => {
+            let freq = freq.unwrap_or_else(|| usage());
+            let rate = rate.unwrap_or(2_048_000);
+            let mut dev = rtlsdr::open(index).unwrap();
+            dev.set_sample_rate(rate).unwrap();
+            dev.set_center_freq(freq).unwrap();
+            if ppm != 0 {
+                dev.set_freq_correction(ppm).unwrap();
+            }
+            match gain {
+                Some(g) => {
+                    dev.set_tuner_gain_mode(true).unwrap();
+                    dev.set_tuner_gain(g).unwrap();
+                },
+                None => dev.set_tuner_gain_mode(false).unwrap()
+            }
+            dev.reset_buffer().unwrap();
+            let mut waterfall = Waterfall::new(config, rate, freq);
+            render(&mut dev, &mut waterfall, rate, duration.or(Some(10.0)));
+            dev.close().unwrap();
+            waterfall
+        }
+    };
+
+    eprintln!("{} rows of {} bins", waterfall.rows().len(), config.fft_size);
+    let file = File::create(&output).unwrap_or_else(|e| {
+        eprintln!("{}: {}", output, e);
+        std::process::exit(1);
+    });
+    waterfall.write_png(&mut BufWriter::new(file)).unwrap();
+}
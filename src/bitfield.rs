@@ -0,0 +1,11 @@
+// Bit fields of received messages for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+/// Read `len` bits (at most 32) of `data` starting at bit `start`, with
+/// bit 0 the most significant bit of the first byte.
+pub fn bits(data: &[u8], start: usize, len: usize) -> u32 {
+    (start..start + len).fold(0, |v, b| {
+        v << 1 | ((data[b / 8] >> (7 - b % 8)) & 1) as u32
+    })
+}
@@ -0,0 +1,302 @@
+// GSM FCCH based frequency calibration for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// GSM base stations transmit a frequency correction burst (FCCH) on their
+// BCCH carrier several times a second. The burst is a pure tone 1625/24 kHz
+// above the carrier, and carrier frequencies are held to within 0.05ppm, so
+// measuring where the tone lands tells us the error of our own oscillator.
+
+use super::{RTLSDRError, rtlsdr_error};
+use super::dsp::{Complex, autocorr, tone_freq};
+use super::samples::iq_from_u8;
+use super::source::Source;
+
+/// GSM symbol rate (in Hz), which is also the sample rate used for scanning.
+pub const GSM_RATE: u32 = 270_833;
+
+/// Offset of the FCCH tone above the carrier (in Hz).
+pub const FCCH_OFFSET: f64 = 1_625_000.0 / 24.0;
+
+/// Length of an FCCH burst (in symbols).
+const FCCH_SYMBOLS: usize = 148;
+
+/// Samples to discard after retuning, to let the tuner settle.
+const SETTLE_BYTES: usize = 32768;
+
+/// Samples used to measure channel power during the band scan.
+const POWER_BYTES: usize = 16384;
+
+/// Burst estimates must be within this many ppm of the median to count.
+const CONSISTENCY_PPM: f64 = 0.5;
+
+/// A GSM downlink band.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GsmBand {
+    Gsm850, Gsm900, EGsm900, Dcs1800, Pcs1900
+}
+
+impl GsmBand {
+    /// Downlink frequency (in Hz) of an ARFCN in this band, or None if the
+    /// ARFCN is not part of the band.
+    pub fn arfcn_to_freq(&self, arfcn: u32) -> Option<u64> {
+        let khz = match (*self, arfcn) {
+            (GsmBand::Gsm850, 128..=251) => 869_200 + 200 * (arfcn - 128),
+            (GsmBand::Gsm900, 1..=124) => 935_000 + 200 * arfcn,
+            (GsmBand::EGsm900, 0..=124) => 935_000 + 200 * arfcn,
+            (GsmBand::EGsm900, 975..=1023) =>
+                935_000 - 200 * (1024 - arfcn),
+            (GsmBand::Dcs1800, 512..=885) => 1_805_200 + 200 * (arfcn - 512),
+            (GsmBand::Pcs1900, 512..=810) => 1_930_200 + 200 * (arfcn - 512),
+            _ => return None
+        };
+        Some(khz as u64 * 1000)
+    }
+
+    /// All ARFCNs in this band with their downlink frequencies (in Hz).
+    pub fn channels(&self) -> Vec<(u32, u64)> {
+        let ranges: &[(u32, u32)] = match *self {
+            GsmBand::Gsm850 => &[(128, 251)],
+            GsmBand::Gsm900 => &[(1, 124)],
+            GsmBand::EGsm900 => &[(975, 1023), (0, 124)],
+            GsmBand::Dcs1800 => &[(512, 885)],
+            GsmBand::Pcs1900 => &[(512, 810)]
+        };
+        ranges.iter()
+              .flat_map(|&(a, b)| a..=b)
+              .filter_map(|n| self.arfcn_to_freq(n).map(|f| (n, f)))
+              .collect()
+    }
+}
+
+/// A detected FCCH burst.
+#[derive(Clone, Copy, Debug)]
+pub struct FcchBurst {
+    /// Index of the first sample of the burst.
+    pub sample: usize,
+    /// Frequency of the tone relative to where it should be (in Hz).
+    pub offset: f64,
+    /// How tone-like the burst is, from 0 to 1.
+    pub coherence: f32
+}
+
+/// Find FCCH bursts in `samples`, which must be at a sample rate `rate` (in
+/// Hz) and centred on a GSM carrier.
+///
+/// A sliding window measures the lag-one autocorrelation of the signal. Over
+/// a pure tone its phase is constant and its magnitude equals the signal
+/// power, while over GMSK data or noise it averages away. Each run of
+/// coherent windows about the length of a burst is measured at its most
+/// coherent window.
+pub fn detect_fcch(samples: &[Complex], rate: f64) -> Vec<FcchBurst> {
+    let burst = (FCCH_SYMBOLS as f64 * rate / GSM_RATE as f64) as usize;
+    let window = burst * 3 / 4;
+    let threshold = 0.6;
+    let mut bursts: Vec<FcchBurst> = Vec::new();
+    if window < 2 || samples.len() < window + 1 {
+        return bursts;
+    }
+
+    let prods: Vec<Complex> = samples.windows(2)
+                                     .map(|w| w[1] * w[0].conj())
+                                     .collect();
+    let mags: Vec<f32> = prods.iter().map(|p| p.norm()).collect();
+
+    let mut acc = prods[..window].iter()
+                                 .fold(Complex::default(), |a, &p| a + p);
+    let mut mag: f32 = mags[..window].iter().sum();
+    // Start of the current run, and its most coherent window so far.
+    let mut run: Option<(usize, usize, f32)> = None;
+    for n in 0..=(prods.len() - window) {
+        if n > 0 {
+            acc += prods[n + window - 1] - prods[n - 1];
+            mag += mags[n + window - 1] - mags[n - 1];
+        }
+        let coherence = if mag > 0.0 { acc.norm() / mag } else { 0.0 };
+        run = match run {
+            None if coherence > threshold => Some((n, n, coherence)),
+            Some((start, _, best)) if coherence > best =>
+                Some((start, n, coherence)),
+            Some((start, peak, _)) if coherence <= threshold => {
+                let len = n - 1 + window - start;
+                if len >= burst / 2 && len <= burst * 2 {
+                    let span = &samples[peak..peak + window + 1];
+                    let b = measure(span, peak, rate);
+                    match bursts.last_mut() {
+                        Some(last) if peak - last.sample < burst => {
+                            if b.coherence > last.coherence {
+                                *last = b;
+                            }
+                        },
+                        _ => bursts.push(b)
+                    }
+                }
+                None
+            },
+            r => r
+        };
+    }
+    bursts
+}
+
+/// Measure the tone over `span`, which starts at `sample`.
+fn measure(span: &[Complex], sample: usize, rate: f64) -> FcchBurst {
+    let acc = autocorr(span, 1);
+    let mag: f32 = span.windows(2).map(|w| w[0].norm() * w[1].norm()).sum();
+    FcchBurst {
+        sample,
+        offset: tone_freq(span) * rate - FCCH_OFFSET,
+        coherence: acc.norm() / mag
+    }
+}
+
+/// Result of a frequency calibration.
+#[derive(Clone, Copy, Debug)]
+pub struct Calibration {
+    /// Estimated total frequency correction (in ppm), including whatever
+    /// correction was applied while measuring.
+    pub ppm: f64,
+    /// Standard deviation of the individual burst estimates (in ppm).
+    pub std_dev: f64,
+    /// Number of bursts which contributed to the estimate.
+    pub bursts: usize,
+    /// Fraction of detected bursts which agreed with the estimate, from 0
+    /// to 1.
+    pub confidence: f64
+}
+
+/// Combine per-burst estimates (in ppm) into a calibration.
+///
+/// Estimates within CONSISTENCY_PPM of the median are averaged; the rest are
+/// treated as false detections.
+fn combine(estimates: &[f64]) -> Option<Calibration> {
+    if estimates.is_empty() {
+        return None;
+    }
+    let mut sorted = estimates.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+    let good: Vec<f64> = sorted.into_iter()
+                               .filter(|e| (e - median).abs() < CONSISTENCY_PPM)
+                               .collect();
+    let n = good.len() as f64;
+    let mean = good.iter().sum::<f64>() / n;
+    let var = good.iter().map(|e| (e - mean) * (e - mean)).sum::<f64>() / n;
+    Some(Calibration {
+        ppm: mean,
+        std_dev: var.sqrt(),
+        bursts: good.len(),
+        confidence: n / estimates.len() as f64
+    })
+}
+
+/// Estimate the frequency error from a recording of a GSM carrier.
+///
+/// `samples` are at `rate` (in Hz) and were recorded tuned to `carrier` (in
+/// Hz). The returned ppm is relative to whatever correction was in effect
+/// during the recording.
+pub fn calibrate_iq(samples: &[Complex], rate: f64, carrier: u64)
+                    -> Option<Calibration> {
+    let estimates: Vec<f64> = detect_fcch(samples, rate)
+        .iter()
+        .map(|b| -b.offset / carrier as f64 * 1e6)
+        .collect();
+    combine(&estimates)
+}
+
+/// Options for calibrate.
+#[derive(Clone, Copy, Debug)]
+pub struct CalibrationOptions {
+    /// Band to scan.
+    pub band: GsmBand,
+    /// Number of the strongest channels to look for FCCH bursts on.
+    pub channels: usize,
+    /// Time to listen to each channel (in seconds).
+    pub duration: f64,
+    /// Whether to apply the result with set_freq_correction.
+    pub apply: bool
+}
+
+impl Default for CalibrationOptions {
+    fn default() -> CalibrationOptions {
+        CalibrationOptions {
+            band: GsmBand::Gsm900, channels: 4, duration: 1.0, apply: false
+        }
+    }
+}
+
+/// Power of one channel found by scan_band.
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelPower {
+    pub arfcn: u32,
+    pub freq: u64,
+    /// Mean power relative to full scale (in dB).
+    pub power: f64
+}
+
+/// Retune `source` and read `len` bytes once the tuner has settled.
+fn capture<S: Source>(source: &mut S, freq: u64, len: usize)
+                      -> Result<Vec<Complex>, RTLSDRError> {
+    source.set_center_freq(freq)?;
+    source.reset_buffer()?;
+    source.read_sync(SETTLE_BYTES)?;
+    Ok(iq_from_u8(&source.read_sync(len)?))
+}
+
+/// Measure the power on every channel of `band`, strongest first.
+///
+/// The source must already be at GSM_RATE.
+pub fn scan_band<S: Source>(source: &mut S, band: GsmBand)
+                            -> Result<Vec<ChannelPower>, RTLSDRError> {
+    let mut powers = Vec::new();
+    for (arfcn, freq) in band.channels() {
+        let samples = capture(source, freq, POWER_BYTES)?;
+        let p = samples.iter().map(|s| s.norm_sqr() as f64).sum::<f64>()
+                / samples.len().max(1) as f64;
+        powers.push(ChannelPower { arfcn, freq, power: 10.0 * p.log10() });
+    }
+    powers.sort_by(|a, b| b.power.total_cmp(&a.power));
+    Ok(powers)
+}
+
+/// Calibrate `source` against GSM base stations.
+///
+/// Scans the band, listens for FCCH bursts on the strongest channels and
+/// combines every burst into one estimate. The source's centre frequency and
+/// sample rate are restored afterwards; if `options.apply` is set the
+/// nearest integer ppm is then applied with set_freq_correction.
+pub fn calibrate<S: Source>(source: &mut S, options: &CalibrationOptions)
+                            -> Result<Calibration, RTLSDRError> {
+    let old_freq = source.get_center_freq()?;
+    let old_rate = source.get_sample_rate()?;
+    let current = source.get_freq_correction() as f64;
+
+    source.set_sample_rate(GSM_RATE)?;
+    let result = listen(source, options);
+    source.set_sample_rate(old_rate)?;
+    source.set_center_freq(old_freq)?;
+
+    let mut cal = result?;
+    cal.ppm += current;
+    if options.apply {
+        source.set_freq_correction(cal.ppm.round() as i32)?;
+    }
+    Ok(cal)
+}
+
+fn listen<S: Source>(source: &mut S, options: &CalibrationOptions)
+                     -> Result<Calibration, RTLSDRError> {
+    let rate = source.get_sample_rate()? as f64;
+    // Round up to a whole number of USB transfers.
+    let len = ((options.duration * rate * 2.0) as usize).div_ceil(16384)
+              * 16384;
+    let powers = scan_band(source, options.band)?;
+    let mut estimates = Vec::new();
+    for ch in powers.iter().take(options.channels) {
+        let samples = capture(source, ch.freq, len)?;
+        for b in detect_fcch(&samples, rate) {
+            estimates.push(-b.offset / ch.freq as f64 * 1e6);
+        }
+    }
+    combine(&estimates).ok_or(rtlsdr_error(-1, "No FCCH bursts found"))
+}
@@ -0,0 +1,466 @@
+// Channelisation for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Splits one wideband capture into many narrowband channel streams. A
+// polyphase filter bank (PFB) covers a uniform grid of channels for the cost
+// of one prototype filter and one DFT shared between them all. Channels at
+// arbitrary frequencies and bandwidths are extracted instead by mixing each
+// to baseband with an NCO and filtering, which costs more per channel.
+
+use super::{RTLSDRError, rtlsdr_error};
+use super::dsp::{Complex, Nco};
+use super::dsp::decimate::Decimator;
+use super::dsp::fft::Dft;
+use super::dsp::filter::{Fir, lowpass, taps_for_transition};
+use super::dsp::window::Window;
+use super::samples::iq_from_u8;
+use super::source::Source;
+
+/// Fraction of the sample rate in which channels may be placed, since the
+/// RTL2832U's anti-aliasing filter rolls off towards the capture edges.
+pub const USABLE_BANDWIDTH: f64 = 0.9;
+
+/// Choose a centre frequency (in Hz) for a capture at `rate` Hz that holds
+/// every channel in `frequencies` (in Hz), each `bandwidth` Hz wide, within
+/// the usable part of the capture.
+///
+/// Where possible the centre is placed at least a channel's width from
+/// every channel, to keep them all clear of the spike at DC.
+pub fn plan_center(frequencies: &[f64], bandwidth: f64, rate: u32)
+                   -> Result<u64, RTLSDRError> {
+    if frequencies.is_empty() || bandwidth <= 0.0 {
+        return Err(rtlsdr_error(-1, "No channels given"));
+    }
+    let low = frequencies.iter().cloned().fold(f64::INFINITY, f64::min) -
+              bandwidth / 2.0;
+    let high = frequencies.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+               + bandwidth / 2.0;
+    let half = USABLE_BANDWIDTH * rate as f64 / 2.0;
+    if high - low > 2.0 * half {
+        return Err(rtlsdr_error(-1, "Channels do not fit in the capture"));
+    }
+    // Any centre from high - half to low + half fits.
+    let mid = (low + high) / 2.0;
+    let fits = |c: f64| c >= high - half && c <= low + half;
+    let clear = |c: f64| frequencies.iter().all(|f| (f - c).abs() >= bandwidth);
+    let best = frequencies.iter()
+        .flat_map(|&f| [f - bandwidth, f + bandwidth])
+        .chain(std::iter::once(mid))
+        .filter(|&c| fits(c) && clear(c))
+        .min_by(|a, b| (a - mid).abs().total_cmp(&(b - mid).abs()));
+    Ok(best.unwrap_or(mid).round() as u64)
+}
+
+/// A block of samples from one channel.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    /// Centre frequency of the channel (in Hz); RF if the capture's centre
+    /// frequency is known, otherwise relative to it.
+    pub frequency: f64,
+    /// Sample rate of the channel (in Hz).
+    pub rate: f64,
+    /// Baseband samples, with the channel centre at DC.
+    pub samples: Vec<Complex>
+}
+
+/// Something which splits a capture into channels.
+pub trait Channelizer {
+    /// Sample rate of the capture (in Hz).
+    fn input_rate(&self) -> f64;
+
+    /// Set the centre frequency of the capture (in Hz), which channel
+    /// frequencies are reported relative to.
+    fn set_center_freq(&mut self, frequency: u64);
+
+    /// Centre frequency of each channel (in Hz), in the order `process`
+    /// returns them.
+    fn frequencies(&self) -> Vec<f64>;
+
+    /// Clear all filter state.
+    fn reset(&mut self);
+
+    /// Channelise a block of samples, returning one Channel per channel
+    /// holding however many outputs are due.
+    fn process(&mut self, samples: &[Complex]) -> Vec<Channel>;
+
+    /// Read `len` bytes from `source` and channelise them, following the
+    /// source's centre frequency.
+    fn read<S: Source>(&mut self, source: &mut S, len: usize)
+                       -> Result<Vec<Channel>, RTLSDRError>
+                       where Self: Sized {
+        if source.get_sample_rate()? as f64 != self.input_rate() {
+            return Err(rtlsdr_error(-1, "Sample rate does not match"));
+        }
+        if let Ok(frequency) = source.get_center_freq() {
+            self.set_center_freq(frequency);
+        }
+        let buf = source.read_sync(len)?;
+        Ok(self.process(&iq_from_u8(&buf)))
+    }
+}
+
+/// Settings for a PfbChannelizer.
+#[derive(Clone, Copy, Debug)]
+pub struct PfbConfig {
+    /// Sample rate of the capture (in Hz).
+    pub rate: f64,
+    /// Channel spacing (in Hz), which must divide the sample rate.
+    pub spacing: f64,
+    /// Channel sample rate as a multiple of the spacing: 1 for critical
+    /// sampling, or 2 so that the edges of each channel don't alias. Must
+    /// divide the number of channels.
+    pub oversample: usize,
+    /// Prototype filter taps per channel.
+    pub taps_per_channel: usize,
+    /// -6dB bandwidth of each channel as a fraction of the spacing.
+    pub bandwidth: f64,
+    /// Window used to design the prototype filter.
+    pub window: Window
+}
+
+impl PfbConfig {
+    /// Channels every `spacing` Hz across a capture at `rate` Hz, sampled
+    /// at twice the spacing, with 24 Blackman windowed taps per channel.
+    pub fn new(rate: f64, spacing: f64) -> PfbConfig {
+        PfbConfig {
+            rate,
+            spacing,
+            oversample: 2,
+            taps_per_channel: 24,
+            bandwidth: 1.0,
+            window: Window::Blackman
+        }
+    }
+
+    /// Total number of channels, which together cover the whole capture.
+    pub fn channels(&self) -> usize {
+        (self.rate / self.spacing).round() as usize
+    }
+
+    /// Design the prototype lowpass filter.
+    pub fn prototype(&self) -> Vec<f32> {
+        let n = self.channels();
+        let cutoff = 0.5 * self.bandwidth / n as f64;
+        lowpass(n * self.taps_per_channel, cutoff, self.window)
+    }
+}
+
+/// Splits a capture into uniformly spaced channels with a polyphase filter
+/// bank, keeping state between blocks.
+///
+/// Channels are numbered from the most negative frequency, with the capture
+/// centre in channel `channels() / 2`.
+pub struct PfbChannelizer {
+    config: PfbConfig,
+    taps: Vec<f32>,
+    dft: Dft,
+    /// exp(-2 pi j k / n), to correct the phase of each channel when
+    /// decimating by less than the number of channels.
+    rotation: Vec<Complex>,
+    /// Channel numbers to output.
+    selected: Vec<usize>,
+    center: u64,
+    history: Vec<Complex>,
+    skip: usize,
+    /// Index of the next output's newest input sample, modulo the number
+    /// of channels.
+    phase: usize,
+    scratch: Vec<Complex>
+}
+
+impl PfbChannelizer {
+    /// Create a channelizer with a prototype filter designed from `config`.
+    pub fn new(config: PfbConfig) -> Result<PfbChannelizer, RTLSDRError> {
+        let taps = config.prototype();
+        PfbChannelizer::with_prototype(config, taps)
+    }
+
+    /// Create a channelizer with the given prototype lowpass filter, which
+    /// is designed at the capture's sample rate and is zero padded to a
+    /// multiple of the number of channels.
+    pub fn with_prototype(config: PfbConfig, mut taps: Vec<f32>)
+                          -> Result<PfbChannelizer, RTLSDRError> {
+        if config.rate <= 0.0 || config.spacing <= 0.0 {
+            return Err(rtlsdr_error(-1, "Invalid channel spacing"));
+        }
+        let n = config.channels();
+        if n == 0 || (n as f64 * config.spacing - config.rate).abs() >
+                     config.rate * 1e-9 {
+            return Err(rtlsdr_error(-1,
+                "Channel spacing must divide the sample rate"));
+        }
+        if config.oversample == 0 || !n.is_multiple_of(config.oversample) {
+            return Err(rtlsdr_error(-1,
+                "Oversampling must divide the number of channels"));
+        }
+        let len = taps.len().max(1).div_ceil(n) * n;
+        taps.resize(len, 0.0);
+        let rotation = (0..n).map(|k| {
+            let phase = -2.0 * std::f64::consts::PI * k as f64 / n as f64;
+            Complex::new(phase.cos() as f32, phase.sin() as f32)
+        }).collect();
+        Ok(PfbChannelizer {
+            config,
+            history: vec![Complex::default(); len - 1],
+            taps,
+            dft: Dft::new(n),
+            rotation,
+            selected: (0..n).collect(),
+            center: 0,
+            skip: 0,
+            phase: 0,
+            scratch: vec![Complex::default(); n]
+        })
+    }
+
+    pub fn config(&self) -> &PfbConfig {
+        &self.config
+    }
+
+    /// Total number of channels.
+    pub fn channels(&self) -> usize {
+        self.dft.len()
+    }
+
+    /// Sample rate of each channel (in Hz).
+    pub fn output_rate(&self) -> f64 {
+        self.config.spacing * self.config.oversample as f64
+    }
+
+    /// Centre frequency (in Hz) of channel `channel`.
+    pub fn channel_freq(&self, channel: usize) -> f64 {
+        let offset = channel as f64 - (self.channels() / 2) as f64;
+        self.center as f64 + offset * self.config.spacing
+    }
+
+    /// The channel containing `frequency` (in Hz), if any.
+    pub fn channel_for(&self, frequency: f64) -> Option<usize> {
+        let offset = (frequency - self.center as f64) / self.config.spacing;
+        let channel = offset.round() + (self.channels() / 2) as f64;
+        if channel >= 0.0 && channel < self.channels() as f64 {
+            Some(channel as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Output only the channels numbered in `channels`, in that order.
+    /// Numbers past the last channel are ignored.
+    pub fn select(&mut self, channels: &[usize]) {
+        let n = self.channels();
+        self.selected = channels.iter().cloned().filter(|&c| c < n).collect();
+    }
+
+    /// Output every channel, in increasing frequency. This is the default.
+    pub fn select_all(&mut self) {
+        self.selected = (0..self.channels()).collect();
+    }
+}
+
+impl Channelizer for PfbChannelizer {
+    fn input_rate(&self) -> f64 {
+        self.config.rate
+    }
+
+    fn set_center_freq(&mut self, frequency: u64) {
+        self.center = frequency;
+    }
+
+    fn frequencies(&self) -> Vec<f64> {
+        self.selected.iter().map(|&c| self.channel_freq(c)).collect()
+    }
+
+    fn reset(&mut self) {
+        for h in self.history.iter_mut() {
+            *h = Complex::default();
+        }
+        self.skip = 0;
+        self.phase = 0;
+    }
+
+    fn process(&mut self, samples: &[Complex]) -> Vec<Channel> {
+        let n = self.channels();
+        let len = self.taps.len();
+        let step = n / self.config.oversample;
+        let mut buf = std::mem::take(&mut self.history);
+        buf.extend_from_slice(samples);
+        let mut outputs: Vec<Vec<Complex>> =
+            vec![Vec::with_capacity(samples.len() / step + 1);
+                 self.selected.len()];
+        let mut i = self.skip;
+        while i + len <= buf.len() {
+            // Each branch of the bank filters every nth sample. The branch
+            // outputs are conjugated so that the forward DFT mixes each
+            // channel down rather than up.
+            let newest = i + len - 1;
+            for (r, u) in self.scratch.iter_mut().enumerate() {
+                let mut acc = Complex::default();
+                for (j, &h) in self.taps.iter().enumerate().skip(r)
+                                        .step_by(n) {
+                    acc += buf[newest - j] * h;
+                }
+                *u = acc.conj();
+            }
+            self.dft.process(&mut self.scratch);
+            for (out, &c) in outputs.iter_mut().zip(&self.selected) {
+                let bin = (c + n - n / 2) % n;
+                let turn = self.rotation[bin * self.phase % n];
+                out.push(self.scratch[bin].conj() * turn);
+            }
+            self.phase = (self.phase + step) % n;
+            i += step;
+        }
+        let keep = buf.len() - (len - 1);
+        self.skip = i - keep;
+        self.history = buf.split_off(keep);
+
+        let rate = self.output_rate();
+        outputs.into_iter().zip(&self.selected).map(|(samples, &c)| {
+            Channel { frequency: self.channel_freq(c), rate, samples }
+        }).collect()
+    }
+}
+
+/// Extracts one channel at an arbitrary frequency, keeping state between
+/// blocks.
+///
+/// The channel is mixed to baseband by an NCO, decimated to a rate of at
+/// least twice its bandwidth, then filtered to its bandwidth.
+pub struct Extractor {
+    frequency: f64,
+    bandwidth: f64,
+    rate: f64,
+    nco: Nco,
+    decimator: Decimator<Complex>,
+    filter: Fir<Complex>
+}
+
+impl Extractor {
+    /// Extract `bandwidth` Hz centred on `frequency` (in Hz) from a capture
+    /// at `rate` Hz centred on `center` Hz.
+    pub fn new(frequency: f64, bandwidth: f64, rate: f64, center: u64)
+               -> Extractor {
+        let bandwidth = bandwidth.clamp(1.0, rate);
+        let factor = ((rate / (2.0 * bandwidth)) as usize).max(1);
+        let out_rate = rate / factor as f64;
+        // Only the channel itself need be protected from aliases here; the
+        // rest of the output band is removed by the channel filter.
+        let passband = bandwidth / out_rate;
+        let cutoff = 0.6 * bandwidth / out_rate;
+        let taps = taps_for_transition(0.2 * bandwidth / out_rate);
+        Extractor {
+            frequency,
+            bandwidth,
+            rate,
+            nco: Nco::new(center as f64 - frequency, rate),
+            decimator: Decimator::new(factor, passband),
+            filter: Fir::new(lowpass(taps, cutoff.min(0.5), Window::Blackman))
+        }
+    }
+
+    /// Centre frequency of the channel (in Hz).
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Bandwidth of the channel (in Hz).
+    pub fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    /// Sample rate of the output (in Hz).
+    pub fn output_rate(&self) -> f64 {
+        self.rate / self.decimator.factor() as f64
+    }
+
+    /// Follow a change in the capture's centre frequency (in Hz).
+    pub fn set_center_freq(&mut self, center: u64) {
+        self.nco.set_freq(center as f64 - self.frequency, self.rate);
+    }
+
+    /// Clear the filter state.
+    pub fn reset(&mut self) {
+        self.decimator.reset();
+        self.filter.reset();
+    }
+
+    /// Extract the channel from a block, returning however many outputs
+    /// are due.
+    pub fn process(&mut self, samples: &[Complex]) -> Vec<Complex> {
+        let mut mixed = samples.to_vec();
+        self.nco.mix(&mut mixed);
+        self.filter.process(&self.decimator.process(&mixed))
+    }
+}
+
+/// Extracts any number of channels at arbitrary frequencies.
+pub struct ExtractorBank {
+    rate: f64,
+    center: u64,
+    extractors: Vec<Extractor>
+}
+
+impl ExtractorBank {
+    /// Create an empty bank for a capture at `rate` Hz.
+    pub fn new(rate: f64) -> ExtractorBank {
+        ExtractorBank { rate, center: 0, extractors: Vec::new() }
+    }
+
+    /// Add a channel of `bandwidth` Hz centred on `frequency` (in Hz),
+    /// which must lie within the capture. Set the capture's centre
+    /// frequency first to give RF frequencies.
+    pub fn add(&mut self, frequency: f64, bandwidth: f64)
+               -> Result<(), RTLSDRError> {
+        let edge = (frequency - self.center as f64).abs() + bandwidth / 2.0;
+        if bandwidth <= 0.0 || edge > self.rate / 2.0 {
+            return Err(rtlsdr_error(-1, "Channel is outside the capture"));
+        }
+        self.extractors.push(Extractor::new(frequency, bandwidth, self.rate,
+                                            self.center));
+        Ok(())
+    }
+
+    pub fn extractors(&self) -> &[Extractor] {
+        &self.extractors
+    }
+
+    pub fn len(&self) -> usize {
+        self.extractors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extractors.is_empty()
+    }
+}
+
+impl Channelizer for ExtractorBank {
+    fn input_rate(&self) -> f64 {
+        self.rate
+    }
+
+    fn set_center_freq(&mut self, frequency: u64) {
+        self.center = frequency;
+        for e in self.extractors.iter_mut() {
+            e.set_center_freq(frequency);
+        }
+    }
+
+    fn frequencies(&self) -> Vec<f64> {
+        self.extractors.iter().map(|e| e.frequency()).collect()
+    }
+
+    fn reset(&mut self) {
+        for e in self.extractors.iter_mut() {
+            e.reset();
+        }
+    }
+
+    fn process(&mut self, samples: &[Complex]) -> Vec<Channel> {
+        self.extractors.iter_mut().map(|e| Channel {
+            frequency: e.frequency(),
+            rate: e.output_rate(),
+            samples: e.process(samples)
+        }).collect()
+    }
+}
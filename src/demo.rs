@@ -4,9 +4,7 @@
 
 extern crate rtlsdr;
 
-use std::fs::File;
-use std::path::Path;
-use std::io::prelude::*;
+use rtlsdr::sigmf::{Metadata, SigmfWriter};
 
 fn main() {
     let count = rtlsdr::get_device_count();
@@ -93,10 +91,11 @@ fn main() {
 
         dev.reset_buffer().unwrap();
 
-        let data = dev.read_sync(131072).unwrap();
-
-        let mut file = File::create(&Path::new("data.bin")).unwrap();
-        file.write(&data).unwrap();
+        println!("  Recording to data.sigmf-data...");
+        let meta = Metadata::from_device(&mut dev).unwrap();
+        let mut rec = SigmfWriter::create("data", &meta).unwrap();
+        rec.record_from(&mut dev, 131072).unwrap();
+        rec.finish().unwrap();
 
         println!("  Closing device...");
         dev.close().unwrap();
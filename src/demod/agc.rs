@@ -0,0 +1,52 @@
+// Audio AGC for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+/// Audio automatic gain control with separate attack and decay times.
+///
+/// An envelope follower rises quickly when the signal gets louder and falls
+/// slowly when it gets quieter; the gain brings the envelope to the target
+/// level, up to a maximum gain so silence is not amplified without limit.
+#[derive(Clone, Debug)]
+pub struct Agc {
+    target: f32,
+    max_gain: f32,
+    attack: f32,
+    decay: f32,
+    level: f32
+}
+
+impl Agc {
+    /// Create an AGC with `attack` and `decay` times (in seconds) for audio
+    /// at `rate` (in Hz), bringing peaks to 0.5 with at most 60dB of gain.
+    pub fn new(attack: f64, decay: f64, rate: f64) -> Agc {
+        let coef = |t: f64| (1.0 - (-1.0 / (t * rate)).exp()) as f32;
+        Agc { target: 0.5, max_gain: 1000.0, attack: coef(attack),
+              decay: coef(decay), level: 0.0 }
+    }
+
+    /// Set the output level the AGC aims for.
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    /// Set the largest gain (as a ratio) the AGC may apply.
+    pub fn set_max_gain(&mut self, max_gain: f32) {
+        self.max_gain = max_gain;
+    }
+
+    /// Current gain (as a ratio).
+    pub fn gain(&self) -> f32 {
+        (self.target / self.level.max(1e-9)).min(self.max_gain)
+    }
+
+    /// Apply the AGC to a block in place.
+    pub fn process(&mut self, buf: &mut [f32]) {
+        for x in buf.iter_mut() {
+            let m = x.abs();
+            let coef = if m > self.level { self.attack } else { self.decay };
+            self.level += coef * (m - self.level);
+            *x *= self.gain();
+        }
+    }
+}
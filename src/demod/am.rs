@@ -0,0 +1,143 @@
+// AM demodulators for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::{Channel, Demodulator, Squelch, audio_resampler};
+use super::agc::Agc;
+use crate::dsp::Complex;
+use crate::dsp::filter::SinglePole;
+use crate::dsp::resample::Resampler;
+
+/// Time constant of the filter removing the carrier's DC (in seconds).
+const DC_TAU: f64 = 0.05;
+
+/// How AM is detected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmMode {
+    /// Envelope detection, as a diode detector would.
+    Envelope,
+    /// Synchronous detection against a PLL locked to the carrier, which
+    /// copes better with selective fading.
+    Synchronous
+}
+
+/// Settings for an AmDemod.
+#[derive(Clone, Copy, Debug)]
+pub struct AmConfig {
+    /// Input sample rate (in Hz).
+    pub rate: u32,
+    /// Offset of the channel from the centre of the input (in Hz).
+    pub offset: f64,
+    /// Channel filter bandwidth (in Hz).
+    pub bandwidth: f64,
+    pub mode: AmMode,
+    /// Output audio sample rate (in Hz).
+    pub audio_rate: u32,
+    /// Audio lowpass cutoff (in Hz).
+    pub audio_cutoff: f64,
+    /// Squelch threshold (in dB relative to full scale), if any.
+    pub squelch: Option<f64>
+}
+
+impl AmConfig {
+    /// Envelope detected AM with a channel suiting airband and broadcast.
+    pub fn new(rate: u32) -> AmConfig {
+        AmConfig {
+            rate, offset: 0.0, bandwidth: 10_000.0, mode: AmMode::Envelope,
+            audio_rate: 48_000, audio_cutoff: 4_500.0, squelch: None
+        }
+    }
+}
+
+/// Second order PLL tracking the carrier for synchronous detection.
+#[derive(Clone, Debug)]
+struct CarrierPll {
+    phase: f32,
+    freq: f32,
+    alpha: f32,
+    beta: f32
+}
+
+impl CarrierPll {
+    /// PLL with a loop bandwidth of `bandwidth` (in Hz) at `rate` (in Hz).
+    fn new(bandwidth: f64, rate: f64) -> CarrierPll {
+        let wn = 2.0 * std::f64::consts::PI * bandwidth / rate;
+        let zeta = std::f64::consts::FRAC_1_SQRT_2;
+        CarrierPll { phase: 0.0, freq: 0.0, alpha: (2.0 * zeta * wn) as f32,
+                     beta: (wn * wn) as f32 }
+    }
+
+    /// Mix a sample down by the tracked carrier and update the loop.
+    fn step(&mut self, x: Complex) -> Complex {
+        let y = x * Complex::expj(-self.phase);
+        let err = y.arg();
+        self.freq += self.beta * err;
+        self.phase += self.freq + self.alpha * err;
+        self.phase %= 2.0 * std::f32::consts::PI;
+        y
+    }
+}
+
+/// AM demodulator: channel filter, envelope or synchronous detection, DC
+/// removal, resampling to the audio rate, squelch and AGC.
+pub struct AmDemod {
+    channel: Channel,
+    pll: Option<CarrierPll>,
+    dc: SinglePole<f32>,
+    resampler: Resampler<f32>,
+    agc: Agc,
+    squelch: Squelch
+}
+
+impl AmDemod {
+    pub fn new(config: &AmConfig) -> AmDemod {
+        let channel = Channel::new(config.rate, config.offset,
+                                   config.bandwidth);
+        let rate = channel.rate();
+        let pll = match config.mode {
+            AmMode::Envelope => None,
+            AmMode::Synchronous => Some(CarrierPll::new(50.0, rate))
+        };
+        AmDemod {
+            pll,
+            dc: SinglePole::new(DC_TAU, rate),
+            resampler: audio_resampler(&channel, config.rate,
+                                       config.audio_rate, config.audio_cutoff),
+            agc: Agc::new(0.005, 0.5, config.audio_rate as f64),
+            squelch: Squelch::new(config.squelch),
+            channel
+        }
+    }
+
+    /// The AGC, for adjusting its target and maximum gain.
+    pub fn agc_mut(&mut self) -> &mut Agc {
+        &mut self.agc
+    }
+}
+
+impl Demodulator for AmDemod {
+    fn process(&mut self, samples: &[Complex]) -> Vec<f32> {
+        let channel = self.channel.process(samples);
+        let open = self.squelch.check(&channel);
+        let mut audio: Vec<f32> = match self.pll {
+            Some(ref mut pll) => channel.iter().map(|&x| pll.step(x).re)
+                                        .collect(),
+            None => channel.iter().map(|x| x.norm()).collect()
+        };
+        let mut dc = audio.clone();
+        self.dc.process(&mut dc);
+        for (a, d) in audio.iter_mut().zip(&dc) {
+            *a -= d;
+        }
+        let mut audio = self.resampler.process(&audio);
+        self.agc.process(&mut audio);
+        if !open {
+            audio.iter_mut().for_each(|a| *a = 0.0);
+        }
+        audio
+    }
+
+    fn squelch(&self) -> &Squelch {
+        &self.squelch
+    }
+}
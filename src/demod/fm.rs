@@ -0,0 +1,128 @@
+// FM demodulator for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::{Channel, Demodulator, Squelch, audio_resampler};
+use crate::dsp::Complex;
+use crate::dsp::filter::SinglePole;
+use crate::dsp::resample::Resampler;
+
+/// De-emphasis time constant used in Europe (in seconds).
+pub const DEEMPHASIS_EU: f64 = 50e-6;
+
+/// De-emphasis time constant used in the Americas (in seconds).
+pub const DEEMPHASIS_US: f64 = 75e-6;
+
+/// Settings for an FmDemod.
+#[derive(Clone, Copy, Debug)]
+pub struct FmConfig {
+    /// Input sample rate (in Hz).
+    pub rate: u32,
+    /// Offset of the channel from the centre of the input (in Hz).
+    pub offset: f64,
+    /// Channel filter bandwidth (in Hz).
+    pub bandwidth: f64,
+    /// Peak deviation (in Hz), which gives full scale audio.
+    pub deviation: f64,
+    /// De-emphasis time constant (in seconds), if any.
+    pub deemphasis: Option<f64>,
+    /// Output audio sample rate (in Hz).
+    pub audio_rate: u32,
+    /// Audio lowpass cutoff (in Hz).
+    pub audio_cutoff: f64,
+    /// Squelch threshold (in dB relative to full scale), if any.
+    pub squelch: Option<f64>
+}
+
+impl FmConfig {
+    /// Broadcast (wideband) FM, with European de-emphasis.
+    pub fn broadcast(rate: u32) -> FmConfig {
+        FmConfig {
+            rate, offset: 0.0, bandwidth: 180_000.0, deviation: 75_000.0,
+            deemphasis: Some(DEEMPHASIS_EU), audio_rate: 48_000,
+            audio_cutoff: 15_000.0, squelch: None
+        }
+    }
+
+    /// Narrowband FM, as used for voice on 12.5kHz channels.
+    pub fn narrow(rate: u32) -> FmConfig {
+        FmConfig {
+            rate, offset: 0.0, bandwidth: 12_500.0, deviation: 2_500.0,
+            deemphasis: None, audio_rate: 48_000, audio_cutoff: 3_500.0,
+            squelch: None
+        }
+    }
+}
+
+/// Quadrature discriminator, producing instantaneous frequency as a
+/// fraction of `gain`, keeping its last sample between blocks.
+#[derive(Clone, Debug)]
+pub struct Discriminator {
+    last: Complex,
+    gain: f32
+}
+
+impl Discriminator {
+    /// Discriminator giving an output of 1 at `deviation` (in Hz) for input
+    /// at `rate` (in Hz).
+    pub fn new(deviation: f64, rate: f64) -> Discriminator {
+        let gain = rate / (2.0 * std::f64::consts::PI * deviation);
+        Discriminator { last: Complex::new(1.0, 0.0), gain: gain as f32 }
+    }
+
+    pub fn process(&mut self, samples: &[Complex]) -> Vec<f32> {
+        samples.iter().map(|&s| {
+            let d = (s * self.last.conj()).arg() * self.gain;
+            self.last = s;
+            d
+        }).collect()
+    }
+}
+
+/// FM demodulator: channel filter, decimation, discriminator, optional
+/// de-emphasis, resampling to the audio rate and squelch.
+pub struct FmDemod {
+    channel: Channel,
+    discriminator: Discriminator,
+    deemphasis: Option<SinglePole<f32>>,
+    resampler: Resampler<f32>,
+    squelch: Squelch
+}
+
+impl FmDemod {
+    pub fn new(config: &FmConfig) -> FmDemod {
+        let channel = Channel::new(config.rate, config.offset,
+                                   config.bandwidth);
+        let if_rate = channel.rate();
+        let resampler = audio_resampler(&channel, config.rate,
+                                        config.audio_rate, config.audio_cutoff);
+        FmDemod {
+            discriminator: Discriminator::new(config.deviation, if_rate),
+            deemphasis: config.deemphasis
+                              .map(|tau| SinglePole::new(tau, if_rate)),
+            resampler,
+            squelch: Squelch::new(config.squelch),
+            channel
+        }
+    }
+}
+
+impl Demodulator for FmDemod {
+    fn process(&mut self, samples: &[Complex]) -> Vec<f32> {
+        let channel = self.channel.process(samples);
+        let open = self.squelch.check(&channel);
+        let mut audio = self.discriminator.process(&channel);
+        if let Some(ref mut d) = self.deemphasis {
+            d.process(&mut audio);
+        }
+        let mut audio = self.resampler.process(&audio);
+        if !open {
+            audio.iter_mut().for_each(|a| *a = 0.0);
+        }
+        audio
+    }
+
+    fn squelch(&self) -> &Squelch {
+        &self.squelch
+    }
+}
@@ -0,0 +1,263 @@
+// Demodulators for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+pub mod agc;
+pub mod am;
+pub mod fm;
+pub mod ssb;
+
+use super::dsp::{Complex, Nco};
+use super::dsp::decimate::{Decimator, HalfBand};
+use super::dsp::filter::{Fir, lowpass, taps_for_transition};
+use super::dsp::resample::Resampler;
+use super::dsp::window::Window;
+
+/// Longest channel filter we will design.
+const MAX_TAPS: usize = 4095;
+
+/// Width of the channel filter's transition band, as a fraction of the
+/// channel bandwidth.
+const TRANSITION: f64 = 0.1;
+
+/// Common interface to the demodulators, so the mode can be chosen at
+/// runtime.
+pub trait Demodulator {
+    /// Demodulate a block of samples into audio.
+    ///
+    /// While the squelch is closed the audio is silent, but the same number
+    /// of samples is produced so timing is preserved.
+    fn process(&mut self, samples: &[Complex]) -> Vec<f32>;
+
+    /// The squelch, for reading the channel power.
+    fn squelch(&self) -> &Squelch;
+}
+
+/// Demodulation modes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    WidebandFm, NarrowFm, Am, SyncAm, Usb, Lsb, Cw
+}
+
+impl Mode {
+    /// Look up a mode by its short name, as used by rtl_fm: wbfm, fm, am,
+    /// sam, usb, lsb or cw.
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "wbfm" => Some(Mode::WidebandFm),
+            "fm" | "nbfm" => Some(Mode::NarrowFm),
+            "am" => Some(Mode::Am),
+            "sam" => Some(Mode::SyncAm),
+            "usb" => Some(Mode::Usb),
+            "lsb" => Some(Mode::Lsb),
+            "cw" => Some(Mode::Cw),
+            _ => None
+        }
+    }
+}
+
+/// Create a demodulator for `mode` with its default settings, for input at
+/// `rate` (in Hz) with the channel at `offset` (in Hz) from the centre.
+pub fn new_demodulator(mode: Mode, rate: u32, offset: f64, audio_rate: u32,
+                       squelch: Option<f64>) -> Box<dyn Demodulator> {
+    match mode {
+        Mode::WidebandFm | Mode::NarrowFm => {
+            let mut c = match mode {
+                Mode::WidebandFm => fm::FmConfig::broadcast(rate),
+                _ => fm::FmConfig::narrow(rate)
+            };
+            c.offset = offset;
+            c.audio_rate = audio_rate;
+            c.squelch = squelch;
+            Box::new(fm::FmDemod::new(&c))
+        },
+        Mode::Am | Mode::SyncAm => {
+            let mut c = am::AmConfig::new(rate);
+            if mode == Mode::SyncAm {
+                c.mode = am::AmMode::Synchronous;
+            }
+            c.offset = offset;
+            c.audio_rate = audio_rate;
+            c.squelch = squelch;
+            Box::new(am::AmDemod::new(&c))
+        },
+        Mode::Usb | Mode::Lsb | Mode::Cw => {
+            let sideband = match mode {
+                Mode::Usb => ssb::Sideband::Usb,
+                Mode::Lsb => ssb::Sideband::Lsb,
+                _ => ssb::Sideband::Cw
+            };
+            let mut c = ssb::SsbConfig::new(rate, sideband);
+            c.offset = offset;
+            c.audio_rate = audio_rate;
+            c.squelch = squelch;
+            Box::new(ssb::SsbDemod::new(&c))
+        }
+    }
+}
+
+/// Resampler from a channel's output to `audio_rate` (in Hz), lowpass
+/// filtered at `cutoff` (in Hz).
+///
+/// The ratio is formed from the integer input rate `rate` (in Hz) and the
+/// channel's decimation so it is exact.
+pub fn audio_resampler(channel: &Channel, rate: u32, audio_rate: u32,
+                       cutoff: f64) -> Resampler<f32> {
+    let cutoff = cutoff.min(0.45 * audio_rate as f64);
+    Resampler::with_cutoff(audio_rate as usize * channel.decimation(),
+                           rate as usize, cutoff / channel.rate())
+}
+
+/// Channel selection front end shared by the demodulators.
+///
+/// Shifts the wanted channel to 0Hz, filters it to its bandwidth and
+/// decimates to a rate at least twice that bandwidth.
+///
+/// Decimation is done in stages: half-band filters while the factor left
+/// is even, then one decimating filter for the rest, each only protecting
+/// the channel itself from aliasing. The channel filter then runs at the
+/// output rate, where a sharp cutoff is cheap, with its stopband starting
+/// at the channel edge.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    nco: Option<Nco>,
+    halfbands: Vec<HalfBand<Complex>>,
+    decimator: Option<Decimator<Complex>>,
+    filter: Fir<Complex>,
+    decimation: usize,
+    rate: f64
+}
+
+impl Channel {
+    /// Select a channel `bandwidth` wide (in Hz) at `offset` (in Hz) from
+    /// the centre of a stream at `rate` (in Hz).
+    pub fn new(rate: u32, offset: f64, bandwidth: f64) -> Channel {
+        Channel::with_min_rate(rate, offset, bandwidth, 0.0)
+    }
+
+    /// As `new`, but decimating to no less than `min_rate` (in Hz).
+    pub fn with_min_rate(rate: u32, offset: f64, bandwidth: f64,
+                         min_rate: f64) -> Channel {
+        let out_rate = (2.0 * bandwidth).max(min_rate);
+        let decimation = ((rate as f64 / out_rate) as usize).max(1);
+
+        let mut halfbands = Vec::new();
+        let mut stage_rate = rate as f64;
+        let mut remaining = decimation;
+        while remaining.is_multiple_of(2) {
+            // Pass the channel, and stop everything which would fold onto
+            // it.
+            let transition = 0.5 - bandwidth / stage_rate;
+            halfbands.push(HalfBand::new(taps_for_transition(transition)));
+            stage_rate /= 2.0;
+            remaining /= 2;
+        }
+        let decimator = if remaining > 1 {
+            let transition = 1.0 / remaining as f64 - bandwidth / stage_rate;
+            let taps = taps_for_transition(transition).min(MAX_TAPS);
+            let cutoff = 0.5 / remaining as f64;
+            Some(Decimator::with_taps(lowpass(taps, cutoff, Window::Blackman),
+                                      remaining))
+        } else {
+            None
+        };
+        let out_rate = rate as f64 / decimation as f64;
+
+        let transition = TRANSITION * bandwidth;
+        let taps = taps_for_transition(transition / out_rate).min(MAX_TAPS);
+        let cutoff = ((0.5 * bandwidth - 0.5 * transition) / out_rate)
+                     .min(0.5);
+        let filter = Fir::new(lowpass(taps, cutoff, Window::Blackman));
+        let nco = if offset == 0.0 {
+            None
+        } else {
+            Some(Nco::new(-offset, rate as f64))
+        };
+        Channel { nco, halfbands, decimator, filter, decimation,
+                  rate: out_rate }
+    }
+
+    /// Output sample rate (in Hz).
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Decimation from input to output rate.
+    pub fn decimation(&self) -> usize {
+        self.decimation
+    }
+
+    /// Clear the filter state.
+    pub fn reset(&mut self) {
+        for h in self.halfbands.iter_mut() {
+            h.reset();
+        }
+        if let Some(ref mut d) = self.decimator {
+            d.reset();
+        }
+        self.filter.reset();
+    }
+
+    /// Select the channel from a block of samples.
+    pub fn process(&mut self, samples: &[Complex]) -> Vec<Complex> {
+        let mut x = samples.to_vec();
+        if let Some(ref mut nco) = self.nco {
+            nco.mix(&mut x);
+        }
+        for h in self.halfbands.iter_mut() {
+            x = h.process(&x);
+        }
+        if let Some(ref mut d) = self.decimator {
+            x = d.process(&x);
+        }
+        self.filter.process(&x)
+    }
+}
+
+/// Power squelch, judged a block at a time.
+#[derive(Clone, Debug)]
+pub struct Squelch {
+    threshold: Option<f64>,
+    hysteresis: f64,
+    power: f64,
+    open: bool
+}
+
+impl Squelch {
+    /// Open the squelch when channel power reaches `threshold` (in dB
+    /// relative to full scale), or leave it always open if None.
+    pub fn new(threshold: Option<f64>) -> Squelch {
+        Squelch { threshold, hysteresis: 3.0, power: -200.0,
+                  open: threshold.is_none() }
+    }
+
+    /// Set how far (in dB) power must fall below the threshold to close an
+    /// open squelch. Default 3dB.
+    pub fn set_hysteresis(&mut self, hysteresis: f64) {
+        self.hysteresis = hysteresis;
+    }
+
+    /// Power of the most recent block (in dB relative to full scale).
+    pub fn power(&self) -> f64 {
+        self.power
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Measure a block of channel samples and decide whether the squelch is
+    /// open for it.
+    pub fn check(&mut self, samples: &[Complex]) -> bool {
+        let p = samples.iter().map(|s| s.norm_sqr() as f64).sum::<f64>()
+                / samples.len().max(1) as f64;
+        self.power = 10.0 * p.max(1e-20).log10();
+        if let Some(t) = self.threshold {
+            self.open = match self.open {
+                true => self.power >= t - self.hysteresis,
+                false => self.power >= t
+            };
+        }
+        self.open
+    }
+}
@@ -0,0 +1,123 @@
+// SSB and CW demodulators for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::{Channel, Demodulator, Squelch, audio_resampler};
+use super::agc::Agc;
+use crate::dsp::{Complex, Nco};
+use crate::dsp::resample::Resampler;
+
+/// Which part of the spectrum around the dial frequency becomes audio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sideband {
+    /// Upper sideband: audio is the spectrum above the dial frequency.
+    Usb,
+    /// Lower sideband: audio is the spectrum below the dial frequency.
+    Lsb,
+    /// CW: a narrow band centred on the dial frequency, heard as a tone at
+    /// the configured pitch.
+    Cw
+}
+
+/// Settings for an SsbDemod.
+#[derive(Clone, Copy, Debug)]
+pub struct SsbConfig {
+    /// Input sample rate (in Hz).
+    pub rate: u32,
+    /// Offset of the dial frequency from the centre of the input (in Hz).
+    pub offset: f64,
+    pub sideband: Sideband,
+    /// Audio bandwidth (in Hz).
+    pub bandwidth: f64,
+    /// Lowest audio frequency passed for USB/LSB (in Hz).
+    pub low_cut: f64,
+    /// Tone heard for CW (in Hz).
+    pub pitch: f64,
+    /// Output audio sample rate (in Hz).
+    pub audio_rate: u32,
+    /// Squelch threshold (in dB relative to full scale), if any.
+    pub squelch: Option<f64>
+}
+
+impl SsbConfig {
+    /// Voice SSB on the given sideband, or CW with a 500Hz filter and a
+    /// 700Hz pitch.
+    pub fn new(rate: u32, sideband: Sideband) -> SsbConfig {
+        let bandwidth = match sideband {
+            Sideband::Cw => 500.0,
+            _ => 2_700.0
+        };
+        SsbConfig {
+            rate, offset: 0.0, sideband, bandwidth, low_cut: 300.0,
+            pitch: 700.0, audio_rate: 48_000, squelch: None
+        }
+    }
+}
+
+/// SSB/CW demodulator.
+///
+/// The wanted sideband is centred on 0Hz so the shared channel filter can
+/// select it, then shifted to its place in the audio band and the real part
+/// taken.
+pub struct SsbDemod {
+    channel: Channel,
+    bfo: Nco,
+    resampler: Resampler<f32>,
+    agc: Agc,
+    squelch: Squelch
+}
+
+impl SsbDemod {
+    pub fn new(config: &SsbConfig) -> SsbDemod {
+        // Centre of the passband relative to the dial frequency.
+        let middle = config.low_cut + config.bandwidth / 2.0;
+        let (centre, audio) = match config.sideband {
+            Sideband::Usb => (middle, middle),
+            Sideband::Lsb => (-middle, middle),
+            Sideband::Cw => (0.0, config.pitch)
+        };
+        let top = audio + config.bandwidth / 2.0;
+        let channel = Channel::with_min_rate(config.rate,
+                                             config.offset + centre,
+                                             config.bandwidth, 4.0 * top);
+        let rate = channel.rate();
+        // LSB audio is the mirror image, so it is shifted the other way and
+        // comes out the right way round when the real part is taken.
+        let bfo = match config.sideband {
+            Sideband::Lsb => Nco::new(-audio, rate),
+            _ => Nco::new(audio, rate)
+        };
+        SsbDemod {
+            bfo,
+            resampler: audio_resampler(&channel, config.rate,
+                                       config.audio_rate, top),
+            agc: Agc::new(0.002, 0.8, config.audio_rate as f64),
+            squelch: Squelch::new(config.squelch),
+            channel
+        }
+    }
+
+    /// The AGC, for adjusting its target and maximum gain.
+    pub fn agc_mut(&mut self) -> &mut Agc {
+        &mut self.agc
+    }
+}
+
+impl Demodulator for SsbDemod {
+    fn process(&mut self, samples: &[Complex]) -> Vec<f32> {
+        let mut channel = self.channel.process(samples);
+        let open = self.squelch.check(&channel);
+        self.bfo.mix(&mut channel);
+        let audio: Vec<f32> = channel.iter().map(|x| x.re).collect();
+        let mut audio = self.resampler.process(&audio);
+        self.agc.process(&mut audio);
+        if !open {
+            audio.iter_mut().for_each(|a| *a = 0.0);
+        }
+        audio
+    }
+
+    fn squelch(&self) -> &Squelch {
+        &self.squelch
+    }
+}
@@ -0,0 +1,290 @@
+// Energy detection of signals for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Finds occupied bins in power spectra by comparing each bin with a local
+// noise floor estimated from the bins either side of it (constant false
+// alarm rate, or CFAR, detection). Occupied bins are merged into signals,
+// which are followed from frame to frame to give start and stop times.
+
+use std::time::SystemTime;
+
+use super::spectrum::Psd;
+
+/// How the noise floor around each bin is estimated from its training
+/// cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cfar {
+    /// Mean of the training cells (CA-CFAR). Best in uniform noise, but
+    /// masked by other signals among the training cells.
+    CellAveraging,
+    /// The training cell at the given rank, from 0 (lowest) to 1 (highest)
+    /// (OS-CFAR). Tolerates signals occupying the training cells.
+    OrderedStatistic(f64)
+}
+
+/// Settings for a Detector.
+#[derive(Clone, Copy, Debug)]
+pub struct DetectorConfig {
+    pub method: Cfar,
+    /// Bins either side of the bin under test excluded from the estimate,
+    /// so a signal doesn't raise its own noise floor.
+    pub guard: usize,
+    /// Bins either side, outside the guard bins, used for the estimate.
+    pub train: usize,
+    /// Level above the noise floor (in dB) at which a bin is occupied.
+    pub threshold: f64,
+    /// Largest run of unoccupied bins merged into a signal either side of
+    /// it.
+    pub merge_gap: usize,
+    /// Frames a signal may go undetected before it is considered ended.
+    pub hold: u32
+}
+
+impl DetectorConfig {
+    /// Median OS-CFAR with 2 guard and 16 training bins each side, and a
+    /// 10dB threshold.
+    pub fn new() -> DetectorConfig {
+        DetectorConfig {
+            method: Cfar::OrderedStatistic(0.5),
+            guard: 2,
+            train: 16,
+            threshold: 10.0,
+            merge_gap: 1,
+            hold: 2
+        }
+    }
+}
+
+impl Default for DetectorConfig {
+    fn default() -> DetectorConfig {
+        DetectorConfig::new()
+    }
+}
+
+/// A signal found in one frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detection {
+    /// Power-weighted centre frequency (in Hz).
+    pub center: f64,
+    /// Width of the occupied bins (in Hz).
+    pub bandwidth: f64,
+    /// Total power in the occupied bins (in dB).
+    pub power: f64,
+    /// Noise floor at the strongest bin (in dB per bin).
+    pub noise: f64,
+    /// Strongest bin relative to its noise floor (in dB).
+    pub snr: f64
+}
+
+impl Detection {
+    /// Lowest frequency occupied (in Hz).
+    pub fn low(&self) -> f64 {
+        self.center - self.bandwidth / 2.0
+    }
+
+    /// Highest frequency occupied (in Hz).
+    pub fn high(&self) -> f64 {
+        self.center + self.bandwidth / 2.0
+    }
+}
+
+/// A signal followed over one or more frames.
+#[derive(Clone, Debug)]
+pub struct Signal {
+    /// Lowest frequency occupied in any frame (in Hz).
+    pub low: f64,
+    /// Highest frequency occupied in any frame (in Hz).
+    pub high: f64,
+    /// Detection from the frame with the highest SNR.
+    pub peak: Detection,
+    /// Time of the first frame the signal was detected in.
+    pub start: SystemTime,
+    /// Time of the last frame the signal was detected in.
+    pub stop: SystemTime,
+    /// Number of frames the signal was detected in.
+    pub frames: u64,
+    missed: u32
+}
+
+impl Signal {
+    /// Centre of the occupied range (in Hz).
+    pub fn center(&self) -> f64 {
+        (self.low + self.high) / 2.0
+    }
+
+    /// Width of the occupied range (in Hz).
+    pub fn bandwidth(&self) -> f64 {
+        self.high - self.low
+    }
+
+    /// Time from first to last detection.
+    pub fn duration(&self) -> std::time::Duration {
+        self.stop.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+/// Estimate the noise floor (in dB) around each bin of `power` (in dB).
+///
+/// Near the edges, where one side has no training cells, only the other
+/// side is used.
+pub fn noise_floor(power: &[f64], config: &DetectorConfig) -> Vec<f64> {
+    let linear: Vec<f64> = power.iter().map(|p| 10f64.powf(p / 10.0))
+                                .collect();
+    let n = linear.len();
+    let near = config.guard + 1;
+    let far = config.guard + config.train;
+    let mut cells = Vec::with_capacity(2 * config.train);
+    (0..n).map(|i| {
+        cells.clear();
+        cells.extend((near..=far).filter(|&d| d <= i).map(|d| linear[i - d]));
+        cells.extend((near..=far).filter(|&d| i + d < n)
+                                 .map(|d| linear[i + d]));
+        let floor = if cells.is_empty() {
+            linear[i]
+        } else {
+            match config.method {
+                Cfar::CellAveraging =>
+                    cells.iter().sum::<f64>() / cells.len() as f64,
+                Cfar::OrderedStatistic(rank) => {
+                    cells.sort_by(f64::total_cmp);
+                    let k = (rank.clamp(0.0, 1.0) *
+                             (cells.len() - 1) as f64).round() as usize;
+                    cells[k]
+                }
+            }
+        };
+        10.0 * floor.max(1e-30).log10()
+    }).collect()
+}
+
+/// Finds signals in successive spectrum frames.
+pub struct Detector {
+    config: DetectorConfig,
+    active: Vec<Signal>
+}
+
+impl Detector {
+    pub fn new(config: DetectorConfig) -> Detector {
+        Detector { config, active: Vec::new() }
+    }
+
+    pub fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    /// Find the signals in one frame, given the frequency of its first bin
+    /// `start` and the bin width `step` (both in Hz), and the power in each
+    /// bin (in dB).
+    pub fn detect(&self, start: f64, step: f64, power: &[f64])
+                  -> Vec<Detection> {
+        let noise = noise_floor(power, &self.config);
+        let occupied: Vec<bool> = power.iter().zip(&noise)
+            .map(|(p, n)| p - n >= self.config.threshold)
+            .collect();
+
+        let mut detections = Vec::new();
+        let mut i = 0;
+        while i < occupied.len() {
+            if !occupied[i] {
+                i += 1;
+                continue;
+            }
+            // Extend the run while the next occupied bin is near enough.
+            let first = i;
+            let mut last = i;
+            let mut j = i + 1;
+            while j < occupied.len() && j - last <= self.config.merge_gap + 1 {
+                if occupied[j] {
+                    last = j;
+                }
+                j += 1;
+            }
+            let bins = first..=last;
+            let linear: Vec<f64> = power[bins.clone()].iter()
+                .map(|p| 10f64.powf(p / 10.0)).collect();
+            let total: f64 = linear.iter().sum();
+            let centroid = linear.iter().enumerate()
+                .map(|(k, p)| (first + k) as f64 * p).sum::<f64>() / total;
+            let strongest = bins.max_by(|&a, &b| power[a].total_cmp(&power[b]))
+                                .unwrap_or(first);
+            detections.push(Detection {
+                center: start + centroid * step,
+                bandwidth: (last - first + 1) as f64 * step,
+                power: 10.0 * total.log10(),
+                noise: noise[strongest],
+                snr: power[strongest] - noise[strongest]
+            });
+            i = last + 1;
+        }
+        detections
+    }
+
+    /// Detect signals in a frame taken at `time`, and follow them from
+    /// previous frames. Returns the signals which have ended.
+    pub fn process_frame(&mut self, start: f64, step: f64, power: &[f64],
+                         time: SystemTime) -> Vec<Signal> {
+        let detections = self.detect(start, step, power);
+        let mut seen = vec![false; self.active.len()];
+        for d in detections {
+            let matched = self.active.iter().position(|s| {
+                d.low() < s.high && d.high() > s.low
+            });
+            match matched {
+                Some(k) => {
+                    let s = &mut self.active[k];
+                    s.low = s.low.min(d.low());
+                    s.high = s.high.max(d.high());
+                    s.stop = time;
+                    s.frames += 1;
+                    s.missed = 0;
+                    if d.snr > s.peak.snr {
+                        s.peak = d;
+                    }
+                    seen[k] = true;
+                },
+                None => {
+                    self.active.push(Signal {
+                        low: d.low(), high: d.high(), peak: d,
+                        start: time, stop: time, frames: 1, missed: 0
+                    });
+                    seen.push(true);
+                }
+            }
+        }
+
+        let mut ended = Vec::new();
+        let mut k = 0;
+        for was_seen in seen {
+            if !was_seen {
+                self.active[k].missed += 1;
+                if self.active[k].missed > self.config.hold {
+                    ended.push(self.active.remove(k));
+                    continue;
+                }
+            }
+            k += 1;
+        }
+        ended
+    }
+
+    /// As `process_frame`, for a power spectral density estimate.
+    pub fn process(&mut self, psd: &Psd, time: SystemTime) -> Vec<Signal> {
+        // Integrate the density over each bin so that bin powers sum to
+        // band power.
+        let offset = 10.0 * psd.step.log10();
+        let power: Vec<f64> = psd.density.iter().map(|d| d + offset)
+                                 .collect();
+        self.process_frame(psd.start, psd.step, &power, time)
+    }
+
+    /// Signals detected recently enough that they have not yet ended.
+    pub fn active(&self) -> &[Signal] {
+        &self.active
+    }
+
+    /// End every active signal, returning them.
+    pub fn flush(&mut self) -> Vec<Signal> {
+        std::mem::take(&mut self.active)
+    }
+}
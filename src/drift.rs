@@ -0,0 +1,246 @@
+// Oscillator drift tracking for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// The tracker repeatedly measures a known stable signal, smooths the
+// resulting ppm estimates, and splits the correction into a whole number of
+// ppm applied by the hardware plus a fractional residual applied digitally.
+
+use std::time::SystemTime;
+
+use super::RTLSDRError;
+use super::dsp::{Complex, Nco, cic_decimate, tone_freq};
+use super::samples::iq_from_u8;
+use super::source::Source;
+
+/// Frequency of the FM stereo pilot tone (in Hz).
+pub const FM_PILOT: f64 = 19_000.0;
+
+/// Bandwidth the reference is examined over (in Hz), which bounds the
+/// initial error that can be tracked.
+const CARRIER_RATE: f64 = 50_000.0;
+const FM_RATE: f64 = 200_000.0;
+const PILOT_RATE: f64 = 2_000.0;
+
+/// A stable signal to track.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reference {
+    /// An unmodulated or AM carrier at this RF frequency (in Hz), such as a
+    /// beacon or broadcast station.
+    Carrier(u64),
+    /// The 19kHz stereo pilot of the FM broadcast station at this RF
+    /// frequency (in Hz).
+    FmPilot(u64)
+}
+
+impl Reference {
+    /// RF frequency of the signal (in Hz).
+    pub fn freq(&self) -> u64 {
+        match *self {
+            Reference::Carrier(f) | Reference::FmPilot(f) => f
+        }
+    }
+}
+
+/// One entry in the drift history.
+#[derive(Clone, Copy, Debug)]
+pub struct DriftRecord {
+    /// When the measurement was made.
+    pub time: SystemTime,
+    /// Error measured from this block alone (in ppm).
+    pub measured: f64,
+    /// Smoothed total error estimate (in ppm).
+    pub estimate: f64,
+    /// Whole ppm correction applied by the hardware.
+    pub correction: i32,
+    /// Fractional correction applied digitally (in ppm).
+    pub residual: f64
+}
+
+/// Tracks oscillator drift against a Reference.
+pub struct DriftTracker {
+    reference: Reference,
+    smoothing: f64,
+    hysteresis: f64,
+    estimate: Option<f64>,
+    correction: i32,
+    nco: Nco,
+    history: Vec<DriftRecord>,
+    history_len: usize
+}
+
+impl DriftTracker {
+    /// Create a tracker following `reference`, starting from a hardware
+    /// correction of `correction` ppm.
+    pub fn new(reference: Reference, correction: i32) -> DriftTracker {
+        DriftTracker {
+            reference,
+            smoothing: 0.2,
+            hysteresis: 0.75,
+            estimate: None,
+            correction,
+            nco: Nco::new(0.0, 1.0),
+            history: Vec::new(),
+            history_len: 3600
+        }
+    }
+
+    /// Set the weight given to each new measurement, from 0 to 1. Smaller
+    /// values track more slowly but with less noise. Default 0.2.
+    pub fn set_smoothing(&mut self, smoothing: f64) {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+    }
+
+    /// Set how far (in ppm) the estimate may stray from the hardware
+    /// correction before the hardware is retuned. Default 0.75.
+    pub fn set_hysteresis(&mut self, hysteresis: f64) {
+        self.hysteresis = hysteresis.max(0.5);
+    }
+
+    /// Set the maximum number of records kept in the history. Default 3600.
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history_len = len;
+        self.trim_history();
+    }
+
+    /// Smoothed total error estimate (in ppm), once anything is measured.
+    pub fn ppm(&self) -> Option<f64> {
+        self.estimate
+    }
+
+    /// Whole ppm correction the hardware should be using.
+    pub fn correction(&self) -> i32 {
+        self.correction
+    }
+
+    /// Fractional correction (in ppm) applied digitally by `correct`.
+    pub fn residual(&self) -> f64 {
+        match self.estimate {
+            Some(e) => e - self.correction as f64,
+            None => 0.0
+        }
+    }
+
+    /// Measurement history, oldest first.
+    pub fn history(&self) -> &[DriftRecord] {
+        &self.history
+    }
+
+    /// Measure the reference in `samples`, taken at `rate` (in Hz) and
+    /// tuned to `center` (in Hz).
+    ///
+    /// Returns the error (in ppm) relative to the correction in effect when
+    /// the samples were taken, or None if the reference is outside the
+    /// captured bandwidth.
+    pub fn measure(&self, samples: &[Complex], center: u64, rate: f64)
+                   -> Option<f64> {
+        let offset = self.reference.freq() as f64 - center as f64;
+        if offset.abs() > rate / 2.0 {
+            return None;
+        }
+        // Centre the reference, allowing for the error already known.
+        let expected = offset - self.residual() * 1e-6 *
+                       self.reference.freq() as f64;
+        let mut shifted = samples.to_vec();
+        Nco::new(-expected, rate).mix(&mut shifted);
+
+        match self.reference {
+            Reference::Carrier(f) => {
+                let factor = (rate / CARRIER_RATE).max(1.0) as usize;
+                let narrow = cic_decimate(&shifted, factor);
+                if narrow.len() < 16 {
+                    return None;
+                }
+                let hz = tone_freq(&narrow) * rate / factor as f64;
+                Some(self.residual() - (hz / f as f64) * 1e6)
+            },
+            Reference::FmPilot(_) => {
+                // The pilot's measured frequency depends only on our sample
+                // clock, which shares the tuner's oscillator.
+                let factor = (rate / FM_RATE).max(1.0) as usize;
+                let fm = cic_decimate(&shifted, factor);
+                let fm_rate = rate / factor as f64;
+                let mut pilot: Vec<Complex> = fm.windows(2)
+                    .map(|w| Complex::new((w[1] * w[0].conj()).arg(), 0.0))
+                    .collect();
+                Nco::new(-FM_PILOT, fm_rate).mix(&mut pilot);
+                let factor = (fm_rate / PILOT_RATE).max(1.0) as usize;
+                let narrow = cic_decimate(&pilot, factor);
+                if narrow.len() < 16 {
+                    return None;
+                }
+                let hz = tone_freq(&narrow) * fm_rate / factor as f64;
+                Some(-(hz / FM_PILOT) * 1e6)
+            }
+        }
+    }
+
+    /// Measure `samples` (see `measure`) and update the estimate.
+    ///
+    /// Returns the whole ppm correction the hardware should now use, which
+    /// only changes when the estimate strays outside the hysteresis.
+    pub fn update(&mut self, samples: &[Complex], center: u64, rate: f64)
+                  -> i32 {
+        let measured = match self.measure(samples, center, rate) {
+            Some(m) => m + self.correction as f64,
+            None => return self.correction
+        };
+        let estimate = match self.estimate {
+            Some(e) => e + self.smoothing * (measured - e),
+            None => measured
+        };
+        self.estimate = Some(estimate);
+        if (estimate - self.correction as f64).abs() > self.hysteresis {
+            self.correction = estimate.round() as i32;
+        }
+        self.history.push(DriftRecord {
+            time: SystemTime::now(),
+            measured,
+            estimate,
+            correction: self.correction,
+            residual: self.residual()
+        });
+        self.trim_history();
+        self.correction
+    }
+
+    /// Apply the fractional residual correction to `samples`, taken at
+    /// `rate` (in Hz) and tuned to `center` (in Hz).
+    pub fn correct(&mut self, samples: &mut [Complex], center: u64,
+                   rate: f64) {
+        let residual = self.residual();
+        self.shift(samples, residual, center, rate);
+    }
+
+    fn shift(&mut self, samples: &mut [Complex], ppm: f64, center: u64,
+             rate: f64) {
+        self.nco.set_freq(ppm * 1e-6 * center as f64, rate);
+        self.nco.mix(samples);
+    }
+
+    /// Read `len` bytes from `source`, update the estimate, feed any change
+    /// in whole ppm back with set_freq_correction, and return the samples
+    /// with the fractional residual corrected.
+    pub fn track<S: Source>(&mut self, source: &mut S, len: usize)
+                            -> Result<Vec<Complex>, RTLSDRError> {
+        let center = source.get_center_freq()?;
+        let rate = source.get_sample_rate()? as f64;
+        let mut samples = iq_from_u8(&source.read_sync(len)?);
+        let old = self.correction;
+        let new = self.update(&samples, center, rate);
+        // These samples were taken with the old hardware correction.
+        let residual = self.estimate.map_or(0.0, |e| e - old as f64);
+        self.shift(&mut samples, residual, center, rate);
+        if new != old {
+            source.set_freq_correction(new)?;
+        }
+        Ok(samples)
+    }
+
+    fn trim_history(&mut self) {
+        if self.history.len() > self.history_len {
+            let excess = self.history.len() - self.history_len;
+            self.history.drain(..excess);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+// Decimation for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Large integer rate reductions are cheapest as a cascade of half-band
+// stages, each halving the rate. Half-band filters have every other tap
+// zero and are symmetric, so each output costs about a quarter of the
+// multiplies of a general FIR of the same length. Early stages only need
+// to protect the part of the band that survives to the final output, so
+// they can be much shorter than the last.
+
+use super::{Complex, Sample};
+use super::filter::{Fir, lowpass, taps_for_transition};
+use super::window::Window;
+use crate::samples::iq_from_u8;
+
+/// Half-band taps used before the last stage of a cascade.
+const SHORT_TAPS: usize = 19;
+
+/// Half-band taps used in the last stage of a cascade, passing 80% of the
+/// output band.
+const LONG_TAPS: usize = 47;
+
+/// Fractional bits in the integer taps of the first u8 stage.
+const U8_TAP_BITS: u32 = 14;
+
+/// A lowpass decimating FIR filter, designed for a given factor.
+///
+/// Only every `factor`th output is computed, which costs the same as a
+/// polyphase implementation.
+#[derive(Clone, Debug)]
+pub struct Decimator<T: Sample> {
+    fir: Fir<T>
+}
+
+impl<T: Sample> Decimator<T> {
+    /// Decimate by `factor`, passing up to `passband` of the output
+    /// bandwidth (from 0 to 1, typically 0.8) with good rejection of
+    /// everything that would alias into it.
+    pub fn new(factor: usize, passband: f64) -> Decimator<T> {
+        let factor = factor.max(1);
+        let out_nyquist = 0.5 / factor as f64;
+        let edge = out_nyquist * passband.clamp(0.05, 0.95);
+        let transition = 2.0 * (out_nyquist - edge);
+        let taps = taps_for_transition(transition);
+        let cutoff = (edge + out_nyquist) / 2.0;
+        Decimator::with_taps(lowpass(taps, cutoff, Window::Blackman), factor)
+    }
+
+    /// Decimate by `factor` with the given filter taps.
+    pub fn with_taps(taps: Vec<f32>, factor: usize) -> Decimator<T> {
+        Decimator { fir: Fir::decimating(taps, factor) }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.fir.decimation()
+    }
+
+    pub fn taps(&self) -> &[f32] {
+        self.fir.taps()
+    }
+
+    /// Clear the filter state.
+    pub fn reset(&mut self) {
+        self.fir.reset();
+    }
+
+    /// Decimate a block, returning however many outputs are due.
+    pub fn process(&mut self, input: &[T]) -> Vec<T> {
+        self.fir.process(input)
+    }
+}
+
+/// Design a half-band lowpass filter with `taps` taps, which must be 3 more
+/// than a multiple of 4.
+///
+/// Returns only the non-zero taps from one side of the centre, nearest
+/// first; the centre tap is always 0.5.
+pub fn halfband(taps: usize) -> Vec<f32> {
+    let n = taps | 3;
+    let h = lowpass(n, 0.25, Window::Blackman);
+    let mid = n / 2;
+    // Rescale so the centre tap is exactly 0.5 and the gain exactly 1.
+    let side: Vec<f64> = (1..=mid).step_by(2).map(|k| h[mid + k] as f64)
+                                  .collect();
+    let sum: f64 = 2.0 * side.iter().sum::<f64>();
+    side.iter().map(|&x| (x * 0.5 / sum) as f32).collect()
+}
+
+/// A half-band filter decimating by two, keeping its state between blocks.
+#[derive(Clone, Debug)]
+pub struct HalfBand<T: Sample> {
+    side: Vec<f32>,
+    history: Vec<T>,
+    skip: usize
+}
+
+impl<T: Sample> HalfBand<T> {
+    /// Create a stage with `taps` taps (see `halfband`).
+    pub fn new(taps: usize) -> HalfBand<T> {
+        let side = halfband(taps);
+        let len = 4 * side.len() - 1;
+        HalfBand { side, history: vec![T::default(); len - 1], skip: 0 }
+    }
+
+    /// Total length of the filter, including zero taps.
+    pub fn len(&self) -> usize {
+        4 * self.side.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.side.is_empty()
+    }
+
+    /// Clear the filter state.
+    pub fn reset(&mut self) {
+        for h in self.history.iter_mut() {
+            *h = T::default();
+        }
+        self.skip = 0;
+    }
+
+    /// Filter and decimate a block.
+    pub fn process(&mut self, input: &[T]) -> Vec<T> {
+        let n = self.len();
+        let mid = n / 2;
+        let mut buf = std::mem::take(&mut self.history);
+        buf.extend_from_slice(input);
+        let mut out = Vec::with_capacity(input.len() / 2 + 1);
+        let mut i = self.skip;
+        while i + n <= buf.len() {
+            let c = i + mid;
+            let mut acc = buf[c] * 0.5;
+            for (k, &h) in self.side.iter().enumerate() {
+                let d = 2 * k + 1;
+                acc += (buf[c - d] + buf[c + d]) * h;
+            }
+            out.push(acc);
+            i += 2;
+        }
+        let keep = buf.len() - (n - 1);
+        self.skip = i - keep;
+        self.history = buf.split_off(keep);
+        out
+    }
+}
+
+/// A half-band stage taking raw 8 bit I/Q, computed in integer arithmetic.
+#[derive(Clone, Debug)]
+struct U8HalfBand {
+    side: Vec<i32>,
+    /// Pending samples as 2x - 255, which is 255 times the usual -1 to +1
+    /// scaling, I and Q interleaved.
+    history: Vec<i32>,
+    /// An I sample whose Q has not arrived yet.
+    odd: Option<i32>,
+    skip: usize
+}
+
+impl U8HalfBand {
+    fn new(taps: usize) -> U8HalfBand {
+        let scale = (1 << U8_TAP_BITS) as f32;
+        let side: Vec<i32> = halfband(taps).iter()
+                                           .map(|&h| (h * scale).round() as i32)
+                                           .collect();
+        let len = 4 * side.len() - 1;
+        U8HalfBand { side, history: vec![0; 2 * (len - 1)], odd: None,
+                     skip: 0 }
+    }
+
+    fn reset(&mut self) {
+        for h in self.history.iter_mut() {
+            *h = 0;
+        }
+        self.odd = None;
+        self.skip = 0;
+    }
+
+    fn process(&mut self, buf: &[u8]) -> Vec<Complex> {
+        let n = 4 * self.side.len() - 1;
+        let mid = n / 2;
+        let mut x = std::mem::take(&mut self.history);
+        x.extend(self.odd.take());
+        x.extend(buf.iter().map(|&b| 2 * b as i32 - 255));
+        if x.len() % 2 == 1 {
+            self.odd = x.pop();
+        }
+        let len = x.len() / 2;
+        let half = 1 << (U8_TAP_BITS - 1);
+        let norm = 1.0 / (255.0 * (1 << U8_TAP_BITS) as f32);
+        let mut out = Vec::with_capacity(buf.len() / 4 + 1);
+        let mut i = self.skip;
+        while i + n <= len {
+            let c = 2 * (i + mid);
+            let (mut re, mut im) = (x[c] * half, x[c + 1] * half);
+            for (k, &h) in self.side.iter().enumerate() {
+                let d = 2 * (2 * k + 1);
+                re += (x[c - d] + x[c + d]) * h;
+                im += (x[c - d + 1] + x[c + d + 1]) * h;
+            }
+            out.push(Complex::new(re as f32 * norm, im as f32 * norm));
+            i += 2;
+        }
+        let keep = len - (n - 1);
+        self.skip = i - keep;
+        self.history = x.split_off(2 * keep);
+        out
+    }
+}
+
+/// Decimates raw 8 bit I/Q by a power of two through a cascade of
+/// half-band stages, keeping state between blocks.
+///
+/// The first stage works directly on the u8 samples in integer arithmetic;
+/// the rest on complex floats. The output passes 80% of its bandwidth.
+#[derive(Clone, Debug)]
+pub struct HalfBandCascade {
+    first: Option<U8HalfBand>,
+    stages: Vec<HalfBand<Complex>>
+}
+
+impl HalfBandCascade {
+    /// Decimate by 2^`stages`.
+    pub fn new(stages: usize) -> HalfBandCascade {
+        let taps = |s: usize| if s + 1 == stages { LONG_TAPS }
+                              else { SHORT_TAPS };
+        HalfBandCascade {
+            first: if stages > 0 { Some(U8HalfBand::new(taps(0))) }
+                   else { None },
+            stages: (1..stages).map(|s| HalfBand::new(taps(s))).collect()
+        }
+    }
+
+    /// Decimation factor.
+    pub fn factor(&self) -> usize {
+        match self.first {
+            Some(_) => 2 << self.stages.len(),
+            None => 1
+        }
+    }
+
+    /// Clear all filter state.
+    pub fn reset(&mut self) {
+        if let Some(ref mut first) = self.first {
+            first.reset();
+        }
+        for s in self.stages.iter_mut() {
+            s.reset();
+        }
+    }
+
+    /// Decimate a block of interleaved 8 bit I/Q, as from read_sync.
+    pub fn process(&mut self, buf: &[u8]) -> Vec<Complex> {
+        let mut x = match self.first {
+            Some(ref mut first) => first.process(buf),
+            None => return iq_from_u8(buf)
+        };
+        for s in self.stages.iter_mut() {
+            x = s.process(&x);
+        }
+        x
+    }
+}
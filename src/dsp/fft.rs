@@ -0,0 +1,161 @@
+// Fast Fourier transform for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::Complex;
+
+/// A precomputed radix-2 FFT of a fixed power-of-two size.
+#[derive(Clone, Debug)]
+pub struct Fft {
+    n: usize,
+    twiddles: Vec<Complex>,
+    bitrev: Vec<usize>
+}
+
+impl Fft {
+    /// Plan an FFT of size `n`, which must be a power of two.
+    pub fn new(n: usize) -> Fft {
+        assert!(n.is_power_of_two(), "FFT size must be a power of two");
+        let bits = n.trailing_zeros();
+        let twiddles = (0..n / 2).map(|k| {
+            let phase = -2.0 * std::f64::consts::PI * k as f64 / n as f64;
+            Complex::new(phase.cos() as f32, phase.sin() as f32)
+        }).collect();
+        let bitrev = (0..n).map(|i| match bits {
+            0 => 0,
+            b => i.reverse_bits() >> (usize::BITS - b)
+        }).collect();
+        Fft { n, twiddles, bitrev }
+    }
+
+    /// Size of the transform.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Transform `buf` in place. `buf` must be exactly `len()` long.
+    ///
+    /// The output is in natural order, with bin 0 at DC; see `shift` to
+    /// centre it. No scaling is applied.
+    pub fn process(&self, buf: &mut [Complex]) {
+        assert_eq!(buf.len(), self.n, "FFT buffer has the wrong length");
+        for i in 0..self.n {
+            let j = self.bitrev[i];
+            if i < j {
+                buf.swap(i, j);
+            }
+        }
+        let mut size = 2;
+        while size <= self.n {
+            let half = size / 2;
+            let stride = self.n / size;
+            for start in (0..self.n).step_by(size) {
+                for k in 0..half {
+                    let w = self.twiddles[k * stride];
+                    let a = buf[start + k];
+                    let b = buf[start + k + half] * w;
+                    buf[start + k] = a + b;
+                    buf[start + k + half] = a - b;
+                }
+            }
+            size *= 2;
+        }
+    }
+
+    /// Inverse transform `buf` in place, scaled by 1/n so that it undoes
+    /// `process`.
+    pub fn inverse(&self, buf: &mut [Complex]) {
+        for x in buf.iter_mut() {
+            *x = x.conj();
+        }
+        self.process(buf);
+        let scale = 1.0 / self.n as f32;
+        for x in buf.iter_mut() {
+            *x = x.conj() * scale;
+        }
+    }
+}
+
+/// A DFT of any size.
+///
+/// Powers of two use `Fft` directly; other sizes use Bluestein's algorithm,
+/// which turns the transform into a convolution done with a power-of-two
+/// FFT at least twice as long.
+#[derive(Clone, Debug)]
+pub struct Dft {
+    n: usize,
+    fft: Fft,
+    /// Bluestein chirp, exp(-j pi k^2 / n), empty for powers of two.
+    chirp: Vec<Complex>,
+    /// Transform of the conjugate chirp, wrapped to the FFT length.
+    kernel: Vec<Complex>
+}
+
+impl Dft {
+    /// Plan a DFT of size `n`, which must not be zero.
+    pub fn new(n: usize) -> Dft {
+        assert!(n > 0, "DFT size must not be zero");
+        if n.is_power_of_two() {
+            return Dft { n, fft: Fft::new(n), chirp: Vec::new(),
+                         kernel: Vec::new() };
+        }
+        let m = (2 * n - 1).next_power_of_two();
+        let fft = Fft::new(m);
+        // Reduce k^2 modulo 2n before scaling so large k keep precision.
+        let chirp: Vec<Complex> = (0..n).map(|k| {
+            let k2 = (k as u64 * k as u64) % (2 * n as u64);
+            let phase = -std::f64::consts::PI * k2 as f64 / n as f64;
+            Complex::new(phase.cos() as f32, phase.sin() as f32)
+        }).collect();
+        let mut kernel = vec![Complex::default(); m];
+        kernel[0] = chirp[0].conj();
+        for k in 1..n {
+            kernel[k] = chirp[k].conj();
+            kernel[m - k] = chirp[k].conj();
+        }
+        fft.process(&mut kernel);
+        Dft { n, fft, chirp, kernel }
+    }
+
+    /// Size of the transform.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Transform `buf` in place, with the same conventions as
+    /// `Fft::process`. `buf` must be exactly `len()` long.
+    pub fn process(&self, buf: &mut [Complex]) {
+        assert_eq!(buf.len(), self.n, "DFT buffer has the wrong length");
+        if self.chirp.is_empty() {
+            self.fft.process(buf);
+            return;
+        }
+        let mut work = vec![Complex::default(); self.fft.len()];
+        for ((w, &x), &c) in work.iter_mut().zip(buf.iter()).zip(&self.chirp) {
+            *w = x * c;
+        }
+        self.fft.process(&mut work);
+        for (w, &k) in work.iter_mut().zip(&self.kernel) {
+            *w *= k;
+        }
+        self.fft.inverse(&mut work);
+        for ((x, &w), &c) in buf.iter_mut().zip(&work).zip(&self.chirp) {
+            *x = w * c;
+        }
+    }
+}
+
+/// Rotate a natural-order spectrum so the most negative frequency is first
+/// and DC is in the middle.
+pub fn shift<T>(buf: &mut [T]) {
+    let half = buf.len() / 2;
+    buf.rotate_left(buf.len() - half);
+}
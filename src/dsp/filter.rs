@@ -0,0 +1,122 @@
+// FIR filters for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::Sample;
+use super::window::Window;
+
+/// Design a lowpass FIR filter by the windowed-sinc method.
+///
+/// `cutoff` is the -6dB frequency as a fraction of the sample rate, from 0
+/// to 0.5. The taps are normalised for unity gain at DC.
+pub fn lowpass(taps: usize, cutoff: f64, window: Window) -> Vec<f32> {
+    let w = window.symmetric(taps);
+    let mid = (taps as f64 - 1.0) / 2.0;
+    let h: Vec<f64> = (0..taps).map(|i| {
+        let x = i as f64 - mid;
+        let sinc = if x == 0.0 {
+            2.0 * cutoff
+        } else {
+            (2.0 * std::f64::consts::PI * cutoff * x).sin() /
+                (std::f64::consts::PI * x)
+        };
+        sinc * w[i] as f64
+    }).collect();
+    let sum: f64 = h.iter().sum();
+    h.iter().map(|x| (x / sum) as f32).collect()
+}
+
+/// Number of taps for a windowed-sinc filter with a transition band
+/// `transition` wide (as a fraction of the sample rate), for a window with
+/// roughly Blackman performance. Always odd, so the filter has a centre tap.
+pub fn taps_for_transition(transition: f64) -> usize {
+    let n = (5.5 / transition.max(1e-6)).ceil() as usize;
+    n | 1
+}
+
+/// A FIR filter with optional decimation, keeping its state between blocks.
+#[derive(Clone, Debug)]
+pub struct Fir<T: Sample> {
+    taps: Vec<f32>,
+    history: Vec<T>,
+    decimation: usize,
+    skip: usize
+}
+
+impl<T: Sample> Fir<T> {
+    /// Create a filter with the given taps.
+    pub fn new(taps: Vec<f32>) -> Fir<T> {
+        Fir::decimating(taps, 1)
+    }
+
+    /// Create a filter which only computes every `decimation`th output.
+    pub fn decimating(taps: Vec<f32>, decimation: usize) -> Fir<T> {
+        let history = vec![T::default(); taps.len().saturating_sub(1)];
+        Fir { taps, history, decimation: decimation.max(1), skip: 0 }
+    }
+
+    pub fn taps(&self) -> &[f32] {
+        &self.taps
+    }
+
+    pub fn decimation(&self) -> usize {
+        self.decimation
+    }
+
+    /// Clear the filter state.
+    pub fn reset(&mut self) {
+        for h in self.history.iter_mut() {
+            *h = T::default();
+        }
+        self.skip = 0;
+    }
+
+    /// Filter a block of input, returning however many outputs are due.
+    pub fn process(&mut self, input: &[T]) -> Vec<T> {
+        let n = self.taps.len();
+        if n == 0 {
+            return input.iter().step_by(self.decimation).cloned().collect();
+        }
+        let mut buf = std::mem::take(&mut self.history);
+        buf.extend_from_slice(input);
+        let mut out = Vec::with_capacity(input.len() / self.decimation + 1);
+        let mut i = self.skip;
+        while i + n <= buf.len() {
+            let mut acc = T::default();
+            for (&h, &x) in self.taps.iter().rev().zip(&buf[i..i + n]) {
+                acc += x * h;
+            }
+            out.push(acc);
+            i += self.decimation;
+        }
+        let keep = buf.len() - (n - 1);
+        self.skip = i - keep;
+        self.history = buf.split_off(keep);
+        out
+    }
+}
+
+/// Single-pole IIR lowpass filter, y += a(x - y), keeping its state between
+/// blocks.
+#[derive(Clone, Debug)]
+pub struct SinglePole<T: Sample> {
+    alpha: f32,
+    state: T
+}
+
+impl<T: Sample> SinglePole<T> {
+    /// Create a filter with time constant `tau` (in seconds) at sample rate
+    /// `rate` (in Hz).
+    pub fn new(tau: f64, rate: f64) -> SinglePole<T> {
+        let alpha = 1.0 - (-1.0 / (tau * rate)).exp();
+        SinglePole { alpha: alpha as f32, state: T::default() }
+    }
+
+    /// Filter a block in place.
+    pub fn process(&mut self, buf: &mut [T]) {
+        for x in buf.iter_mut() {
+            self.state += (*x - self.state) * self.alpha;
+            *x = self.state;
+        }
+    }
+}
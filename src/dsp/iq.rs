@@ -0,0 +1,132 @@
+// I/Q impairment correction for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Zero-IF tuners leave a DC offset and a gain and phase mismatch between
+// the I and Q branches, which show up as a spike at the centre of the
+// spectrum and as images of strong signals mirrored about it.
+
+use super::Complex;
+
+/// Removes a slowly varying DC offset with a one-pole running mean.
+#[derive(Clone, Copy, Debug)]
+pub struct DcBlocker {
+    alpha: f64,
+    mean: (f64, f64)
+}
+
+impl DcBlocker {
+    /// Track the mean with a time constant of `samples` samples.
+    pub fn new(samples: f64) -> DcBlocker {
+        DcBlocker { alpha: 1.0 / samples.max(1.0), mean: (0.0, 0.0) }
+    }
+
+    /// Current DC estimate.
+    pub fn offset(&self) -> Complex {
+        Complex::new(self.mean.0 as f32, self.mean.1 as f32)
+    }
+
+    /// Forget the DC estimate.
+    pub fn reset(&mut self) {
+        self.mean = (0.0, 0.0);
+    }
+
+    /// Remove DC from `samples` in place.
+    pub fn process(&mut self, samples: &mut [Complex]) {
+        let a = self.alpha;
+        let (mut mi, mut mq) = self.mean;
+        for s in samples.iter_mut() {
+            mi += a * (s.re as f64 - mi);
+            mq += a * (s.im as f64 - mq);
+            s.re -= mi as f32;
+            s.im -= mq as f32;
+        }
+        self.mean = (mi, mq);
+    }
+}
+
+/// Blind I/Q gain and phase imbalance correction.
+///
+/// Relies on the input being statistically the same in I and Q and
+/// uncorrelated between them, as is true of noise and of most signals
+/// averaged over enough time. Q is made orthogonal to I and scaled to match
+/// it. The input should already be free of DC.
+#[derive(Clone, Copy, Debug)]
+pub struct IqBalancer {
+    memory: f64,
+    ii: f64,
+    qq: f64,
+    iq: f64,
+    primed: bool
+}
+
+impl IqBalancer {
+    /// Average the estimate over roughly `samples` samples.
+    pub fn new(samples: f64) -> IqBalancer {
+        IqBalancer { memory: samples.max(1.0), ii: 0.0, qq: 0.0, iq: 0.0,
+                     primed: false }
+    }
+
+    /// Forget the estimate.
+    pub fn reset(&mut self) {
+        self.primed = false;
+    }
+
+    /// Estimated gain of Q relative to I (in dB).
+    pub fn gain_error(&self) -> f64 {
+        if self.ii > 0.0 && self.qq > 0.0 {
+            10.0 * (self.qq / self.ii).log10()
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated departure of Q from quadrature with I (in degrees).
+    pub fn phase_error(&self) -> f64 {
+        let norm = (self.ii * self.qq).sqrt();
+        if norm > 0.0 {
+            (self.iq / norm).clamp(-1.0, 1.0).asin().to_degrees()
+        } else {
+            0.0
+        }
+    }
+
+    /// Update the estimate from `samples` and correct them in place.
+    pub fn process(&mut self, samples: &mut [Complex]) {
+        if samples.is_empty() {
+            return;
+        }
+        let (mut ii, mut qq, mut iq) = (0.0, 0.0, 0.0);
+        for s in samples.iter() {
+            ii += (s.re * s.re) as f64;
+            qq += (s.im * s.im) as f64;
+            iq += (s.re * s.im) as f64;
+        }
+        let n = samples.len() as f64;
+        let (ii, qq, iq) = (ii / n, qq / n, iq / n);
+        if self.primed {
+            let a = n / (n + self.memory);
+            self.ii += a * (ii - self.ii);
+            self.qq += a * (qq - self.qq);
+            self.iq += a * (iq - self.iq);
+        } else {
+            (self.ii, self.qq, self.iq) = (ii, qq, iq);
+            self.primed = true;
+        }
+        if self.ii <= 0.0 {
+            return;
+        }
+
+        // Q' = Q - pI is uncorrelated with I; scale it to I's power.
+        let p = self.iq / self.ii;
+        let residual = self.qq - p * self.iq;
+        if residual <= 0.0 {
+            return;
+        }
+        let scale = (self.ii / residual).sqrt();
+        let (c_i, c_q) = ((-p * scale) as f32, scale as f32);
+        for s in samples.iter_mut() {
+            s.im = c_i * s.re + c_q * s.im;
+        }
+    }
+}
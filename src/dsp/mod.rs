@@ -0,0 +1,215 @@
+// Signal processing building blocks for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+pub mod decimate;
+pub mod fft;
+pub mod filter;
+pub mod iq;
+pub mod resample;
+pub mod window;
+
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// Operations needed by filters, so they can work on real or complex
+/// samples.
+pub trait Sample: Copy + Default + Add<Output=Self> + Sub<Output=Self> +
+                  AddAssign + Mul<f32, Output=Self> {}
+
+impl Sample for f32 {}
+impl Sample for Complex {}
+
+/// A complex sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    /// Unit-magnitude complex number with the given phase (in radians).
+    pub fn expj(phase: f32) -> Complex {
+        Complex { re: phase.cos(), im: phase.sin() }
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex { re: self.re, im: -self.im }
+    }
+
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn norm(self) -> f32 {
+        self.norm_sqr().sqrt()
+    }
+
+    /// Phase angle (in radians).
+    pub fn arg(self) -> f32 {
+        self.im.atan2(self.re)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, o: Complex) -> Complex {
+        Complex { re: self.re + o.re, im: self.im + o.im }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, o: Complex) -> Complex {
+        Complex { re: self.re - o.re, im: self.im - o.im }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, o: Complex) -> Complex {
+        Complex { re: self.re * o.re - self.im * o.im,
+                  im: self.re * o.im + self.im * o.re }
+    }
+}
+
+impl Mul<f32> for Complex {
+    type Output = Complex;
+    fn mul(self, k: f32) -> Complex {
+        Complex { re: self.re * k, im: self.im * k }
+    }
+}
+
+impl Div<f32> for Complex {
+    type Output = Complex;
+    fn div(self, k: f32) -> Complex {
+        Complex { re: self.re / k, im: self.im / k }
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex { re: -self.re, im: -self.im }
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, o: Complex) {
+        self.re += o.re;
+        self.im += o.im;
+    }
+}
+
+impl SubAssign for Complex {
+    fn sub_assign(&mut self, o: Complex) {
+        self.re -= o.re;
+        self.im -= o.im;
+    }
+}
+
+impl MulAssign for Complex {
+    fn mul_assign(&mut self, o: Complex) {
+        *self = *self * o;
+    }
+}
+
+impl MulAssign<f32> for Complex {
+    fn mul_assign(&mut self, k: f32) {
+        self.re *= k;
+        self.im *= k;
+    }
+}
+
+/// Numerically controlled oscillator for frequency shifting a stream.
+///
+/// Phase is kept in f64 cycles so long runs don't accumulate error, and is
+/// preserved across frequency changes.
+#[derive(Clone, Debug)]
+pub struct Nco {
+    phase: f64,
+    step: f64
+}
+
+impl Nco {
+    /// Create an NCO at `freq` (in Hz) for a stream at `rate` (in Hz).
+    pub fn new(freq: f64, rate: f64) -> Nco {
+        Nco { phase: 0.0, step: freq / rate }
+    }
+
+    /// Change the NCO frequency without disturbing its phase.
+    pub fn set_freq(&mut self, freq: f64, rate: f64) {
+        self.step = freq / rate;
+    }
+
+    /// Current NCO frequency as a fraction of the sample rate.
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Return the next oscillator sample.
+    pub fn next_sample(&mut self) -> Complex {
+        let out = Complex::expj((self.phase * 2.0 * std::f64::consts::PI)
+                                as f32);
+        self.phase += self.step;
+        self.phase -= self.phase.floor();
+        out
+    }
+
+    /// Shift `samples` in place by the NCO frequency.
+    pub fn mix(&mut self, samples: &mut [Complex]) {
+        for s in samples.iter_mut() {
+            *s *= self.next_sample();
+        }
+    }
+}
+
+/// Autocorrelation of `samples` at `lag`, summed over the overlap.
+pub fn autocorr(samples: &[Complex], lag: usize) -> Complex {
+    samples.iter().zip(samples.iter().skip(lag))
+           .fold(Complex::default(), |a, (&x0, &x1)| a + x1 * x0.conj())
+}
+
+/// Estimate the frequency of the dominant tone in `samples`, in cycles per
+/// sample.
+///
+/// The lag-one autocorrelation gives a coarse estimate, which is refined
+/// using progressively longer lags. Each lag is short enough that the
+/// remaining error cannot alias, and the longest spans half the samples.
+pub fn tone_freq(samples: &[Complex]) -> f64 {
+    let tau = 2.0 * std::f64::consts::PI;
+    let mut cycles = autocorr(samples, 1).arg() as f64 / tau;
+    let longest = samples.len() / 2;
+    let mut lag = 8;
+    while lag < longest * 2 {
+        let lag_n = lag.min(longest);
+        let phase = -tau * cycles * lag_n as f64;
+        let r = autocorr(samples, lag_n) * Complex::expj(phase as f32);
+        cycles += r.arg() as f64 / (tau * lag_n as f64);
+        lag *= 8;
+    }
+    cycles
+}
+
+/// Decimate `samples` by `factor` with a second order CIC filter, which
+/// averages each output over a triangular window two decimation periods
+/// long. Any trailing partial period is dropped.
+pub fn cic_decimate(samples: &[Complex], factor: usize) -> Vec<Complex> {
+    let factor = factor.max(1);
+    let norm = (factor * factor) as f32;
+    let mut out = Vec::with_capacity(samples.len() / factor);
+    let mut k = 0;
+    while k + 2 * factor - 1 <= samples.len() {
+        let mut acc = Complex::default();
+        for (j, &s) in samples[k..k + 2 * factor - 1].iter().enumerate() {
+            let w = if j < factor { j + 1 } else { 2 * factor - 1 - j };
+            acc += s * w as f32;
+        }
+        out.push(acc / norm);
+        k += factor;
+    }
+    out
+}
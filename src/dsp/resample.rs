@@ -0,0 +1,187 @@
+// Sample rate conversion for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::Sample;
+use super::filter::lowpass;
+use super::window::Window;
+
+/// Taps per polyphase branch of a resampler, multiplied by the factor the
+/// rate is reduced by so that the filter's transition band stays a fixed
+/// fraction of the output rate.
+const TAPS_PER_PHASE: usize = 24;
+
+/// Polyphase branches in a fractional resampler, between which the filter
+/// is linearly interpolated.
+const FRACTIONAL_PHASES: usize = 128;
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Polyphase rational resampler, changing the rate by `interp/decim` and
+/// keeping its state between blocks.
+#[derive(Clone, Debug)]
+pub struct Resampler<T: Sample> {
+    interp: usize,
+    decim: usize,
+    taps: usize,
+    phases: Vec<Vec<f32>>,
+    history: Vec<T>,
+    t: usize
+}
+
+impl<T: Sample> Resampler<T> {
+    /// Resample by `interp/decim`, with the anti-alias cutoff at 90% of the
+    /// lower of the input and output Nyquist frequencies. The stopband
+    /// begins a little above that Nyquist frequency, so only the top of the
+    /// band can alias, into the top of the band.
+    pub fn new(interp: usize, decim: usize) -> Resampler<T> {
+        let cutoff = 0.45 * (interp as f64 / decim as f64).min(1.0);
+        Resampler::with_cutoff(interp, decim, cutoff)
+    }
+
+    /// Resample by `interp/decim` with the filter cutoff at `cutoff`, as a
+    /// fraction of the input sample rate.
+    pub fn with_cutoff(interp: usize, decim: usize, cutoff: f64)
+                       -> Resampler<T> {
+        let g = gcd(interp.max(1), decim.max(1));
+        let (interp, decim) = (interp.max(1) / g, decim.max(1) / g);
+        let taps = TAPS_PER_PHASE * decim.div_ceil(interp);
+        let proto = lowpass(taps * interp, cutoff / interp as f64,
+                            Window::Blackman);
+        // Branch p holds every interp'th tap starting at p, scaled to make
+        // up for the zeros an interpolator would have inserted.
+        let phases = (0..interp).map(|p| {
+            proto.iter().skip(p).step_by(interp)
+                 .map(|&h| h * interp as f32).collect()
+        }).collect();
+        Resampler {
+            interp, decim, taps, phases,
+            history: vec![T::default(); taps - 1],
+            t: 0
+        }
+    }
+
+    /// Resample between two rates (in Hz).
+    pub fn between(in_rate: usize, out_rate: usize) -> Resampler<T> {
+        Resampler::new(out_rate, in_rate)
+    }
+
+    /// Ratio of output to input rate, reduced to lowest terms.
+    pub fn ratio(&self) -> (usize, usize) {
+        (self.interp, self.decim)
+    }
+
+    /// Resample a block, returning however many outputs are due.
+    pub fn process(&mut self, input: &[T]) -> Vec<T> {
+        let k = self.taps;
+        let mut buf = std::mem::take(&mut self.history);
+        buf.extend_from_slice(input);
+        let mut out = Vec::with_capacity(
+            input.len() * self.interp / self.decim + 1);
+        // `t` counts in units of the interpolated rate from the start of
+        // this block.
+        while self.t / self.interp < input.len() {
+            let base = self.t / self.interp + k - 1;
+            let phase = &self.phases[self.t % self.interp];
+            let mut acc = T::default();
+            for (j, &h) in phase.iter().enumerate() {
+                acc += buf[base - j] * h;
+            }
+            out.push(acc);
+            self.t += self.decim;
+        }
+        self.t -= input.len() * self.interp;
+        self.history = buf.split_off(buf.len() - (k - 1));
+        out
+    }
+}
+
+/// Resampler for an arbitrary, possibly irrational or slowly varying, ratio
+/// of output to input rate, keeping its state between blocks.
+///
+/// A finely divided polyphase filter is evaluated at the exact fractional
+/// position of each output, interpolating linearly between branches.
+#[derive(Clone, Debug)]
+pub struct FractionalResampler<T: Sample> {
+    taps: usize,
+    phases: Vec<Vec<f32>>,
+    step: f64,
+    history: Vec<T>,
+    t: f64
+}
+
+impl<T: Sample> FractionalResampler<T> {
+    /// Resample by `ratio` (output rate over input rate), with the
+    /// anti-alias cutoff at 90% of the lower Nyquist frequency.
+    pub fn new(ratio: f64) -> FractionalResampler<T> {
+        FractionalResampler::with_cutoff(ratio, 0.45 * ratio.min(1.0))
+    }
+
+    /// Resample by `ratio` with the filter cutoff at `cutoff`, as a fraction
+    /// of the input sample rate. The cutoff and filter length are fixed
+    /// when the ratio is later changed.
+    pub fn with_cutoff(ratio: f64, cutoff: f64) -> FractionalResampler<T> {
+        let p = FRACTIONAL_PHASES;
+        let taps = TAPS_PER_PHASE * (1.0 / ratio).ceil().max(1.0) as usize;
+        let proto = lowpass(taps * p, cutoff / p as f64, Window::Blackman);
+        // One extra branch, the first delayed by a sample, so there is
+        // always a branch either side of the position.
+        let phases = (0..=p).map(|b| {
+            (0..taps).map(|j| {
+                proto.get(b + j * p).map_or(0.0, |&h| h * p as f32)
+            }).collect()
+        }).collect();
+        FractionalResampler {
+            taps, phases,
+            step: 1.0 / ratio,
+            history: vec![T::default(); taps - 1],
+            t: 0.0
+        }
+    }
+
+    /// Resample between two rates (in Hz).
+    pub fn between(in_rate: f64, out_rate: f64) -> FractionalResampler<T> {
+        FractionalResampler::new(out_rate / in_rate)
+    }
+
+    /// Ratio of output to input rate.
+    pub fn ratio(&self) -> f64 {
+        1.0 / self.step
+    }
+
+    /// Change the ratio without disturbing the output, for instance to
+    /// follow a drifting clock.
+    pub fn set_ratio(&mut self, ratio: f64) {
+        self.step = 1.0 / ratio;
+    }
+
+    /// Resample a block, returning however many outputs are due.
+    pub fn process(&mut self, input: &[T]) -> Vec<T> {
+        let k = self.taps;
+        let p = FRACTIONAL_PHASES as f64;
+        let mut buf = std::mem::take(&mut self.history);
+        buf.extend_from_slice(input);
+        let mut out = Vec::with_capacity(
+            (input.len() as f64 / self.step) as usize + 1);
+        // `t` is the position of the next output in input samples from the
+        // start of this block.
+        while (self.t as usize) < input.len() {
+            let base = self.t as usize + k - 1;
+            let pos = self.t.fract() * p;
+            let b = pos as usize;
+            let a = (pos - b as f64) as f32;
+            let (lo, hi) = (&self.phases[b], &self.phases[b + 1]);
+            let mut acc = T::default();
+            for j in 0..k {
+                acc += buf[base - j] * (lo[j] + a * (hi[j] - lo[j]));
+            }
+            out.push(acc);
+            self.t += self.step;
+        }
+        self.t -= input.len() as f64;
+        self.history = buf.split_off(buf.len() - (k - 1));
+        out
+    }
+}
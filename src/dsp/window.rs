@@ -0,0 +1,43 @@
+// Window functions for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+/// A window function for spectral analysis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Window {
+    Rectangular, Hann, Hamming, Blackman, BlackmanHarris
+}
+
+impl Window {
+    /// Generate `n` coefficients of this window.
+    ///
+    /// Windows are periodic rather than symmetric, as suits use before an
+    /// FFT.
+    pub fn coefficients(&self, n: usize) -> Vec<f32> {
+        self.generate(n, n)
+    }
+
+    /// Generate `n` coefficients of the symmetric form of this window, as
+    /// suits FIR filter design.
+    pub fn symmetric(&self, n: usize) -> Vec<f32> {
+        self.generate(n, n.saturating_sub(1).max(1))
+    }
+
+    fn generate(&self, n: usize, period: usize) -> Vec<f32> {
+        let cosines: &[f64] = match *self {
+            Window::Rectangular => &[1.0],
+            Window::Hann => &[0.5, 0.5],
+            Window::Hamming => &[0.54, 0.46],
+            Window::Blackman => &[0.42, 0.5, 0.08],
+            Window::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168]
+        };
+        (0..n).map(|i| {
+            let x = 2.0 * std::f64::consts::PI * i as f64 / period as f64;
+            cosines.iter().enumerate().map(|(k, a)| {
+                // Terms alternate in sign: a0 - a1 cos(x) + a2 cos(2x) - ...
+                let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                sign * a * (k as f64 * x).cos()
+            }).sum::<f64>() as f32
+        }).collect()
+    }
+}
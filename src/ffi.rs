@@ -134,6 +134,11 @@ unsafe extern "C" {
     // Returns -1 for error, 0 for disabled, 1 for enabled
     pub fn rtlsdr_get_offset_tuning(dev: *mut rtlsdr_dev) -> c_int;
 
+    // 1 = enable bias tee on GPIO 0
+    // 0 = disable bias tee
+    // Returns 0 for success
+    pub fn rtlsdr_set_bias_tee(dev: *mut rtlsdr_dev, on: c_int) -> c_int;
+
     pub fn rtlsdr_reset_buffer(dev: *mut rtlsdr_dev) -> c_int;
     pub fn rtlsdr_read_sync(dev: *mut rtlsdr_dev, buf: *mut c_void, len: c_int,
                             n_read: *mut c_int) -> c_int;
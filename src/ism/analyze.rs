@@ -0,0 +1,448 @@
+// Pulse analysis for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// For working out how an unknown device transmits. Bursts are cut from the
+// converted samples wherever the signal stands above the noise floor, and
+// each is classified as on-off keyed, two level amplitude keyed or two tone
+// frequency keyed, then turned into pulses and gaps of its two states.
+// Pulse and gap widths are grouped into histograms, a symbol period is
+// fitted to them, and the line codes they suggest are tried, leaving the
+// bits for a person to make sense of.
+
+use std::collections::VecDeque;
+
+use crate::dsp::Complex;
+use crate::samples::Converter;
+use super::bits::Bitbuffer;
+use super::pulse::{Modulation, PulseConfig, PulseData};
+use super::slicer::{self, LineCode};
+
+/// Bursts are cut off at this length (in microseconds).
+const MAX_BURST: f64 = 1_000_000.0;
+
+/// Samples averaged for the first noise floor estimate.
+const WARMUP: u64 = 1024;
+
+/// Weight of each new sample in the noise floor estimate.
+const NOISE_ALPHA: f32 = 1.0 / 1024.0;
+
+/// Fraction of a burst's samples which must sit near one of two levels or
+/// tones for it to count as ASK or FSK.
+const CLUSTERED: f64 = 0.8;
+
+/// Smallest ratio of ASK levels.
+const MIN_ASK_RATIO: f32 = 1.4;
+
+/// The lower ASK level must be this far over the noise, or the carrier is
+/// taken to be off.
+const ASK_FLOOR: f32 = 2.0;
+
+/// Widths within this fraction of a bin's mean join it.
+const TOLERANCE: f64 = 0.2;
+
+/// Fraction of the most widths explained by any symbol period which a
+/// longer period must explain to be preferred.
+const NEARLY_ALL: f64 = 0.95;
+
+/// Candidate line codes giving no row this long (in bits) are dropped.
+const MIN_ROW: usize = 8;
+
+/// Widths of more than this many symbols are taken as syncs or message
+/// breaks rather than data.
+const MAX_SYMBOLS: f64 = 8.0;
+
+/// A stretch of signal with no gap longer than the maximum gap.
+#[derive(Clone, Debug)]
+pub struct Burst {
+    /// Index of the first sample, counted from the first sample processed.
+    pub start: u64,
+    pub sample_rate: u32,
+    pub samples: Vec<Complex>,
+    /// Mean magnitude of the noise before the burst.
+    pub noise: f32
+}
+
+impl Burst {
+    /// Length (in microseconds).
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 * 1e6 / self.sample_rate as f64
+    }
+}
+
+/// Cuts bursts out of u8 I/Q, keeping state between blocks.
+pub struct Segmenter {
+    converter: Converter,
+    config: PulseConfig,
+    sample_rate: u32,
+    envelope: f32,
+    noise: f32,
+    burst: Vec<Complex>,
+    /// Samples before the burst, so its start isn't lost to the envelope
+    /// filter's delay.
+    history: VecDeque<Complex>,
+    active: bool,
+    /// Samples since the signal was last above the threshold.
+    quiet: usize,
+    start: u64,
+    sample: u64
+}
+
+impl Segmenter {
+    /// A segmenter using the pulse detector's threshold and maximum gap.
+    pub fn new(sample_rate: u32, config: PulseConfig) -> Segmenter {
+        let mut converter = Converter::new();
+        converter.set_dc_block(true);
+        Segmenter {
+            converter, config, sample_rate, envelope: 0.0, noise: 0.0,
+            burst: Vec::new(), history: VecDeque::new(), active: false,
+            quiet: 0, start: 0, sample: 0
+        }
+    }
+
+    /// Current noise floor estimate (in dBFS).
+    pub fn noise_level(&self) -> f64 {
+        20.0 * self.noise.max(1e-6).log10() as f64
+    }
+
+    /// Process interleaved u8 I/Q, returning the bursts completed. A
+    /// trailing odd byte is ignored.
+    pub fn process_u8(&mut self, buf: &[u8]) -> Vec<Burst> {
+        let ratio = 10f32.powf(self.config.min_snr as f32 / 20.0);
+        let floor = 10f32.powf(self.config.min_level as f32 / 20.0);
+        let us = |us: f64| (us * self.sample_rate as f64 / 1e6) as usize;
+        let (max_gap, max_len) = (us(self.config.max_gap), us(MAX_BURST));
+        // Average the envelope over about the shortest pulse, which keeps
+        // noise spikes from holding a burst open.
+        let min_pulse = us(self.config.min_pulse).max(1);
+        let alpha = 1.0 / min_pulse as f32;
+        let mut bursts = Vec::new();
+        for x in self.converter.convert(buf) {
+            self.envelope += (x.norm() - self.envelope) * alpha;
+            let env = self.envelope;
+            self.sample += 1;
+            if self.sample <= WARMUP {
+                self.noise += (env - self.noise) / self.sample as f32;
+                continue;
+            }
+            let threshold = (self.noise * ratio).max(floor);
+            if !self.active {
+                self.history.push_back(x);
+                if self.history.len() > 2 * min_pulse {
+                    self.history.pop_front();
+                }
+                if env > threshold {
+                    self.active = true;
+                    self.start = self.sample - self.history.len() as u64;
+                    self.quiet = 0;
+                    self.burst.extend(self.history.drain(..));
+                } else {
+                    self.noise += (env - self.noise) * NOISE_ALPHA;
+                }
+                continue;
+            }
+            self.burst.push(x);
+            self.quiet = if env > threshold { 0 } else { self.quiet + 1 };
+            if self.quiet > max_gap || self.burst.len() > max_len {
+                bursts.push(self.end_burst());
+            }
+        }
+        bursts
+    }
+
+    /// Return the burst in progress, if any, as at the end of a recording.
+    pub fn finish(&mut self) -> Option<Burst> {
+        if self.active { Some(self.end_burst()) } else { None }
+    }
+
+    /// End the burst in progress, without the quiet samples at its end.
+    fn end_burst(&mut self) -> Burst {
+        let len = self.burst.len() - self.quiet;
+        self.burst.truncate(len);
+        self.active = false;
+        Burst {
+            start: self.start,
+            sample_rate: self.sample_rate,
+            samples: std::mem::take(&mut self.burst),
+            noise: self.noise
+        }
+    }
+}
+
+/// How a burst was keyed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keying {
+    /// Carrier on and off.
+    Ook,
+    /// Carrier between two levels.
+    Ask,
+    /// Carrier between two frequencies.
+    Fsk
+}
+
+/// A group of similar widths (in microseconds).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bin {
+    pub count: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64
+}
+
+/// Group widths into bins of similar width, narrowest first.
+pub fn histogram(widths: &[f64]) -> Vec<Bin> {
+    let mut sorted = widths.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mut bins: Vec<Bin> = Vec::new();
+    for w in sorted {
+        match bins.last_mut() {
+            Some(bin) if w <= bin.mean * (1.0 + TOLERANCE) => {
+                bin.mean = (bin.mean * bin.count as f64 + w) /
+                           (bin.count + 1) as f64;
+                bin.count += 1;
+                bin.max = w;
+            },
+            _ => bins.push(Bin { count: 1, mean: w, min: w, max: w })
+        }
+    }
+    bins
+}
+
+/// Fit a symbol period (in microseconds) to the bins: the longest period of
+/// which nearly as many widths as possible are close to whole multiples.
+pub fn symbol_period(bins: &[Bin]) -> Option<f64> {
+    let fit = |t: f64| {
+        let (mut total, mut symbols, mut explained) = (0.0, 0.0, 0);
+        for b in bins {
+            let n = (b.mean / t).round();
+            if (1.0..=MAX_SYMBOLS).contains(&n) &&
+               (b.mean / t - n).abs() < 0.2 {
+                total += b.mean * b.count as f64;
+                symbols += n * b.count as f64;
+                explained += b.count;
+            }
+        }
+        (explained, total / symbols)
+    };
+    // Try each bin as one symbol, and as two, for codes where no width is
+    // a single symbol.
+    let fits: Vec<(usize, f64)> = bins.iter()
+        .flat_map(|b| [b.mean, b.mean / 2.0]).map(fit)
+        .filter(|&(n, _)| n > 0).collect();
+    let most = fits.iter().map(|f| f.0).max()?;
+    fits.iter().filter(|f| f.0 as f64 >= NEARLY_ALL * most as f64)
+        .map(|f| f.1).max_by(|a, b| a.total_cmp(b))
+}
+
+/// What was learnt about a burst.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub keying: Keying,
+    /// Carrier offset for OOK and ASK, or the pulse and gap tones for FSK
+    /// (in Hz).
+    pub tones: Vec<f64>,
+    /// Pulse level (in dBFS) and its ratio to the noise (in dB).
+    pub rssi: f64,
+    pub snr: f64,
+    /// The burst as pulses of its high level or tone and gaps of its low;
+    /// the last gap is the end of the burst.
+    pub pulses: PulseData,
+    pub pulse_bins: Vec<Bin>,
+    /// Bins of every gap but the last.
+    pub gap_bins: Vec<Bin>,
+    /// Symbol period (in microseconds).
+    pub symbol_period: Option<f64>,
+    /// Line codes suggested by the widths, each with the bits it slices.
+    pub candidates: Vec<(LineCode, Bitbuffer)>
+}
+
+impl Analysis {
+    /// Symbols per second.
+    pub fn symbol_rate(&self) -> Option<f64> {
+        self.symbol_period.map(|t| 1e6 / t)
+    }
+}
+
+/// The 10th and 90th percentiles of `x`, and whether most values lie near
+/// one or the other.
+fn two_levels(x: &[f32]) -> (f32, f32, bool) {
+    let mut sorted = x.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let low = sorted[sorted.len() / 10];
+    let high = sorted[sorted.len() * 9 / 10];
+    let near = (high - low) / 4.0;
+    let clustered = x.iter().filter(|&&v| {
+        (v - low).abs() <= near || (v - high).abs() <= near
+    }).count();
+    (low, high, clustered as f64 >= CLUSTERED * x.len() as f64)
+}
+
+fn mean(x: impl Iterator<Item = f32>) -> f64 {
+    let (sum, n) = x.fold((0.0, 0), |(s, n), v| (s + v as f64, n + 1));
+    if n > 0 { sum / n as f64 } else { 0.0 }
+}
+
+/// Classify a burst and slice it every way its widths suggest. Returns
+/// None if the burst is too short to say anything about.
+pub fn analyze(burst: &Burst, config: &PulseConfig) -> Option<Analysis> {
+    let x = &burst.samples;
+    if x.len() < 16 {
+        return None;
+    }
+    let rate = burst.sample_rate as f64;
+    let hz = rate / (2.0 * std::f64::consts::PI);
+    let env: Vec<f32> = x.windows(3).map(|w| {
+        w.iter().map(|s| s.norm()).sum::<f32>() / 3.0
+    }).collect();
+    let step: Vec<f32> = x.windows(2).map(|w| {
+        (w[1] * w[0].conj()).arg()
+    }).collect();
+    let freq: Vec<f32> = step.windows(3).map(|w| w.iter().sum::<f32>() / 3.0)
+                             .collect();
+
+    // The carrier is on above the midpoint of the peak and the noise.
+    let mut sorted = env.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let peak = sorted[sorted.len() * 9 / 10];
+    let noise = burst.noise.max(1e-6);
+    let on: Vec<bool> = env.iter().map(|&e| e > (peak + noise) / 2.0)
+                           .collect();
+    let n = env.len().min(freq.len());
+    let on_freq: Vec<f32> = (0..n).filter(|&i| on[i]).map(|i| freq[i])
+                                  .collect();
+
+    let (f_low, f_high, fsk) = if on_freq.len() >= 16 {
+        two_levels(&on_freq)
+    } else {
+        (0.0, 0.0, false)
+    };
+    let deviation = (config.min_deviation / hz) as f32;
+    let (a_low, a_high, ask) = two_levels(&env);
+    let (keying, high): (Keying, Vec<bool>) = if fsk &&
+        f_high - f_low > deviation {
+        let mid = (f_low + f_high) / 2.0;
+        (Keying::Fsk, (0..n).map(|i| on[i] && freq[i] > mid).collect())
+    } else if ask && a_high >= MIN_ASK_RATIO * a_low &&
+              a_low > ASK_FLOOR * noise {
+        let mid = (a_low + a_high) / 2.0;
+        (Keying::Ask, env.iter().map(|&e| e > mid).collect())
+    } else {
+        (Keying::Ook, on.clone())
+    };
+
+    let tones = match keying {
+        Keying::Fsk => {
+            let mid = (f_low + f_high) / 2.0;
+            vec![mean(on_freq.iter().cloned().filter(|&f| f > mid)) * hz,
+                 mean(on_freq.iter().cloned().filter(|&f| f <= mid)) * hz]
+        },
+        _ => vec![mean(on_freq.iter().cloned()) * hz]
+    };
+
+    // Runs of the high and low states, with any shorter than the shortest
+    // pulse merged into the run before.
+    let min_pulse = (config.min_pulse * rate / 1e6).round() as usize;
+    let mut runs: Vec<(bool, usize)> = Vec::new();
+    for &h in &high {
+        match runs.last_mut() {
+            Some(last) if last.0 == h => last.1 += 1,
+            _ => runs.push((h, 1))
+        }
+    }
+    let mut merged: Vec<(bool, usize)> = Vec::new();
+    for (h, len) in runs {
+        match merged.last_mut() {
+            Some(last) if last.0 == h || len < min_pulse => last.1 += len,
+            _ => merged.push((h, len))
+        }
+    }
+    let mut pulses = Vec::new();
+    let mut gaps = Vec::new();
+    for (h, len) in merged {
+        if h {
+            pulses.push(len);
+        } else if !pulses.is_empty() {
+            gaps.push(len);
+        }
+    }
+    if pulses.is_empty() {
+        return None;
+    }
+    gaps.truncate(pulses.len() - 1);
+    // End with a gap longer than any code's reset.
+    gaps.push(burst.sample_rate as usize);
+
+    let pulses = PulseData {
+        modulation: match keying {
+            Keying::Fsk => Modulation::Fsk,
+            _ => Modulation::Ook
+        },
+        sample_rate: burst.sample_rate,
+        pulses, gaps, start: burst.start,
+        rssi: 20.0 * (peak.max(1e-6) as f64).log10(),
+        snr: 20.0 * (peak.max(1e-6) / noise).log10() as f64
+    };
+    let pulse_widths: Vec<f64> = pulses.pulses.iter()
+                                       .map(|&p| pulses.to_us(p)).collect();
+    let gap_widths: Vec<f64> = pulses.gaps[..pulses.len() - 1].iter()
+                                     .map(|&g| pulses.to_us(g)).collect();
+    let pulse_bins = histogram(&pulse_widths);
+    let gap_bins = histogram(&gap_widths);
+    let all: Vec<Bin> = pulse_bins.iter().chain(&gap_bins).cloned().collect();
+    let period = symbol_period(&all);
+    let candidates = match period {
+        Some(t) => candidates(t, &pulse_bins, &gap_bins).into_iter()
+            .map(|code| (code, slicer::slice(&pulses, &code)))
+            .filter(|(_, bits)| bits.rows().any(|r| r.len() >= MIN_ROW))
+            .collect(),
+        None => Vec::new()
+    };
+
+    Some(Analysis {
+        keying, tones, rssi: pulses.rssi, snr: pulses.snr,
+        symbol_period: period, pulse_bins, gap_bins, candidates, pulses
+    })
+}
+
+/// A gap limit between the gap bins no wider than `max` and the rest,
+/// which are taken to split messages or rows.
+fn gap_limit(gap_bins: &[Bin], max: f64, t: f64) -> f64 {
+    let longest = gap_bins.iter().filter(|b| b.mean <= max).map(|b| b.max)
+                          .fold(t, f64::max);
+    match gap_bins.iter().find(|b| b.mean > max) {
+        Some(b) => (longest + b.min) / 2.0,
+        None => longest * 1.5 + t
+    }
+}
+
+/// Line codes worth trying for symbol period `t`.
+fn candidates(t: f64, pulse_bins: &[Bin], gap_bins: &[Bin]) -> Vec<LineCode> {
+    let limit = gap_limit(gap_bins, MAX_SYMBOLS * t, t);
+    let mut codes = vec![
+        LineCode::Pcm { short: t, long: t, reset: limit },
+        LineCode::Manchester { half: t, reset: limit }
+    ];
+    let data = |b: &&Bin| b.mean <= MAX_SYMBOLS * t;
+    let pulses: Vec<&Bin> = pulse_bins.iter().filter(data).collect();
+    let gaps: Vec<&Bin> = gap_bins.iter().filter(data).collect();
+    if pulses.len() >= 2 {
+        // Gaps within a message are no longer than the long pulses.
+        let (short, long) = (pulses[0].mean, pulses[1].mean);
+        let limit = gap_limit(gap_bins, pulses[1].max * 1.5, t);
+        codes.push(LineCode::Pwm { short, long, gap_limit: limit,
+                                   reset: limit });
+    }
+    if pulses.len() == 1 && gaps.len() >= 2 {
+        let (short, long) = (gaps[0].mean, gaps[1].mean);
+        let limit = gap_limit(gap_bins, gaps[1].max, t);
+        codes.push(LineCode::Ppm { short, long, gap_limit: limit,
+                                   reset: limit });
+    }
+    if pulses.len() == 1 && pulses[0].mean < t * 1.5 {
+        // Equal pulses with gaps of whole periods: return to zero.
+        let period = gaps.first().map_or(2.0 * t, |g| g.mean + pulses[0].mean);
+        codes.push(LineCode::Pcm { short: pulses[0].mean, long: period,
+                                   reset: limit });
+    }
+    codes
+}
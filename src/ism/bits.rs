@@ -0,0 +1,191 @@
+// Bit buffers for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Sliced pulse trains become rows of bits, one row for each burst of a
+// transmission, which device decoders search and unpack.
+
+/// A row of bits, packed most significant bit first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitRow {
+    bytes: Vec<u8>,
+    len: usize
+}
+
+impl BitRow {
+    pub fn new() -> BitRow {
+        BitRow::default()
+    }
+
+    /// A row holding the first `len` bits of `bytes`.
+    pub fn from_bytes(bytes: &[u8], len: usize) -> BitRow {
+        let mut row = BitRow::new();
+        for i in 0..len.min(8 * bytes.len()) {
+            row.push(bytes[i / 8] >> (7 - i % 8) & 1 != 0);
+        }
+        row
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            self.bytes[self.len / 8] |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    /// Number of bits.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bit `i`, or false past the end.
+    pub fn get(&self, i: usize) -> bool {
+        i < self.len && self.bytes[i / 8] >> (7 - i % 8) & 1 != 0
+    }
+
+    /// The bits packed into bytes, with the last byte padded with zeros.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// `len` bits from bit `start`, packed into bytes and padded with
+    /// zeros.
+    pub fn extract(&self, start: usize, len: usize) -> Vec<u8> {
+        let mut out = vec![0u8; len.div_ceil(8)];
+        for i in 0..len {
+            if self.get(start + i) {
+                out[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        out
+    }
+
+    /// Position of the first match of the first `len` bits of `pattern`
+    /// at or after bit `start`.
+    pub fn search(&self, start: usize, pattern: &[u8], len: usize)
+                  -> Option<usize> {
+        let want = BitRow::from_bytes(pattern, len);
+        (start..(self.len + 1).saturating_sub(len)).find(|&p| {
+            (0..len).all(|i| self.get(p + i) == want.get(i))
+        })
+    }
+
+    /// Manchester decode pairs of bits from bit `start`, with 01 for a one
+    /// and 10 for a zero (IEEE 802.3), stopping after `max` bits or at the
+    /// first invalid pair.
+    pub fn manchester_decode(&self, start: usize, max: usize) -> BitRow {
+        let mut out = BitRow::new();
+        let mut i = start;
+        while out.len() < max && i + 1 < self.len {
+            let (a, b) = (self.get(i), self.get(i + 1));
+            if a == b {
+                break;
+            }
+            out.push(b);
+            i += 2;
+        }
+        out
+    }
+
+    /// Flip every bit.
+    pub fn invert(&mut self) {
+        for b in self.bytes.iter_mut() {
+            *b = !*b;
+        }
+        // Keep the padding zero, so equal rows compare equal.
+        if !self.len.is_multiple_of(8) {
+            let last = self.bytes.len() - 1;
+            self.bytes[last] &= 0xff << (8 - self.len % 8);
+        }
+    }
+}
+
+/// Rows of bits sliced from one transmission.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bitbuffer {
+    rows: Vec<BitRow>
+}
+
+impl Bitbuffer {
+    pub fn new() -> Bitbuffer {
+        Bitbuffer::default()
+    }
+
+    /// Start a new row, unless the current one is empty.
+    pub fn add_row(&mut self) {
+        if self.rows.last().is_none_or(|r| !r.is_empty()) {
+            self.rows.push(BitRow::new());
+        }
+    }
+
+    /// Add a bit to the current row.
+    pub fn add_bit(&mut self, bit: bool) {
+        if self.rows.is_empty() {
+            self.rows.push(BitRow::new());
+        }
+        let last = self.rows.len() - 1;
+        self.rows[last].push(bit);
+    }
+
+    /// The non-empty rows.
+    pub fn rows(&self) -> impl Iterator<Item = &BitRow> {
+        self.rows.iter().filter(|r| !r.is_empty())
+    }
+
+    pub fn row(&self, i: usize) -> Option<&BitRow> {
+        self.rows().nth(i)
+    }
+
+    /// Number of non-empty rows.
+    pub fn len(&self) -> usize {
+        self.rows().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Flip every bit of every row.
+    pub fn invert(&mut self) {
+        for r in self.rows.iter_mut() {
+            r.invert();
+        }
+    }
+
+    /// A row of at least `min_bits` bits which appears at least `repeats`
+    /// times, as transmitters usually repeat each message.
+    pub fn find_repeated_row(&self, repeats: usize, min_bits: usize)
+                             -> Option<&BitRow> {
+        self.rows().find(|r| {
+            r.len() >= min_bits && self.rows().filter(|o| o == r).count() >=
+                                   repeats
+        })
+    }
+}
+
+/// CRC-8 of `data`, most significant bit first, with polynomial `poly`
+/// (without its x^8 term) and initial value `init`.
+pub fn crc8(data: &[u8], poly: u8, init: u8) -> u8 {
+    data.iter().fold(init, |crc, &b| {
+        (0..8).fold(crc ^ b, |c, _| {
+            if c & 0x80 != 0 { (c << 1) ^ poly } else { c << 1 }
+        })
+    })
+}
+
+/// XOR of every byte.
+pub fn xor_bytes(data: &[u8]) -> u8 {
+    data.iter().fold(0, |x, &b| x ^ b)
+}
+
+/// Sum of every byte, modulo 256.
+pub fn add_bytes(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |s, &b| s.wrapping_add(b))
+}
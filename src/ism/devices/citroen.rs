@@ -0,0 +1,77 @@
+// Citroen TPMS decoder for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Tyre pressure sensors fitted to Citroen and Peugeot cars send FSK at
+// 19.2kbaud, Manchester coded after a preamble ending 0x5556. The ten byte
+// message is
+//
+//   SS IIIIIIII FR PP TT BB CC
+//
+// with state S, sensor ID I, flags F, repeat count R, pressure P in units
+// of 1.364kPa, temperature T in degrees Celsius offset by 50, battery B,
+// and a check byte C making the XOR of all but the state zero.
+
+use crate::ism::Decoder;
+use crate::ism::bits::{BitRow, Bitbuffer, xor_bytes};
+use crate::ism::pulse::Modulation;
+use crate::ism::slicer::LineCode;
+use crate::json::Value;
+
+const PREAMBLE: [u8; 2] = [0x55, 0x56];
+
+/// Message length (in bits).
+const MESSAGE_BITS: usize = 80;
+
+/// Citroen TPMS sensors.
+pub struct Citroen;
+
+impl Citroen {
+    fn decode_row(&self, row: &BitRow) -> Option<Value> {
+        let start = row.search(0, &PREAMBLE, 16)? + 16;
+        let bits = row.manchester_decode(start, MESSAGE_BITS);
+        if bits.len() < MESSAGE_BITS {
+            return None;
+        }
+        let b = bits.bytes();
+        if b[6] == 0 || b[7] == 0 || xor_bytes(&b[1..10]) != 0 {
+            return None;
+        }
+        let id = u32::from_be_bytes([b[1], b[2], b[3], b[4]]);
+        Some(Value::object()
+            .with("model", "Citroen")
+            .with("type", "TPMS")
+            .with("state", format!("{:02x}", b[0]))
+            .with("id", format!("{:08x}", id))
+            .with("flags", (b[5] >> 4) as u32)
+            .with("repeat", (b[5] & 0x0f) as u32)
+            .with("pressure_kPa", (b[6] as f64 * 1.364 * 10.0).round() / 10.0)
+            .with("temperature_C", b[7] as i32 - 50)
+            .with("maybe_battery", b[8] as u32)
+            .with("mic", "CHECKSUM"))
+    }
+}
+
+impl Decoder for Citroen {
+    fn name(&self) -> &str {
+        "Citroen"
+    }
+
+    fn modulation(&self) -> Modulation {
+        Modulation::Fsk
+    }
+
+    fn line_code(&self) -> LineCode {
+        LineCode::Pcm { short: 52.0, long: 52.0, reset: 150.0 }
+    }
+
+    fn decode(&self, bits: &Bitbuffer) -> Vec<Value> {
+        // Which tone is the mark depends on the receiver's view of the
+        // spectrum, so try both.
+        let mut inverted = bits.clone();
+        inverted.invert();
+        bits.rows().chain(inverted.rows()).filter_map(|r| {
+            self.decode_row(r)
+        }).take(1).collect()
+    }
+}
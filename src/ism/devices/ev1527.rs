@@ -0,0 +1,56 @@
+// EV1527 doorbell decoder for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Most cheap wireless doorbells, and many remotes, use the EV1527 encoder
+// or a copy of it. Each message is a short sync pulse and a gap of 31
+// periods, then 24 bits as a pulse of three periods and a gap of one for a
+// one, or the other way around for a zero. The first 20 bits are a fixed
+// ID and the last four the buttons. Period varies between devices, from
+// about 250 to 450us.
+
+use crate::ism::Decoder;
+use crate::ism::bits::Bitbuffer;
+use crate::ism::pulse::Modulation;
+use crate::ism::slicer::LineCode;
+use crate::json::Value;
+
+/// EV1527 doorbells.
+pub struct Ev1527;
+
+impl Decoder for Ev1527 {
+    fn name(&self) -> &str {
+        "EV1527"
+    }
+
+    fn modulation(&self) -> Modulation {
+        Modulation::Ook
+    }
+
+    fn line_code(&self) -> LineCode {
+        LineCode::Pwm { short: 350.0, long: 1050.0, gap_limit: 2000.0,
+                        reset: 16_000.0 }
+    }
+
+    fn decode(&self, bits: &Bitbuffer) -> Vec<Value> {
+        // Rows are split at the sync gap, so hold 24 bits and the next
+        // message's sync pulse. Short pulses slice as ones, so invert.
+        let row = match bits.find_repeated_row(2, 24) {
+            Some(row) if row.len() <= 25 => row,
+            _ => return Vec::new()
+        };
+        let mut data = row.clone();
+        data.invert();
+        let b = data.extract(0, 24);
+        let id = (b[0] as u32) << 12 | (b[1] as u32) << 4 | (b[2] >> 4) as u32;
+        // All ones or all zeros is more likely noise than a device.
+        if id == 0 || id == 0xfffff {
+            return Vec::new();
+        }
+        vec![Value::object()
+            .with("model", "EV1527")
+            .with("type", "doorbell")
+            .with("id", format!("{:05x}", id))
+            .with("button", (b[2] & 0x0f) as u32)]
+    }
+}
@@ -0,0 +1,18 @@
+// Built in ISM device decoders for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::Decoder;
+
+pub mod citroen;
+pub mod ev1527;
+pub mod nexus;
+
+/// One of each built in decoder.
+pub fn all() -> Vec<Box<dyn Decoder>> {
+    vec![
+        Box::new(nexus::Nexus),
+        Box::new(citroen::Citroen),
+        Box::new(ev1527::Ev1527)
+    ]
+}
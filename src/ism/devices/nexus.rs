@@ -0,0 +1,59 @@
+// Nexus weather sensor decoder for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Nexus temperature and humidity sensors, also sold under many other names,
+// send 36 bit messages in gap widths, repeated about a dozen times:
+//
+//   IIIIIIII B0CC TTTT TTTTTTTT 1111 HHHHHHHH
+//
+// with an ID which changes when the batteries do, a battery good flag B,
+// channel C, signed temperature T in tenths of a degree Celsius, and
+// relative humidity H.
+
+use crate::ism::Decoder;
+use crate::ism::bits::Bitbuffer;
+use crate::ism::pulse::Modulation;
+use crate::ism::slicer::LineCode;
+use crate::json::Value;
+
+/// Nexus-TH sensors.
+pub struct Nexus;
+
+impl Decoder for Nexus {
+    fn name(&self) -> &str {
+        "Nexus-TH"
+    }
+
+    fn modulation(&self) -> Modulation {
+        Modulation::Ook
+    }
+
+    fn line_code(&self) -> LineCode {
+        LineCode::Ppm { short: 1000.0, long: 2000.0, gap_limit: 3000.0,
+                        reset: 5000.0 }
+    }
+
+    fn decode(&self, bits: &Bitbuffer) -> Vec<Value> {
+        let row = match bits.find_repeated_row(3, 36) {
+            Some(row) if row.len() <= 37 => row,
+            _ => return Vec::new()
+        };
+        let b = row.bytes();
+        if b[3] & 0xf0 != 0xf0 {
+            return Vec::new();
+        }
+        let temperature = ((b[1] as i16) << 12 | (b[2] as i16) << 4) >> 4;
+        let humidity = (b[3] & 0x0f) << 4 | b[4] >> 4;
+        let mut event = Value::object()
+            .with("model", "Nexus-TH")
+            .with("id", b[0] as u32)
+            .with("channel", ((b[1] >> 4 & 3) + 1) as u32)
+            .with("battery_ok", (b[1] & 0x80 != 0) as u32)
+            .with("temperature_C", temperature as f64 / 10.0);
+        if humidity != 0 {
+            event.set("humidity", humidity as u32);
+        }
+        vec![event]
+    }
+}
@@ -0,0 +1,178 @@
+// ISM band device decoding for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Sensors, remotes and the like in the 433/868/915MHz bands, in the manner
+// of rtl_433. The pulse detector turns I/Q into trains of pulses, each
+// decoder slices them with its own line code and timings, and any device
+// it recognises is reported as a JSON event. Decoders are trait objects,
+// so devices of one's own can be added to the table alongside the built in
+// ones.
+
+use std::time::SystemTime;
+
+use super::json::Value;
+use super::timestamp::UtcTime;
+
+pub mod analyze;
+pub mod bits;
+pub mod devices;
+pub mod pulse;
+pub mod slicer;
+
+use self::bits::Bitbuffer;
+use self::pulse::{Modulation, PulseData, PulseDetector};
+use self::slicer::LineCode;
+
+/// Common frequencies (in Hz).
+pub const FREQUENCY_433: u64 = 433_920_000;
+pub const FREQUENCY_868: u64 = 868_300_000;
+pub const FREQUENCY_915: u64 = 915_000_000;
+
+/// Sample rate which suits most devices (in Hz).
+pub const SAMPLE_RATE: u32 = 250_000;
+
+/// A device decoder.
+pub trait Decoder: Send {
+    /// Name for listing and removing the decoder.
+    fn name(&self) -> &str;
+
+    /// Which pulse trains to decode.
+    fn modulation(&self) -> Modulation;
+
+    /// How to slice the pulse trains into bits.
+    fn line_code(&self) -> LineCode;
+
+    /// Events for each message found in the bits, usually with a "model"
+    /// field first. Bits from other devices should give none.
+    fn decode(&self, bits: &Bitbuffer) -> Vec<Value>;
+}
+
+/// The decoders to try on each pulse train.
+pub struct DecoderTable {
+    decoders: Vec<Box<dyn Decoder>>
+}
+
+impl DecoderTable {
+    /// An empty table.
+    pub fn new() -> DecoderTable {
+        DecoderTable { decoders: Vec::new() }
+    }
+
+    /// A table of every built in decoder.
+    pub fn with_defaults() -> DecoderTable {
+        DecoderTable { decoders: devices::all() }
+    }
+
+    pub fn add(&mut self, decoder: Box<dyn Decoder>) {
+        self.decoders.push(decoder);
+    }
+
+    /// Remove the decoder called `name`, returning whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.decoders.len();
+        self.decoders.retain(|d| d.name() != name);
+        self.decoders.len() != len
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.decoders.iter().map(|d| d.name()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.decoders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+
+    /// Run every decoder for the train's modulation, returning their
+    /// events with the time first and the signal levels last.
+    pub fn decode(&self, data: &PulseData, time: SystemTime) -> Vec<Value> {
+        let t = UtcTime::from_system(time);
+        let time = format!("{} {}", t.date(), t.time());
+        let mut events = Vec::new();
+        for d in self.decoders.iter() {
+            if d.modulation() != data.modulation {
+                continue;
+            }
+            let bits = slicer::slice(data, &d.line_code());
+            if bits.is_empty() {
+                continue;
+            }
+            for event in d.decode(&bits) {
+                let mut out = Value::object().with("time", time.as_str());
+                if let Value::Object(fields) = event {
+                    for (k, v) in fields {
+                        out.set(&k, v);
+                    }
+                }
+                let modulation = match data.modulation {
+                    Modulation::Ook => "ASK",
+                    Modulation::Fsk => "FSK"
+                };
+                out.set("mod", modulation);
+                out.set("rssi", (data.rssi * 100.0).round() / 100.0);
+                out.set("snr", (data.snr * 100.0).round() / 100.0);
+                events.push(out);
+            }
+        }
+        events
+    }
+}
+
+impl Default for DecoderTable {
+    fn default() -> DecoderTable {
+        DecoderTable::new()
+    }
+}
+
+/// A pulse detector feeding a decoder table.
+pub struct Receiver {
+    detector: PulseDetector,
+    table: DecoderTable
+}
+
+impl Receiver {
+    /// A receiver with the built in decoders.
+    pub fn new(sample_rate: u32) -> Receiver {
+        Receiver {
+            detector: PulseDetector::new(sample_rate),
+            table: DecoderTable::with_defaults()
+        }
+    }
+
+    pub fn detector(&self) -> &PulseDetector {
+        &self.detector
+    }
+
+    pub fn detector_mut(&mut self) -> &mut PulseDetector {
+        &mut self.detector
+    }
+
+    pub fn table(&self) -> &DecoderTable {
+        &self.table
+    }
+
+    /// The decoder table, to add or remove decoders.
+    pub fn table_mut(&mut self) -> &mut DecoderTable {
+        &mut self.table
+    }
+
+    /// Process interleaved u8 I/Q, returning the events decoded.
+    pub fn process_u8(&mut self, buf: &[u8]) -> Vec<Value> {
+        let now = SystemTime::now();
+        self.detector.process_u8(buf).iter().flat_map(|data| {
+            self.table.decode(data, now)
+        }).collect()
+    }
+
+    /// Decode any train still in progress, as at the end of a recording.
+    pub fn flush(&mut self) -> Vec<Value> {
+        let now = SystemTime::now();
+        self.detector.flush().iter().flat_map(|data| {
+            self.table.decode(data, now)
+        }).collect()
+    }
+}
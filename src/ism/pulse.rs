@@ -0,0 +1,409 @@
+// Pulse detection for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Most ISM band devices key a carrier on and off (OOK), or shift it between
+// two frequencies while it is on (FSK). The detector follows the envelope
+// against an adaptive noise floor to find carrier bursts, measuring the
+// width of each pulse and the gap after it. A train of pulses ends at a gap
+// longer than any device leaves within a message. While the carrier is on,
+// the phase steps between samples give its frequency, and a burst which
+// moves between two clear tones is also reported as an FSK train, with the
+// higher tone as the pulses and the lower as the gaps.
+
+use crate::samples::IqPairs;
+
+/// How a pulse train was keyed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Modulation {
+    Ook,
+    Fsk
+}
+
+/// A train of pulses, widths in samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PulseData {
+    pub modulation: Modulation,
+    pub sample_rate: u32,
+    /// Width of each pulse.
+    pub pulses: Vec<usize>,
+    /// Gap after each pulse; the last is the gap which ended the train.
+    pub gaps: Vec<usize>,
+    /// Index of the first sample of the first pulse, counted from the first
+    /// sample processed.
+    pub start: u64,
+    /// Pulse level, in dB relative to full scale.
+    pub rssi: f64,
+    /// Pulse level over the noise floor, in dB.
+    pub snr: f64
+}
+
+impl PulseData {
+    /// Number of pulses.
+    pub fn len(&self) -> usize {
+        self.pulses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pulses.is_empty()
+    }
+
+    /// Convert a width in samples to microseconds.
+    pub fn to_us(&self, samples: usize) -> f64 {
+        samples as f64 * 1e6 / self.sample_rate as f64
+    }
+
+    /// Convert a width in microseconds to samples.
+    pub fn to_samples(&self, us: f64) -> f64 {
+        us * self.sample_rate as f64 / 1e6
+    }
+}
+
+/// Settings for the pulse detector.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PulseConfig {
+    /// Level over the noise floor which starts a pulse (in dB).
+    pub min_snr: f64,
+    /// Lowest level which starts a pulse, however quiet the noise (in
+    /// dBFS).
+    pub min_level: f64,
+    /// Shorter pulses are taken as noise (in microseconds).
+    pub min_pulse: f64,
+    /// A longer pulse is a steady carrier rather than part of a message,
+    /// and ends the train without being counted (in microseconds).
+    pub max_pulse: f64,
+    /// A longer gap ends the train (in microseconds).
+    pub max_gap: f64,
+    /// Trains are cut off after this many pulses.
+    pub max_pulses: usize,
+    /// Smallest separation of the two tones of an FSK burst (in Hz).
+    pub min_deviation: f64
+}
+
+impl PulseConfig {
+    pub fn new() -> PulseConfig {
+        PulseConfig {
+            min_snr: 10.0,
+            min_level: -40.0,
+            min_pulse: 20.0,
+            max_pulse: 100_000.0,
+            max_gap: 20_000.0,
+            max_pulses: 1200,
+            min_deviation: 10_000.0
+        }
+    }
+}
+
+impl Default for PulseConfig {
+    fn default() -> PulseConfig {
+        PulseConfig::new()
+    }
+}
+
+/// Full scale magnitude of u8 I/Q.
+const FULL_SCALE: f32 = 127.5;
+
+/// Weight of each new sample in the envelope low pass filter.
+const ENVELOPE_ALPHA: f32 = 0.5;
+
+/// Weight of each new sample in the noise floor estimate.
+const NOISE_ALPHA: f32 = 1.0 / 1024.0;
+
+/// Weight of each new sample in the pulse level estimate.
+const LEVEL_ALPHA: f32 = 1.0 / 8.0;
+
+/// Samples averaged for the first noise floor estimate, before any pulses
+/// are looked for.
+const WARMUP: u64 = 1024;
+
+/// Fraction of an FSK burst's samples which must lie near one tone or the
+/// other, so noise isn't taken for FSK.
+const FSK_CLUSTERED: f64 = 0.8;
+
+/// Phase steps skipped at each end of a burst, where the carrier is still
+/// rising or falling.
+const FSK_EDGE: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Pulse,
+    Gap
+}
+
+/// Finds pulse trains in u8 I/Q, keeping state between blocks.
+pub struct PulseDetector {
+    config: PulseConfig,
+    sample_rate: u32,
+    /// Magnitude of I + jQ, indexed by I << 8 | Q.
+    magnitude: Vec<f32>,
+    /// Phase of I + jQ, as a fraction of a turn scaled to 65536.
+    phase: Vec<u16>,
+    state: State,
+    envelope: f32,
+    noise: f32,
+    level: f32,
+    /// Length of the current pulse or gap.
+    count: usize,
+    pulses: Vec<usize>,
+    gaps: Vec<usize>,
+    start: u64,
+    /// Phase steps during the current pulse.
+    steps: Vec<i16>,
+    last_phase: u16,
+    /// Index of the next sample since the start.
+    sample: u64,
+    iq: IqPairs,
+    found: Vec<PulseData>
+}
+
+impl PulseDetector {
+    pub fn new(sample_rate: u32) -> PulseDetector {
+        PulseDetector::with_config(sample_rate, PulseConfig::new())
+    }
+
+    pub fn with_config(sample_rate: u32, config: PulseConfig)
+                       -> PulseDetector {
+        let iq = |x: u32| ((x >> 8) as f32 - 127.5, (x & 0xff) as f32 - 127.5);
+        let magnitude = (0..65536).map(|x| {
+            let (i, q) = iq(x);
+            (i * i + q * q).sqrt()
+        }).collect();
+        let phase = (0..65536).map(|x| {
+            let (i, q) = iq(x);
+            let turns = q.atan2(i) / (2.0 * std::f32::consts::PI);
+            (turns * 65536.0).round() as i32 as u16
+        }).collect();
+        PulseDetector {
+            config, sample_rate, magnitude, phase, state: State::Idle,
+            envelope: 0.0, noise: 1.0, level: 0.0, count: 0,
+            pulses: Vec::new(), gaps: Vec::new(), start: 0,
+            steps: Vec::new(), last_phase: 0, sample: 0, iq: IqPairs::new(),
+            found: Vec::new()
+        }
+    }
+
+    pub fn config(&self) -> &PulseConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: PulseConfig) {
+        self.config = config;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Current noise floor estimate (in dBFS).
+    pub fn noise_level(&self) -> f64 {
+        20.0 * (self.noise.max(1e-3) / FULL_SCALE).log10() as f64
+    }
+
+    /// Forget any partial train and restart the sample count.
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+        self.pulses.clear();
+        self.gaps.clear();
+        self.steps.clear();
+        self.sample = 0;
+        self.iq.reset();
+    }
+
+    /// Process interleaved u8 I/Q, returning the trains completed.
+    pub fn process_u8(&mut self, buf: &[u8]) -> Vec<PulseData> {
+        for (i, q) in self.iq.pairs(buf) {
+            self.push(i, q);
+        }
+        std::mem::take(&mut self.found)
+    }
+
+    /// End any train in progress, as at the end of a recording, returning
+    /// it if it holds any pulses.
+    pub fn flush(&mut self) -> Vec<PulseData> {
+        if self.state == State::Pulse {
+            self.end_pulse();
+        }
+        if self.state == State::Gap {
+            self.gaps.push(self.count);
+            self.end_train();
+        }
+        std::mem::take(&mut self.found)
+    }
+
+    fn us_to_samples(&self, us: f64) -> usize {
+        (us * self.sample_rate as f64 / 1e6).round() as usize
+    }
+
+    /// Step the state machine by one sample.
+    fn push(&mut self, i: u8, q: u8) {
+        let x = (i as usize) << 8 | q as usize;
+        let phase = self.phase[x];
+        let step = phase.wrapping_sub(self.last_phase) as i16;
+        self.last_phase = phase;
+        self.envelope += (self.magnitude[x] - self.envelope) * ENVELOPE_ALPHA;
+        let env = self.envelope;
+        if self.sample < WARMUP {
+            self.noise += (env - self.noise) / (self.sample + 1) as f32;
+            self.sample += 1;
+            return;
+        }
+
+        // Pulses end below, and gaps end above, the midpoint of the pulse
+        // level and noise floor, with some hysteresis.
+        let mid = self.noise + (self.level - self.noise) / 2.0;
+        let hysteresis = (self.level - self.noise) / 8.0;
+        match self.state {
+            State::Idle => {
+                let ratio = 10f32.powf(self.config.min_snr as f32 / 20.0);
+                let floor = FULL_SCALE *
+                            10f32.powf(self.config.min_level as f32 / 20.0);
+                if env > (self.noise * ratio).max(floor) {
+                    self.state = State::Pulse;
+                    self.count = 1;
+                    self.level = env;
+                    self.start = self.sample;
+                    self.pulses.clear();
+                    self.gaps.clear();
+                    self.steps.clear();
+                } else {
+                    self.noise += (env - self.noise) * NOISE_ALPHA;
+                }
+            },
+            State::Pulse => {
+                self.count += 1;
+                self.level += (env - self.level) * LEVEL_ALPHA;
+                self.steps.push(step);
+                if env < mid - hysteresis {
+                    self.end_pulse();
+                } else if self.count >
+                          self.us_to_samples(self.config.max_pulse) {
+                    // The gap before the carrier ends the train.
+                    self.steps.clear();
+                    if self.pulses.is_empty() {
+                        self.state = State::Idle;
+                    } else {
+                        self.end_train();
+                    }
+                }
+            },
+            State::Gap => {
+                self.count += 1;
+                if env > mid + hysteresis {
+                    self.gaps.push(self.count);
+                    self.count = 1;
+                    self.state = State::Pulse;
+                    self.steps.clear();
+                } else if self.count >
+                          self.us_to_samples(self.config.max_gap) ||
+                          self.pulses.len() >= self.config.max_pulses {
+                    self.gaps.push(self.count);
+                    self.end_train();
+                }
+            }
+        }
+        self.sample += 1;
+    }
+
+    fn end_pulse(&mut self) {
+        if self.count < self.us_to_samples(self.config.min_pulse) {
+            // A glitch: fold it into the gap before it, or forget the
+            // train if it was the first pulse.
+            match self.gaps.pop() {
+                Some(gap) => {
+                    self.count += gap;
+                    self.state = State::Gap;
+                },
+                None => self.state = State::Idle
+            }
+            return;
+        }
+        self.pulses.push(self.count);
+        if let Some(fsk) = self.fsk() {
+            self.found.push(fsk);
+        }
+        self.count = 0;
+        self.state = State::Gap;
+    }
+
+    fn end_train(&mut self) {
+        self.state = State::Idle;
+        let (rssi, snr) = self.levels();
+        self.found.push(PulseData {
+            modulation: Modulation::Ook,
+            sample_rate: self.sample_rate,
+            pulses: std::mem::take(&mut self.pulses),
+            gaps: std::mem::take(&mut self.gaps),
+            start: self.start, rssi, snr
+        });
+    }
+
+    fn levels(&self) -> (f64, f64) {
+        let level = self.level.max(1e-3);
+        (20.0 * (level / FULL_SCALE).log10() as f64,
+         20.0 * (level / self.noise.max(1e-3)).log10() as f64)
+    }
+
+    /// Split the pulse just ended into the runs of its two tones, if it
+    /// has two.
+    fn fsk(&self) -> Option<PulseData> {
+        if self.steps.len() < 4 * FSK_EDGE + 4 {
+            return None;
+        }
+        let steps = &self.steps[FSK_EDGE..self.steps.len() - FSK_EDGE];
+        let smooth: Vec<i32> = steps.windows(3).map(|w| {
+            w.iter().map(|&s| s as i32).sum::<i32>()
+        }).collect();
+        let mut sorted = smooth.clone();
+        sorted.sort_unstable();
+        let low = sorted[sorted.len() / 10];
+        let high = sorted[sorted.len() * 9 / 10];
+        let deviation = 3.0 * 65536.0 * self.config.min_deviation /
+                        self.sample_rate as f64;
+        if ((high - low) as f64) < deviation {
+            return None;
+        }
+        let mid = (low + high) / 2;
+        let near = (high - low) / 4;
+        let clustered = smooth.iter().filter(|&&s| {
+            (s - low).abs() <= near || (s - high).abs() <= near
+        }).count();
+        if (clustered as f64) < FSK_CLUSTERED * smooth.len() as f64 {
+            return None;
+        }
+
+        let mut pulses = Vec::new();
+        let mut gaps = Vec::new();
+        let mut run = 0;
+        let mut mark = false;
+        for &s in &smooth {
+            let m = s > mid;
+            if m != mark && run > 0 {
+                if mark {
+                    pulses.push(run);
+                } else if !pulses.is_empty() {
+                    gaps.push(run);
+                }
+                run = 0;
+            }
+            mark = m;
+            run += 1;
+        }
+        if mark {
+            pulses.push(run);
+            gaps.push(0);
+        } else {
+            gaps.push(run);
+        }
+        if pulses.len() < 2 || gaps.len() != pulses.len() {
+            return None;
+        }
+        let (rssi, snr) = self.levels();
+        Some(PulseData {
+            modulation: Modulation::Fsk,
+            sample_rate: self.sample_rate,
+            pulses, gaps,
+            start: self.sample - self.steps.len() as u64, rssi, snr
+        })
+    }
+}
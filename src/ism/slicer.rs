@@ -0,0 +1,115 @@
+// Pulse slicing for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// The line codes follow rtl_433's conventions, so timings from its device
+// definitions can be used unchanged. All widths are in microseconds.
+
+use super::bits::Bitbuffer;
+use super::pulse::PulseData;
+
+/// How bits are carried by pulses and gaps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineCode {
+    /// One bit per `long` period, with a pulse for each one. Pulses fill
+    /// the period (NRZ) if `short` equals `long`, else they are `short`
+    /// wide (RZ).
+    Pcm { short: f64, long: f64, reset: f64 },
+    /// Bits in the gaps: a `short` gap is a zero and a `long` gap a one.
+    /// A gap past `gap_limit` starts a new row.
+    Ppm { short: f64, long: f64, gap_limit: f64, reset: f64 },
+    /// Bits in the pulses: a `short` pulse is a one and a `long` pulse a
+    /// zero. A gap past `gap_limit` starts a new row.
+    Pwm { short: f64, long: f64, gap_limit: f64, reset: f64 },
+    /// Manchester coding with `half` wide half bits, taking the first
+    /// pulse to start a zero (10), and 01 as a one.
+    Manchester { half: f64, reset: f64 }
+}
+
+impl LineCode {
+    /// Gap which ends a message.
+    pub fn reset(&self) -> f64 {
+        match *self {
+            LineCode::Pcm { reset, .. } | LineCode::Ppm { reset, .. } |
+            LineCode::Pwm { reset, .. } |
+            LineCode::Manchester { reset, .. } => reset
+        }
+    }
+}
+
+/// Slice a pulse train into rows of bits. Each gap past the reset limit
+/// ends a message, and so a row.
+pub fn slice(data: &PulseData, code: &LineCode) -> Bitbuffer {
+    let us = |samples: usize| data.to_us(samples);
+    let mut out = Bitbuffer::new();
+    match *code {
+        LineCode::Pcm { short, long, reset } => {
+            let nrz = short == long;
+            for (&p, &g) in data.pulses.iter().zip(&data.gaps) {
+                let (p, g) = (us(p), us(g));
+                let ones = if nrz {
+                    ((p / long).round() as usize).max(1)
+                } else {
+                    1
+                };
+                for _ in 0..ones {
+                    out.add_bit(true);
+                }
+                if g > reset {
+                    out.add_row();
+                    continue;
+                }
+                let zeros = if nrz {
+                    (g / long).round() as usize
+                } else {
+                    ((p + g) / long).round().max(1.0) as usize - 1
+                };
+                for _ in 0..zeros {
+                    out.add_bit(false);
+                }
+            }
+        },
+        LineCode::Ppm { short, long, gap_limit, reset } => {
+            for &g in &data.gaps {
+                let g = us(g);
+                if g > gap_limit || g > reset {
+                    out.add_row();
+                } else {
+                    out.add_bit(g >= (short + long) / 2.0);
+                }
+            }
+        },
+        LineCode::Pwm { short, long, gap_limit, reset } => {
+            for (&p, &g) in data.pulses.iter().zip(&data.gaps) {
+                out.add_bit(us(p) < (short + long) / 2.0);
+                let g = us(g);
+                if g > gap_limit || g > reset {
+                    out.add_row();
+                }
+            }
+        },
+        LineCode::Manchester { half, reset } => {
+            // Slice the half bits as NRZ, starting a new row wherever the
+            // coding breaks.
+            let code = LineCode::Pcm { short: half, long: half, reset };
+            let halves = slice(data, &code);
+            for row in halves.rows() {
+                // The low half of a final zero runs into the reset gap.
+                let mut row = row.clone();
+                if row.len() % 2 == 1 {
+                    row.push(false);
+                }
+                let mut start = 0;
+                while start + 1 < row.len() {
+                    let bits = row.manchester_decode(start, usize::MAX);
+                    out.add_row();
+                    for i in 0..bits.len() {
+                        out.add_bit(bits.get(i));
+                    }
+                    start += 2 * bits.len() + 1;
+                }
+            }
+        }
+    }
+    out
+}
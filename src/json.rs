@@ -0,0 +1,422 @@
+// Minimal JSON values for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt::Write;
+
+use super::{RTLSDRError, rtlsdr_error};
+
+/// A JSON value. Objects keep their keys in insertion order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>)
+}
+
+impl Value {
+    /// An empty object.
+    pub fn object() -> Value {
+        Value::Object(Vec::new())
+    }
+
+    /// Set `key` on an object, replacing any existing value. Does nothing
+    /// if this is not an object.
+    pub fn set<V: Into<Value>>(&mut self, key: &str, value: V) {
+        if let Value::Object(ref mut fields) = *self {
+            let value = value.into();
+            match fields.iter_mut().find(|(k, _)| k == key) {
+                Some(field) => field.1 = value,
+                None => fields.push((key.to_string(), value))
+            }
+        }
+    }
+
+    /// Builder form of `set`.
+    pub fn with<V: Into<Value>>(mut self, key: &str, value: V) -> Value {
+        self.set(key, value);
+        self
+    }
+
+    /// Look up `key` on an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref fields) =>
+                fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(n) => Some(n),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref a) => Some(a),
+            _ => None
+        }
+    }
+
+    /// Serialise without any whitespace.
+    pub fn to_compact(&self) -> String {
+        let mut s = String::new();
+        self.write(&mut s, None, 0);
+        s
+    }
+
+    /// Serialise with two-space indentation.
+    pub fn to_pretty(&self) -> String {
+        let mut s = String::new();
+        self.write(&mut s, Some(2), 0);
+        s
+    }
+
+    fn write(&self, s: &mut String, indent: Option<usize>, depth: usize) {
+        let newline = |s: &mut String, depth: usize| {
+            if let Some(i) = indent {
+                s.push('\n');
+                s.extend(std::iter::repeat_n(' ', i * depth));
+            }
+        };
+        match *self {
+            Value::Null => s.push_str("null"),
+            Value::Bool(b) => s.push_str(if b { "true" } else { "false" }),
+            Value::Number(n) => write_number(s, n),
+            Value::String(ref v) => write_string(s, v),
+            Value::Array(ref a) => {
+                s.push('[');
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        s.push(',');
+                    }
+                    newline(s, depth + 1);
+                    v.write(s, indent, depth + 1);
+                }
+                if !a.is_empty() {
+                    newline(s, depth);
+                }
+                s.push(']');
+            },
+            Value::Object(ref fields) => {
+                s.push('{');
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        s.push(',');
+                    }
+                    newline(s, depth + 1);
+                    write_string(s, k);
+                    s.push(':');
+                    if indent.is_some() {
+                        s.push(' ');
+                    }
+                    v.write(s, indent, depth + 1);
+                }
+                if !fields.is_empty() {
+                    newline(s, depth);
+                }
+                s.push('}');
+            }
+        }
+    }
+}
+
+/// Parse a JSON document.
+pub fn parse(text: &str) -> Result<Value, RTLSDRError> {
+    let mut p = Parser { s: text.as_bytes(), pos: 0 };
+    let value = p.value(0)?;
+    p.whitespace();
+    if p.pos != p.s.len() {
+        return Err(p.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Nesting deeper than this is rejected rather than risking the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, what: &str) -> RTLSDRError {
+        rtlsdr_error(-1, &format!("Invalid JSON: {} at byte {}", what,
+                                  self.pos))
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), RTLSDRError> {
+        self.whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c as char)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value)
+               -> Result<Value, RTLSDRError> {
+        if self.s[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, RTLSDRError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.whitespace();
+        match self.peek() {
+            None => Err(self.error("unexpected end")),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => { self.pos += 1; break; },
+                        _ => return Err(self.error("expected ',' or ']'"))
+                    }
+                }
+                Ok(Value::Array(items))
+            },
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.whitespace();
+                    if self.peek() != Some(b'"') {
+                        return Err(self.error("expected key"));
+                    }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value(depth + 1)?));
+                    self.whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => { self.pos += 1; break; },
+                        _ => return Err(self.error("expected ',' or '}'"))
+                    }
+                }
+                Ok(Value::Object(fields))
+            },
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character"))
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, RTLSDRError> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+                self.peek() {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.pos]).ok()
+            .and_then(|t| t.parse::<f64>().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("bad number"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, RTLSDRError> {
+        let digits = self.s.get(self.pos..self.pos + 4)
+                           .and_then(|d| std::str::from_utf8(d).ok())
+                           .and_then(|d| u32::from_str_radix(d, 16).ok())
+                           .ok_or_else(|| self.error("bad escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, RTLSDRError> {
+        // Opening quote.
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            // Copy runs of ordinary characters in one go.
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == b'"' || c == b'\\' || c < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            out.push_str(std::str::from_utf8(&self.s[start..self.pos])
+                             .map_err(|_| self.error("invalid UTF-8"))?);
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = self.peek()
+                                .ok_or_else(|| self.error("bad escape"))?;
+                    self.pos += 1;
+                    match c {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let mut c = self.hex4()?;
+                            // Surrogate pair.
+                            if (0xd800..0xdc00).contains(&c) &&
+                               self.s[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                c = 0x10000 + ((c - 0xd800) << 10) +
+                                    (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            out.push(char::from_u32(c).unwrap_or('\u{fffd}'));
+                        },
+                        _ => return Err(self.error("bad escape"))
+                    }
+                },
+                _ => return Err(self.error("unterminated string"))
+            }
+        }
+    }
+}
+
+fn write_number(s: &mut String, n: f64) {
+    if !n.is_finite() {
+        s.push_str("null");
+    } else if n.fract() == 0.0 && n.abs() < 1e15 {
+        let _ = write!(s, "{}", n as i64);
+    } else {
+        let _ = write!(s, "{}", n);
+    }
+}
+
+fn write_string(s: &mut String, v: &str) {
+    s.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(s, "\\u{:04x}", c as u32);
+            },
+            c => s.push(c)
+        }
+    }
+    s.push('"');
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.to_compact())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value { Value::Bool(b) }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value { Value::Number(n) }
+}
+
+impl From<f32> for Value {
+    fn from(n: f32) -> Value { Value::Number(n as f64) }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value { Value::Number(n as f64) }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Value { Value::Number(n as f64) }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value { Value::Number(n as f64) }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value { Value::Number(n as f64) }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value { Value::Number(n as f64) }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value { Value::String(s.to_string()) }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value { Value::String(s) }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Value {
+        Value::Array(v.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Value {
+        match v {
+            Some(x) => x.into(),
+            None => Value::Null
+        }
+    }
+}
@@ -6,6 +6,31 @@
 
 extern crate libc;
 mod ffi;
+pub mod adsb;
+pub mod ais;
+pub mod bitfield;
+pub mod calibrate;
+pub mod channelizer;
+pub mod demod;
+pub mod detect;
+pub mod drift;
+pub mod dsp;
+pub mod ism;
+pub mod json;
+pub mod playback;
+pub mod png;
+pub mod samples;
+pub mod sigmf;
+pub mod source;
+pub mod spectrum;
+pub mod sweep;
+pub mod tcp;
+pub mod timestamp;
+pub mod trigger;
+pub mod tuning;
+pub mod uat;
+pub mod waterfall;
+pub mod wav;
 
 #[derive(Debug)]
 pub struct RTLSDRError {
@@ -21,17 +46,29 @@ impl std::fmt::Display for RTLSDRError {
 
 impl std::error::Error for RTLSDRError {}
 
+impl From<std::io::Error> for RTLSDRError {
+    fn from(err: std::io::Error) -> RTLSDRError {
+        RTLSDRError { errno: err.raw_os_error().unwrap_or(-1),
+                      errstr: err.to_string() }
+    }
+}
+
 fn rtlsdr_error(errno: libc::c_int, errstr: &str) -> RTLSDRError {
     RTLSDRError { errno: errno as i32, errstr: errstr.to_string() }
 }
 
-#[derive(Clone, Copy,Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DirectSampling {
     Disabled, I, Q
 }
 
 pub struct RTLSDRDevice {
-    ptr: *mut ffi::rtlsdr_dev
+    ptr: *mut ffi::rtlsdr_dev,
+    translation: tuning::FreqTranslation,
+    bandwidth: u32,
+    manual_gain: bool,
+    compensate: bool,
+    converter: samples::Converter
 }
 
 impl Drop for RTLSDRDevice {
@@ -100,7 +137,14 @@ pub fn get_index_by_serial(serial: String) -> Result<i32, RTLSDRError> {
 /// Returns a Result on an RTLSDRDevice object which exposes further
 /// methods.
 pub fn open(index: i32) -> Result<RTLSDRDevice, RTLSDRError> {
-    let mut device: RTLSDRDevice = RTLSDRDevice { ptr: std::ptr::null_mut() };
+    let mut device: RTLSDRDevice = RTLSDRDevice {
+        ptr: std::ptr::null_mut(),
+        translation: tuning::FreqTranslation::none(),
+        bandwidth: 0,
+        manual_gain: false,
+        compensate: false,
+        converter: samples::Converter::new()
+    };
     let idx = index as u32;
     match unsafe { ffi::rtlsdr_open(&mut device.ptr, idx) } {
         0 => Ok(device),
@@ -128,7 +172,7 @@ impl RTLSDRDevice {
         let rfreq = rtl_freq as u32;
         let tfreq = tuner_freq as u32;
         match unsafe { ffi::rtlsdr_set_xtal_freq(self.ptr, rfreq, tfreq) } {
-            0 => Ok(()),
+            0 => self.update_compensation(),
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
@@ -172,29 +216,139 @@ impl RTLSDRDevice {
         }
     }
 
+    /// Set the frequency translation applied by an external up- or
+    /// down-converter.
+    ///
+    /// Subsequent calls to set_center_freq and get_center_freq use the RF
+    /// frequency at the converter input, and read_sync undoes any spectral
+    /// inversion.
+    pub fn set_freq_translation(&mut self,
+                                translation: tuning::FreqTranslation)
+                                -> Result<(), RTLSDRError> {
+        self.translation = translation;
+        self.update_compensation()
+    }
+
+    /// Get the current frequency translation.
+    pub fn get_freq_translation(&self) -> tuning::FreqTranslation {
+        self.translation
+    }
+
     /// Set the RTL-SDR's centre frequency (in Hz).
-    pub fn set_center_freq(&mut self, frequency: u32)
+    ///
+    /// The frequency is the RF frequency after any frequency translation.
+    pub fn set_center_freq(&mut self, frequency: u64)
                            -> Result<(), RTLSDRError> {
-        let freq = frequency as u32;
+        let freq = self.translation.rf_to_tuner(frequency)?;
         match unsafe { ffi::rtlsdr_set_center_freq(self.ptr, freq)} {
-            0 => Ok(()),
+            0 => self.update_compensation(),
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
 
     /// Get the RTL-SDR's center frequency (in Hz).
-    pub fn get_center_freq(&mut self) -> Result<u32, RTLSDRError> {
+    ///
+    /// The frequency is the RF frequency after any frequency translation.
+    pub fn get_center_freq(&mut self) -> Result<u64, RTLSDRError> {
         match unsafe { ffi::rtlsdr_get_center_freq(self.ptr) } {
             0 => Err(rtlsdr_error(0, "Unknown")),
-            freq => Ok(freq as u32)
+            freq => Ok(self.translation.tuner_to_rf(freq))
         }
     }
 
+    /// Get a model of the tuning hardware in its current configuration.
+    pub fn get_tuner_model(&mut self)
+                           -> Result<tuning::TunerModel, RTLSDRError> {
+        let (rtl_xtal, tuner_xtal) = self.get_xtal_freq()?;
+        let bandwidth = match self.bandwidth {
+            0 => self.get_sample_rate()?,
+            bw => bw
+        };
+        let direct_sampling = !matches!(self.get_direct_sampling()?,
+                                        DirectSampling::Disabled);
+        Ok(tuning::TunerModel {
+            tuner: self.get_tuner_type().0,
+            rtl_xtal, tuner_xtal, bandwidth, direct_sampling
+        })
+    }
+
+    /// Get the RF centre frequency (in Hz) actually received.
+    ///
+    /// This differs from get_center_freq by the quantisation error of the
+    /// tuner PLL and the RTL2832 IF oscillator.
+    pub fn get_actual_center_freq(&mut self) -> Result<f64, RTLSDRError> {
+        let model = self.get_tuner_model()?;
+        let tuner = match unsafe { ffi::rtlsdr_get_center_freq(self.ptr) } {
+            0 => return Err(rtlsdr_error(0, "Unknown")),
+            freq => freq
+        };
+        let error = model.achieved_freq(tuner) - tuner as f64;
+        let rf = self.translation.tuner_to_rf(tuner) as f64;
+        match self.translation.inverted {
+            false => Ok(rf + error),
+            true => Ok(rf - error)
+        }
+    }
+
+    /// Enable or disable compensation of the tuning error in read_iq.
+    ///
+    /// When enabled, samples from read_iq are frequency shifted so that the
+    /// frequency requested with set_center_freq is at 0Hz, rather than the
+    /// frequency the hardware actually achieved.
+    pub fn set_freq_compensation(&mut self, enabled: bool)
+                                 -> Result<(), RTLSDRError> {
+        self.compensate = enabled;
+        self.update_compensation()
+    }
+
+    /// Work out the shift read_iq applies to compensate the tuning error,
+    /// after anything it depends on has changed.
+    fn update_compensation(&mut self) -> Result<(), RTLSDRError> {
+        if !self.compensate {
+            self.converter.clear_shift();
+            return Ok(());
+        }
+        // Until both are set there is no tuning error to speak of.
+        let (wanted, rate) = match (self.get_center_freq(),
+                                    self.get_sample_rate()) {
+            (Ok(freq), Ok(rate)) => (freq as f64, rate as f64),
+            _ => {
+                self.converter.clear_shift();
+                return Ok(());
+            }
+        };
+        let actual = self.get_actual_center_freq()?;
+        self.converter.set_shift(actual - wanted, rate);
+        Ok(())
+    }
+
+    /// Enable or disable adaptive DC offset removal in read_iq.
+    ///
+    /// Clears the spike at 0Hz on zero-IF tuners such as the E4000 without
+    /// needing set_offset_tuning.
+    pub fn set_dc_correction(&mut self, enabled: bool) {
+        self.converter.set_dc_block(enabled);
+    }
+
+    /// Enable or disable blind I/Q imbalance correction in read_iq.
+    ///
+    /// Suppresses the mirror images of strong signals produced by gain and
+    /// phase mismatch in zero-IF tuners. Works best with DC correction also
+    /// enabled.
+    pub fn set_iq_correction(&mut self, enabled: bool) {
+        self.converter.set_iq_correction(enabled);
+    }
+
+    /// The sample converter used by read_iq, for inspecting its state.
+    pub fn get_converter(&self) -> &samples::Converter {
+        &self.converter
+    }
+
     /// Set the RTL-SDR's frequency correction (in ppm).
     pub fn set_freq_correction(&mut self, ppm: i32) -> Result<(), RTLSDRError> {
         let cppm = ppm as libc::c_int;
         match unsafe { ffi::rtlsdr_set_freq_correction(self.ptr, cppm) } {
-            0 => Ok(()),
+            0 => self.update_compensation(),
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
@@ -283,16 +437,25 @@ impl RTLSDRDevice {
                                -> Result<(), RTLSDRError> {
         let m: libc::c_int = match manual { true => 1, false => 0 };
         match unsafe { ffi::rtlsdr_set_tuner_gain_mode(self.ptr, m) } {
-            0 => Ok(()),
+            0 => {
+                self.manual_gain = manual;
+                Ok(())
+            },
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
 
+    /// Get whether manual gain is enabled, as last set with
+    /// set_tuner_gain_mode. Devices open with automatic gain.
+    pub fn get_tuner_gain_mode(&self) -> bool {
+        self.manual_gain
+    }
+
     /// Set sample rate (in Hz).
     pub fn set_sample_rate(&mut self, rate: u32) -> Result<(), RTLSDRError> {
         let r = rate as libc::c_uint;
         match unsafe { ffi::rtlsdr_set_sample_rate(self.ptr, r) } {
-            0 => Ok(()),
+            0 => self.update_compensation(),
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
@@ -309,7 +472,10 @@ impl RTLSDRDevice {
     pub fn set_tuner_bandwidth(&mut self, bw: u32) -> Result<(), RTLSDRError> {
         let bwc = bw as libc::c_uint;
         match unsafe { ffi::rtlsdr_set_tuner_bandwidth(self.ptr, bwc) } {
-            0 => Ok(()),
+            0 => {
+                self.bandwidth = bw;
+                self.update_compensation()
+            },
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
@@ -351,7 +517,7 @@ impl RTLSDRDevice {
         };
 
         match unsafe { ffi::rtlsdr_set_direct_sampling(self.ptr, m) } {
-            0 => Ok(()),
+            0 => self.update_compensation(),
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
@@ -388,6 +554,18 @@ impl RTLSDRDevice {
         }
     }
 
+    /// Set the bias tee on or off.
+    ///
+    /// On dongles which have one, this powers an active antenna or LNA
+    /// through the coax.
+    pub fn set_bias_tee(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        let e: libc::c_int = match enabled { true => 1, false => 0 };
+        match unsafe { ffi::rtlsdr_set_bias_tee(self.ptr, e) } {
+            0 => Ok(()),
+            err => Err(rtlsdr_error(err, "Unknown"))
+        }
+    }
+
     /// Reset streaming buffer.
     pub fn reset_buffer(&mut self) -> Result<(), RTLSDRError> {
         match unsafe { ffi::rtlsdr_reset_buffer(self.ptr) } {
@@ -397,6 +575,9 @@ impl RTLSDRDevice {
     }
 
     /// Read a buffer synchronously.
+    ///
+    /// If the frequency translation is inverted, I and Q are swapped so the
+    /// spectrum is the right way round.
     pub fn read_sync(&mut self, len: usize)
                      -> Result<std::vec::Vec<u8>, RTLSDRError> {
         use std::vec::Vec;
@@ -407,9 +588,24 @@ impl RTLSDRDevice {
                                              &mut n) } {
             0 => {
                 unsafe { v.set_len(n as usize) };
+                if self.translation.inverted {
+                    for iq in v.chunks_exact_mut(2) {
+                        iq.swap(0, 1);
+                    }
+                }
                 Ok(v)
             },
             err => Err(rtlsdr_error(err, "Unknown"))
         }
     }
+
+    /// Read a buffer synchronously as complex samples.
+    ///
+    /// `len` is the number of bytes to read, giving `len/2` samples. If
+    /// frequency compensation is enabled the tuning error is corrected.
+    pub fn read_iq(&mut self, len: usize)
+                   -> Result<std::vec::Vec<dsp::Complex>, RTLSDRError> {
+        let buf = self.read_sync(len)?;
+        Ok(self.converter.convert(&buf))
+    }
 }
@@ -0,0 +1,324 @@
+// Recorded sample playback for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Plays back SigMF recordings and raw captures, such as those made by
+// rtl_sdr, through the Source trait so anything written against a live
+// device can be run offline.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{RTLSDRError, rtlsdr_error};
+use super::json;
+use super::source::Source;
+use super::timestamp::UtcTime;
+
+/// How quickly samples are delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    /// Deliver samples no faster than the recording's sample rate, as a live
+    /// device would.
+    RealTime,
+    /// Deliver samples as fast as they can be read.
+    Unthrottled
+}
+
+/// A frequency change within a recording.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// Index of the first sample at this frequency.
+    pub sample_start: u64,
+    /// Centre frequency (in Hz).
+    pub frequency: u64
+}
+
+/// A Source which reads recorded samples from a file.
+///
+/// Samples must be interleaved unsigned 8 bit I/Q: raw rtl_sdr captures
+/// (usually `.bin` or `.cu8`) or SigMF recordings with datatype "cu8".
+pub struct FileSource {
+    path: PathBuf,
+    file: BufReader<File>,
+    rate: u32,
+    ppm: i32,
+    segments: Vec<Segment>,
+    start_time: Option<SystemTime>,
+    samples: u64,
+    offset: u64,
+    pacing: Pacing,
+    looping: bool,
+    started: Instant,
+    delivered: u64
+}
+
+impl FileSource {
+    /// Open a raw capture of `rate` (in Hz) tuned to `frequency` (in Hz).
+    pub fn open_raw<P: AsRef<Path>>(path: P, rate: u32, frequency: u64)
+                                    -> Result<FileSource, RTLSDRError> {
+        let segments = vec![Segment { sample_start: 0, frequency }];
+        FileSource::with_segments(path.as_ref(), rate, segments)
+    }
+
+    /// Open a SigMF recording, given the path of its `.sigmf-meta` or
+    /// `.sigmf-data` file or their common base name.
+    pub fn open_sigmf<P: AsRef<Path>>(path: P)
+                                      -> Result<FileSource, RTLSDRError> {
+        let path = path.as_ref();
+        let base = match path.extension().and_then(|e| e.to_str()) {
+            Some("sigmf-meta") | Some("sigmf-data") => path.with_extension(""),
+            _ => path.to_path_buf()
+        };
+        let mut meta_path = base.clone().into_os_string();
+        meta_path.push(".sigmf-meta");
+        let mut data_path = base.into_os_string();
+        data_path.push(".sigmf-data");
+
+        let text = std::fs::read_to_string(&meta_path)?;
+        let meta = json::parse(&text)?;
+        let invalid = |what| rtlsdr_error(-1, &format!("SigMF: {}", what));
+        let global = meta.get("global").ok_or(invalid("no global"))?;
+        match global.get("core:datatype").and_then(|d| d.as_str()) {
+            Some("cu8") => (),
+            Some(other) => return Err(invalid(&format!(
+                "unsupported datatype {}", other))),
+            None => return Err(invalid("no datatype"))
+        }
+        let rate = global.get("core:sample_rate").and_then(|r| r.as_f64())
+                         .ok_or(invalid("no sample rate"))?;
+        if rate.fract() != 0.0 || !(1.0..=u32::MAX as f64).contains(&rate) {
+            return Err(invalid("sample rate is not a whole number of Hz"));
+        }
+        let mut segments: Vec<Segment> = meta.get("captures")
+            .and_then(|c| c.as_array()).unwrap_or(&[]).iter()
+            .map(|c| Segment {
+                sample_start: c.get("core:sample_start")
+                               .and_then(|s| s.as_f64())
+                               .unwrap_or(0.0) as u64,
+                frequency: c.get("core:frequency")
+                            .and_then(|f| f.as_f64())
+                            .unwrap_or(0.0) as u64
+            }).collect();
+        let start_time = meta.get("captures")
+            .and_then(|c| c.as_array()).and_then(|c| c.first())
+            .and_then(|c| c.get("core:datetime")).and_then(|d| d.as_str())
+            .and_then(UtcTime::parse_iso8601).map(|t| t.to_system());
+        segments.sort_by_key(|s| s.sample_start);
+        if segments.first().is_none_or(|s| s.sample_start != 0) {
+            segments.insert(0, Segment { sample_start: 0, frequency: 0 });
+        }
+        let mut source = FileSource::with_segments(Path::new(&data_path),
+                                                   rate as u32, segments)?;
+        source.start_time = start_time;
+        Ok(source)
+    }
+
+    /// Open `path`, as SigMF if it looks like a SigMF recording or else as a
+    /// raw capture at 2.048MS/s and unknown frequency.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileSource, RTLSDRError> {
+        let path = path.as_ref();
+        let mut meta = path.as_os_str().to_owned();
+        meta.push(".sigmf-meta");
+        let ext = path.extension().and_then(|e| e.to_str());
+        if matches!(ext, Some("sigmf-meta") | Some("sigmf-data")) ||
+           Path::new(&meta).exists() {
+            FileSource::open_sigmf(path)
+        } else {
+            FileSource::open_raw(path, 2_048_000, 0)
+        }
+    }
+
+    fn with_segments(path: &Path, rate: u32, segments: Vec<Segment>)
+                     -> Result<FileSource, RTLSDRError> {
+        if rate == 0 {
+            return Err(rtlsdr_error(-1, "Sample rate must be non-zero"));
+        }
+        let file = File::open(path)?;
+        let samples = file.metadata()?.len() / 2;
+        Ok(FileSource {
+            path: path.to_path_buf(),
+            file: BufReader::with_capacity(1 << 16, file),
+            rate,
+            ppm: 0,
+            segments,
+            start_time: None,
+            samples,
+            offset: 0,
+            pacing: Pacing::Unthrottled,
+            looping: false,
+            started: Instant::now(),
+            delivered: 0
+        })
+    }
+
+    /// Path of the sample data being played.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Set how quickly samples are delivered. Default Unthrottled.
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+        self.restart_clock();
+    }
+
+    /// Set whether playback restarts from the beginning at the end of the
+    /// file. Default off.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// When the recording started, if known.
+    pub fn start_time(&self) -> Option<SystemTime> {
+        self.start_time
+    }
+
+    /// Frequency segments in the recording.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Total number of complex samples in the file.
+    pub fn len(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    /// Length of the recording.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples as f64 / self.rate as f64)
+    }
+
+    /// Index of the next sample to be read.
+    pub fn position(&self) -> u64 {
+        self.offset / 2
+    }
+
+    /// Move to sample `sample`.
+    pub fn seek(&mut self, sample: u64) -> Result<(), RTLSDRError> {
+        if sample > self.samples {
+            return Err(rtlsdr_error(-1, "Seek past end of recording"));
+        }
+        self.file.seek(SeekFrom::Start(sample * 2))?;
+        self.offset = sample * 2;
+        self.restart_clock();
+        Ok(())
+    }
+
+    /// Move to `time` after the start of the recording.
+    pub fn seek_time(&mut self, time: Duration) -> Result<(), RTLSDRError> {
+        self.seek((time.as_secs_f64() * self.rate as f64) as u64)
+    }
+
+    fn restart_clock(&mut self) {
+        self.started = Instant::now();
+        self.delivered = 0;
+    }
+
+    fn segment(&self) -> &Segment {
+        let position = self.position();
+        let idx = self.segments
+                      .partition_point(|s| s.sample_start <= position);
+        &self.segments[idx.saturating_sub(1)]
+    }
+
+    /// Wait until `samples` more samples would have arrived from a live
+    /// device.
+    fn pace(&mut self, samples: u64) {
+        self.delivered += samples;
+        if self.pacing == Pacing::RealTime {
+            let due = Duration::from_secs_f64(
+                self.delivered as f64 / self.rate as f64);
+            if let Some(wait) = due.checked_sub(self.started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+    }
+}
+
+impl Source for FileSource {
+    /// Recordings cannot be retuned, so this only succeeds if `frequency`
+    /// is where the recording currently is.
+    fn set_center_freq(&mut self, frequency: u64) -> Result<(), RTLSDRError> {
+        if frequency == self.segment().frequency {
+            Ok(())
+        } else {
+            Err(rtlsdr_error(-1, "Recordings cannot be retuned"))
+        }
+    }
+
+    /// The frequency the recording was made at, at the current position.
+    fn get_center_freq(&mut self) -> Result<u64, RTLSDRError> {
+        Ok(self.segment().frequency)
+    }
+
+    /// Only succeeds if `rate` is the recording's rate.
+    fn set_sample_rate(&mut self, rate: u32) -> Result<(), RTLSDRError> {
+        if rate == self.rate {
+            Ok(())
+        } else {
+            Err(rtlsdr_error(-1, "Recordings cannot be resampled"))
+        }
+    }
+
+    fn get_sample_rate(&mut self) -> Result<u32, RTLSDRError> {
+        Ok(self.rate)
+    }
+
+    /// Recorded for the benefit of get_freq_correction, but has no effect on
+    /// the samples.
+    fn set_freq_correction(&mut self, ppm: i32) -> Result<(), RTLSDRError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+
+    fn get_freq_correction(&mut self) -> i32 {
+        self.ppm
+    }
+
+    fn reset_buffer(&mut self) -> Result<(), RTLSDRError> {
+        self.restart_clock();
+        Ok(())
+    }
+
+    /// Read the next `len` bytes.
+    ///
+    /// At the end of the recording, fewer bytes are returned unless looping,
+    /// and once nothing is left an error is returned.
+    fn read_sync(&mut self, len: usize) -> Result<Vec<u8>, RTLSDRError> {
+        let mut buf = vec![0u8; len & !1];
+        let mut filled = 0;
+        // Stop before any odd byte at the very end of the file, so every
+        // pass starts on an I byte.
+        let end = 2 * self.samples;
+        while filled < buf.len() {
+            let left = (end.saturating_sub(self.offset) as usize)
+                           .min(buf.len() - filled);
+            let n = match left {
+                0 => 0,
+                _ => self.file.read(&mut buf[filled..filled + left])?
+            };
+            if n == 0 {
+                if self.looping && self.samples > 0 {
+                    self.file.seek(SeekFrom::Start(0))?;
+                    self.offset = 0;
+                    continue;
+                }
+                break;
+            }
+            filled += n;
+            self.offset += n as u64;
+        }
+        if filled == 0 && !buf.is_empty() {
+            return Err(rtlsdr_error(-1, "End of recording"));
+        }
+        buf.truncate(filled);
+        self.pace((filled / 2) as u64);
+        Ok(buf)
+    }
+}
@@ -0,0 +1,245 @@
+// PNG image output for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Writes 8 bit RGB images. Image data is compressed with a simple LZ77
+// matcher and the fixed Huffman codes of DEFLATE, which does well enough on
+// plots with large flat areas without needing a full compressor.
+
+use std::io::Write;
+
+/// An 8 bit RGB image.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Pixels as R, G, B bytes, row by row from the top left.
+    pub pixels: Vec<u8>
+}
+
+impl Image {
+    /// A `width` by `height` image filled with `colour`.
+    pub fn new(width: usize, height: usize, colour: [u8; 3]) -> Image {
+        let pixels = colour.iter().cloned().cycle()
+                           .take(width * height * 3).collect();
+        Image { width, height, pixels }
+    }
+
+    /// Set the pixel at (`x`, `y`), ignoring anything off the image.
+    pub fn set(&mut self, x: usize, y: usize, colour: [u8; 3]) {
+        if x < self.width && y < self.height {
+            let i = (y * self.width + x) * 3;
+            self.pixels[i..i + 3].copy_from_slice(&colour);
+        }
+    }
+
+    /// Fill a rectangle, clipped to the image.
+    pub fn fill(&mut self, x: usize, y: usize, w: usize, h: usize,
+                colour: [u8; 3]) {
+        for yy in y..(y + h).min(self.height) {
+            for xx in x..(x + w).min(self.width) {
+                self.set(xx, yy, colour);
+            }
+        }
+    }
+
+    /// Encode as PNG.
+    pub fn write_png<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, no filtering, no interlace.
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(w, b"IHDR", &ihdr)?;
+
+        let stride = self.width * 3;
+        let mut raw = Vec::with_capacity((stride + 1) * self.height);
+        for row in self.pixels.chunks_exact(stride.max(1)) {
+            // Filter type 0 (none) for every row.
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        write_chunk(w, b"IDAT", &zlib(&raw))?;
+        write_chunk(w, b"IEND", &[])
+    }
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8])
+                         -> std::io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32(crc32(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    w.write_all(&crc.to_be_bytes())
+}
+
+/// Update a CRC-32 (as used by PNG and zip) with `data`.
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Wrap `data` in a zlib stream.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+    67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4,
+    5, 5, 5, 5, 0
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+    769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+    11, 11, 12, 12, 13, 13
+];
+
+const WINDOW: usize = 32768;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 64;
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    bits: u32
+}
+
+impl BitWriter {
+    /// Append the `n` low bits of `value`, least significant first.
+    fn bits(&mut self, value: u32, n: u32) {
+        self.acc |= (value as u64) << self.bits;
+        self.bits += n;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Append a Huffman code, which is sent most significant bit first.
+    fn code(&mut self, code: u32, n: u32) {
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    /// Append a literal or length symbol from the fixed code.
+    fn symbol(&mut self, sym: u16) {
+        let s = sym as u32;
+        match sym {
+            0..=143 => self.code(0x30 + s, 8),
+            144..=255 => self.code(0x190 + s - 144, 9),
+            256..=279 => self.code(s - 256, 7),
+            _ => self.code(0xc0 + s - 280, 8)
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Compress `data` as a single fixed Huffman DEFLATE block.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter { out: Vec::new(), acc: 0, bits: 0 };
+    // Final block, fixed Huffman codes.
+    w.bits(1, 1);
+    w.bits(1, 2);
+
+    let hash = |i: usize| {
+        let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 |
+                data[i + 2] as u32;
+        (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, i: usize| {
+        if i + 2 < data.len() {
+            let h = hash(i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 2 < data.len() {
+            let mut cand = head[hash(i)];
+            let max = (data.len() - i).min(MAX_MATCH);
+            for _ in 0..MAX_CHAIN {
+                if cand == usize::MAX || i - cand > WINDOW - 1 {
+                    break;
+                }
+                let len = data[cand..].iter().zip(&data[i..i + max])
+                                      .take_while(|(a, b)| a == b).count();
+                if len > best.0 {
+                    best = (len, i - cand);
+                    if len == max {
+                        break;
+                    }
+                }
+                let next = prev[cand % WINDOW];
+                if next == usize::MAX || next >= cand {
+                    break;
+                }
+                cand = next;
+            }
+        }
+        if best.0 >= 3 {
+            let (len, dist) = best;
+            let l = LENGTH_BASE.iter().rposition(|&b| b as usize <= len)
+                               .unwrap();
+            w.symbol(257 + l as u16);
+            w.bits((len - LENGTH_BASE[l] as usize) as u32,
+                   LENGTH_EXTRA[l] as u32);
+            let d = DIST_BASE.iter().rposition(|&b| b as usize <= dist)
+                             .unwrap();
+            w.code(d as u32, 5);
+            w.bits((dist - DIST_BASE[d] as usize) as u32,
+                   DIST_EXTRA[d] as u32);
+            for j in i..i + len {
+                insert(&mut head, &mut prev, j);
+            }
+            i += len;
+        } else {
+            w.symbol(data[i] as u16);
+            insert(&mut head, &mut prev, i);
+            i += 1;
+        }
+    }
+    w.symbol(256);
+    w.finish()
+}
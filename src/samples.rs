@@ -0,0 +1,158 @@
+// Sample conversion for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::dsp::{Complex, Nco};
+use super::dsp::iq::{DcBlocker, IqBalancer};
+
+/// Time constant of the DC blocker (in samples).
+const DC_TIME_CONSTANT: f64 = 32768.0;
+
+/// Number of samples the I/Q imbalance estimate is averaged over.
+const IQ_MEMORY: f64 = 1_048_576.0;
+
+/// Convert one unsigned 8 bit sample to the range -1 to +1.
+#[inline]
+pub fn u8_to_f32(x: u8) -> f32 {
+    (x as f32 - 127.5) / 127.5
+}
+
+/// Convert interleaved unsigned 8 bit I/Q, as returned by read_sync, into
+/// complex samples. A trailing odd byte is ignored.
+pub fn iq_from_u8(buf: &[u8]) -> Vec<Complex> {
+    buf.chunks_exact(2)
+       .map(|iq| Complex::new(u8_to_f32(iq[0]), u8_to_f32(iq[1])))
+       .collect()
+}
+
+/// Convert complex samples back to interleaved unsigned 8 bit I/Q,
+/// clipping anything outside -1 to +1.
+pub fn iq_to_u8(samples: &[Complex]) -> Vec<u8> {
+    let q = |x: f32| (x * 127.5 + 127.5).round().clamp(0.0, 255.0) as u8;
+    let mut out = Vec::with_capacity(samples.len() * 2);
+    for s in samples {
+        out.push(q(s.re));
+        out.push(q(s.im));
+    }
+    out
+}
+
+/// Pairs up interleaved unsigned 8 bit I/Q arriving in blocks of any
+/// length, holding an I byte whose Q has not arrived yet for the next
+/// block.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IqPairs {
+    odd: Option<u8>
+}
+
+impl IqPairs {
+    pub fn new() -> IqPairs {
+        IqPairs { odd: None }
+    }
+
+    /// Forget any held byte.
+    pub fn reset(&mut self) {
+        self.odd = None;
+    }
+
+    /// The whole (I, Q) pairs in `buf`, starting with any completed by its
+    /// first byte.
+    pub fn pairs<'a>(&mut self, buf: &'a [u8])
+                     -> impl Iterator<Item = (u8, u8)> + use<'a> {
+        let mut bytes = buf;
+        let mut first = None;
+        if let Some(i) = self.odd.take() {
+            match buf.split_first() {
+                Some((&q, rest)) => {
+                    first = Some((i, q));
+                    bytes = rest;
+                },
+                None => self.odd = Some(i)
+            }
+        }
+        let pairs = bytes.chunks_exact(2);
+        if let [i] = *pairs.remainder() {
+            self.odd = Some(i);
+        }
+        first.into_iter().chain(pairs.map(|p| (p[0], p[1])))
+    }
+}
+
+/// Stateful conversion from raw 8 bit I/Q to complex samples, with optional
+/// processing stages applied on the way.
+///
+/// The stages run in the order DC removal, I/Q imbalance correction, then
+/// frequency shift, and all are off by default.
+#[derive(Clone, Debug, Default)]
+pub struct Converter {
+    dc: Option<DcBlocker>,
+    iq: Option<IqBalancer>,
+    shift: Option<Nco>
+}
+
+impl Converter {
+    pub fn new() -> Converter {
+        Converter { dc: None, iq: None, shift: None }
+    }
+
+    /// Enable or disable adaptive removal of the DC offset.
+    pub fn set_dc_block(&mut self, enabled: bool) {
+        self.dc = match enabled {
+            true => self.dc.or(Some(DcBlocker::new(DC_TIME_CONSTANT))),
+            false => None
+        };
+    }
+
+    /// Enable or disable blind correction of I/Q gain and phase imbalance.
+    ///
+    /// This is only effective with DC removal also enabled, unless the
+    /// input is already free of DC.
+    pub fn set_iq_correction(&mut self, enabled: bool) {
+        self.iq = match enabled {
+            true => self.iq.or(Some(IqBalancer::new(IQ_MEMORY))),
+            false => None
+        };
+    }
+
+    /// The DC blocker, if enabled.
+    pub fn dc_blocker(&self) -> Option<&DcBlocker> {
+        self.dc.as_ref()
+    }
+
+    /// The I/Q imbalance corrector, if enabled, which reports the current
+    /// estimate.
+    pub fn iq_balancer(&self) -> Option<&IqBalancer> {
+        self.iq.as_ref()
+    }
+
+    /// Shift the converted stream by `freq` (in Hz) at sample rate `rate`.
+    ///
+    /// The oscillator phase is kept when the shift changes, so this may be
+    /// called between blocks without causing discontinuities.
+    pub fn set_shift(&mut self, freq: f64, rate: f64) {
+        match self.shift {
+            Some(ref mut nco) => nco.set_freq(freq, rate),
+            None => self.shift = Some(Nco::new(freq, rate))
+        }
+    }
+
+    /// Remove any frequency shift.
+    pub fn clear_shift(&mut self) {
+        self.shift = None;
+    }
+
+    /// Convert a buffer of interleaved 8 bit I/Q.
+    pub fn convert(&mut self, buf: &[u8]) -> Vec<Complex> {
+        let mut out = iq_from_u8(buf);
+        if let Some(ref mut dc) = self.dc {
+            dc.process(&mut out);
+        }
+        if let Some(ref mut iq) = self.iq {
+            iq.process(&mut out);
+        }
+        if let Some(ref mut nco) = self.shift {
+            nco.mix(&mut out);
+        }
+        out
+    }
+}
@@ -0,0 +1,240 @@
+// SigMF recording for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// A recording is a pair of files sharing a base name: raw interleaved
+// unsigned 8 bit I/Q in `<base>.sigmf-data` (SigMF datatype "cu8") and JSON
+// metadata in `<base>.sigmf-meta`. The metadata is rewritten whenever it
+// changes, so an interrupted recording is still described correctly up to
+// its last retune.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{RTLSDRDevice, RTLSDRError};
+use super::json::Value;
+use super::source::Source;
+use super::timestamp::UtcTime;
+
+/// SigMF specification version written.
+pub const SIGMF_VERSION: &str = "1.0.0";
+
+/// Description of the receiver and its settings at the start of a
+/// recording.
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    /// Sample rate (in Hz).
+    pub sample_rate: u32,
+    /// Centre frequency (in Hz).
+    pub frequency: u64,
+    /// Tuner gain (in dB), or None if automatic.
+    pub gain: Option<f64>,
+    /// Frequency correction (in ppm).
+    pub ppm: i32,
+    /// Tuner type, e.g. "R820T".
+    pub tuner: String,
+    /// USB serial number.
+    pub serial: String,
+    /// Free text description of the recording.
+    pub description: String,
+    /// When the recording started.
+    pub datetime: SystemTime
+}
+
+impl Metadata {
+    /// Metadata for a recording at `frequency` and `sample_rate` (in Hz)
+    /// from an unspecified receiver.
+    pub fn new(frequency: u64, sample_rate: u32) -> Metadata {
+        Metadata {
+            sample_rate,
+            frequency,
+            gain: None,
+            ppm: 0,
+            tuner: String::new(),
+            serial: String::new(),
+            description: String::new(),
+            datetime: SystemTime::now()
+        }
+    }
+
+    /// Read the current settings of `dev`.
+    pub fn from_device(dev: &mut RTLSDRDevice)
+                       -> Result<Metadata, RTLSDRError> {
+        let mut meta = Metadata::new(dev.get_center_freq()?,
+                                     dev.get_sample_rate()?);
+        meta.gain = if dev.get_tuner_gain_mode() {
+            Some(dev.get_tuner_gain() as f64 / 10.0)
+        } else {
+            None
+        };
+        meta.ppm = dev.get_freq_correction();
+        meta.tuner = dev.get_tuner_type().1;
+        meta.serial = dev.get_usb_strings()?.serial;
+        Ok(meta)
+    }
+
+    fn global(&self) -> Value {
+        let hw = match self.tuner.as_str() {
+            "" => "RTL-SDR".to_string(),
+            t => format!("RTL-SDR ({})", t)
+        };
+        let extension = Value::object().with("name", "rtlsdr")
+                                       .with("version", "1.0.0")
+                                       .with("optional", true);
+        let mut global = Value::object()
+            .with("core:datatype", "cu8")
+            .with("core:sample_rate", self.sample_rate)
+            .with("core:version", SIGMF_VERSION)
+            .with("core:recorder", concat!("rtlsdr-rs ",
+                                           env!("CARGO_PKG_VERSION")))
+            .with("core:hw", hw)
+            .with("core:extensions", vec![extension]);
+        if !self.description.is_empty() {
+            global.set("core:description", self.description.as_str());
+        }
+        global.set("rtlsdr:gain", self.gain);
+        global.set("rtlsdr:ppm", self.ppm);
+        if !self.tuner.is_empty() {
+            global.set("rtlsdr:tuner", self.tuner.as_str());
+        }
+        if !self.serial.is_empty() {
+            global.set("rtlsdr:serial", self.serial.as_str());
+        }
+        global
+    }
+}
+
+/// One capture segment: a run of samples recorded at a single frequency.
+#[derive(Clone, Copy, Debug)]
+pub struct Capture {
+    /// Index of the first sample (not byte) in the segment.
+    pub sample_start: u64,
+    /// Centre frequency (in Hz).
+    pub frequency: u64,
+    /// When the first sample was taken.
+    pub datetime: SystemTime
+}
+
+impl Capture {
+    fn to_json(self) -> Value {
+        Value::object()
+            .with("core:sample_start", self.sample_start)
+            .with("core:frequency", self.frequency)
+            .with("core:datetime",
+                  UtcTime::from_system(self.datetime).iso8601())
+    }
+}
+
+/// Writes a SigMF recording.
+pub struct SigmfWriter {
+    meta: Metadata,
+    meta_path: PathBuf,
+    data_path: PathBuf,
+    data: BufWriter<File>,
+    captures: Vec<Capture>,
+    bytes: u64
+}
+
+impl SigmfWriter {
+    /// Create `<base>.sigmf-data` and `<base>.sigmf-meta`, replacing any
+    /// existing files, and start the first capture segment at the frequency
+    /// in `meta`.
+    pub fn create<P: AsRef<Path>>(base: P, meta: &Metadata)
+                                  -> std::io::Result<SigmfWriter> {
+        let base = base.as_ref().as_os_str().to_owned();
+        let mut data_path = base.clone();
+        data_path.push(".sigmf-data");
+        let mut meta_path = base;
+        meta_path.push(".sigmf-meta");
+        let data = BufWriter::new(File::create(&data_path)?);
+        let capture = Capture { sample_start: 0, frequency: meta.frequency,
+                                datetime: meta.datetime };
+        let writer = SigmfWriter {
+            meta: meta.clone(),
+            meta_path: meta_path.into(),
+            data_path: data_path.into(),
+            data,
+            captures: vec![capture],
+            bytes: 0
+        };
+        writer.write_meta()?;
+        Ok(writer)
+    }
+
+    /// Path of the data file.
+    pub fn data_path(&self) -> &Path {
+        &self.data_path
+    }
+
+    /// Path of the metadata file.
+    pub fn meta_path(&self) -> &Path {
+        &self.meta_path
+    }
+
+    /// Capture segments so far.
+    pub fn captures(&self) -> &[Capture] {
+        &self.captures
+    }
+
+    /// Number of complex samples written.
+    pub fn samples(&self) -> u64 {
+        self.bytes / 2
+    }
+
+    /// Append interleaved unsigned 8 bit I/Q, as from read_sync.
+    pub fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.data.write_all(data)?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+
+    /// Note that samples written from now on are at `frequency` (in Hz).
+    ///
+    /// Starts a new capture segment and rewrites the metadata if the
+    /// frequency has changed.
+    pub fn set_frequency(&mut self, frequency: u64) -> std::io::Result<()> {
+        let capture = Capture { sample_start: self.samples(), frequency,
+                                datetime: SystemTime::now() };
+        let last = self.captures.last_mut().unwrap();
+        if last.frequency == frequency {
+            return Ok(());
+        }
+        if last.sample_start == capture.sample_start {
+            // Nothing was recorded at the old frequency.
+            *last = capture;
+        } else {
+            self.captures.push(capture);
+        }
+        self.write_meta()
+    }
+
+    /// Read `len` bytes from `source` and append them, first starting a new
+    /// capture segment if the source has been retuned.
+    pub fn record_from<S: Source>(&mut self, source: &mut S, len: usize)
+                                  -> Result<(), RTLSDRError> {
+        let frequency = source.get_center_freq()?;
+        self.set_frequency(frequency)?;
+        let data = source.read_sync(len)?;
+        self.write(&data)?;
+        Ok(())
+    }
+
+    /// Flush the data file and write the final metadata.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.data.flush()?;
+        self.write_meta()
+    }
+
+    fn write_meta(&self) -> std::io::Result<()> {
+        let captures = self.captures.iter().map(|c| c.to_json()).collect();
+        let meta = Value::object()
+            .with("global", self.meta.global())
+            .with("captures", Value::Array(captures))
+            .with("annotations", Value::Array(Vec::new()));
+        let mut f = File::create(&self.meta_path)?;
+        f.write_all(meta.to_pretty().as_bytes())?;
+        f.write_all(b"\n")
+    }
+}
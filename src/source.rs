@@ -0,0 +1,380 @@
+// Sample sources for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::{DirectSampling, RTLSDRDevice, RTLSDRError, rtlsdr_error};
+use super::dsp::Complex;
+use super::samples::iq_to_u8;
+use super::tuning::tuner_gains;
+
+/// Anything which can be tuned and read like an RTL-SDR.
+///
+/// Samples are interleaved unsigned 8 bit I/Q, exactly as returned by
+/// RTLSDRDevice::read_sync, so code written against this trait can run on a
+/// live device or on other sources of samples.
+///
+/// The hardware controls after read_sync are optional: by default they
+/// report an unknown tuner with no gains and refuse any setting.
+pub trait Source {
+    /// Set the centre frequency (in Hz).
+    fn set_center_freq(&mut self, frequency: u64) -> Result<(), RTLSDRError>;
+
+    /// Get the centre frequency (in Hz).
+    fn get_center_freq(&mut self) -> Result<u64, RTLSDRError>;
+
+    /// Set the sample rate (in Hz).
+    fn set_sample_rate(&mut self, rate: u32) -> Result<(), RTLSDRError>;
+
+    /// Get the sample rate (in Hz).
+    fn get_sample_rate(&mut self) -> Result<u32, RTLSDRError>;
+
+    /// Set the frequency correction (in ppm).
+    fn set_freq_correction(&mut self, ppm: i32) -> Result<(), RTLSDRError>;
+
+    /// Get the frequency correction (in ppm).
+    fn get_freq_correction(&mut self) -> i32;
+
+    /// Reset the streaming buffer.
+    fn reset_buffer(&mut self) -> Result<(), RTLSDRError>;
+
+    /// Read `len` bytes of interleaved I/Q.
+    fn read_sync(&mut self, len: usize) -> Result<Vec<u8>, RTLSDRError>;
+
+    /// Get the tuner type as (id, name), with ids as in librtlsdr.
+    fn get_tuner_type(&mut self) -> (i32, String) {
+        (0, "Unknown".to_string())
+    }
+
+    /// Get the allowable tuner gains (in tenths of dB).
+    fn get_tuner_gains(&mut self) -> Result<Vec<i32>, RTLSDRError> {
+        Ok(Vec::new())
+    }
+
+    /// Set automatic or manual gain.
+    fn set_tuner_gain_mode(&mut self, _manual: bool)
+                           -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set the tuner gain (in tenths of dB).
+    fn set_tuner_gain(&mut self, _gain: i32) -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set the gain of tuner IF stage `stage` (in tenths of dB).
+    fn set_tuner_if_gain(&mut self, _stage: i32, _gain: i32)
+                         -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set test mode on or off.
+    fn set_test_mode(&mut self, _enabled: bool) -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set the RTL2832 digital AGC on or off.
+    fn set_agc_mode(&mut self, _enabled: bool) -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set direct sampling.
+    fn set_direct_sampling(&mut self, _mode: DirectSampling)
+                           -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set offset tuning on or off.
+    fn set_offset_tuning(&mut self, _enabled: bool)
+                         -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+
+    /// Set the bias tee on or off.
+    fn set_bias_tee(&mut self, _enabled: bool) -> Result<(), RTLSDRError> {
+        Err(unsupported())
+    }
+}
+
+fn unsupported() -> RTLSDRError {
+    rtlsdr_error(-1, "Not supported by this source")
+}
+
+impl Source for RTLSDRDevice {
+    fn set_center_freq(&mut self, frequency: u64) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_center_freq(self, frequency)
+    }
+
+    fn get_center_freq(&mut self) -> Result<u64, RTLSDRError> {
+        RTLSDRDevice::get_center_freq(self)
+    }
+
+    fn set_sample_rate(&mut self, rate: u32) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_sample_rate(self, rate)
+    }
+
+    fn get_sample_rate(&mut self) -> Result<u32, RTLSDRError> {
+        RTLSDRDevice::get_sample_rate(self)
+    }
+
+    fn set_freq_correction(&mut self, ppm: i32) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_freq_correction(self, ppm)
+    }
+
+    fn get_freq_correction(&mut self) -> i32 {
+        RTLSDRDevice::get_freq_correction(self)
+    }
+
+    fn reset_buffer(&mut self) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::reset_buffer(self)
+    }
+
+    fn read_sync(&mut self, len: usize) -> Result<Vec<u8>, RTLSDRError> {
+        RTLSDRDevice::read_sync(self, len)
+    }
+
+    fn get_tuner_type(&mut self) -> (i32, String) {
+        RTLSDRDevice::get_tuner_type(self)
+    }
+
+    fn get_tuner_gains(&mut self) -> Result<Vec<i32>, RTLSDRError> {
+        RTLSDRDevice::get_tuner_gains(self)
+    }
+
+    fn set_tuner_gain_mode(&mut self, manual: bool)
+                           -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_tuner_gain_mode(self, manual)
+    }
+
+    fn set_tuner_gain(&mut self, gain: i32) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_tuner_gain(self, gain)
+    }
+
+    fn set_tuner_if_gain(&mut self, stage: i32, gain: i32)
+                         -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_tuner_if_gain(self, stage, gain)
+    }
+
+    fn set_test_mode(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_test_mode(self, enabled)
+    }
+
+    fn set_agc_mode(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_agc_mode(self, enabled)
+    }
+
+    fn set_direct_sampling(&mut self, mode: DirectSampling)
+                           -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_direct_sampling(self, mode)
+    }
+
+    fn set_offset_tuning(&mut self, enabled: bool)
+                         -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_offset_tuning(self, enabled)
+    }
+
+    fn set_bias_tee(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        RTLSDRDevice::set_bias_tee(self, enabled)
+    }
+}
+
+/// A tone present at the input of a SimulatedSource.
+#[derive(Clone, Copy, Debug)]
+pub struct SimulatedSignal {
+    /// RF frequency (in Hz).
+    pub freq: u64,
+    /// Amplitude relative to ADC full scale.
+    pub amplitude: f32
+}
+
+/// A Source which synthesises samples, for exercising code without
+/// hardware.
+///
+/// The input is a set of tones plus Gaussian noise. Any tone within the
+/// sampled bandwidth appears where a real receiver would put it, including
+/// the effect of a simulated crystal error not cancelled by
+/// set_freq_correction.
+///
+/// It presents itself as an R820T. Gain settings are accepted but have no
+/// effect, and test mode produces the RTL2832's 8 bit counter.
+pub struct SimulatedSource {
+    center: u64,
+    rate: u32,
+    ppm: i32,
+    xtal_error: f64,
+    noise: f32,
+    signals: Vec<SimulatedSignal>,
+    phases: Vec<f64>,
+    rng: u64,
+    test_mode: bool,
+    counter: u8
+}
+
+impl SimulatedSource {
+    /// Create a source with no signals and a little noise, tuned to 100MHz
+    /// at 2.048MS/s.
+    pub fn new() -> SimulatedSource {
+        SimulatedSource {
+            center: 100_000_000,
+            rate: 2_048_000,
+            ppm: 0,
+            xtal_error: 0.0,
+            noise: 0.01,
+            signals: Vec::new(),
+            phases: Vec::new(),
+            rng: 0x2545_f491_4f6c_dd1d,
+            test_mode: false,
+            counter: 0
+        }
+    }
+
+    /// Add a tone at `freq` (in Hz) with `amplitude` relative to full scale.
+    pub fn add_signal(&mut self, freq: u64, amplitude: f32) {
+        self.signals.push(SimulatedSignal { freq, amplitude });
+        self.phases.push(0.0);
+    }
+
+    /// Remove all tones.
+    pub fn clear_signals(&mut self) {
+        self.signals.clear();
+        self.phases.clear();
+    }
+
+    /// The tones currently present.
+    pub fn signals(&self) -> &[SimulatedSignal] {
+        &self.signals
+    }
+
+    /// Set the RMS noise level per I/Q component, relative to full scale.
+    pub fn set_noise(&mut self, noise: f32) {
+        self.noise = noise;
+    }
+
+    /// Set the simulated crystal error (in ppm).
+    pub fn set_xtal_error(&mut self, ppm: f64) {
+        self.xtal_error = ppm;
+    }
+
+    /// Seed the noise generator, for reproducible output.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = seed.max(1);
+    }
+
+    /// Uniform random number in (0, 1], from xorshift64*.
+    fn uniform(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let r = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        ((r >> 11) as f64 + 1.0) / (1u64 << 53) as f64
+    }
+
+    /// Pair of independent standard normal numbers, by Box-Muller.
+    fn gaussian(&mut self) -> (f32, f32) {
+        let r = (-2.0 * self.uniform().ln()).sqrt();
+        let t = 2.0 * std::f64::consts::PI * self.uniform();
+        ((r * t.cos()) as f32, (r * t.sin()) as f32)
+    }
+}
+
+impl Default for SimulatedSource {
+    fn default() -> SimulatedSource {
+        SimulatedSource::new()
+    }
+}
+
+impl Source for SimulatedSource {
+    fn set_center_freq(&mut self, frequency: u64) -> Result<(), RTLSDRError> {
+        self.center = frequency;
+        Ok(())
+    }
+
+    fn get_center_freq(&mut self) -> Result<u64, RTLSDRError> {
+        Ok(self.center)
+    }
+
+    fn set_sample_rate(&mut self, rate: u32) -> Result<(), RTLSDRError> {
+        self.rate = rate;
+        Ok(())
+    }
+
+    fn get_sample_rate(&mut self) -> Result<u32, RTLSDRError> {
+        Ok(self.rate)
+    }
+
+    fn set_freq_correction(&mut self, ppm: i32) -> Result<(), RTLSDRError> {
+        self.ppm = ppm;
+        Ok(())
+    }
+
+    fn get_freq_correction(&mut self) -> i32 {
+        self.ppm
+    }
+
+    fn reset_buffer(&mut self) -> Result<(), RTLSDRError> {
+        Ok(())
+    }
+
+    fn read_sync(&mut self, len: usize) -> Result<Vec<u8>, RTLSDRError> {
+        if self.test_mode {
+            let start = self.counter;
+            self.counter = self.counter.wrapping_add(len as u8);
+            return Ok((0..len).map(|i| start.wrapping_add(i as u8))
+                              .collect());
+        }
+        let rate = self.rate as f64;
+        let error = (self.xtal_error - self.ppm as f64) * 1e-6;
+        let lo = self.center as f64 * (1.0 + error);
+        // Baseband frequency of each tone in cycles per sample, allowing for
+        // the sample clock sharing the crystal error.
+        let steps: Vec<f64> = self.signals.iter()
+            .map(|s| (s.freq as f64 - lo) / (rate * (1.0 + error)))
+            .collect();
+        let mut samples = Vec::with_capacity(len / 2);
+        for _ in 0..len / 2 {
+            let (ni, nq) = self.gaussian();
+            let mut x = Complex::new(ni * self.noise, nq * self.noise);
+            for ((sig, phase), step) in self.signals.iter()
+                                                    .zip(self.phases.iter_mut())
+                                                    .zip(&steps) {
+                if step.abs() < 0.5 {
+                    let p = *phase * 2.0 * std::f64::consts::PI;
+                    x += Complex::expj(p as f32) * sig.amplitude;
+                }
+                *phase = (*phase + step).fract();
+            }
+            samples.push(x);
+        }
+        Ok(iq_to_u8(&samples))
+    }
+
+    fn get_tuner_type(&mut self) -> (i32, String) {
+        (5, "R820T".to_string())
+    }
+
+    fn get_tuner_gains(&mut self) -> Result<Vec<i32>, RTLSDRError> {
+        Ok(tuner_gains(5).to_vec())
+    }
+
+    fn set_tuner_gain_mode(&mut self, _manual: bool)
+                           -> Result<(), RTLSDRError> {
+        Ok(())
+    }
+
+    fn set_tuner_gain(&mut self, _gain: i32) -> Result<(), RTLSDRError> {
+        Ok(())
+    }
+
+    fn set_tuner_if_gain(&mut self, _stage: i32, _gain: i32)
+                         -> Result<(), RTLSDRError> {
+        Ok(())
+    }
+
+    fn set_test_mode(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        self.test_mode = enabled;
+        Ok(())
+    }
+
+    fn set_agc_mode(&mut self, _enabled: bool) -> Result<(), RTLSDRError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+// Power spectral density estimation for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Welch's method: the input is cut into overlapping windowed frames, each is
+// transformed, and the squared magnitudes are averaged. Levels are relative
+// to ADC full scale, where a complex tone with I and Q swinging over the
+// whole 0 to 255 range of the u8 samples reads 0dBFS.
+
+use super::RTLSDRError;
+use super::dsp::Complex;
+use super::dsp::fft::{Fft, shift};
+use super::dsp::window::Window;
+use super::samples::iq_from_u8;
+use super::source::Source;
+
+/// How successive frames are combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Averaging {
+    /// Mean of every frame since the last reset.
+    Linear,
+    /// Exponential moving average, giving each new frame this weight (0 to
+    /// 1).
+    Exponential(f64),
+    /// Maximum seen in each bin.
+    PeakHold,
+    /// Minimum seen in each bin.
+    MinHold
+}
+
+/// Settings for a SpectrumAnalyzer.
+#[derive(Clone, Copy, Debug)]
+pub struct SpectrumConfig {
+    /// Frame length, which must be a power of two.
+    pub fft_size: usize,
+    /// Window applied to each frame.
+    pub window: Window,
+    /// Fraction of each frame shared with the next, from 0 to just under 1.
+    pub overlap: f64,
+    /// How frames are combined.
+    pub averaging: Averaging
+}
+
+impl SpectrumConfig {
+    /// `fft_size` bins with a Hann window, 50% overlap and linear averaging.
+    pub fn new(fft_size: usize) -> SpectrumConfig {
+        SpectrumConfig {
+            fft_size,
+            window: Window::Hann,
+            overlap: 0.5,
+            averaging: Averaging::Linear
+        }
+    }
+}
+
+/// An averaged spectrum, with the most negative frequency first.
+#[derive(Clone, Debug)]
+pub struct Psd {
+    /// Frequency of the first bin (in Hz); RF if the centre frequency was
+    /// known, otherwise baseband.
+    pub start: f64,
+    /// Width of each bin (in Hz).
+    pub step: f64,
+    /// Equivalent noise bandwidth of each bin (in Hz).
+    pub enbw: f64,
+    /// Number of frames averaged.
+    pub frames: u64,
+    /// Power spectral density in each bin (in dBFS/Hz).
+    pub density: Vec<f64>
+}
+
+impl Psd {
+    /// Frequency (in Hz) of bin `i`.
+    pub fn freq(&self, i: usize) -> f64 {
+        self.start + i as f64 * self.step
+    }
+
+    /// Power in each bin (in dBFS), as read by a narrowband signal centred
+    /// on that bin.
+    pub fn power(&self) -> Vec<f64> {
+        let offset = 10.0 * self.enbw.log10();
+        self.density.iter().map(|d| d + offset).collect()
+    }
+
+    /// Total power between `low` and `high` (in Hz), in dBFS.
+    pub fn band_power(&self, low: f64, high: f64) -> f64 {
+        let total: f64 = self.density.iter().enumerate()
+            .filter(|&(i, _)| (low..high).contains(&self.freq(i)))
+            .map(|(_, d)| 10f64.powf(d / 10.0) * self.step)
+            .sum();
+        10.0 * total.max(1e-30).log10()
+    }
+}
+
+/// Estimates power spectral density by Welch's method.
+///
+/// Samples may be fed in blocks of any size; frames straddling blocks are
+/// handled correctly.
+pub struct SpectrumAnalyzer {
+    config: SpectrumConfig,
+    fft: Fft,
+    window: Vec<f32>,
+    hop: usize,
+    rate: f64,
+    center: Option<u64>,
+    pending: Vec<Complex>,
+    acc: Vec<f64>,
+    frames: u64,
+    scratch: Vec<Complex>
+}
+
+impl SpectrumAnalyzer {
+    /// Create an analyser for samples at `rate` (in Hz).
+    pub fn new(config: SpectrumConfig, rate: f64) -> SpectrumAnalyzer {
+        let n = config.fft_size;
+        let overlap = config.overlap.clamp(0.0, 1.0);
+        let hop = ((n as f64 * (1.0 - overlap)).round() as usize).clamp(1, n);
+        SpectrumAnalyzer {
+            config,
+            fft: Fft::new(n),
+            window: config.window.coefficients(n),
+            hop,
+            rate,
+            center: None,
+            pending: Vec::new(),
+            acc: vec![0.0; n],
+            frames: 0,
+            scratch: vec![Complex::default(); n]
+        }
+    }
+
+    pub fn config(&self) -> &SpectrumConfig {
+        &self.config
+    }
+
+    /// Set the sample rate (in Hz). Clears the average if it changes.
+    pub fn set_rate(&mut self, rate: f64) {
+        if rate != self.rate {
+            self.rate = rate;
+            self.reset();
+        }
+    }
+
+    /// Set the centre frequency (in Hz) used to label bins, or None for
+    /// baseband. Clears the average if it changes.
+    pub fn set_center_freq(&mut self, center: Option<u64>) {
+        if center != self.center {
+            self.center = center;
+            self.reset();
+        }
+    }
+
+    /// Change how frames are combined. Clears the average.
+    pub fn set_averaging(&mut self, averaging: Averaging) {
+        self.config.averaging = averaging;
+        self.reset();
+    }
+
+    /// Discard the average and any partial frame.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.frames = 0;
+        for a in self.acc.iter_mut() {
+            *a = 0.0;
+        }
+    }
+
+    /// Number of frames in the current average.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Add complex samples to the average.
+    pub fn process(&mut self, samples: &[Complex]) {
+        let n = self.config.fft_size;
+        self.pending.extend_from_slice(samples);
+        let mut start = 0;
+        while start + n <= self.pending.len() {
+            for ((s, &x), &w) in self.scratch.iter_mut()
+                                     .zip(&self.pending[start..start + n])
+                                     .zip(&self.window) {
+                *s = x * w;
+            }
+            self.fft.process(&mut self.scratch);
+            self.accumulate();
+            start += self.hop;
+        }
+        self.pending.drain(..start.min(self.pending.len()));
+    }
+
+    /// Add interleaved unsigned 8 bit I/Q, as from read_sync.
+    pub fn process_u8(&mut self, buf: &[u8]) {
+        self.process(&iq_from_u8(buf));
+    }
+
+    /// Read `len` bytes from `source` and add them to the average, first
+    /// picking up its current sample rate and centre frequency.
+    pub fn read<S: Source>(&mut self, source: &mut S, len: usize)
+                           -> Result<(), RTLSDRError> {
+        self.set_rate(source.get_sample_rate()? as f64);
+        self.set_center_freq(source.get_center_freq().ok());
+        let buf = source.read_sync(len)?;
+        self.process_u8(&buf);
+        Ok(())
+    }
+
+    fn accumulate(&mut self) {
+        self.frames += 1;
+        let first = self.frames == 1;
+        let bins = self.acc.iter_mut().zip(&self.scratch);
+        match self.config.averaging {
+            Averaging::Linear => for (a, x) in bins {
+                *a += x.norm_sqr() as f64;
+            },
+            Averaging::Exponential(alpha) => for (a, x) in bins {
+                let p = x.norm_sqr() as f64;
+                *a = if first { p } else { *a + alpha * (p - *a) };
+            },
+            Averaging::PeakHold => for (a, x) in bins {
+                let p = x.norm_sqr() as f64;
+                *a = if first { p } else { a.max(p) };
+            },
+            Averaging::MinHold => for (a, x) in bins {
+                let p = x.norm_sqr() as f64;
+                *a = if first { p } else { a.min(p) };
+            }
+        }
+    }
+
+    /// The current estimate, or None if no whole frame has been seen.
+    pub fn psd(&self) -> Option<Psd> {
+        if self.frames == 0 {
+            return None;
+        }
+        let n = self.config.fft_size;
+        let sum: f64 = self.window.iter().map(|&w| w as f64).sum();
+        let sum_sq: f64 = self.window.iter().map(|&w| (w * w) as f64).sum();
+        let count = match self.config.averaging {
+            Averaging::Linear => self.frames as f64,
+            _ => 1.0
+        };
+        // |X|^2 / (fs * sum(w^2)) integrates over frequency to the mean
+        // power of the input.
+        let scale = 1.0 / (count * self.rate * sum_sq);
+        let mut density: Vec<f64> = self.acc.iter()
+            .map(|a| 10.0 * (a * scale).max(1e-30).log10())
+            .collect();
+        shift(&mut density);
+        let step = self.rate / n as f64;
+        let center = self.center.map_or(0.0, |c| c as f64);
+        Some(Psd {
+            start: center - (n / 2) as f64 * step,
+            step,
+            enbw: self.rate * sum_sq / (sum * sum),
+            frames: self.frames,
+            density
+        })
+    }
+}
@@ -0,0 +1,257 @@
+// Wideband power sweeps for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Covers a frequency range wider than one capture by hopping the tuner,
+// averaging FFTs at each hop, cropping the poor edges of each capture and
+// stitching what remains, in the manner of rtl_power.
+
+use std::io::Write;
+use std::time::SystemTime;
+
+use super::{RTLSDRError, rtlsdr_error};
+use super::dsp::Complex;
+use super::dsp::fft::{Fft, shift};
+use super::dsp::window::Window;
+use super::samples::iq_from_u8;
+use super::source::Source;
+use super::timestamp::UtcTime;
+
+/// One tuning step of a sweep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hop {
+    /// Frequency to tune to (in Hz).
+    pub center: u64,
+    /// First FFT bin kept, counting from the most negative frequency.
+    pub first_bin: usize,
+    /// Number of FFT bins kept.
+    pub bins: usize
+}
+
+/// The hops needed to cover a frequency range.
+#[derive(Clone, Debug)]
+pub struct HopPlan {
+    /// FFT size used at each hop.
+    pub fft_size: usize,
+    /// Sample rate (in Hz).
+    pub rate: u32,
+    /// Tuning steps, in increasing frequency.
+    pub hops: Vec<Hop>
+}
+
+impl HopPlan {
+    /// Plan a sweep from `start` to `stop` (in Hz) with bins no wider than
+    /// `bin_width` (in Hz), sampling at `rate` (in Hz) and discarding the
+    /// fraction `crop` of each capture (half from each edge).
+    pub fn new(start: u64, stop: u64, bin_width: f64, rate: u32, crop: f64)
+               -> Result<HopPlan, RTLSDRError> {
+        if stop <= start || bin_width <= 0.0 || rate == 0 {
+            return Err(rtlsdr_error(-1, "Invalid sweep range"));
+        }
+        if !(0.0..1.0).contains(&crop) {
+            return Err(rtlsdr_error(-1, "Crop must be from 0 to 1"));
+        }
+        let fft_size = ((rate as f64 / bin_width).ceil() as usize)
+                       .next_power_of_two().max(2);
+        let bin = rate as f64 / fft_size as f64;
+        // Keep an even number of bins centred in the capture.
+        let keep = (((1.0 - crop) * fft_size as f64) as usize & !1).max(2);
+        let first = (fft_size - keep) / 2;
+        let step = keep as f64 * bin;
+
+        let mut hops = Vec::new();
+        let mut low = start as f64;
+        while low < stop as f64 {
+            let bins = (((stop as f64 - low) / bin).ceil() as usize).min(keep);
+            let center = low + (fft_size / 2 - first) as f64 * bin;
+            hops.push(Hop { center: center.round() as u64, first_bin: first,
+                            bins });
+            low += step;
+        }
+        Ok(HopPlan { fft_size, rate, hops })
+    }
+
+    /// Width of each FFT bin (in Hz).
+    pub fn bin_width(&self) -> f64 {
+        self.rate as f64 / self.fft_size as f64
+    }
+
+    /// Frequency (in Hz) of the lowest bin kept from `hop`.
+    pub fn hop_low(&self, hop: &Hop) -> f64 {
+        hop.center as f64 +
+            (hop.first_bin as f64 - (self.fft_size / 2) as f64) *
+            self.bin_width()
+    }
+}
+
+/// Averaged power from one hop.
+#[derive(Clone, Debug)]
+pub struct Segment {
+    /// Frequency of the first bin (in Hz).
+    pub low: f64,
+    /// Width of each bin (in Hz).
+    pub step: f64,
+    /// Number of samples averaged.
+    pub samples: usize,
+    /// Power in each bin (in dB relative to full scale).
+    pub power: Vec<f64>
+}
+
+impl Segment {
+    /// Frequency (in Hz) just past the last bin.
+    pub fn high(&self) -> f64 {
+        self.low + self.step * self.power.len() as f64
+    }
+}
+
+/// A contiguous power spectrum.
+#[derive(Clone, Debug)]
+pub struct Spectrum {
+    /// Frequency of the first bin (in Hz).
+    pub start: f64,
+    /// Width of each bin (in Hz).
+    pub step: f64,
+    /// Power in each bin (in dB relative to full scale).
+    pub power: Vec<f64>
+}
+
+/// The result of one complete sweep.
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    /// When the sweep started.
+    pub time: SystemTime,
+    /// One segment per hop, in increasing frequency.
+    pub segments: Vec<Segment>
+}
+
+impl Sweep {
+    /// Join the segments into one spectrum.
+    ///
+    /// Segments are laid end to end, which is exact when they come from one
+    /// HopPlan.
+    pub fn stitch(&self) -> Spectrum {
+        let start = self.segments.first().map_or(0.0, |s| s.low);
+        let step = self.segments.first().map_or(0.0, |s| s.step);
+        let power = self.segments.iter()
+                                 .flat_map(|s| s.power.iter().cloned())
+                                 .collect();
+        Spectrum { start, step, power }
+    }
+
+    /// Write the sweep as rtl_power compatible CSV, one line per segment:
+    /// date, time, low Hz, high Hz, bin Hz, samples, then each bin in dB.
+    ///
+    /// Times are in UTC.
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let t = UtcTime::from_system(self.time);
+        let (date, time) = (t.date(), t.time());
+        for seg in &self.segments {
+            write!(w, "{}, {}, {}, {}, {:.2}, {}", date, time,
+                   seg.low.round() as u64, seg.high().round() as u64,
+                   seg.step, seg.samples)?;
+            for p in &seg.power {
+                write!(w, ", {:.2}", p)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sweeps a Source across a HopPlan.
+pub struct Scanner {
+    plan: HopPlan,
+    window: Vec<f32>,
+    fft: Fft,
+    averages: usize,
+    settle: usize
+}
+
+impl Scanner {
+    /// Create a scanner for `plan`, averaging `averages` FFTs at each hop.
+    pub fn new(plan: HopPlan, averages: usize) -> Scanner {
+        let fft = Fft::new(plan.fft_size);
+        let window = Window::Hann.coefficients(plan.fft_size);
+        Scanner { plan, window, fft, averages: averages.max(1),
+                  settle: 16384 }
+    }
+
+    /// Use `window` rather than the default Hann window.
+    pub fn set_window(&mut self, window: Window) {
+        self.window = window.coefficients(self.plan.fft_size);
+    }
+
+    /// Set the number of bytes discarded after each retune. Default 16384.
+    pub fn set_settle(&mut self, bytes: usize) {
+        self.settle = bytes;
+    }
+
+    /// The plan being swept.
+    pub fn plan(&self) -> &HopPlan {
+        &self.plan
+    }
+
+    /// Average power in each bin of `samples`, in natural FFT order, over
+    /// as many whole FFTs as fit. At least one must fit.
+    pub fn average_power(&self, samples: &[Complex])
+                         -> Result<Vec<f64>, RTLSDRError> {
+        let n = self.plan.fft_size;
+        if samples.len() < n {
+            return Err(rtlsdr_error(-1, "Too few samples for one FFT"));
+        }
+        let mut acc = vec![0.0f64; n];
+        let mut buf = vec![Complex::default(); n];
+        let mut count = 0;
+        for chunk in samples.chunks_exact(n) {
+            for ((b, &s), &w) in buf.iter_mut().zip(chunk).zip(&self.window) {
+                *b = s * w;
+            }
+            self.fft.process(&mut buf);
+            for (a, b) in acc.iter_mut().zip(&buf) {
+                *a += b.norm_sqr() as f64;
+            }
+            count += 1;
+        }
+        // Normalise so a full scale tone reads 0dB.
+        let gain: f64 = self.window.iter().map(|&w| w as f64).sum();
+        let scale = 1.0 / (count as f64 * gain * gain);
+        Ok(acc.iter().map(|a| a * scale).collect())
+    }
+
+    /// Measure one hop.
+    pub fn measure_hop<S: Source>(&self, source: &mut S, hop: &Hop)
+                                  -> Result<Segment, RTLSDRError> {
+        let n = self.plan.fft_size;
+        source.set_center_freq(hop.center)?;
+        source.reset_buffer()?;
+        if self.settle > 0 {
+            source.read_sync(self.settle)?;
+        }
+        // Round each read up to a whole number of USB transfers.
+        let len = (2 * n * self.averages).div_ceil(512) * 512;
+        let samples = iq_from_u8(&source.read_sync(len)?);
+        let mut power = self.average_power(&samples)?;
+        shift(&mut power);
+        let kept = &power[hop.first_bin..hop.first_bin + hop.bins];
+        Ok(Segment {
+            low: self.plan.hop_low(hop),
+            step: self.plan.bin_width(),
+            samples: samples.len() / n * n,
+            power: kept.iter().map(|p| 10.0 * p.max(1e-20).log10()).collect()
+        })
+    }
+
+    /// Perform one complete sweep of the plan.
+    ///
+    /// The source's sample rate is set from the plan.
+    pub fn sweep<S: Source>(&self, source: &mut S)
+                            -> Result<Sweep, RTLSDRError> {
+        source.set_sample_rate(self.plan.rate)?;
+        let time = SystemTime::now();
+        let mut segments = Vec::with_capacity(self.plan.hops.len());
+        for hop in &self.plan.hops {
+            segments.push(self.measure_hop(source, hop)?);
+        }
+        Ok(Sweep { time, segments })
+    }
+}
@@ -0,0 +1,265 @@
+// rtl_tcp client for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::{DirectSampling, RTLSDRError, rtlsdr_error};
+use crate::dsp::Complex;
+use crate::samples::iq_from_u8;
+use crate::source::Source;
+use crate::tuning::{tuner_gains, tuner_name};
+use super::{Command, Header};
+
+/// Time's worth of samples discarded by reset_buffer by default.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Rate assumed for the settle time until one has been set (in Hz).
+const DEFAULT_RATE: u32 = 2_048_000;
+
+/// A remote RTL-SDR reached through an rtl_tcp server.
+///
+/// Implements Source, so code written against that trait works unchanged
+/// with local and remote dongles. The protocol has no way to query the
+/// server, so getters return the last value set through this connection and
+/// fail if nothing has been set yet.
+pub struct TcpDevice {
+    stream: BufReader<TcpStream>,
+    peer: SocketAddr,
+    header: Header,
+    frequency: Option<u64>,
+    rate: Option<u32>,
+    ppm: i32,
+    gain: Option<i32>,
+    direct_sampling: DirectSampling,
+    offset_tuning: bool,
+    settle: Duration
+}
+
+impl TcpDevice {
+    /// Connect to an rtl_tcp server and read its header.
+    pub fn connect<A: ToSocketAddrs>(addr: A)
+                                     -> Result<TcpDevice, RTLSDRError> {
+        let stream = TcpStream::connect(addr)?;
+        let _ = stream.set_nodelay(true);
+        let peer = stream.peer_addr()?;
+        let mut stream = BufReader::with_capacity(1 << 16, stream);
+        let mut buf = [0u8; 12];
+        stream.read_exact(&mut buf)?;
+        let header = Header::from_bytes(&buf)
+            .ok_or_else(|| rtlsdr_error(-1, "Not an rtl_tcp server"))?;
+        Ok(TcpDevice {
+            stream, peer, header,
+            frequency: None,
+            rate: None,
+            ppm: 0,
+            gain: None,
+            direct_sampling: DirectSampling::Disabled,
+            offset_tuning: false,
+            settle: SETTLE_TIME
+        })
+    }
+
+    /// Close the connection.
+    pub fn close(&mut self) -> Result<(), RTLSDRError> {
+        self.stream.get_ref().shutdown(Shutdown::Both)?;
+        Ok(())
+    }
+
+    /// Address of the server.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// The header sent by the server.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Set how long reads wait for the server before failing, or None to
+    /// wait forever (the default).
+    pub fn set_timeout(&mut self, timeout: Option<Duration>)
+                       -> Result<(), RTLSDRError> {
+        self.stream.get_ref().set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Set how long a stretch of samples reset_buffer discards, once it
+    /// has dropped what was already received. Default 100ms.
+    pub fn set_settle_time(&mut self, settle: Duration) {
+        self.settle = settle;
+    }
+
+    /// Send a raw command to the server.
+    pub fn send(&mut self, command: Command) -> Result<(), RTLSDRError> {
+        self.stream.get_mut().write_all(&command.to_bytes())?;
+        Ok(())
+    }
+
+    /// Set the RTL2832 and tuner crystal frequencies (in Hz).
+    pub fn set_xtal_freq(&mut self, rtl_freq: u32, tuner_freq: u32)
+                         -> Result<(), RTLSDRError> {
+        self.send(Command::SetRtlXtal(rtl_freq))?;
+        self.send(Command::SetTunerXtal(tuner_freq))
+    }
+
+    /// Get the tuner gain (in tenths of dB) last set.
+    pub fn get_tuner_gain(&mut self) -> i32 {
+        self.gain.unwrap_or(0)
+    }
+
+    /// Get the direct sampling mode last set.
+    pub fn get_direct_sampling(&mut self)
+                               -> Result<DirectSampling, RTLSDRError> {
+        Ok(self.direct_sampling)
+    }
+
+    /// Get the offset tuning mode last set.
+    pub fn get_offset_tuning(&mut self) -> Result<bool, RTLSDRError> {
+        Ok(self.offset_tuning)
+    }
+
+    /// Read a buffer as complex samples. `len` is the number of bytes to
+    /// read, giving `len/2` samples.
+    pub fn read_iq(&mut self, len: usize)
+                   -> Result<Vec<Complex>, RTLSDRError> {
+        Ok(iq_from_u8(&self.read_sync(len)?))
+    }
+}
+
+impl Source for TcpDevice {
+    /// Set the centre frequency (in Hz). rtl_tcp only carries 32 bit
+    /// frequencies.
+    fn set_center_freq(&mut self, frequency: u64) -> Result<(), RTLSDRError> {
+        let f = u32::try_from(frequency)
+            .map_err(|_| rtlsdr_error(-1, "Frequency out of range"))?;
+        self.send(Command::SetFrequency(f))?;
+        self.frequency = Some(frequency);
+        Ok(())
+    }
+
+    fn get_center_freq(&mut self) -> Result<u64, RTLSDRError> {
+        self.frequency.ok_or_else(|| rtlsdr_error(0, "Frequency not set"))
+    }
+
+    fn set_sample_rate(&mut self, rate: u32) -> Result<(), RTLSDRError> {
+        self.send(Command::SetSampleRate(rate))?;
+        self.rate = Some(rate);
+        Ok(())
+    }
+
+    fn get_sample_rate(&mut self) -> Result<u32, RTLSDRError> {
+        self.rate.ok_or_else(|| rtlsdr_error(0, "Sample rate not set"))
+    }
+
+    fn set_freq_correction(&mut self, ppm: i32) -> Result<(), RTLSDRError> {
+        self.send(Command::SetFreqCorrection(ppm))?;
+        self.ppm = ppm;
+        Ok(())
+    }
+
+    fn get_freq_correction(&mut self) -> i32 {
+        self.ppm
+    }
+
+    /// Discard samples already received, then the settle time's worth
+    /// that follow.
+    ///
+    /// Samples queued on the server or in flight when a command is sent
+    /// still come from before it, and the protocol gives no way to tell
+    /// where they end, so the settle time should cover the server's queue
+    /// and the network's latency as well as the tuner settling.
+    fn reset_buffer(&mut self) -> Result<(), RTLSDRError> {
+        let buffered = self.stream.buffer().len();
+        self.stream.consume(buffered);
+        let sock = self.stream.get_mut();
+        sock.set_nonblocking(true)?;
+        let mut scratch = [0u8; 16384];
+        let result = loop {
+            match sock.read(&mut scratch) {
+                Ok(0) => break Err(rtlsdr_error(-1, "Connection closed")),
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e.into())
+            }
+        };
+        sock.set_nonblocking(false)?;
+        result?;
+        let rate = self.rate.unwrap_or(DEFAULT_RATE) as f64;
+        let samples = (self.settle.as_secs_f64() * rate).round() as usize;
+        self.read_sync(2 * samples)?;
+        Ok(())
+    }
+
+    fn read_sync(&mut self, len: usize) -> Result<Vec<u8>, RTLSDRError> {
+        let mut buf = vec![0u8; len];
+        self.stream.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn get_tuner_type(&mut self) -> (i32, String) {
+        let id = self.header.tuner_type as i32;
+        (id, tuner_name(id).to_string())
+    }
+
+    /// The gains librtlsdr offers for the server's tuner, checked against
+    /// the count in the header.
+    fn get_tuner_gains(&mut self) -> Result<Vec<i32>, RTLSDRError> {
+        let gains = tuner_gains(self.header.tuner_type as i32);
+        if gains.len() == self.header.gain_count as usize {
+            Ok(gains.to_vec())
+        } else {
+            Err(rtlsdr_error(-1, "Unknown gains for this tuner"))
+        }
+    }
+
+    fn set_tuner_gain_mode(&mut self, manual: bool)
+                           -> Result<(), RTLSDRError> {
+        self.send(Command::SetGainMode(manual))?;
+        if !manual {
+            self.gain = None;
+        }
+        Ok(())
+    }
+
+    fn set_tuner_gain(&mut self, gain: i32) -> Result<(), RTLSDRError> {
+        self.send(Command::SetGain(gain))?;
+        self.gain = Some(gain);
+        Ok(())
+    }
+
+    fn set_tuner_if_gain(&mut self, stage: i32, gain: i32)
+                         -> Result<(), RTLSDRError> {
+        self.send(Command::SetIfGain { stage: stage as u16,
+                                       gain: gain as i16 })
+    }
+
+    fn set_test_mode(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        self.send(Command::SetTestMode(enabled))
+    }
+
+    fn set_agc_mode(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        self.send(Command::SetAgcMode(enabled))
+    }
+
+    fn set_direct_sampling(&mut self, mode: DirectSampling)
+                           -> Result<(), RTLSDRError> {
+        self.send(Command::SetDirectSampling(mode))?;
+        self.direct_sampling = mode;
+        Ok(())
+    }
+
+    fn set_offset_tuning(&mut self, enabled: bool)
+                         -> Result<(), RTLSDRError> {
+        self.send(Command::SetOffsetTuning(enabled))?;
+        self.offset_tuning = enabled;
+        Ok(())
+    }
+
+    fn set_bias_tee(&mut self, enabled: bool) -> Result<(), RTLSDRError> {
+        self.send(Command::SetBiasTee(enabled))
+    }
+}
@@ -0,0 +1,170 @@
+// rtl_tcp protocol for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// On connection the server sends a 12 byte header: "RTL0", then the tuner
+// type and number of gains as big endian u32s. After that it streams raw
+// interleaved unsigned 8 bit I/Q, while the client may send 5 byte
+// commands: a command byte followed by a big endian u32 parameter.
+
+use crate::{DirectSampling, RTLSDRError, rtlsdr_error};
+use crate::source::Source;
+
+pub mod client;
+pub mod server;
+
+/// Magic at the start of the header.
+pub const MAGIC: &[u8; 4] = b"RTL0";
+
+/// Port rtl_tcp listens on by default.
+pub const DEFAULT_PORT: u16 = 1234;
+
+/// The header sent by the server when a client connects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// Tuner type, with ids as in librtlsdr.
+    pub tuner_type: u32,
+    /// Number of gains the tuner offers.
+    pub gain_count: u32
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; 12] {
+        let mut b = [0u8; 12];
+        b[..4].copy_from_slice(MAGIC);
+        b[4..8].copy_from_slice(&self.tuner_type.to_be_bytes());
+        b[8..].copy_from_slice(&self.gain_count.to_be_bytes());
+        b
+    }
+
+    /// Decode a header, or None if the magic is wrong.
+    pub fn from_bytes(b: &[u8; 12]) -> Option<Header> {
+        if &b[..4] != MAGIC {
+            return None;
+        }
+        Some(Header {
+            tuner_type: u32::from_be_bytes([b[4], b[5], b[6], b[7]]),
+            gain_count: u32::from_be_bytes([b[8], b[9], b[10], b[11]])
+        })
+    }
+}
+
+/// A command sent from client to server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Centre frequency (in Hz).
+    SetFrequency(u32),
+    /// Sample rate (in Hz).
+    SetSampleRate(u32),
+    /// True for manual gain.
+    SetGainMode(bool),
+    /// Tuner gain (in tenths of dB).
+    SetGain(i32),
+    /// Frequency correction (in ppm).
+    SetFreqCorrection(i32),
+    /// Gain (in tenths of dB) of one tuner IF stage.
+    SetIfGain { stage: u16, gain: i16 },
+    SetTestMode(bool),
+    SetAgcMode(bool),
+    SetDirectSampling(DirectSampling),
+    SetOffsetTuning(bool),
+    /// RTL2832 crystal frequency (in Hz).
+    SetRtlXtal(u32),
+    /// Tuner crystal frequency (in Hz).
+    SetTunerXtal(u32),
+    /// Tuner gain, as an index into the list of allowable gains.
+    SetGainByIndex(u32),
+    SetBiasTee(bool),
+    /// Anything else, as (command, parameter).
+    Unknown(u8, u32)
+}
+
+impl Command {
+    /// Decode a 5 byte command.
+    pub fn from_bytes(b: &[u8; 5]) -> Command {
+        let p = u32::from_be_bytes([b[1], b[2], b[3], b[4]]);
+        match b[0] {
+            0x01 => Command::SetFrequency(p),
+            0x02 => Command::SetSampleRate(p),
+            0x03 => Command::SetGainMode(p != 0),
+            0x04 => Command::SetGain(p as i32),
+            0x05 => Command::SetFreqCorrection(p as i32),
+            0x06 => Command::SetIfGain { stage: (p >> 16) as u16,
+                                         gain: p as u16 as i16 },
+            0x07 => Command::SetTestMode(p != 0),
+            0x08 => Command::SetAgcMode(p != 0),
+            0x09 => match p {
+                0 => Command::SetDirectSampling(DirectSampling::Disabled),
+                1 => Command::SetDirectSampling(DirectSampling::I),
+                2 => Command::SetDirectSampling(DirectSampling::Q),
+                _ => Command::Unknown(b[0], p)
+            },
+            0x0a => Command::SetOffsetTuning(p != 0),
+            0x0b => Command::SetRtlXtal(p),
+            0x0c => Command::SetTunerXtal(p),
+            0x0d => Command::SetGainByIndex(p),
+            0x0e => Command::SetBiasTee(p != 0),
+            c => Command::Unknown(c, p)
+        }
+    }
+
+    /// Encode as 5 bytes.
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let (c, p) = match *self {
+            Command::SetFrequency(f) => (0x01, f),
+            Command::SetSampleRate(r) => (0x02, r),
+            Command::SetGainMode(m) => (0x03, m as u32),
+            Command::SetGain(g) => (0x04, g as u32),
+            Command::SetFreqCorrection(ppm) => (0x05, ppm as u32),
+            Command::SetIfGain { stage, gain } =>
+                (0x06, (stage as u32) << 16 | gain as u16 as u32),
+            Command::SetTestMode(e) => (0x07, e as u32),
+            Command::SetAgcMode(e) => (0x08, e as u32),
+            Command::SetDirectSampling(m) => (0x09, match m {
+                DirectSampling::Disabled => 0,
+                DirectSampling::I => 1,
+                DirectSampling::Q => 2
+            }),
+            Command::SetOffsetTuning(e) => (0x0a, e as u32),
+            Command::SetRtlXtal(f) => (0x0b, f),
+            Command::SetTunerXtal(f) => (0x0c, f),
+            Command::SetGainByIndex(i) => (0x0d, i),
+            Command::SetBiasTee(e) => (0x0e, e as u32),
+            Command::Unknown(c, p) => (c, p)
+        };
+        let p = p.to_be_bytes();
+        [c, p[0], p[1], p[2], p[3]]
+    }
+
+    /// Carry out this command on `source`.
+    ///
+    /// Crystal frequencies cannot be set through the Source trait, so those
+    /// commands fail, as do unknown commands.
+    pub fn apply<S: Source>(&self, source: &mut S) -> Result<(), RTLSDRError> {
+        match *self {
+            Command::SetFrequency(f) => source.set_center_freq(f as u64),
+            Command::SetSampleRate(r) => source.set_sample_rate(r),
+            Command::SetGainMode(m) => source.set_tuner_gain_mode(m),
+            Command::SetGain(g) => source.set_tuner_gain(g),
+            Command::SetFreqCorrection(ppm) =>
+                source.set_freq_correction(ppm),
+            Command::SetIfGain { stage, gain } =>
+                source.set_tuner_if_gain(stage as i32, gain as i32),
+            Command::SetTestMode(e) => source.set_test_mode(e),
+            Command::SetAgcMode(e) => source.set_agc_mode(e),
+            Command::SetDirectSampling(m) => source.set_direct_sampling(m),
+            Command::SetOffsetTuning(e) => source.set_offset_tuning(e),
+            Command::SetGainByIndex(i) => {
+                let gains = source.get_tuner_gains()?;
+                match gains.get(i as usize) {
+                    Some(&g) => source.set_tuner_gain(g),
+                    None => Err(rtlsdr_error(-1, "Gain index out of range"))
+                }
+            },
+            Command::SetBiasTee(e) => source.set_bias_tee(e),
+            Command::SetRtlXtal(_) | Command::SetTunerXtal(_) |
+            Command::Unknown(..) =>
+                Err(rtlsdr_error(-1, "Unsupported rtl_tcp command"))
+        }
+    }
+}
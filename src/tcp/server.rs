@@ -0,0 +1,263 @@
+// rtl_tcp server for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// The source is read on the calling thread, which also carries out commands
+// between reads. A reader thread decodes commands from the client, and a
+// writer thread drains a bounded queue of sample blocks to the socket, so a
+// slow client never stalls the source.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex, mpsc};
+
+use crate::RTLSDRError;
+use crate::source::Source;
+use super::{Command, Header};
+
+/// What to do with a new block when a client's queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Discard the new block.
+    Newest,
+    /// Discard the oldest queued block to make room, keeping latency down.
+    Oldest,
+    /// End the session.
+    Disconnect
+}
+
+/// Server settings.
+#[derive(Clone, Copy, Debug)]
+pub struct ServerOptions {
+    /// Bytes read from the source at a time.
+    pub block_size: usize,
+    /// Maximum number of blocks queued for a client.
+    pub max_queued: usize,
+    /// What to do when the queue is full.
+    pub drop_policy: DropPolicy
+}
+
+impl Default for ServerOptions {
+    /// 256kB blocks, up to 64 queued, dropping the oldest.
+    fn default() -> ServerOptions {
+        ServerOptions {
+            block_size: 262_144,
+            max_queued: 64,
+            drop_policy: DropPolicy::Oldest
+        }
+    }
+}
+
+/// What happened during one client session.
+#[derive(Clone, Debug)]
+pub struct SessionStats {
+    /// Address of the client.
+    pub peer: SocketAddr,
+    /// Blocks read from the source.
+    pub blocks_read: u64,
+    /// Blocks discarded because the client was too slow.
+    pub blocks_dropped: u64,
+    /// Bytes of samples sent to the client.
+    pub bytes_sent: u64,
+    /// Commands received.
+    pub commands: u64,
+    /// Commands which the source refused.
+    pub command_errors: u64,
+    /// True if the session ended because the queue overflowed under
+    /// DropPolicy::Disconnect.
+    pub overflowed: bool
+}
+
+/// Serves a Source to rtl_tcp clients, one at a time.
+pub struct Server {
+    listener: TcpListener,
+    options: ServerOptions
+}
+
+impl Server {
+    /// Listen on `addr`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, options: ServerOptions)
+                                  -> Result<Server, RTLSDRError> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Server { listener, options })
+    }
+
+    /// Address being listened on, useful after binding to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, RTLSDRError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn options(&self) -> &ServerOptions {
+        &self.options
+    }
+
+    /// Wait for a client and stream `source` to it until it disconnects.
+    ///
+    /// Errors are only returned for failures of the listener or source; a
+    /// client going away ends the session normally.
+    pub fn serve_client<S: Source>(&self, source: &mut S)
+                                   -> Result<SessionStats, RTLSDRError> {
+        let (stream, peer) = self.listener.accept()?;
+        let _ = stream.set_nodelay(true);
+        let mut stats = SessionStats {
+            peer, blocks_read: 0, blocks_dropped: 0, bytes_sent: 0,
+            commands: 0, command_errors: 0, overflowed: false
+        };
+
+        let (tuner, _) = source.get_tuner_type();
+        let gains = source.get_tuner_gains().unwrap_or_default();
+        let header = Header { tuner_type: tuner as u32,
+                              gain_count: gains.len() as u32 };
+        if (&stream).write_all(&header.to_bytes()).is_err() {
+            return Ok(stats);
+        }
+        source.reset_buffer()?;
+
+        let queue = Queue::new(self.options.max_queued.max(1));
+        let (tx, rx) = mpsc::channel();
+        let reader = stream.try_clone()?;
+        let writer = stream.try_clone()?;
+        std::thread::scope(|scope| {
+            scope.spawn(|| read_commands(reader, tx));
+            let sent = scope.spawn(|| write_blocks(writer, &queue));
+            let result = self.stream(source, &queue, &rx, &mut stats);
+            // Wake both threads so the scope can end.
+            queue.close();
+            let _ = stream.shutdown(Shutdown::Both);
+            stats.bytes_sent = sent.join().unwrap_or(0);
+            result
+        })?;
+        Ok(stats)
+    }
+
+    /// Serve clients one after another, forever, reporting each session to
+    /// `report`.
+    pub fn run<S, F>(&self, source: &mut S, mut report: F)
+                     -> Result<(), RTLSDRError>
+        where S: Source, F: FnMut(&SessionStats) {
+        loop {
+            let stats = self.serve_client(source)?;
+            report(&stats);
+        }
+    }
+
+    fn stream<S: Source>(&self, source: &mut S, queue: &Queue,
+                         commands: &mpsc::Receiver<Command>,
+                         stats: &mut SessionStats)
+                         -> Result<(), RTLSDRError> {
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(cmd) => {
+                        stats.commands += 1;
+                        if cmd.apply(source).is_err() {
+                            stats.command_errors += 1;
+                        }
+                    },
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => return Ok(())
+                }
+            }
+            if queue.is_closed() {
+                return Ok(());
+            }
+            let block = source.read_sync(self.options.block_size)?;
+            stats.blocks_read += 1;
+            match queue.push(block, self.options.drop_policy) {
+                Push::Queued => (),
+                Push::Dropped => stats.blocks_dropped += 1,
+                Push::Full => {
+                    stats.blocks_dropped += 1;
+                    stats.overflowed = true;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Decode commands until the client closes the connection.
+fn read_commands(mut stream: TcpStream, tx: mpsc::Sender<Command>) {
+    let mut buf = [0u8; 5];
+    while stream.read_exact(&mut buf).is_ok() {
+        if tx.send(Command::from_bytes(&buf)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Send queued blocks until the queue is closed or the client goes away,
+/// returning the number of bytes sent.
+fn write_blocks(mut stream: TcpStream, queue: &Queue) -> u64 {
+    let mut sent = 0;
+    while let Some(block) = queue.pop() {
+        if stream.write_all(&block).is_err() {
+            queue.close();
+            break;
+        }
+        sent += block.len() as u64;
+    }
+    sent
+}
+
+enum Push {
+    Queued,
+    Dropped,
+    Full
+}
+
+/// A bounded queue of blocks shared between the source and writer threads.
+struct Queue {
+    state: Mutex<(VecDeque<Vec<u8>>, bool)>,
+    ready: Condvar,
+    limit: usize
+}
+
+impl Queue {
+    fn new(limit: usize) -> Queue {
+        Queue { state: Mutex::new((VecDeque::new(), false)),
+                ready: Condvar::new(), limit }
+    }
+
+    fn push(&self, block: Vec<u8>, policy: DropPolicy) -> Push {
+        let mut state = self.state.lock().unwrap();
+        let mut result = Push::Queued;
+        if state.0.len() >= self.limit {
+            match policy {
+                DropPolicy::Newest => return Push::Dropped,
+                DropPolicy::Oldest => {
+                    state.0.pop_front();
+                    result = Push::Dropped;
+                },
+                DropPolicy::Disconnect => return Push::Full
+            }
+        }
+        state.0.push_back(block);
+        self.ready.notify_one();
+        result
+    }
+
+    /// Wait for the next block, or None once the queue is closed.
+    fn pop(&self) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.1 {
+                return None;
+            }
+            if let Some(block) = state.0.pop_front() {
+                return Some(block);
+            }
+            state = self.ready.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.ready.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().1
+    }
+}
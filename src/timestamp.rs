@@ -0,0 +1,123 @@
+// UTC timestamp formatting for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::time::{Duration, SystemTime};
+
+/// A UTC calendar date and time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UtcTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millis: u32
+}
+
+impl UtcTime {
+    /// Break a SystemTime down into its UTC calendar fields. Times before
+    /// 1970 are treated as 1970.
+    pub fn from_system(time: SystemTime) -> UtcTime {
+        let d = time.duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or(Duration::ZERO);
+        let secs = d.as_secs();
+        let (days, rem) = (secs / 86400, secs % 86400);
+        // Civil date from days since 1970-01-01 (Howard Hinnant's
+        // algorithm).
+        let z = days as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        UtcTime {
+            year, month, day,
+            hour: (rem / 3600) as u32,
+            minute: ((rem / 60) % 60) as u32,
+            second: (rem % 60) as u32,
+            millis: d.subsec_millis()
+        }
+    }
+
+    /// Parse an ISO 8601 UTC time, "YYYY-MM-DDTHH:MM:SS" with optional
+    /// fractional seconds and a trailing "Z".
+    pub fn parse_iso8601(text: &str) -> Option<UtcTime> {
+        let text = text.strip_suffix('Z').unwrap_or(text);
+        let (date, time) = text.split_once('T')?;
+        let mut d = date.splitn(3, '-');
+        let year = d.next()?.parse().ok()?;
+        let month = d.next()?.parse().ok()?;
+        let day = d.next()?.parse().ok()?;
+        let (hms, frac) = match time.split_once('.') {
+            Some((hms, frac)) => (hms, frac),
+            None => (time, "")
+        };
+        let mut t = hms.splitn(3, ':');
+        let hour = t.next()?.parse().ok()?;
+        let minute = t.next()?.parse().ok()?;
+        let second = t.next()?.parse().ok()?;
+        let millis = match frac.len() {
+            0 => 0,
+            n => {
+                let digits = &frac[..n.min(3)];
+                let v: u32 = digits.parse().ok()?;
+                v * 10u32.pow(3 - digits.len() as u32)
+            }
+        };
+        let valid = (1..=12).contains(&month) && (1..=31).contains(&day) &&
+                    hour < 24 && minute < 60 && second < 61;
+        if !valid {
+            return None;
+        }
+        Some(UtcTime { year, month, day, hour, minute, second, millis })
+    }
+
+    /// Convert back to a SystemTime.
+    pub fn to_system(&self) -> SystemTime {
+        // Days since 1970-01-01 from the civil date, the inverse of
+        // from_system.
+        let y = self.year - if self.month <= 2 { 1 } else { 0 };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let mp = if m > 2 { m - 3 } else { m + 9 };
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        let secs = days * 86400 + self.hour as i64 * 3600 +
+                   self.minute as i64 * 60 + self.second as i64;
+        let offset = Duration::from_secs(secs.unsigned_abs()) +
+                     Duration::from_millis(self.millis as u64);
+        if secs >= 0 {
+            SystemTime::UNIX_EPOCH + offset
+        } else {
+            SystemTime::UNIX_EPOCH - offset
+        }
+    }
+
+    /// "YYYY-MM-DD"
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// "HH:MM:SS"
+    pub fn time(&self) -> String {
+        format!("{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+
+    /// ISO 8601 with milliseconds, "YYYY-MM-DDTHH:MM:SS.sssZ".
+    pub fn iso8601(&self) -> String {
+        format!("{}T{}.{:03}Z", self.date(), self.time(), self.millis)
+    }
+
+    /// Compact form for filenames, "YYYYMMDD_HHMMSS".
+    pub fn compact(&self) -> String {
+        format!("{:04}{:02}{:02}_{:02}{:02}{:02}", self.year, self.month,
+                self.day, self.hour, self.minute, self.second)
+    }
+}
@@ -0,0 +1,298 @@
+// Triggered recording for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Keeps the last few seconds of samples in memory and only writes to disk
+// when something happens, so that intermittent signals can be caught along
+// with the lead up to them without recording continuously.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use super::RTLSDRError;
+use super::detect::{Detector, DetectorConfig};
+use super::sigmf::{Metadata, SigmfWriter};
+use super::source::Source;
+use super::spectrum::{SpectrumAnalyzer, SpectrumConfig};
+use super::timestamp::UtcTime;
+
+/// Fires a Trigger::External from elsewhere, such as another thread.
+#[derive(Clone, Debug, Default)]
+pub struct TriggerHandle {
+    fired: Arc<AtomicBool>
+}
+
+impl TriggerHandle {
+    pub fn new() -> TriggerHandle {
+        TriggerHandle::default()
+    }
+
+    /// Fire the trigger; it is seen with the next block processed.
+    pub fn fire(&self) {
+        self.fired.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the trigger has fired since last taken, clearing it.
+    fn take(&self) -> bool {
+        self.fired.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// A condition which starts a recording.
+pub enum Trigger {
+    /// Mean power of a block reaches the given level (in dBFS).
+    Power(f64),
+    /// A detector finds a signal in the spectrum of a block, optionally
+    /// only between the given frequencies (in Hz).
+    Detector {
+        analyzer: Box<SpectrumAnalyzer>,
+        detector: Detector,
+        band: Option<(f64, f64)>
+    },
+    /// A TriggerHandle is fired.
+    External(TriggerHandle)
+}
+
+impl Trigger {
+    /// A detector trigger using `fft_size` bin spectra of samples at `rate`
+    /// (in Hz).
+    pub fn detector(config: DetectorConfig, fft_size: usize, rate: f64,
+                    band: Option<(f64, f64)>) -> Trigger {
+        Trigger::Detector {
+            analyzer: Box::new(SpectrumAnalyzer::new(
+                SpectrumConfig::new(fft_size), rate)),
+            detector: Detector::new(config),
+            band
+        }
+    }
+
+    /// Whether the trigger fires on a block of interleaved u8 I/Q, centred
+    /// on `center` (in Hz).
+    fn check(&mut self, block: &[u8], center: u64) -> bool {
+        match *self {
+            Trigger::Power(threshold) => {
+                let sum: u64 = block.iter().map(|&b| {
+                    let x = 2 * b as i64 - 255;
+                    (x * x) as u64
+                }).sum();
+                // Mean of I^2 + Q^2, with I and Q scaled to +-1.
+                let p = 2.0 * sum as f64 /
+                        (block.len().max(1) as f64 * 255.0 * 255.0);
+                10.0 * p.max(1e-20).log10() >= threshold
+            },
+            Trigger::Detector { ref mut analyzer, ref detector, band } => {
+                analyzer.set_center_freq(Some(center));
+                analyzer.reset();
+                analyzer.process_u8(block);
+                let psd = match analyzer.psd() {
+                    Some(psd) => psd,
+                    None => return false
+                };
+                let offset = 10.0 * psd.step.log10();
+                let power: Vec<f64> = psd.density.iter().map(|d| d + offset)
+                                         .collect();
+                detector.detect(psd.start, psd.step, &power).iter().any(|d| {
+                    band.is_none_or(|(low, high)| {
+                        d.high() > low && d.low() < high
+                    })
+                })
+            },
+            Trigger::External(ref handle) => handle.take()
+        }
+    }
+}
+
+/// File format for triggered recordings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A SigMF recording pair.
+    Sigmf,
+    /// Raw interleaved u8 I/Q, as written by rtl_sdr, with extension .cu8.
+    Raw
+}
+
+enum Sink {
+    Sigmf(SigmfWriter),
+    Raw(BufWriter<File>)
+}
+
+impl Sink {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match *self {
+            Sink::Sigmf(ref mut w) => w.write(data),
+            Sink::Raw(ref mut w) => w.write_all(data)
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Sink::Sigmf(w) => w.finish(),
+            Sink::Raw(mut w) => w.flush()
+        }
+    }
+}
+
+/// A recording in progress.
+struct Active {
+    sink: Sink,
+    path: String,
+    /// Bytes still to write after the last trigger.
+    remaining: usize
+}
+
+/// Records from the last time any trigger fires, preceded by a fixed
+/// length of history.
+///
+/// Each recording continues for a fixed time after the last trigger, so
+/// triggers firing during a recording extend it.
+pub struct TriggeredRecorder {
+    meta: Metadata,
+    prefix: String,
+    format: Format,
+    history: VecDeque<u8>,
+    pre: usize,
+    post: usize,
+    triggers: Vec<Trigger>,
+    active: Option<Active>
+}
+
+impl TriggeredRecorder {
+    /// Record `pre` seconds before and `post` seconds after each trigger,
+    /// of samples described by `meta`, to files named from `prefix` and
+    /// the time of the first sample to the millisecond.
+    pub fn new(meta: Metadata, pre: f64, post: f64, prefix: &str,
+               format: Format) -> TriggeredRecorder {
+        let bytes = |t: f64| (t.max(0.0) * meta.sample_rate as f64) as usize
+                             * 2;
+        TriggeredRecorder {
+            pre: bytes(pre),
+            post: bytes(post),
+            history: VecDeque::with_capacity(bytes(pre)),
+            meta,
+            prefix: prefix.to_string(),
+            format,
+            triggers: Vec::new(),
+            active: None
+        }
+    }
+
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        self.triggers.push(trigger);
+    }
+
+    /// Add an external trigger, returning the handle which fires it.
+    pub fn external_trigger(&mut self) -> TriggerHandle {
+        let handle = TriggerHandle::new();
+        self.triggers.push(Trigger::External(handle.clone()));
+        handle
+    }
+
+    /// Follow a change of centre frequency, which applies to subsequent
+    /// recordings.
+    pub fn set_frequency(&mut self, frequency: u64) {
+        self.meta.frequency = frequency;
+    }
+
+    /// Path of the recording in progress, if any; for SigMF, without the
+    /// extensions.
+    pub fn recording(&self) -> Option<&str> {
+        self.active.as_ref().map(|a| a.path.as_str())
+    }
+
+    /// Process a block of interleaved u8 I/Q. Returns the path of a
+    /// recording if this block completed it.
+    pub fn process(&mut self, block: &[u8])
+                   -> Result<Option<String>, RTLSDRError> {
+        // Every trigger sees every block, so stateful triggers keep up.
+        let center = self.meta.frequency;
+        let mut fired = false;
+        for t in self.triggers.iter_mut() {
+            fired |= t.check(block, center);
+        }
+        if fired {
+            match self.active {
+                Some(ref mut active) => active.remaining = self.post,
+                None => self.start(block.len())?
+            }
+        }
+
+        let mut done = None;
+        if let Some(ref mut active) = self.active {
+            let n = active.remaining.min(block.len()) & !1;
+            active.sink.write(&block[..n])?;
+            active.remaining -= n;
+            if active.remaining < 2 {
+                done = self.active.take();
+            }
+        }
+        self.remember(block);
+
+        match done {
+            Some(active) => {
+                active.sink.finish()?;
+                Ok(Some(active.path))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Read `len` bytes from `source` and process them.
+    pub fn read<S: Source>(&mut self, source: &mut S, len: usize)
+                           -> Result<Option<String>, RTLSDRError> {
+        let block = source.read_sync(len)?;
+        self.process(&block)
+    }
+
+    /// End any recording in progress early, returning its path.
+    pub fn finish(&mut self) -> Result<Option<String>, RTLSDRError> {
+        match self.active.take() {
+            Some(active) => {
+                active.sink.finish()?;
+                Ok(Some(active.path))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Open a file and write the history into it, ahead of a block of
+    /// `len` bytes.
+    fn start(&mut self, len: usize) -> Result<(), RTLSDRError> {
+        let seconds = (self.history.len() + len) as f64 / 2.0 /
+                      self.meta.sample_rate as f64;
+        let first = SystemTime::now() - Duration::from_secs_f64(seconds);
+        // Histories overlap, so successive recordings can start within the
+        // same second.
+        let time = UtcTime::from_system(first);
+        let base = format!("{}_{}_{:03}", self.prefix, time.compact(),
+                           time.millis);
+        let (mut sink, path) = match self.format {
+            Format::Sigmf => {
+                let mut meta = self.meta.clone();
+                meta.datetime = first;
+                (Sink::Sigmf(SigmfWriter::create(&base, &meta)?), base)
+            },
+            Format::Raw => {
+                let path = format!("{}.cu8", base);
+                (Sink::Raw(BufWriter::new(File::create(&path)?)), path)
+            }
+        };
+        let (a, b) = self.history.as_slices();
+        sink.write(a)?;
+        sink.write(b)?;
+        self.active = Some(Active { sink, path, remaining: self.post });
+        Ok(())
+    }
+
+    /// Keep the end of `block` in the history.
+    fn remember(&mut self, block: &[u8]) {
+        let keep = &block[block.len() - block.len().min(self.pre)..];
+        let excess = (self.history.len() + keep.len())
+                         .saturating_sub(self.pre);
+        self.history.drain(..excess);
+        self.history.extend(keep);
+    }
+}
@@ -0,0 +1,213 @@
+// Tuning helpers for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use super::{RTLSDRError, rtlsdr_error};
+
+/// Translation between the RF frequency at the antenna and the frequency the
+/// tuner is asked to receive, for use behind up- and down-converters.
+///
+/// Without inversion the tuner frequency is `rf + offset`; with inversion it
+/// is `offset - rf`, which is the case for a converter with its local
+/// oscillator above the signal (the spectrum arrives mirrored).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FreqTranslation {
+    pub offset: i64,
+    pub inverted: bool
+}
+
+impl FreqTranslation {
+    /// No translation: the tuner receives the RF frequency directly.
+    pub fn none() -> FreqTranslation {
+        FreqTranslation { offset: 0, inverted: false }
+    }
+
+    /// An upconverter whose local oscillator (in Hz) is added to the RF,
+    /// such as the 125MHz Ham-It-Up.
+    pub fn upconverter(lo: u64) -> FreqTranslation {
+        FreqTranslation { offset: lo as i64, inverted: false }
+    }
+
+    /// A downconverter whose local oscillator (in Hz) is below the RF, such
+    /// as a typical LNB.
+    pub fn downconverter(lo: u64) -> FreqTranslation {
+        FreqTranslation { offset: -(lo as i64), inverted: false }
+    }
+
+    /// A downconverter whose local oscillator (in Hz) is above the RF, which
+    /// inverts the spectrum.
+    pub fn inverting_downconverter(lo: u64) -> FreqTranslation {
+        FreqTranslation { offset: lo as i64, inverted: true }
+    }
+
+    /// Convert an RF frequency (in Hz) to the tuner frequency (in Hz).
+    ///
+    /// Fails if the result cannot be represented as a tuner frequency.
+    pub fn rf_to_tuner(&self, rf: u64) -> Result<u32, RTLSDRError> {
+        let rf = rf as i128;
+        let tuner = match self.inverted {
+            false => rf + self.offset as i128,
+            true => self.offset as i128 - rf
+        };
+        if tuner < 0 || tuner > u32::MAX as i128 {
+            Err(rtlsdr_error(-1, "Frequency outside tuner range"))
+        } else {
+            Ok(tuner as u32)
+        }
+    }
+
+    /// Convert a tuner frequency (in Hz) back to the RF frequency (in Hz).
+    pub fn tuner_to_rf(&self, tuner: u32) -> u64 {
+        let tuner = tuner as i128;
+        let rf = match self.inverted {
+            false => tuner - self.offset as i128,
+            true => self.offset as i128 - tuner
+        };
+        rf.max(0) as u64
+    }
+}
+
+/// Name of tuner type `tuner`, with ids as in librtlsdr.
+pub fn tuner_name(tuner: i32) -> &'static str {
+    match tuner {
+        1 => "E4000",
+        2 => "FC0012",
+        3 => "FC0013",
+        4 => "FC2580",
+        5 => "R820T",
+        6 => "R828D",
+        _ => "Unknown"
+    }
+}
+
+/// Gains (in tenths of dB) librtlsdr offers for tuner type `tuner`.
+pub fn tuner_gains(tuner: i32) -> &'static [i32] {
+    match tuner {
+        1 => &[-10, 15, 40, 65, 90, 115, 140, 165, 190, 215, 240, 290, 340,
+               420],
+        2 => &[-99, -40, 71, 179, 192],
+        3 => &[-99, -73, -65, -63, -60, -58, -54, 58, 61, 63, 65, 67, 68, 70,
+               71, 179, 181, 182, 184, 186, 188, 191, 197],
+        5 | 6 => &[0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207,
+                   229, 254, 280, 297, 328, 338, 364, 372, 386, 402, 421,
+                   434, 439, 445, 480, 496],
+        _ => &[0]
+    }
+}
+
+// R820T/R828D intermediate frequencies, chosen by the tuner according to
+// the configured IF bandwidth.
+const R82XX_IF_WIDE: u32 = 4_570_000;
+const R82XX_IF_MEDIUM: u32 = 3_570_000;
+const R82XX_IF_NARROW: u32 = 2_300_000;
+
+/// Model of the tuning hardware, used to work out which frequency is really
+/// received when a given frequency is requested.
+#[derive(Clone, Copy, Debug)]
+pub struct TunerModel {
+    /// Tuner type id, as returned by get_tuner_type.
+    pub tuner: i32,
+    /// RTL2832 crystal frequency (in Hz), as returned by get_xtal_freq.
+    pub rtl_xtal: u32,
+    /// Tuner crystal frequency (in Hz), as returned by get_xtal_freq.
+    pub tuner_xtal: u32,
+    /// Tuner IF bandwidth (in Hz); when automatic this is the sample rate.
+    pub bandwidth: u32,
+    /// Whether direct sampling is enabled, bypassing the tuner.
+    pub direct_sampling: bool
+}
+
+impl TunerModel {
+    /// Frequency (in Hz) actually received when `freq` (in Hz) is requested
+    /// of the tuner.
+    ///
+    /// Only the R820T/R828D PLL and the RTL2832 IF oscillator are modelled;
+    /// for other tuners `freq` is returned unchanged.
+    pub fn achieved_freq(&self, freq: u32) -> f64 {
+        if self.direct_sampling {
+            return self.rtl_if_freq(freq);
+        }
+        if self.tuner == super::ffi::RTLSDR_TUNER_R820T ||
+           self.tuner == super::ffi::RTLSDR_TUNER_R828D {
+            let if_freq = self.r82xx_if_freq();
+            let lo = r82xx_pll_freq(freq.saturating_add(if_freq),
+                                    self.tuner_xtal);
+            // The R82xx uses high-side injection, so the received centre is
+            // the LO minus the IF the RTL2832 downconverts from.
+            lo - self.rtl_if_freq(if_freq)
+        } else {
+            freq as f64
+        }
+    }
+
+    /// Intermediate frequency the R82xx selects for the current bandwidth.
+    fn r82xx_if_freq(&self) -> u32 {
+        if self.bandwidth > 6_000_000 {
+            R82XX_IF_WIDE
+        } else if self.bandwidth > 2_430_000 {
+            R82XX_IF_MEDIUM
+        } else {
+            R82XX_IF_NARROW
+        }
+    }
+
+    /// Frequency (in Hz) the RTL2832 IF oscillator generates when asked for
+    /// `freq`, given its 22 bit frequency word.
+    fn rtl_if_freq(&self, freq: u32) -> f64 {
+        let xtal = self.rtl_xtal as f64;
+        let word = (freq as f64 * (1u64 << 22) as f64 / xtal).trunc();
+        word * xtal / (1u64 << 22) as f64
+    }
+}
+
+/// Frequency (in Hz) the R82xx PLL synthesises when asked for `freq`, with
+/// reference crystal `xtal`, following the librtlsdr register calculation.
+fn r82xx_pll_freq(freq: u32, xtal: u32) -> f64 {
+    const VCO_MIN: u64 = 1_770_000;
+    const VCO_MAX: u64 = VCO_MIN * 2;
+
+    let freq_khz = (freq as u64 + 500) / 1000;
+    let pll_ref = xtal as u64;
+    let pll_ref_khz = (pll_ref + 500) / 1000;
+
+    let mut mix_div: u64 = 2;
+    while mix_div <= 64 {
+        let vco = freq_khz * mix_div;
+        if (VCO_MIN..VCO_MAX).contains(&vco) {
+            break;
+        }
+        mix_div <<= 1;
+    }
+    let mix_div = mix_div.min(64);
+
+    let vco_freq = freq as u64 * mix_div;
+    let mut nint = vco_freq / (2 * pll_ref);
+    let mut vco_fra = (vco_freq - 2 * pll_ref * nint) / 1000;
+
+    // Boundary spur prevention moves the fractional part away from integer
+    // multiples of the reference.
+    if vco_fra < pll_ref_khz / 64 {
+        vco_fra = 0;
+    } else if vco_fra > pll_ref_khz * 127 / 64 {
+        vco_fra = 0;
+        nint += 1;
+    } else if vco_fra > pll_ref_khz * 127 / 128 && vco_fra < pll_ref_khz {
+        vco_fra = pll_ref_khz * 127 / 128;
+    } else if vco_fra > pll_ref_khz && vco_fra < pll_ref_khz * 129 / 128 {
+        vco_fra = pll_ref_khz * 129 / 128;
+    }
+
+    // 16 bit sigma-delta fraction, found by successive approximation.
+    let mut n_sdm: u64 = 2;
+    let mut sdm: u64 = 0;
+    while vco_fra > 1 && n_sdm <= 0x8000 {
+        if vco_fra > 2 * pll_ref_khz / n_sdm {
+            sdm += 32768 / (n_sdm / 2);
+            vco_fra -= 2 * pll_ref_khz / n_sdm;
+        }
+        n_sdm <<= 1;
+    }
+
+    let vco = 2.0 * pll_ref as f64 * (nint as f64 + sdm as f64 / 65536.0);
+    vco / mix_div as f64
+}
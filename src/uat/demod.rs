@@ -0,0 +1,229 @@
+// UAT demodulation for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// UAT is continuous phase FSK at 1.041667Mbit/s, a one being a shift up in
+// frequency and a zero a shift down. At 2.083334MS/s there are two samples
+// per bit, so each bit is sliced from the phase change between its two
+// samples. Frames start with a 36 bit sync word, which differs between
+// downlink (aircraft) and uplink (ground station) frames, followed by the
+// Reed-Solomon coded payload.
+
+use crate::samples::IqPairs;
+use super::rs::ReedSolomon;
+
+/// Sample rate the demodulator expects (in Hz).
+pub const SAMPLE_RATE: u32 = 2_083_334;
+
+/// Bits in each sync word.
+const SYNC_BITS: usize = 36;
+
+/// Sync word starting downlink ADS-B frames.
+const DOWNLINK_SYNC: u64 = 0xeacdda4e2;
+
+/// Sync word starting uplink frames; the complement of the downlink one.
+const UPLINK_SYNC: u64 = 0x153225b1d;
+
+/// Sync bits which may be wrong before a sync word isn't recognised.
+const MAX_SYNC_ERRORS: u32 = 4;
+
+/// Payload and coded length of basic downlink frames (in bytes).
+pub const BASIC_DATA: usize = 18;
+const BASIC_CODED: usize = 30;
+
+/// Payload and coded length of long downlink frames (in bytes).
+pub const LONG_DATA: usize = 34;
+const LONG_CODED: usize = 48;
+
+/// Uplink frames are interleaved from blocks of this many bytes, each coded
+/// from this many payload bytes.
+const UPLINK_BLOCKS: usize = 6;
+const UPLINK_BLOCK_CODED: usize = 92;
+const UPLINK_BLOCK_DATA: usize = 72;
+
+/// Payload and coded length of uplink frames (in bytes).
+pub const UPLINK_DATA: usize = UPLINK_BLOCKS * UPLINK_BLOCK_DATA;
+const UPLINK_CODED: usize = UPLINK_BLOCKS * UPLINK_BLOCK_CODED;
+
+/// Samples needed from the start of a sync word to the end of the longest
+/// frame, plus one for the last phase difference.
+const SPAN: usize = 2 * (SYNC_BITS + 8 * UPLINK_CODED) + 1;
+
+/// Kind of frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameType {
+    /// Basic ADS-B downlink, with an 18 byte payload.
+    Basic,
+    /// Long ADS-B downlink, with a 34 byte payload.
+    Long,
+    /// Ground station uplink, with a 432 byte payload.
+    Uplink
+}
+
+/// A received frame, after error correction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub kind: FrameType,
+    /// The payload, without parity.
+    pub data: Vec<u8>,
+    /// Index of the first sample of the sync word, counted from the first
+    /// sample processed.
+    pub sample: u64,
+    /// Bytes repaired by the Reed-Solomon code.
+    pub corrected: usize
+}
+
+/// Finds and decodes UAT frames in 2.083334MS/s I/Q, keeping state between
+/// blocks.
+pub struct Demodulator {
+    /// Phase of I + jQ, as a fraction of a turn scaled to 65536, indexed by
+    /// I << 8 | Q.
+    phase: Vec<u16>,
+    /// Samples not yet searched, as phases.
+    pending: Vec<u16>,
+    /// Index of the first pending sample since the start.
+    sample: u64,
+    iq: IqPairs,
+    basic: ReedSolomon,
+    long: ReedSolomon,
+    uplink: ReedSolomon
+}
+
+impl Demodulator {
+    pub fn new() -> Demodulator {
+        let phase = (0..65536u32).map(|x| {
+            let i = (x >> 8) as f64 - 127.5;
+            let q = (x & 0xff) as f64 - 127.5;
+            let turns = q.atan2(i) / (2.0 * std::f64::consts::PI);
+            (turns * 65536.0).round() as i64 as u16
+        }).collect();
+        Demodulator {
+            phase, pending: Vec::new(), sample: 0, iq: IqPairs::new(),
+            basic: ReedSolomon::new(BASIC_CODED - BASIC_DATA),
+            long: ReedSolomon::new(LONG_CODED - LONG_DATA),
+            uplink: ReedSolomon::new(UPLINK_BLOCK_CODED - UPLINK_BLOCK_DATA)
+        }
+    }
+
+    /// Forget any partial frame and restart the sample count.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.sample = 0;
+        self.iq.reset();
+    }
+
+    /// Process interleaved u8 I/Q, returning the frames found.
+    pub fn process_u8(&mut self, buf: &[u8]) -> Vec<Frame> {
+        for (i, q) in self.iq.pairs(buf) {
+            let phase = self.phase(i, q);
+            self.pending.push(phase);
+        }
+        self.search()
+    }
+
+    fn phase(&self, i: u8, q: u8) -> u16 {
+        self.phase[(i as usize) << 8 | q as usize]
+    }
+
+    /// Search the pending samples, keeping any too close to the end to
+    /// hold a whole frame.
+    fn search(&mut self) -> Vec<Frame> {
+        let p = std::mem::take(&mut self.pending);
+        let mut frames = Vec::new();
+        let mut j = 0;
+        while j + SPAN <= p.len() {
+            match self.try_frame(&p[j..j + SPAN]) {
+                Some((mut frame, len)) => {
+                    frame.sample = self.sample + j as u64;
+                    frames.push(frame);
+                    j += len;
+                },
+                None => j += 1
+            }
+        }
+        self.sample += j as u64;
+        self.pending = p[j..].to_vec();
+        frames
+    }
+
+    /// Check for a sync word at the start of `p` and decode the frame after
+    /// it, returning it and the samples it occupies.
+    fn try_frame(&self, p: &[u16]) -> Option<(Frame, usize)> {
+        let dphi = |k: usize| p[2 * k + 1].wrapping_sub(p[2 * k]) as i16;
+        let sync = (0..SYNC_BITS).fold(0u64, |s, k| s << 1 | (dphi(k) > 0)
+                                                             as u64);
+        let uplink = if (sync ^ DOWNLINK_SYNC).count_ones() <=
+                        MAX_SYNC_ERRORS {
+            false
+        } else if (sync ^ UPLINK_SYNC).count_ones() <= MAX_SYNC_ERRORS {
+            true
+        } else {
+            return None;
+        };
+
+        // Slice about the midpoint of the ones and zeros in the sync word,
+        // which takes out any frequency offset.
+        let pattern = if uplink { UPLINK_SYNC } else { DOWNLINK_SYNC };
+        let (mut ones, mut zeros) = (0i64, 0i64);
+        for k in 0..SYNC_BITS {
+            if pattern >> (SYNC_BITS - 1 - k) & 1 != 0 {
+                ones += dphi(k) as i64;
+            } else {
+                zeros += dphi(k) as i64;
+            }
+        }
+        let count = pattern.count_ones() as i64;
+        let center = (ones / count + zeros / (SYNC_BITS as i64 - count)) / 2;
+        if ones / count <= zeros / (SYNC_BITS as i64 - count) {
+            return None;
+        }
+        let byte = |n: usize| (0..8).fold(0u8, |b, k| {
+            let bit = SYNC_BITS + 8 * n + k;
+            b << 1 | (dphi(bit) as i64 > center) as u8
+        });
+
+        if uplink {
+            let coded: Vec<u8> = (0..UPLINK_CODED).map(byte).collect();
+            let mut data = Vec::with_capacity(UPLINK_DATA);
+            let mut corrected = 0;
+            for b in 0..UPLINK_BLOCKS {
+                let mut block: Vec<u8> = (0..UPLINK_BLOCK_CODED).map(|k| {
+                    coded[k * UPLINK_BLOCKS + b]
+                }).collect();
+                corrected += self.uplink.decode(&mut block)?;
+                data.extend_from_slice(&block[..UPLINK_BLOCK_DATA]);
+            }
+            let frame = Frame { kind: FrameType::Uplink, data, sample: 0,
+                                corrected };
+            return Some((frame, 2 * (SYNC_BITS + 8 * UPLINK_CODED)));
+        }
+
+        // The payload type, in the first five bits, is zero only in basic
+        // frames, which tells the two lengths apart.
+        let coded: Vec<u8> = (0..LONG_CODED).map(byte).collect();
+        let mut long = coded.clone();
+        if let Some(corrected) = self.long.decode(&mut long)
+           && long[0] >> 3 != 0 {
+            long.truncate(LONG_DATA);
+            let frame = Frame { kind: FrameType::Long, data: long, sample: 0,
+                                corrected };
+            return Some((frame, 2 * (SYNC_BITS + 8 * LONG_CODED)));
+        }
+        let mut basic = coded[..BASIC_CODED].to_vec();
+        match self.basic.decode(&mut basic) {
+            Some(corrected) if basic[0] >> 3 == 0 => {
+                basic.truncate(BASIC_DATA);
+                let frame = Frame { kind: FrameType::Basic, data: basic,
+                                    sample: 0, corrected };
+                Some((frame, 2 * (SYNC_BITS + 8 * BASIC_CODED)))
+            },
+            _ => None
+        }
+    }
+}
+
+impl Default for Demodulator {
+    fn default() -> Demodulator {
+        Demodulator::new()
+    }
+}
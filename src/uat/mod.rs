@@ -0,0 +1,390 @@
+// UAT for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Universal Access Transceiver is the 978MHz datalink used by general
+// aviation in the US. Aircraft send ADS-B downlink frames with their state
+// vector and, in some frames, mode status; ground stations send uplink
+// frames carrying FIS-B weather and information products. Sample at
+// 2.083334MS/s and feed the Demodulator, then decode its frames.
+
+pub mod demod;
+pub mod rs;
+
+use crate::bitfield::bits;
+use self::demod::{Frame, FrameType};
+
+/// Frequency of UAT (in Hz).
+pub const FREQUENCY: u64 = 978_000_000;
+
+/// Characters of the base 40 callsign alphabet; '.' is unused.
+const BASE40: &[u8; 40] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ  ..";
+
+/// Decode a 24 bit latitude and longitude pair (in degrees).
+fn position(data: &[u8], start: usize) -> (f64, f64) {
+    let scale = 360.0 / 16_777_216.0;
+    let mut lat = bits(data, start, 23) as f64 * scale;
+    let mut lon = bits(data, start + 23, 24) as f64 * scale;
+    if lat > 90.0 {
+        lat -= 180.0;
+    }
+    if lon > 180.0 {
+        lon -= 360.0;
+    }
+    (lat, lon)
+}
+
+/// Decode a 12 bit altitude field (in feet).
+fn altitude(raw: u32) -> Option<i32> {
+    if raw == 0 { None } else { Some((raw as i32 - 1) * 25 - 1000) }
+}
+
+/// Decode an 11 bit signed speed field, a sign bit and 10 bits of
+/// magnitude plus one (in knots).
+fn speed(raw: u32) -> Option<i32> {
+    match raw & 0x3ff {
+        0 => None,
+        v if raw & 0x400 != 0 => Some(1 - v as i32),
+        v => Some(v as i32 - 1)
+    }
+}
+
+/// Whether the aircraft is airborne, and so which velocity fields the state
+/// vector has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AirGround {
+    Subsonic,
+    /// Airborne, with velocities in units of 4 knots.
+    Supersonic,
+    OnGround,
+    Reserved
+}
+
+/// Direction of movement or pointing (in degrees).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Track over the ground from true north.
+    Track(f64),
+    /// Heading from magnetic north.
+    MagneticHeading(f64),
+    /// Heading from true north.
+    TrueHeading(f64)
+}
+
+/// The state vector in every ADS-B frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StateVector {
+    /// Latitude and longitude (in degrees).
+    pub position: Option<(f64, f64)>,
+    /// Navigation integrity category.
+    pub nic: u8,
+    /// Altitude (in feet).
+    pub altitude: Option<i32>,
+    /// The altitude is geometric rather than barometric.
+    pub geometric: bool,
+    pub air_ground: AirGround,
+    /// Northward and eastward velocity (in knots), when airborne.
+    pub north_velocity: Option<i32>,
+    pub east_velocity: Option<i32>,
+    /// Ground speed (in knots).
+    pub ground_speed: Option<f64>,
+    pub direction: Option<Direction>,
+    /// Climb rate (in feet per minute), when airborne.
+    pub vertical_rate: Option<i32>,
+    /// The climb rate is geometric rather than barometric.
+    pub geometric_rate: bool
+}
+
+/// Mode status, in ADS-B frames of payload types 1 and 3.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModeStatus {
+    /// Emitter category, 0 to 39.
+    pub category: u8,
+    /// Callsign, or the squawk code if `squawk` is set.
+    pub callsign: String,
+    pub squawk: bool,
+    /// Emergency or priority status, 0 for none.
+    pub emergency: u8,
+    /// UAT MOPS version.
+    pub version: u8,
+    /// Source integrity level.
+    pub sil: u8,
+    /// Navigation accuracy categories for position and velocity.
+    pub nac_p: u8,
+    pub nac_v: u8,
+    /// Barometric altitude integrity code.
+    pub nic_baro: bool,
+    /// Capability and operational mode flags: CDTI, ACAS, an active ACAS
+    /// resolution advisory, IDENT and ATC services.
+    pub cdti: bool,
+    pub acas: bool,
+    pub acas_ra: bool,
+    pub ident: bool,
+    pub atc_services: bool,
+    /// Headings are magnetic rather than true.
+    pub magnetic_heading: bool
+}
+
+/// An ADS-B downlink message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdsbMessage {
+    /// Payload type, 0 for basic frames.
+    pub payload_type: u8,
+    /// Address qualifier: 0 for an ICAO address, others for TIS-B, ADS-R
+    /// and anonymous addresses.
+    pub address_qualifier: u8,
+    /// 24 bit address.
+    pub address: u32,
+    pub state_vector: StateVector,
+    pub mode_status: Option<ModeStatus>,
+    /// Altitude of the other type to the state vector's (in feet), from
+    /// the auxiliary state vector.
+    pub secondary_altitude: Option<i32>
+}
+
+/// Time given in a FIS-B APDU header; which fields are present depends on
+/// the time option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ApduTime {
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: Option<u8>
+}
+
+/// A FIS-B application protocol data unit, carrying one product.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Apdu {
+    pub product_id: u16,
+    /// Application method flags: A, G and P.
+    pub a_flag: bool,
+    pub g_flag: bool,
+    pub p_flag: bool,
+    /// The product is split across several APDUs.
+    pub segmented: bool,
+    pub time: ApduTime,
+    /// Product data after the header.
+    pub payload: Vec<u8>
+}
+
+/// An information frame in an uplink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InfoFrame {
+    /// Frame type, 0 for FIS-B APDUs.
+    pub frame_type: u8,
+    pub data: Vec<u8>,
+    /// The APDU, for frames of type 0 long enough to hold its header.
+    pub apdu: Option<Apdu>
+}
+
+/// A ground station uplink message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UplinkMessage {
+    /// Position of the ground station (in degrees), if given.
+    pub position: Option<(f64, f64)>,
+    /// Time is coupled to UTC.
+    pub utc_coupled: bool,
+    /// Slot the station transmits in.
+    pub slot_id: u8,
+    /// TIS-B site identifier.
+    pub tisb_site_id: u8,
+    /// Information frames, if the application data is valid.
+    pub info_frames: Vec<InfoFrame>
+}
+
+/// A decoded UAT message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Adsb(AdsbMessage),
+    Uplink(UplinkMessage)
+}
+
+/// Decode the state vector of a downlink payload.
+fn decode_state_vector(d: &[u8]) -> StateVector {
+    let nic = d[11] & 0x0f;
+    let (lat, lon) = (bits(d, 32, 23), bits(d, 55, 24));
+    let air_ground = match d[12] >> 6 {
+        0 => AirGround::Subsonic,
+        1 => AirGround::Supersonic,
+        2 => AirGround::OnGround,
+        _ => AirGround::Reserved
+    };
+    let mut sv = StateVector {
+        position: if nic != 0 || lat != 0 || lon != 0 {
+            Some(position(d, 32))
+        } else {
+            None
+        },
+        nic,
+        altitude: altitude(bits(d, 80, 12)),
+        geometric: bits(d, 79, 1) != 0,
+        air_ground,
+        north_velocity: None,
+        east_velocity: None,
+        ground_speed: None,
+        direction: None,
+        vertical_rate: None,
+        geometric_rate: false
+    };
+    let (a, b) = (bits(d, 99, 11), bits(d, 110, 11));
+    match air_ground {
+        AirGround::Subsonic | AirGround::Supersonic => {
+            let scale = if air_ground == AirGround::Supersonic { 4 } else { 1 };
+            sv.north_velocity = speed(a).map(|v| v * scale);
+            sv.east_velocity = speed(b).map(|v| v * scale);
+            if let (Some(n), Some(e)) = (sv.north_velocity, sv.east_velocity) {
+                let (n, e) = (n as f64, e as f64);
+                sv.ground_speed = Some(n.hypot(e));
+                if n != 0.0 || e != 0.0 {
+                    let track = e.atan2(n).to_degrees();
+                    sv.direction = Some(Direction::Track(
+                        if track < 0.0 { track + 360.0 } else { track }));
+                }
+            }
+            let rate = bits(d, 121, 11);
+            if rate & 0x1ff != 0 {
+                let v = ((rate & 0x1ff) as i32 - 1) * 64;
+                sv.vertical_rate = Some(if rate & 0x200 != 0 { -v } else { v });
+                sv.geometric_rate = rate & 0x400 == 0;
+            }
+        },
+        AirGround::OnGround => {
+            if a & 0x3ff != 0 {
+                sv.ground_speed = Some(((a & 0x3ff) - 1) as f64);
+            }
+            let angle = (b & 0x1ff) as f64 * 360.0 / 512.0;
+            sv.direction = match b >> 9 & 3 {
+                1 => Some(Direction::Track(angle)),
+                2 => Some(Direction::MagneticHeading(angle)),
+                3 => Some(Direction::TrueHeading(angle)),
+                _ => None
+            };
+        },
+        AirGround::Reserved => ()
+    }
+    sv
+}
+
+/// Decode the mode status of a long downlink payload.
+fn decode_mode_status(d: &[u8]) -> ModeStatus {
+    let mut chars = Vec::with_capacity(9);
+    for k in 0..3 {
+        let v = (d[17 + 2 * k] as u32) << 8 | d[18 + 2 * k] as u32;
+        chars.extend([v / 1600 % 40, v / 40 % 40, v % 40]);
+    }
+    // The first character is the emitter category.
+    let callsign: String = chars[1..].iter()
+                                     .map(|&c| BASE40[c as usize] as char)
+                                     .collect();
+    ModeStatus {
+        category: chars[0] as u8,
+        callsign: callsign.trim_end().to_string(),
+        squawk: d[26] & 0x02 == 0,
+        emergency: d[23] >> 5,
+        version: d[23] >> 2 & 7,
+        sil: d[23] & 3,
+        nac_p: d[25] >> 4,
+        nac_v: d[25] >> 1 & 7,
+        nic_baro: d[25] & 1 != 0,
+        cdti: d[26] & 0x80 != 0,
+        acas: d[26] & 0x40 != 0,
+        acas_ra: d[26] & 0x20 != 0,
+        ident: d[26] & 0x10 != 0,
+        atc_services: d[26] & 0x08 != 0,
+        magnetic_heading: d[26] & 0x04 != 0
+    }
+}
+
+/// Decode a downlink payload.
+fn decode_adsb(d: &[u8]) -> AdsbMessage {
+    let payload_type = d[0] >> 3;
+    let long = d.len() >= demod::LONG_DATA;
+    AdsbMessage {
+        payload_type,
+        address_qualifier: d[0] & 7,
+        address: bits(d, 8, 24),
+        state_vector: decode_state_vector(d),
+        mode_status: match payload_type {
+            1 | 3 if long => Some(decode_mode_status(d)),
+            _ => None
+        },
+        secondary_altitude: match payload_type {
+            1 | 2 | 5 | 6 if long => altitude(bits(d, 232, 12)),
+            _ => None
+        }
+    }
+}
+
+/// Decode the header of a FIS-B APDU.
+fn decode_apdu(d: &[u8]) -> Option<Apdu> {
+    let option = bits(d, 15, 2);
+    let header = match option { 0 => 4, 1 | 2 => 5, _ => 6 };
+    if d.len() < header {
+        return None;
+    }
+    let mut time = ApduTime::default();
+    let mut at = 17;
+    let mut field = |len: usize| {
+        let v = bits(d, at, len) as u8;
+        at += len;
+        v
+    };
+    if option >= 2 {
+        time.month = Some(field(4));
+        time.day = Some(field(5));
+    }
+    time.hours = field(5);
+    time.minutes = field(6);
+    if option & 1 != 0 {
+        time.seconds = Some(field(6));
+    }
+    Some(Apdu {
+        product_id: bits(d, 3, 11) as u16,
+        a_flag: d[0] & 0x80 != 0,
+        g_flag: d[0] & 0x40 != 0,
+        p_flag: d[0] & 0x20 != 0,
+        segmented: d[1] & 0x02 != 0,
+        time,
+        payload: d[header..].to_vec()
+    })
+}
+
+/// Decode an uplink payload.
+fn decode_uplink(d: &[u8]) -> UplinkMessage {
+    let mut info_frames = Vec::new();
+    if d[6] & 0x20 != 0 {
+        // Application data is a sequence of information frames, each with
+        // a 9 bit length and 4 bit type, ending with an empty frame of
+        // type 0.
+        let app = &d[8..];
+        let mut p = 0;
+        while p + 2 <= app.len() {
+            let len = bits(app, 8 * p, 9) as usize;
+            let frame_type = app[p + 1] & 0x0f;
+            if (len == 0 && frame_type == 0) || p + 2 + len > app.len() {
+                break;
+            }
+            let data = app[p + 2..p + 2 + len].to_vec();
+            let apdu = if frame_type == 0 { decode_apdu(&data) } else { None };
+            info_frames.push(InfoFrame { frame_type, data, apdu });
+            p += 2 + len;
+        }
+    }
+    UplinkMessage {
+        position: if d[5] & 1 != 0 { Some(position(d, 0)) } else { None },
+        utc_coupled: d[6] & 0x80 != 0,
+        slot_id: d[6] & 0x1f,
+        tisb_site_id: d[7] >> 4,
+        info_frames
+    }
+}
+
+/// Decode a frame from the Demodulator.
+pub fn decode(frame: &Frame) -> Message {
+    match frame.kind {
+        FrameType::Basic | FrameType::Long =>
+            Message::Adsb(decode_adsb(&frame.data)),
+        FrameType::Uplink => Message::Uplink(decode_uplink(&frame.data))
+    }
+}
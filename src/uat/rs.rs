@@ -0,0 +1,259 @@
+// Reed-Solomon coding for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// UAT protects every frame with a shortened Reed-Solomon code over GF(256),
+// with field polynomial x^8 + x^7 + x^2 + x + 1 and generator roots
+// starting at alpha^120. Decoding finds the error locator with
+// Berlekamp-Massey, its roots with a Chien search, and the error values
+// with Forney's algorithm, as in Phil Karn's well known implementation.
+
+/// Field generator polynomial.
+const GF_POLY: u32 = 0x187;
+
+/// Number of non-zero field elements, and the longest unshortened block.
+const NN: usize = 255;
+
+/// Log of zero, standing in for minus infinity.
+const A0: usize = NN;
+
+/// First consecutive root of the generator, as a power of alpha.
+const FCR: usize = 120;
+
+/// alpha^i for each i, with alpha^A0 = 0.
+const ALPHA_TO: [u8; 256] = make_tables().0;
+
+/// log_alpha(x) for each x, with log(0) = A0.
+const INDEX_OF: [usize; 256] = make_tables().1;
+
+const fn make_tables() -> ([u8; 256], [usize; 256]) {
+    let mut alpha_to = [0u8; 256];
+    let mut index_of = [0usize; 256];
+    index_of[0] = A0;
+    let mut sr = 1u32;
+    let mut i = 0;
+    while i < NN {
+        index_of[sr as usize] = i;
+        alpha_to[i] = sr as u8;
+        sr <<= 1;
+        if sr & 0x100 != 0 {
+            sr ^= GF_POLY;
+        }
+        sr &= 0xff;
+        i += 1;
+    }
+    (alpha_to, index_of)
+}
+
+fn modnn(x: usize) -> usize {
+    x % NN
+}
+
+/// A shortened Reed-Solomon code with a fixed number of parity bytes.
+pub struct ReedSolomon {
+    nroots: usize,
+    /// Generator polynomial coefficients in log form, lowest power first.
+    genpoly: Vec<usize>
+}
+
+impl ReedSolomon {
+    /// A code with `nroots` parity bytes, correcting up to half that many
+    /// byte errors.
+    pub fn new(nroots: usize) -> ReedSolomon {
+        let mut genpoly = vec![0u8; nroots + 1];
+        genpoly[0] = 1;
+        for i in 0..nroots {
+            let root = FCR + i;
+            genpoly[i + 1] = 1;
+            for j in (1..=i).rev() {
+                genpoly[j] = if genpoly[j] != 0 {
+                    genpoly[j - 1] ^
+                        ALPHA_TO[modnn(INDEX_OF[genpoly[j] as usize] + root)]
+                } else {
+                    genpoly[j - 1]
+                };
+            }
+            genpoly[0] = ALPHA_TO[modnn(INDEX_OF[genpoly[0] as usize] + root)];
+        }
+        let genpoly = genpoly.iter().map(|&g| INDEX_OF[g as usize]).collect();
+        ReedSolomon { nroots, genpoly }
+    }
+
+    /// Number of parity bytes.
+    pub fn parity_len(&self) -> usize {
+        self.nroots
+    }
+
+    /// Compute the parity bytes for `data`, which with them must be no
+    /// longer than 255 bytes.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        let n = self.nroots;
+        let mut parity = vec![0u8; n];
+        for &d in data {
+            let feedback = INDEX_OF[(d ^ parity[0]) as usize];
+            if feedback != A0 {
+                for j in 1..n {
+                    parity[j] ^= ALPHA_TO[modnn(feedback +
+                                                self.genpoly[n - j])];
+                }
+            }
+            parity.rotate_left(1);
+            parity[n - 1] = if feedback != A0 {
+                ALPHA_TO[modnn(feedback + self.genpoly[0])]
+            } else {
+                0
+            };
+        }
+        parity
+    }
+
+    /// Correct `block`, data followed by parity, in place. Returns the
+    /// number of bytes corrected, or None if there are too many errors.
+    pub fn decode(&self, block: &mut [u8]) -> Option<usize> {
+        let n = self.nroots;
+        if block.len() > NN || block.len() <= n {
+            return None;
+        }
+        let pad = NN - block.len();
+
+        // Evaluate the block at each root of the generator.
+        let mut s = vec![block[0] as usize; n];
+        for &b in &block[1..] {
+            for (i, si) in s.iter_mut().enumerate() {
+                *si = if *si == 0 {
+                    b as usize
+                } else {
+                    (b ^ ALPHA_TO[modnn(INDEX_OF[*si] + FCR + i)]) as usize
+                };
+            }
+        }
+        if s.iter().all(|&x| x == 0) {
+            return Some(0);
+        }
+        let s: Vec<usize> = s.iter().map(|&x| INDEX_OF[x]).collect();
+
+        // Berlekamp-Massey for the error locator polynomial lambda.
+        let mut lambda = vec![0u8; n + 1];
+        lambda[0] = 1;
+        let mut b: Vec<usize> = lambda.iter().map(|&l| INDEX_OF[l as usize])
+                                      .collect();
+        let mut t = vec![0u8; n + 1];
+        let mut el = 0;
+        for r in 1..=n {
+            let mut discr = 0u8;
+            for i in 0..r {
+                if lambda[i] != 0 && s[r - i - 1] != A0 {
+                    discr ^= ALPHA_TO[modnn(INDEX_OF[lambda[i] as usize] +
+                                            s[r - i - 1])];
+                }
+            }
+            let discr = INDEX_OF[discr as usize];
+            if discr == A0 {
+                b.rotate_right(1);
+                b[0] = A0;
+                continue;
+            }
+            t[0] = lambda[0];
+            for i in 0..n {
+                t[i + 1] = if b[i] != A0 {
+                    lambda[i + 1] ^ ALPHA_TO[modnn(discr + b[i])]
+                } else {
+                    lambda[i + 1]
+                };
+            }
+            if 2 * el < r {
+                el = r - el;
+                for (bi, &l) in b.iter_mut().zip(&lambda) {
+                    *bi = if l == 0 {
+                        A0
+                    } else {
+                        modnn(INDEX_OF[l as usize] + NN - discr)
+                    };
+                }
+            } else {
+                b.rotate_right(1);
+                b[0] = A0;
+            }
+            lambda.copy_from_slice(&t);
+        }
+        let lambda: Vec<usize> = lambda.iter().map(|&l| INDEX_OF[l as usize])
+                                       .collect();
+        let deg_lambda = lambda.iter().rposition(|&l| l != A0).unwrap_or(0);
+        if deg_lambda == 0 {
+            return None;
+        }
+
+        // Chien search for the roots of lambda, which locate the errors.
+        let mut reg = lambda.clone();
+        let mut roots = Vec::with_capacity(deg_lambda);
+        let mut locs = Vec::with_capacity(deg_lambda);
+        for i in 1..=NN {
+            let mut q = 1u8;
+            for (j, rj) in reg.iter_mut().enumerate().skip(1)
+                              .take(deg_lambda) {
+                if *rj != A0 {
+                    *rj = modnn(*rj + j);
+                    q ^= ALPHA_TO[*rj];
+                }
+            }
+            if q == 0 {
+                roots.push(i);
+                locs.push(i - 1);
+                if roots.len() == deg_lambda {
+                    break;
+                }
+            }
+        }
+        if roots.len() != deg_lambda {
+            return None;
+        }
+
+        // The error evaluator omega = s * lambda mod x^nroots.
+        let deg_omega = deg_lambda - 1;
+        let omega: Vec<usize> = (0..=deg_omega).map(|i| {
+            let mut tmp = 0u8;
+            for j in 0..=i {
+                if s[i - j] != A0 && lambda[j] != A0 {
+                    tmp ^= ALPHA_TO[modnn(s[i - j] + lambda[j])];
+                }
+            }
+            INDEX_OF[tmp as usize]
+        }).collect();
+
+        // Forney: each error value is omega over the derivative of lambda
+        // at the inverse of its location.
+        for (&root, &loc) in roots.iter().zip(&locs) {
+            if loc < pad {
+                // An error in the padding means the block is too damaged.
+                return None;
+            }
+            let mut num1 = 0u8;
+            for (i, &o) in omega.iter().enumerate() {
+                if o != A0 {
+                    num1 ^= ALPHA_TO[modnn(o + i * root)];
+                }
+            }
+            let num2 = ALPHA_TO[modnn(root * (FCR - 1))];
+            let mut den = 0u8;
+            let mut i = deg_lambda.min(n - 1) & !1;
+            loop {
+                if lambda[i + 1] != A0 {
+                    den ^= ALPHA_TO[modnn(lambda[i + 1] + i * root)];
+                }
+                if i < 2 {
+                    break;
+                }
+                i -= 2;
+            }
+            if den == 0 {
+                return None;
+            }
+            if num1 != 0 {
+                block[loc - pad] ^= ALPHA_TO[modnn(
+                    INDEX_OF[num1 as usize] + INDEX_OF[num2 as usize] + NN -
+                    INDEX_OF[den as usize])];
+            }
+        }
+        Some(deg_lambda)
+    }
+}
@@ -0,0 +1,378 @@
+// Waterfall images for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+//
+// Each row of the waterfall is the averaged spectrum of a fixed length of
+// input, newest at the bottom, coloured by level and optionally framed with
+// frequency and time axes and a colour scale.
+
+use std::io::Write;
+use std::time::SystemTime;
+
+use super::RTLSDRError;
+use super::dsp::Complex;
+use super::dsp::window::Window;
+use super::png::Image;
+use super::samples::iq_from_u8;
+use super::source::Source;
+use super::spectrum::{SpectrumAnalyzer, SpectrumConfig};
+use super::timestamp::UtcTime;
+
+/// Colour maps from low to high level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Grayscale, Viridis, Inferno, Jet
+}
+
+impl Colormap {
+    /// Look up a colour map by its lower case name.
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        match name {
+            "gray" | "grey" | "grayscale" => Some(Colormap::Grayscale),
+            "viridis" => Some(Colormap::Viridis),
+            "inferno" => Some(Colormap::Inferno),
+            "jet" => Some(Colormap::Jet),
+            _ => None
+        }
+    }
+
+    /// Colour for `x` from 0 to 1, clamping anything outside.
+    pub fn colour(&self, x: f64) -> [u8; 3] {
+        let points: &[u32] = match *self {
+            Colormap::Grayscale => &[0x000000, 0xffffff],
+            Colormap::Viridis => &[0x440154, 0x482878, 0x3e4989, 0x31688e,
+                                   0x26828e, 0x1f9e89, 0x35b779, 0x6ece58,
+                                   0xb5de2b, 0xfde725],
+            Colormap::Inferno => &[0x000004, 0x1b0c41, 0x4a0c6b, 0x781c6d,
+                                   0xa52c60, 0xcf4446, 0xed6925, 0xfb9b06,
+                                   0xf7d13d, 0xfcffa4],
+            Colormap::Jet => &[0x00007f, 0x0000ff, 0x007fff, 0x00ffff,
+                               0x7fff7f, 0xffff00, 0xff7f00, 0xff0000,
+                               0x7f0000]
+        };
+        let x = if x.is_nan() { 0.0 } else { x.clamp(0.0, 1.0) };
+        let pos = x * (points.len() - 1) as f64;
+        let i = (pos as usize).min(points.len() - 2);
+        let t = pos - i as f64;
+        let mut out = [0u8; 3];
+        for (c, o) in out.iter_mut().enumerate() {
+            let shift = 16 - 8 * c;
+            let a = ((points[i] >> shift) & 0xff) as f64;
+            let b = ((points[i + 1] >> shift) & 0xff) as f64;
+            *o = (a + t * (b - a)).round() as u8;
+        }
+        out
+    }
+}
+
+/// Settings for a Waterfall.
+#[derive(Clone, Copy, Debug)]
+pub struct WaterfallConfig {
+    /// FFT size, and so the width of the plot in pixels. Must be a power of
+    /// two.
+    pub fft_size: usize,
+    /// Window applied before each FFT.
+    pub window: Window,
+    /// Length of input summarised by each row (in seconds). At least one
+    /// FFT's worth is always used.
+    pub row_time: f64,
+    pub colormap: Colormap,
+    /// Level (in dBFS) drawn at the bottom of the colour map, or None to
+    /// choose from the data.
+    pub min_db: Option<f64>,
+    /// Level (in dBFS) drawn at the top of the colour map, or None to choose
+    /// from the data.
+    pub max_db: Option<f64>,
+    /// Whether to draw axes, labels and a colour scale around the plot.
+    pub labels: bool
+}
+
+impl WaterfallConfig {
+    /// `fft_size` bins, 0.1s per row, Viridis, automatic range, labelled.
+    pub fn new(fft_size: usize) -> WaterfallConfig {
+        WaterfallConfig {
+            fft_size,
+            window: Window::Hann,
+            row_time: 0.1,
+            colormap: Colormap::Viridis,
+            min_db: None,
+            max_db: None,
+            labels: true
+        }
+    }
+}
+
+const BACKGROUND: [u8; 3] = [0xff, 0xff, 0xff];
+const FOREGROUND: [u8; 3] = [0x00, 0x00, 0x00];
+const LEFT: usize = 48;
+const TOP: usize = 26;
+const RIGHT: usize = 56;
+const BOTTOM: usize = 6;
+/// Space taken by the frequency unit left of the axis.
+const UNIT: usize = 24;
+
+/// Builds a waterfall from a stream of samples.
+pub struct Waterfall {
+    config: WaterfallConfig,
+    analyzer: SpectrumAnalyzer,
+    rate: u32,
+    center: u64,
+    start: SystemTime,
+    row_len: usize,
+    pending: Vec<Complex>,
+    rows: Vec<Vec<f32>>
+}
+
+impl Waterfall {
+    /// Create a waterfall for samples at `rate` (in Hz) tuned to `center`
+    /// (in Hz), starting now.
+    pub fn new(config: WaterfallConfig, rate: u32, center: u64) -> Waterfall {
+        let mut spectrum = SpectrumConfig::new(config.fft_size);
+        spectrum.window = config.window;
+        let row_len = ((config.row_time * rate as f64).round() as usize)
+                      .max(config.fft_size);
+        Waterfall {
+            config,
+            analyzer: SpectrumAnalyzer::new(spectrum, rate as f64),
+            rate,
+            center,
+            start: SystemTime::now(),
+            row_len,
+            pending: Vec::new(),
+            rows: Vec::new()
+        }
+    }
+
+    /// Set the time of the first sample, used in the image header.
+    pub fn set_start_time(&mut self, time: SystemTime) {
+        self.start = time;
+    }
+
+    /// Time covered by each row (in seconds).
+    pub fn row_time(&self) -> f64 {
+        self.row_len as f64 / self.rate as f64
+    }
+
+    /// Rows so far, oldest first, each with the power in every bin (in
+    /// dBFS) from the most negative frequency up.
+    pub fn rows(&self) -> &[Vec<f32>] {
+        &self.rows
+    }
+
+    /// Add complex samples.
+    pub fn process(&mut self, samples: &[Complex]) {
+        self.pending.extend_from_slice(samples);
+        let mut start = 0;
+        while start + self.row_len <= self.pending.len() {
+            self.analyzer.reset();
+            self.analyzer.process(&self.pending[start..start + self.row_len]);
+            if let Some(psd) = self.analyzer.psd() {
+                self.rows.push(psd.power().iter().map(|&p| p as f32)
+                                  .collect());
+            }
+            start += self.row_len;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// Add interleaved unsigned 8 bit I/Q, as from read_sync.
+    pub fn process_u8(&mut self, buf: &[u8]) {
+        self.process(&iq_from_u8(buf));
+    }
+
+    /// Read `len` bytes from `source` and add them.
+    pub fn read<S: Source>(&mut self, source: &mut S, len: usize)
+                           -> Result<(), RTLSDRError> {
+        let buf = source.read_sync(len)?;
+        self.process_u8(&buf);
+        Ok(())
+    }
+
+    /// Level range (in dBFS) mapped onto the colour map.
+    pub fn range(&self) -> (f64, f64) {
+        let mut levels: Vec<f32> = self.rows.iter().flatten().cloned()
+                                       .collect();
+        levels.sort_by(|a, b| a.total_cmp(b));
+        let pick = |q: f64| match levels.len() {
+            0 => None,
+            n => Some(levels[((n - 1) as f64 * q) as usize] as f64)
+        };
+        // By default the noise floor sits just above the bottom of the map.
+        let low = self.config.min_db.or(pick(0.02)).unwrap_or(-100.0);
+        let high = self.config.max_db.or(pick(1.0)).unwrap_or(0.0);
+        if high > low { (low, high) } else { (low, low + 1.0) }
+    }
+
+    /// Draw the waterfall.
+    pub fn render(&self) -> Image {
+        let width = self.config.fft_size;
+        let height = self.rows.len();
+        let (x0, y0, w, h) = match self.config.labels {
+            true => {
+                let w = (LEFT + width + RIGHT)
+                        .max(LEFT + text_width(&self.header()) + 4);
+                (LEFT, TOP, w, TOP + height + BOTTOM)
+            },
+            false => (0, 0, width, height)
+        };
+        let mut img = Image::new(w, h, BACKGROUND);
+        let (low, high) = self.range();
+        let cmap = self.config.colormap;
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, &p) in row.iter().enumerate() {
+                let c = cmap.colour((p as f64 - low) / (high - low));
+                img.set(x0 + x, y0 + y, c);
+            }
+        }
+        if self.config.labels {
+            self.draw_axes(&mut img, low, high);
+        }
+        img
+    }
+
+    /// Draw the waterfall as PNG.
+    pub fn write_png<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        self.render().write_png(w)
+    }
+
+    fn header(&self) -> String {
+        format!("{}Hz {}S/s {}", si(self.center as f64, 6),
+                si(self.rate as f64, 3),
+                UtcTime::from_system(self.start).iso8601())
+    }
+
+    fn draw_axes(&self, img: &mut Image, low: f64, high: f64) {
+        let width = self.config.fft_size;
+        let height = self.rows.len();
+
+        draw_text(img, LEFT, 2, &self.header());
+
+        // Frequency axis along the top, in MHz.
+        let rate = self.rate as f64;
+        let first = self.center as f64 - rate / 2.0;
+        let step = nice_step(rate, width as f64 / 80.0);
+        let decimals = (-(step / 1e6).log10().floor()).clamp(0.0, 6.0);
+        let mut f = (first / step).ceil() * step;
+        while f < first + rate {
+            let x = LEFT + ((f - first) / rate * width as f64) as usize;
+            img.fill(x, TOP - 4, 1, 4, FOREGROUND);
+            let label = format!("{:.*}", decimals as usize, f / 1e6);
+            let lw = text_width(&label);
+            // Keep clear of the unit at the far left.
+            if x >= lw / 2 + UNIT && x + lw / 2 < img.width {
+                draw_text(img, x - lw / 2, TOP - 13, &label);
+            }
+            f += step;
+        }
+        draw_text(img, 2, TOP - 13, "MHz");
+
+        // Time axis down the left, in seconds from the start.
+        let row_time = self.row_time();
+        let total = height as f64 * row_time;
+        let step = nice_step(total, height as f64 / 40.0);
+        let decimals = (-step.log10().floor()).clamp(0.0, 3.0) as usize;
+        let mut t = 0.0;
+        while t < total {
+            let y = TOP + (t / row_time) as usize;
+            img.fill(LEFT - 4, y, 4, 1, FOREGROUND);
+            let label = format!("{:.*}s", decimals, t);
+            let x = (LEFT - 6).saturating_sub(text_width(&label));
+            draw_text(img, x, y.saturating_sub(3).max(TOP), &label);
+            t += step;
+        }
+
+        // Frame around the plot.
+        img.fill(LEFT - 1, TOP - 1, width + 2, 1, FOREGROUND);
+        img.fill(LEFT - 1, TOP + height, width + 2, 1, FOREGROUND);
+        img.fill(LEFT - 1, TOP - 1, 1, height + 2, FOREGROUND);
+        img.fill(LEFT + width, TOP - 1, 1, height + 2, FOREGROUND);
+
+        // Colour scale on the right, high at the top.
+        let bar = LEFT + width + 6;
+        for y in 0..height {
+            let x = 1.0 - y as f64 / (height.max(2) - 1) as f64;
+            img.fill(bar, TOP + y, 10, 1, self.config.colormap.colour(x));
+        }
+        draw_text(img, bar + 13, TOP, &format!("{:.0}", high));
+        if height >= 20 {
+            draw_text(img, bar + 13, TOP + height - 7,
+                      &format!("{:.0}", low));
+        }
+        if height >= 40 {
+            draw_text(img, bar + 13, TOP + height / 2 - 3, "dB");
+        }
+    }
+}
+
+/// Format `x` with an SI prefix and `decimals` decimal places.
+fn si(x: f64, decimals: usize) -> String {
+    let (scale, prefix) = match x.abs() {
+        a if a >= 1e9 => (1e9, "G"),
+        a if a >= 1e6 => (1e6, "M"),
+        a if a >= 1e3 => (1e3, "k"),
+        _ => (1.0, "")
+    };
+    format!("{:.*}{}", decimals, x / scale, prefix)
+}
+
+/// A round tick spacing (1, 2 or 5 times a power of ten) giving about
+/// `ticks` ticks over `span`.
+fn nice_step(span: f64, ticks: f64) -> f64 {
+    let raw = span / ticks.max(1.0);
+    let mag = 10f64.powf(raw.log10().floor());
+    match raw / mag {
+        n if n < 1.5 => mag,
+        n if n < 3.5 => 2.0 * mag,
+        n if n < 7.5 => 5.0 * mag,
+        _ => 10.0 * mag
+    }
+}
+
+/// 5x7 pixel glyphs, one byte per row with the leftmost pixel in bit 4.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '/' => [0x01, 0x02, 0x02, 0x04, 0x08, 0x08, 0x10],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        'd' => [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f],
+        'k' => [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12],
+        's' => [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e],
+        'z' => [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f],
+        _ => [0; 7]
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    (text.chars().count() * 6).saturating_sub(1)
+}
+
+fn draw_text(img: &mut Image, x: usize, y: usize, text: &str) {
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..5 {
+                if bits & (0x10 >> col) != 0 {
+                    img.set(x + i * 6 + col, y + row, FOREGROUND);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// WAV and raw PCM output for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::io::{Seek, SeekFrom, Write};
+
+/// Convert audio in the range -1 to +1 to signed 16 bit little endian PCM,
+/// clipping anything outside that range.
+pub fn to_pcm16(samples: &[f32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(samples.len() * 2);
+    for s in samples {
+        let v = (s * 32767.0).round().clamp(-32768.0, 32767.0) as i16;
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+/// Writes 16 bit PCM audio with a WAV header.
+///
+/// The header is written with unknown (maximum) lengths, as is usual when
+/// streaming to a pipe; for seekable outputs `finalize` fills them in.
+pub struct WavWriter<W: Write> {
+    w: W,
+    written: u64
+}
+
+impl<W: Write> WavWriter<W> {
+    /// Start a WAV stream at `rate` (in Hz) with `channels` interleaved
+    /// channels.
+    pub fn new(mut w: W, rate: u32, channels: u16)
+               -> std::io::Result<WavWriter<W>> {
+        let block = channels * 2;
+        w.write_all(b"RIFF")?;
+        w.write_all(&u32::MAX.to_le_bytes())?;
+        w.write_all(b"WAVEfmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        w.write_all(&1u16.to_le_bytes())?;
+        w.write_all(&channels.to_le_bytes())?;
+        w.write_all(&rate.to_le_bytes())?;
+        w.write_all(&(rate * block as u32).to_le_bytes())?;
+        w.write_all(&block.to_le_bytes())?;
+        w.write_all(&16u16.to_le_bytes())?;
+        w.write_all(b"data")?;
+        w.write_all(&u32::MAX.to_le_bytes())?;
+        Ok(WavWriter { w, written: 0 })
+    }
+
+    /// Append audio in the range -1 to +1.
+    pub fn write(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        let pcm = to_pcm16(samples);
+        self.w.write_all(&pcm)?;
+        self.written += pcm.len() as u64;
+        Ok(())
+    }
+
+    /// Bytes of audio written so far.
+    pub fn data_len(&self) -> u64 {
+        self.written
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Fill in the header lengths and return the underlying writer.
+    pub fn finalize(mut self) -> std::io::Result<W> {
+        let data = self.written.min(u32::MAX as u64 - 36) as u32;
+        self.w.seek(SeekFrom::Start(4))?;
+        self.w.write_all(&(data + 36).to_le_bytes())?;
+        self.w.seek(SeekFrom::Start(40))?;
+        self.w.write_all(&data.to_le_bytes())?;
+        self.w.seek(SeekFrom::End(0))?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
@@ -0,0 +1,170 @@
+// Tests of Mode S decoding for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::time::{Duration, SystemTime};
+
+use rtlsdr::adsb::{self, Content, Message, Velocity, crc, cpr};
+use rtlsdr::adsb::aircraft::AircraftTable;
+use rtlsdr::adsb::cpr::Cpr;
+use rtlsdr::adsb::demod::Frame;
+
+/// Reference messages from "The 1090MHz Riddle".
+const IDENT: &str = "8D4840D6202CC371C32CE0576098";
+const EVEN: &str = "8D40621D58C382D690C8AC2863A7";
+const ODD: &str = "8D40621D58C386435CC412692AD6";
+const GROUND_SPEED: &str = "8D485020994409940838175B284F";
+const AIRSPEED: &str = "8DA05F219B06B6AF189400CBC33F";
+const SURFACE_EVEN: &str = "8C4841753AAB238733C8CD4020B1";
+const SURFACE_ODD: &str = "8C4841753A8A35323FAEBDAC702D";
+const COMM_B: &str = "A0001838CA3E51F0A8000047A36A";
+
+fn frame(hex: &str) -> Frame {
+    let data = (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect();
+    Frame { data, timestamp: 0, signal: 0.25, corrected: None }
+}
+
+fn decode(hex: &str) -> Message {
+    adsb::decode(&frame(hex), |_| false).expect("not decoded")
+}
+
+fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+    (a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5
+}
+
+#[test]
+fn crc_reference() {
+    for hex in [IDENT, EVEN, ODD, GROUND_SPEED, AIRSPEED, SURFACE_EVEN,
+                SURFACE_ODD] {
+        let data = frame(hex).data;
+        let parity = u32::from_str_radix(&hex[22..], 16).unwrap();
+        assert_eq!(crc::crc(&data[..11]), parity, "{}", hex);
+        assert_eq!(crc::syndrome(&data), 0, "{}", hex);
+    }
+
+    let good = frame(EVEN).data;
+    for bit in [5, 40, 87, 111] {
+        let mut data = good.clone();
+        crc::flip_bit(&mut data, bit);
+        assert_ne!(crc::syndrome(&data), 0);
+        assert_eq!(crc::fix_single_bit(&mut data), Some(bit));
+        assert_eq!(data, good);
+    }
+    // The downlink format is never repaired.
+    let mut data = good.clone();
+    crc::flip_bit(&mut data, 2);
+    assert_eq!(crc::fix_single_bit(&mut data), None);
+}
+
+#[test]
+fn cpr_reference() {
+    assert_eq!(cpr::nl(0.0), 59);
+    assert_eq!(cpr::nl(52.2572), 36);
+    assert_eq!(cpr::nl(-52.2572), 36);
+    assert_eq!(cpr::nl(87.0), 2);
+    assert_eq!(cpr::nl(88.0), 1);
+
+    let even = Cpr { odd: false, lat: 93000, lon: 51372 };
+    let odd = Cpr { odd: true, lat: 74158, lon: 50194 };
+    let position = cpr::global_airborne(&even, &odd, false).unwrap();
+    assert!(close(position, (52.25720, 3.91937)), "{:?}", position);
+    let position = cpr::local(&even, false, 52.258, 3.918);
+    assert!(close(position, (52.25720, 3.91937)), "{:?}", position);
+
+    let even = Cpr { odd: false, lat: 115609, lon: 116941 };
+    let odd = Cpr { odd: true, lat: 39199, lon: 110269 };
+    let position = cpr::global_surface(&even, &odd, true, 51.990, 4.375)
+                       .unwrap();
+    assert!(close(position, (52.32061, 4.73473)), "{:?}", position);
+}
+
+#[test]
+fn decode_reference() {
+    let msg = decode(IDENT);
+    assert_eq!((msg.df, msg.icao), (17, 0x4840d6));
+    assert_eq!(msg.content, Content::Identification {
+        category: 0, callsign: "KLM1023".to_string() });
+
+    let msg = decode(EVEN);
+    assert_eq!(msg.icao, 0x40621d);
+    assert_eq!(msg.content, Content::AirbornePosition {
+        altitude: Some(38_000), gnss: false,
+        cpr: Cpr { odd: false, lat: 93000, lon: 51372 } });
+    assert_eq!(decode(ODD).content, Content::AirbornePosition {
+        altitude: Some(38_000), gnss: false,
+        cpr: Cpr { odd: true, lat: 74158, lon: 50194 } });
+
+    let msg = decode(GROUND_SPEED);
+    assert_eq!(msg.icao, 0x485020);
+    let Content::Velocity(v) = msg.content else { panic!("{:?}", msg) };
+    assert!((v.ground_speed.unwrap() - 159.20).abs() < 0.01);
+    assert!((v.track.unwrap() - 182.88).abs() < 0.01);
+    assert_eq!(v.vertical_rate, Some(-832));
+
+    let msg = decode(AIRSPEED);
+    assert_eq!(msg.icao, 0xa05f21);
+    assert_eq!(msg.content, Content::Velocity(Velocity {
+        airspeed: Some(375.0), true_airspeed: true,
+        heading: Some(243.984375), vertical_rate: Some(-2304),
+        ..Velocity::default() }));
+
+    let msg = decode(SURFACE_ODD);
+    assert_eq!(msg.icao, 0x484175);
+    let Content::SurfacePosition { cpr, .. } = msg.content else {
+        panic!("{:?}", msg)
+    };
+    assert_eq!(cpr, Cpr { odd: true, lat: 39199, lon: 110269 });
+
+    // The address of a Comm-B reply is only in its parity, so it is kept
+    // only for an aircraft already seen.
+    let f = frame(COMM_B);
+    let icao = crc::syndrome(&f.data);
+    assert!(adsb::decode(&f, |_| false).is_none());
+    let msg = adsb::decode(&f, |a| a == icao).unwrap();
+    assert_eq!((msg.df, msg.icao), (20, icao));
+    assert_eq!(msg.content, Content::Altitude { altitude: Some(38_000) });
+
+    assert_eq!(adsb::decode_ac13(0x1838), Some(38_000));
+    assert_eq!(adsb::decode_squawk(0xaaa), 7700);
+    assert_eq!(adsb::decode_squawk(0), 0);
+}
+
+#[test]
+fn landing_and_taking_off_split_pairs() {
+    let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut table = AircraftTable::new();
+    let odd = decode(ODD);
+    let even = decode(EVEN);
+    // The same latitude as the airborne even message, but a different
+    // longitude, so pairing it with the odd one would give a position.
+    let surface = Message {
+        content: Content::SurfacePosition {
+            speed: None, track: None,
+            cpr: Cpr { odd: false, lat: 93000, lon: 0 } },
+        ..even.clone()
+    };
+
+    table.update(&surface, t0);
+    let a = table.update(&odd, t0 + Duration::from_secs(1)).unwrap();
+    assert!(!a.on_ground);
+    assert_eq!(a.position, None);
+    let a = table.update(&even, t0 + Duration::from_secs(2)).unwrap();
+    assert!(close(a.position.unwrap(), (52.25720, 3.91937)));
+
+    // And the other way round.
+    let surface = Message {
+        content: Content::SurfacePosition {
+            speed: None, track: None,
+            cpr: Cpr { odd: true, lat: 74158, lon: 0 } },
+        ..even.clone()
+    };
+    let mut table = AircraftTable::new();
+    table.update(&even, t0);
+    let a = table.update(&surface, t0 + Duration::from_secs(1)).unwrap();
+    assert!(a.on_ground);
+    assert_eq!(a.position, None);
+}
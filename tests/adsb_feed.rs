@@ -0,0 +1,235 @@
+// Tests of Mode S feed formats for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use rtlsdr::adsb::{self, Content, Message, Velocity};
+use rtlsdr::adsb::aircraft::AircraftTable;
+use rtlsdr::adsb::cpr::Cpr;
+use rtlsdr::adsb::demod::Frame;
+use rtlsdr::adsb::feed::{FeedServer, JsonServer, beast, sbs};
+use rtlsdr::json::Value;
+
+const ICAO: u32 = 0x40621d;
+
+/// 2023-11-14 22:13:20.123 UTC.
+fn time() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)
+}
+
+fn frame(hex: &str) -> Frame {
+    let data = (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect();
+    Frame { data, timestamp: 0, signal: 0.25, corrected: None }
+}
+
+fn message(df: u8, content: Content) -> Message {
+    Message { df, icao: ICAO, frame: frame("00000000000000"), content }
+}
+
+/// The fields of the SBS line for `msg`, after checking the parts common
+/// to every type.
+fn fields(msg: &Message, table: Option<&AircraftTable>, kind: &str)
+          -> Vec<String> {
+    let aircraft = table.and_then(|t| t.get(msg.icao));
+    let line = sbs(msg, aircraft, time()).expect("no SBS line");
+    assert!(line.ends_with("\r\n"));
+    let f: Vec<String> = line.trim_end().split(',').map(String::from)
+                             .collect();
+    assert_eq!(f.len(), 22, "{}", line);
+    assert_eq!(&f[..10], ["MSG", kind, "1", "1", "40621D", "1",
+                          "2023/11/14", "22:13:20.123", "2023/11/14",
+                          "22:13:20.123"]);
+    f
+}
+
+/// Assert that only the listed fields after the header are set.
+fn only(f: &[String], set: &[(usize, &str)]) {
+    for (i, value) in f.iter().enumerate().skip(10) {
+        let want = set.iter().find(|&&(k, _)| k == i).map_or("", |s| s.1);
+        assert_eq!(value, want, "field {}", i);
+    }
+}
+
+/// A short frame with 0x1a in its timestamp, level and data, and the
+/// Beast encoding of it.
+fn escaped() -> (Frame, [u8; 22]) {
+    let f = Frame {
+        data: vec![0x8d, 0x1a, 0x62, 0x1d, 0x58, 0xc3, 0x1a],
+        timestamp: 0x1a_0000_1a1a,
+        // A level byte of 0x1a.
+        signal: (26.0f64 / 255.0).powi(2),
+        corrected: None
+    };
+    (f, [
+        0x1a, b'2',
+        0x00, 0x1a, 0x1a, 0x00, 0x00, 0x1a, 0x1a, 0x1a, 0x1a,
+        0x1a, 0x1a,
+        0x8d, 0x1a, 0x1a, 0x62, 0x1d, 0x58, 0xc3, 0x1a, 0x1a
+    ])
+}
+
+#[test]
+fn beast_escapes() {
+    let (f, bytes) = escaped();
+    assert_eq!(beast(&f), bytes);
+
+    let long = frame("8D40621D58C382D690C8AC2863A7");
+    let out = beast(&long);
+    assert_eq!(&out[..2], [0x1a, b'3']);
+    assert_eq!(out.len(), 2 + 6 + 1 + 14);
+    assert_eq!(out[8], 128);
+    assert_eq!(&out[9..], &long.data[..]);
+}
+
+#[test]
+fn sbs_lines() {
+    let ident = message(17, Content::Identification {
+        category: 3, callsign: "KLM1023".to_string() });
+    only(&fields(&ident, None, "1"), &[(10, "KLM1023")]);
+
+    let velocity = message(17, Content::Velocity(Velocity {
+        ground_speed: Some(159.2), track: Some(182.88),
+        vertical_rate: Some(-832), ..Velocity::default() }));
+    only(&fields(&velocity, None, "4"),
+         &[(12, "159"), (13, "183"), (16, "-832")]);
+
+    let altitude = Content::Altitude { altitude: Some(38_000) };
+    let surveillance = message(4, altitude.clone());
+    only(&fields(&surveillance, None, "5"), &[(11, "38000")]);
+    let air_to_air = message(0, altitude);
+    only(&fields(&air_to_air, None, "7"), &[(11, "38000")]);
+
+    let squawk = message(5, Content::Squawk { squawk: 7700 });
+    only(&fields(&squawk, None, "6"),
+         &[(17, "7700"), (18, "0"), (19, "-1"), (20, "0")]);
+    let squawk = message(21, Content::Squawk { squawk: 1200 });
+    only(&fields(&squawk, None, "6"),
+         &[(17, "1200"), (18, "0"), (19, "0"), (20, "0")]);
+
+    let all_call = message(11, Content::AllCall { capability: 5 });
+    only(&fields(&all_call, None, "8"), &[]);
+
+    let other = message(17, Content::Other { type_code: 28 });
+    assert_eq!(sbs(&other, None, time()), None);
+}
+
+#[test]
+fn sbs_positions() {
+    let cpr = Cpr { odd: false, lat: 0, lon: 0 };
+    let surface = message(17, Content::SurfacePosition {
+        speed: Some(17.0), track: Some(92.8), cpr });
+    only(&fields(&surface, None, "2"),
+         &[(12, "17"), (13, "93"), (21, "-1")]);
+
+    // An even and odd pair, 52.25720N 3.91937E at 38000ft.
+    let mut table = AircraftTable::new();
+    let mut last = None;
+    for hex in ["8D40621D58C386435CC412692AD6",
+                "8D40621D58C382D690C8AC2863A7"] {
+        let msg = adsb::decode(&frame(hex), |_| false).unwrap();
+        table.update(&msg, time());
+        last = Some(msg);
+    }
+    let f = fields(&last.unwrap(), Some(&table), "3");
+    only(&f, &[(11, "38000"), (14, "52.25720"), (15, "3.91937"),
+               (21, "0")]);
+
+    // Other types take the altitude but not the position.
+    let squawk = message(5, Content::Squawk { squawk: 1000 });
+    let f = fields(&squawk, Some(&table), "6");
+    assert_eq!(f[11], "38000");
+    assert_eq!(f[14], "");
+}
+
+/// Send `request` to `addr`, returning the status line and body.
+fn get(addr: SocketAddr, request: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let length = format!("Content-Length: {}", body.len());
+    assert!(head.lines().any(|l| l == length), "{}", head);
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[test]
+fn json_over_http() {
+    let server = JsonServer::bind("127.0.0.1:0", "/data/aircraft.json")
+        .unwrap();
+    let addr = server.local_addr();
+    let (status, body) = get(addr, "GET / HTTP/1.1\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, "{}");
+
+    server.set(&Value::object().with("now", 1.5).with("messages", 3));
+    let expected = Value::object().with("now", 1.5).with("messages", 3)
+                                  .to_compact();
+    // A client which connects and sends nothing must not hold up others.
+    let _idle = TcpStream::connect(addr).unwrap();
+    let started = Instant::now();
+    let (status, body) = get(addr, "GET /data/aircraft.json?_=1 HTTP/1.1\r\n\
+                                    Host: localhost\r\n\r\n");
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, expected);
+
+    let (status, _) = get(addr, "GET /other.json HTTP/1.1\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+}
+
+/// Connect to `server`, waiting until it has accepted the connection.
+fn subscribe(server: &FeedServer) -> TcpStream {
+    let before = server.clients();
+    let stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let started = Instant::now();
+    while server.clients() == before {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(1));
+    }
+    stream
+}
+
+#[test]
+fn feeds_over_tcp() {
+    let server = FeedServer::bind("127.0.0.1:0").unwrap();
+    let mut a = subscribe(&server);
+    let mut b = subscribe(&server);
+    assert_eq!(server.clients(), 2);
+
+    let (f, bytes) = escaped();
+    server.send(&beast(&f));
+    for stream in [&mut a, &mut b] {
+        let mut got = [0u8; 22];
+        stream.read_exact(&mut got).unwrap();
+        assert_eq!(got, bytes);
+    }
+
+    let ident = message(17, Content::Identification {
+        category: 3, callsign: "KLM1023".to_string() });
+    server.send(sbs(&ident, None, time()).unwrap().as_bytes());
+    let expected = "MSG,1,1,1,40621D,1,2023/11/14,22:13:20.123,2023/11/14,\
+                    22:13:20.123,KLM1023,,,,,,,,,,,\r\n";
+    let mut got = vec![0u8; expected.len()];
+    a.read_exact(&mut got).unwrap();
+    assert_eq!(String::from_utf8(got).unwrap(), expected);
+
+    // A client which has gone is forgotten once writing to it fails.
+    drop(a);
+    let started = Instant::now();
+    while server.clients() > 1 {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        server.send(b"x");
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(server.clients(), 1);
+}
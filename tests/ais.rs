@@ -0,0 +1,219 @@
+// Tests of AIS decoding for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::f64::consts::PI;
+
+use rtlsdr::ais::{self, CENTER_FREQ, Channel, Content, Dimensions, Position,
+                  SAMPLE_RATE, StaticB, StaticVoyage};
+use rtlsdr::ais::demod::{BAUD, Demodulator, fcs};
+use rtlsdr::ais::nmea::{self, Encoder};
+
+/// Reference sentences from the AIVDM/AIVDO protocol decoding notes.
+const TYPE_1: &str = "!AIVDM,1,1,,B,15M67FC000G?ufbE`FepT@3n00Sa,0*5C";
+const TYPE_5: [&str; 2] = [
+    "!AIVDM,2,1,1,A,55?MbV02;H;s<HtKR20EHE:0@T4@Dn2222222216L961O5Gf0NSQEp6C\
+     lRp8,0*1C",
+    "!AIVDM,2,2,1,A,88888888880,2*25"
+];
+const TYPE_18: &str = "!AIVDM,1,1,,A,B5NJ;PP005l4ot5Isbl03wsUkP06,0*76";
+const TYPE_24A: &str = "!AIVDM,1,1,,A,H42O55i18tMET00000000000000,2*6D";
+const TYPE_24B: &str = "!AIVDM,1,1,,A,H42O55lti4hhhilD3nink000?050,0*40";
+
+/// The payload of a message's sentences, after checking their checksums,
+/// and the channel they were received on.
+fn payload(sentences: &[&str]) -> (Vec<u8>, Channel) {
+    let mut armored = String::new();
+    let mut fill = 0;
+    let mut channel = Channel::A;
+    for s in sentences {
+        let (body, sum) = s[1..].split_once('*').unwrap();
+        assert_eq!(format!("{:02X}", nmea::checksum(body)), sum, "{}", s);
+        let f: Vec<&str> = body.split(',').collect();
+        channel = if f[4] == "A" { Channel::A } else { Channel::B };
+        armored.push_str(f[5]);
+        fill = f[6].parse().unwrap();
+    }
+    (nmea::dearmor(&armored, fill).unwrap(), channel)
+}
+
+fn decode(sentences: &[&str]) -> ais::Message {
+    ais::decode(&payload(sentences).0).unwrap()
+}
+
+#[test]
+fn armor_round_trip() {
+    for sentences in [&[TYPE_1][..], &TYPE_5, &[TYPE_18], &[TYPE_24A],
+                      &[TYPE_24B]] {
+        let (data, channel) = payload(sentences);
+        let mut encoder = Encoder::new();
+        if sentences.len() > 1 {
+            // Message IDs count from zero, and the reference uses 1.
+            encoder.encode(&data, channel);
+        }
+        assert_eq!(encoder.encode(&data, channel), sentences);
+    }
+
+    // Type 5 is 424 bits, or 53 bytes, which take 71 characters with two
+    // fill bits.
+    let (data, _) = payload(&TYPE_5);
+    assert_eq!(data.len(), 53);
+    let (armored, fill) = nmea::armor(&data);
+    assert_eq!((armored.len(), fill), (71, 2));
+    assert_eq!(nmea::dearmor(&armored, fill).unwrap(), data);
+    assert_eq!(nmea::dearmor("5!", 0), None);
+    assert_eq!(nmea::dearmor("5", 7), None);
+}
+
+#[test]
+fn frame_check_sequence() {
+    // The CRC-16/X.25 check value.
+    assert_eq!(fcs(b"123456789"), 0x906e);
+    // Sent least significant byte first, which leaves a fixed residue.
+    let (mut data, _) = payload(&[TYPE_1]);
+    let sum = fcs(&data);
+    data.extend(sum.to_le_bytes());
+    assert_eq!(fcs(&data), 0x0f47);
+}
+
+#[test]
+fn decodes_reference_messages() {
+    let msg = decode(&[TYPE_1]);
+    assert_eq!((msg.msg_type, msg.repeat, msg.mmsi), (1, 0, 366053209));
+    assert_eq!(msg.content, Content::Position(Position {
+        status: Some(3), turn: Some(0.0), speed: Some(0.0), accurate: false,
+        lat: Some(22_681_271.0 / 600_000.0),
+        lon: Some(-73_404_971.0 / 600_000.0),
+        course: Some(219.3), heading: Some(1), second: Some(59) }));
+
+    let msg = decode(&TYPE_5);
+    assert_eq!((msg.msg_type, msg.mmsi), (5, 351759000));
+    assert_eq!(msg.content, Content::StaticVoyage(StaticVoyage {
+        imo: Some(9134270), callsign: Some("3FOF8".to_string()),
+        name: Some("EVER DIADEM".to_string()), ship_type: 70,
+        dimensions: Dimensions { bow: 225, stern: 70, port: 1,
+                                 starboard: 31 },
+        eta: Some((5, 15, 14, 0)), draught: Some(12.2),
+        destination: Some("NEW YORK".to_string()) }));
+    let json = msg.to_json();
+    assert_eq!(json.get("eta").and_then(|v| v.as_str()), Some("05-15T14:00Z"));
+
+    let msg = decode(&[TYPE_18]);
+    assert_eq!((msg.msg_type, msg.mmsi), (18, 367430530));
+    assert_eq!(msg.content, Content::Position(Position {
+        status: None, turn: None, speed: Some(0.0), accurate: false,
+        lat: Some(22_671_021.0 / 600_000.0),
+        lon: Some(-73_360_392.0 / 600_000.0),
+        course: Some(0.0), heading: None, second: Some(55) }));
+
+    let msg = decode(&[TYPE_24A]);
+    assert_eq!((msg.msg_type, msg.mmsi), (24, 271041815));
+    assert_eq!(msg.content, Content::StaticB(StaticB::A {
+        name: Some("PROGUY".to_string()) }));
+    let msg = decode(&[TYPE_24B]);
+    assert_eq!(msg.mmsi, 271041815);
+    assert_eq!(msg.content, Content::StaticB(StaticB::B {
+        ship_type: 60, vendor: Some("1D0".to_string()),
+        callsign: Some("TC6163".to_string()),
+        dimensions: Dimensions { bow: 0, stern: 15, port: 0,
+                                 starboard: 5 } }));
+}
+
+/// Bits of an HDLC frame carrying `data`, from the training sequence to
+/// the closing flag, before NRZI coding.
+fn hdlc(data: &[u8]) -> Vec<bool> {
+    let mut bits: Vec<bool> = (0..24).map(|i| i % 2 == 1).collect();
+    let flag = |bits: &mut Vec<bool>| {
+        bits.extend((0..8).map(|i| 0x7eu8 >> i & 1 != 0));
+    };
+    flag(&mut bits);
+    let mut body = data.to_vec();
+    body.extend(fcs(data).to_le_bytes());
+    let mut ones = 0;
+    for byte in body {
+        for i in 0..8 {
+            let bit = byte >> i & 1 != 0;
+            bits.push(bit);
+            ones = if bit { ones + 1 } else { 0 };
+            if ones == 5 {
+                bits.push(false);
+                ones = 0;
+            }
+        }
+    }
+    flag(&mut bits);
+    bits
+}
+
+/// Instantaneous frequency (in Hz) at each sample of a GMSK burst of
+/// `bits`, NRZI coded, with a BT of 0.4 and the usual 2400Hz deviation.
+fn gmsk(bits: &[bool]) -> Vec<f64> {
+    let per_bit = SAMPLE_RATE as f64 / BAUD;
+    let mut level = 1.0;
+    let mut nrz = Vec::new();
+    for (k, &bit) in bits.iter().enumerate() {
+        if !bit {
+            level = -level;
+        }
+        let end = ((k + 1) as f64 * per_bit).round() as usize;
+        nrz.resize(end, level);
+    }
+    let sigma = (2f64.ln()).sqrt() / (2.0 * PI * 0.4) * per_bit;
+    let span = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f64> = (-span..=span)
+        .map(|t| (-(t as f64).powi(2) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = kernel.iter().sum();
+    (0..nrz.len() as i64).map(|n| {
+        kernel.iter().enumerate().map(|(j, &k)| {
+            let m = (n + j as i64 - span).clamp(0, nrz.len() as i64 - 1);
+            k * nrz[m as usize]
+        }).sum::<f64>() * 2400.0 / total
+    }).collect()
+}
+
+#[test]
+fn demodulates_both_channels() {
+    let (a, _) = payload(&[TYPE_1]);
+    let (b, _) = payload(&[TYPE_18]);
+    // The frame on B starts during the one on A and ends after it.
+    let start = SAMPLE_RATE as usize / 10;
+    let bursts = [(Channel::A, &a, start), (Channel::B, &b, start + 3000)];
+    let len = 2 * start;
+
+    let mut signal = vec![(0.0f64, 0.0f64); len];
+    for &(channel, data, start) in bursts.iter() {
+        let offset = channel.frequency() as f64 - CENTER_FREQ as f64;
+        let mut phase = 0.0;
+        for (n, f) in gmsk(&hdlc(data)).into_iter().enumerate() {
+            phase += 2.0 * PI * (offset + f) / SAMPLE_RATE as f64;
+            let s = &mut signal[start + n];
+            s.0 += 0.3 * phase.cos();
+            s.1 += 0.3 * phase.sin();
+        }
+    }
+    let mut seed = 7u32;
+    let mut noise = || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        ((seed >> 16) as f64 / 65536.0 - 0.5) * 0.04
+    };
+    let mut iq = Vec::with_capacity(2 * len);
+    for (i, q) in signal {
+        for x in [i + noise(), q + noise()] {
+            iq.push((127.5 + 127.5 * x).round().clamp(0.0, 255.0) as u8);
+        }
+    }
+
+    let mut demod = Demodulator::new(SAMPLE_RATE, CENTER_FREQ).unwrap();
+    let mut frames = Vec::new();
+    // An odd block size splits samples between blocks.
+    for block in iq.chunks(12_345) {
+        frames.extend(demod.process_u8(block));
+    }
+    assert_eq!(frames.len(), 2, "{:?}", frames);
+    assert_eq!((frames[0].channel, &frames[0].data), (Channel::A, &a));
+    assert_eq!((frames[1].channel, &frames[1].data), (Channel::B, &b));
+    assert!(frames[0].sample < frames[1].sample);
+}
@@ -0,0 +1,105 @@
+// Tests of GSM FCCH calibration for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use rtlsdr::calibrate::{FCCH_OFFSET, GSM_RATE, GsmBand, calibrate_iq,
+                        detect_fcch};
+use rtlsdr::dsp::Complex;
+use rtlsdr::samples::iq_from_u8;
+use rtlsdr::source::{SimulatedSource, Source};
+
+/// Length of an FCCH burst, and the gap between bursts (in samples).
+const BURST: usize = 148;
+const GAP: usize = 1100;
+
+/// Amplitude of the FCCH tone, and the noise standing in for the bursts of
+/// other timeslots, which a base station sends at the same power.
+const TONE: f32 = 0.5;
+const TRAFFIC: f32 = 0.35;
+
+/// A source tuned to `carrier` at GSM_RATE, with a crystal `ppm` out.
+fn source(carrier: u64, ppm: f64) -> SimulatedSource {
+    let mut source = SimulatedSource::new();
+    source.set_center_freq(carrier).unwrap();
+    source.set_sample_rate(GSM_RATE).unwrap();
+    source.set_xtal_error(ppm);
+    source.set_noise(0.05);
+    source
+}
+
+fn read(source: &mut SimulatedSource, samples: usize) -> Vec<Complex> {
+    iq_from_u8(&source.read_sync(2 * samples).unwrap())
+}
+
+/// Receive `bursts` FCCH bursts from `tone`, separated by other traffic.
+fn bursts(tone: &mut SimulatedSource, bursts: usize) -> Vec<Complex> {
+    let mut traffic = SimulatedSource::new();
+    traffic.set_noise(TRAFFIC);
+    traffic.seed(2);
+    let mut samples = read(&mut traffic, GAP);
+    for _ in 0..bursts {
+        samples.extend(read(tone, BURST));
+        samples.extend(read(&mut traffic, GAP));
+    }
+    samples
+}
+
+/// Receive `n` FCCH bursts from a carrier at `carrier` Hz with a crystal
+/// `ppm` out.
+fn fcch(carrier: u64, ppm: f64, n: usize) -> Vec<Complex> {
+    let mut tone = source(carrier, ppm);
+    tone.add_signal(carrier + FCCH_OFFSET.round() as u64, TONE);
+    tone.seed(1);
+    bursts(&mut tone, n)
+}
+
+#[test]
+fn finds_each_burst() {
+    let carrier = GsmBand::Gsm900.arfcn_to_freq(50).unwrap();
+    let found = detect_fcch(&fcch(carrier, 0.0, 8), GSM_RATE as f64);
+    assert_eq!(found.len(), 8);
+    for (n, b) in found.iter().enumerate() {
+        let start = GAP + n * (BURST + GAP);
+        assert!(b.sample + BURST > start && b.sample < start + BURST,
+                "burst {} at {}", n, b.sample);
+        // Within 0.1ppm at 945MHz.
+        assert!(b.offset.abs() < 95.0, "offset {}", b.offset);
+    }
+}
+
+#[test]
+fn ignores_noise() {
+    let carrier = GsmBand::Gsm900.arfcn_to_freq(50).unwrap();
+    let mut traffic = source(carrier, 0.0);
+    traffic.set_noise(TRAFFIC);
+    let samples = read(&mut traffic, 20 * GAP);
+    assert!(detect_fcch(&samples, GSM_RATE as f64).is_empty());
+    assert!(calibrate_iq(&samples, GSM_RATE as f64, carrier).is_none());
+}
+
+#[test]
+fn estimates_xtal_error() {
+    for &(arfcn, ppm) in &[(50, 12.0), (10, -35.0), (100, 3.5)] {
+        let carrier = GsmBand::Gsm900.arfcn_to_freq(arfcn).unwrap();
+        let samples = fcch(carrier, ppm, 10);
+        let cal = calibrate_iq(&samples, GSM_RATE as f64, carrier).unwrap();
+        assert!((cal.ppm - ppm).abs() < 0.2, "{} ppm estimated as {}", ppm,
+                cal.ppm);
+        assert_eq!(cal.bursts, 10);
+        assert!(cal.confidence > 0.99);
+    }
+}
+
+#[test]
+fn corrected_source_reads_zero() {
+    // With the error corrected, what remains is the error of the correction.
+    let carrier = GsmBand::Gsm900.arfcn_to_freq(50).unwrap();
+    let mut tone = source(carrier, 20.0);
+    tone.set_freq_correction(18).unwrap();
+    tone.add_signal(carrier + FCCH_OFFSET.round() as u64, TONE);
+    let samples = bursts(&mut tone, 10);
+    let cal = calibrate_iq(&samples, GSM_RATE as f64, carrier).unwrap();
+    assert!((cal.ppm - 2.0).abs() < 0.2, "estimated {}", cal.ppm);
+}
@@ -0,0 +1,166 @@
+// Tests of the AM, SSB and CW demodulators for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use rtlsdr::demod::Demodulator;
+use rtlsdr::demod::am::{AmConfig, AmDemod, AmMode};
+use rtlsdr::demod::ssb::{Sideband, SsbConfig, SsbDemod};
+use rtlsdr::dsp::{Complex, Nco};
+
+const RATE: u32 = 240_000;
+/// A dongle's usual full rate, where channels need the most filtering.
+const FULL_RATE: u32 = 2_400_000;
+const AUDIO_RATE: u32 = 48_000;
+
+/// Offset of the channel from the centre of the input (in Hz).
+const OFFSET: f64 = 20_000.0;
+
+/// One second of a tone at `freq` (in Hz) from the centre of the input.
+fn tone(freq: f64, amplitude: f32) -> Vec<Complex> {
+    tone_at(RATE, freq, amplitude)
+}
+
+/// As `tone`, sampled at `rate` (in Hz).
+fn tone_at(rate: u32, freq: f64, amplitude: f32) -> Vec<Complex> {
+    let mut nco = Nco::new(freq, rate as f64);
+    (0..rate).map(|_| nco.next_sample() * amplitude).collect()
+}
+
+/// One second of a carrier at `freq` (in Hz) amplitude modulated to `depth`
+/// by a tone at `audio` (in Hz).
+fn am(freq: f64, audio: f64, depth: f32) -> Vec<Complex> {
+    let mut carrier = Nco::new(freq, RATE as f64);
+    let mut modulation = Nco::new(audio, RATE as f64);
+    (0..RATE).map(|_| {
+        let envelope = 0.4 * (1.0 + depth * modulation.next_sample().re);
+        carrier.next_sample() * envelope
+    }).collect()
+}
+
+/// Demodulate `samples` a block at a time.
+fn demodulate(demod: &mut dyn Demodulator, samples: &[Complex]) -> Vec<f32> {
+    samples.chunks(16384).flat_map(|b| demod.process(b)).collect()
+}
+
+/// Frequency (in Hz) and RMS level of the tone in `audio`, ignoring the
+/// first quarter while the filters and AGC settle.
+fn pitch(audio: &[f32]) -> (f64, f32) {
+    assert!(audio.len() > AUDIO_RATE as usize * 9 / 10);
+    let audio = &audio[audio.len() / 4..];
+    let crossings = audio.windows(2)
+                         .filter(|w| (w[0] > 0.0) != (w[1] > 0.0))
+                         .count();
+    let rms = (audio.iter().map(|a| a * a).sum::<f32>() /
+               audio.len() as f32).sqrt();
+    (crossings as f64 / 2.0 * AUDIO_RATE as f64 / audio.len() as f64, rms)
+}
+
+fn ssb(sideband: Sideband) -> SsbDemod {
+    let mut config = SsbConfig::new(RATE, sideband);
+    config.offset = OFFSET;
+    config.audio_rate = AUDIO_RATE;
+    SsbDemod::new(&config)
+}
+
+fn assert_pitch(audio: &[f32], expected: f64) {
+    let (freq, rms) = pitch(audio);
+    assert!((freq - expected).abs() < 5.0, "heard {}Hz not {}Hz", freq,
+            expected);
+    assert!(rms > 0.05, "level {}", rms);
+}
+
+#[test]
+fn envelope_am() {
+    let mut config = AmConfig::new(RATE);
+    config.offset = OFFSET;
+    config.audio_rate = AUDIO_RATE;
+    let mut demod = AmDemod::new(&config);
+    assert_pitch(&demodulate(&mut demod, &am(OFFSET, 1000.0, 0.5)), 1000.0);
+}
+
+#[test]
+fn synchronous_am() {
+    // The carrier is 150Hz off the channel centre, for the PLL to find.
+    let mut config = AmConfig::new(RATE);
+    config.offset = OFFSET;
+    config.audio_rate = AUDIO_RATE;
+    config.mode = AmMode::Synchronous;
+    let mut demod = AmDemod::new(&config);
+    let samples = am(OFFSET + 150.0, 1500.0, 0.5);
+    assert_pitch(&demodulate(&mut demod, &samples), 1500.0);
+}
+
+#[test]
+fn usb() {
+    let samples = tone(OFFSET + 1000.0, 0.3);
+    assert_pitch(&demodulate(&mut ssb(Sideband::Usb), &samples), 1000.0);
+}
+
+#[test]
+fn lsb() {
+    // Below the dial frequency, so heard the right way up only if the BFO
+    // is flipped.
+    let samples = tone(OFFSET - 1000.0, 0.3);
+    assert_pitch(&demodulate(&mut ssb(Sideband::Lsb), &samples), 1000.0);
+    let samples = tone(OFFSET - 2200.0, 0.3);
+    assert_pitch(&demodulate(&mut ssb(Sideband::Lsb), &samples), 2200.0);
+}
+
+#[test]
+fn sidebands_reject_each_other() {
+    // A tone in the wrong sideband comes out far quieter than in the right
+    // one, even after the AGC.
+    let mut config = SsbConfig::new(RATE, Sideband::Usb);
+    config.offset = OFFSET;
+    config.audio_rate = AUDIO_RATE;
+    let wanted = tone(OFFSET + 1000.0, 0.3);
+    let unwanted = tone(OFFSET - 1000.0, 0.3);
+    for sideband in [Sideband::Usb, Sideband::Lsb] {
+        config.sideband = sideband;
+        let (right, wrong) = match sideband {
+            Sideband::Usb => (&wanted, &unwanted),
+            _ => (&unwanted, &wanted)
+        };
+        let mut demod = SsbDemod::new(&config);
+        demod.agc_mut().set_max_gain(1.0);
+        let (_, on) = pitch(&demodulate(&mut demod, right));
+        let mut demod = SsbDemod::new(&config);
+        demod.agc_mut().set_max_gain(1.0);
+        let (_, off) = pitch(&demodulate(&mut demod, wrong));
+        assert!(off < on / 100.0, "{:?}: {} vs {}", sideband, off, on);
+    }
+}
+
+#[test]
+fn selectivity_at_full_rate() {
+    let mut config = SsbConfig::new(FULL_RATE, Sideband::Usb);
+    config.offset = OFFSET;
+    config.audio_rate = AUDIO_RATE;
+    // Audio heard from a tone `freq` (in Hz) from the dial frequency.
+    let hear = |freq: f64| {
+        let mut demod = SsbDemod::new(&config);
+        demod.agc_mut().set_max_gain(1.0);
+        let samples = tone_at(FULL_RATE, OFFSET + freq, 0.3);
+        demodulate(&mut demod, &samples)
+    };
+    let wanted = hear(1000.0);
+    assert_pitch(&wanted, 1000.0);
+    let (_, on) = pitch(&wanted);
+    // The opposite sideband, close in and further out, at least 60dB
+    // down.
+    for freq in [-500.0, -1000.0, -2000.0] {
+        let (_, off) = pitch(&hear(freq));
+        assert!(off < on / 1000.0, "{}Hz: {} vs {}", freq, off, on);
+    }
+}
+
+#[test]
+fn cw() {
+    // Heard at the 700Hz pitch, and higher as the signal moves up.
+    let samples = tone(OFFSET, 0.3);
+    assert_pitch(&demodulate(&mut ssb(Sideband::Cw), &samples), 700.0);
+    let samples = tone(OFFSET + 150.0, 0.3);
+    assert_pitch(&demodulate(&mut ssb(Sideband::Cw), &samples), 850.0);
+}
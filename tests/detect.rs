@@ -0,0 +1,177 @@
+// Tests of signal detection for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::time::{Duration, SystemTime};
+
+use rtlsdr::detect::{Cfar, Detector, DetectorConfig, noise_floor};
+use rtlsdr::source::SimulatedSource;
+use rtlsdr::spectrum::{Psd, SpectrumAnalyzer, SpectrumConfig};
+
+const CENTER: u64 = 100_000_000;
+const FFT: usize = 1024;
+/// Width of each bin (in Hz) at the simulated source's 2.048MHz.
+const STEP: u64 = 2000;
+/// Amplitude of each tone, some 40dB above the noise in its bin.
+const TONE: f32 = 0.1;
+
+/// A source with tones centred on each of `bins`, counted from the centre.
+fn source(bins: &[i64]) -> SimulatedSource {
+    tones(bins, TONE)
+}
+
+/// A source with tones of amplitude `amp` centred on each of `bins`.
+fn tones(bins: &[i64], amp: f32) -> SimulatedSource {
+    let mut source = SimulatedSource::new();
+    source.seed(3);
+    for &b in bins {
+        source.add_signal((CENTER as i64 + b * STEP as i64) as u64, amp);
+    }
+    source
+}
+
+/// Averaged spectrum of 65 half-overlapping frames read from `source`.
+fn spectrum(source: &mut SimulatedSource) -> Psd {
+    let mut analyzer = SpectrumAnalyzer::new(SpectrumConfig::new(FFT), 0.0);
+    analyzer.read(source, 2 * FFT * 33).unwrap();
+    analyzer.psd().unwrap()
+}
+
+/// Index into the spectrum of bin `b` from the centre.
+fn index(b: i64) -> usize {
+    (FFT as i64 / 2 + b) as usize
+}
+
+fn median(power: &[f64]) -> f64 {
+    let mut sorted = power.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted[sorted.len() / 2]
+}
+
+fn config(method: Cfar) -> DetectorConfig {
+    DetectorConfig { method, ..DetectorConfig::new() }
+}
+
+#[test]
+fn floor_excludes_tone() {
+    let psd = spectrum(&mut source(&[100]));
+    assert_eq!(psd.freq(index(100)), (CENTER + 100 * STEP) as f64);
+    let power = psd.power();
+    let noise = median(&power);
+    for method in [Cfar::CellAveraging, Cfar::OrderedStatistic(0.5)] {
+        let floor = noise_floor(&power, &config(method));
+        assert_eq!(floor.len(), FFT);
+        // The guard bins keep the tone out of its own estimate.
+        let at = floor[index(100)];
+        assert!((at - noise).abs() < 1.5, "{:?}: {} vs {}", method, at,
+                noise);
+        assert!(power[index(100)] - at > 30.0);
+        for i in (0..FFT).step_by(97) {
+            assert!((floor[i] - noise).abs() < 1.5, "{:?} bin {}: {}",
+                    method, i, floor[i]);
+        }
+    }
+}
+
+#[test]
+fn ordered_statistic_ignores_neighbours() {
+    // The second tone lies among the first's training cells.
+    let psd = spectrum(&mut source(&[100, 108]));
+    let power = psd.power();
+    let noise = median(&power);
+    let ca = noise_floor(&power, &config(Cfar::CellAveraging));
+    let os = noise_floor(&power, &config(Cfar::OrderedStatistic(0.5)));
+    assert!(ca[index(100)] - noise > 6.0, "{} vs {}", ca[index(100)], noise);
+    assert!((os[index(100)] - noise).abs() < 1.5, "{} vs {}", os[index(100)],
+            noise);
+}
+
+#[test]
+fn detects_tones() {
+    let psd = spectrum(&mut source(&[-200, 100]));
+    let detector = Detector::new(DetectorConfig::new());
+    let found = detector.detect(psd.start, psd.step, &psd.power());
+    assert_eq!(found.len(), 2);
+    for (d, b) in found.iter().zip([-200, 100]) {
+        let freq = (CENTER as i64 + b * STEP as i64) as f64;
+        assert!((d.center - freq).abs() < STEP as f64 / 4.0,
+                "{} vs {}", d.center, freq);
+        // A Hann window spreads a tone over three bins.
+        assert_eq!(d.bandwidth, 3.0 * STEP as f64);
+        assert!(d.snr > 30.0);
+    }
+}
+
+#[test]
+fn merges_across_gap() {
+    // Occupied bins 99 to 101 and 105 to 107, leaving a gap of three.
+    let psd = spectrum(&mut source(&[100, 106]));
+    let power = psd.power();
+    let detect = |merge_gap| {
+        let config = DetectorConfig { merge_gap, ..DetectorConfig::new() };
+        Detector::new(config).detect(psd.start, psd.step, &power)
+    };
+    let apart = detect(2);
+    assert_eq!(apart.len(), 2);
+    assert_eq!(apart[0].bandwidth, 3.0 * STEP as f64);
+    let merged = detect(3);
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].bandwidth, 9.0 * STEP as f64);
+    let middle = (CENTER + 103 * STEP) as f64;
+    assert!((merged[0].center - middle).abs() < STEP as f64);
+}
+
+#[test]
+fn follows_signals_over_frames() {
+    let on = spectrum(&mut source(&[100])).power();
+    let off = spectrum(&mut source(&[])).power();
+    let start = (CENTER - FFT as u64 / 2 * STEP) as f64;
+    let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let at = |frame: u64| t0 + Duration::from_secs(frame);
+
+    // hold is 2, so a gap of one frame is bridged but three end it.
+    let mut detector = Detector::new(DetectorConfig::new());
+    let frames = [&on, &on, &on, &off, &on, &on, &off, &off];
+    for (k, power) in frames.iter().enumerate() {
+        let ended = detector.process_frame(start, STEP as f64, power,
+                                           at(k as u64));
+        assert!(ended.is_empty(), "ended at frame {}", k);
+    }
+    assert_eq!(detector.active().len(), 1);
+    let ended = detector.process_frame(start, STEP as f64, &off, at(8));
+    assert_eq!(ended.len(), 1);
+    assert!(detector.active().is_empty());
+
+    let signal = &ended[0];
+    assert_eq!(signal.start, at(0));
+    assert_eq!(signal.stop, at(5));
+    assert_eq!(signal.duration(), Duration::from_secs(5));
+    assert_eq!(signal.frames, 5);
+    let freq = (CENTER + 100 * STEP) as f64;
+    assert!((signal.center() - freq).abs() < STEP as f64);
+
+    // A signal still active is returned by flush.
+    detector.process_frame(start, STEP as f64, &on, at(9));
+    let flushed = detector.flush();
+    assert_eq!(flushed.len(), 1);
+    assert_eq!(flushed[0].start, at(9));
+}
+
+#[test]
+fn detects_near_threshold() {
+    // Some 13dB above the noise in its bin, 3dB over the threshold, while
+    // the Hann window puts its neighbours 6dB lower and so below it.
+    let psd = spectrum(&mut tones(&[100], 0.0024));
+    let detector = Detector::new(DetectorConfig::new());
+    let found = detector.detect(psd.start, psd.step, &psd.power());
+    assert_eq!(found.len(), 1, "{:?}", found);
+    assert_eq!(found[0].center, (CENTER + 100 * STEP) as f64);
+    assert_eq!(found[0].bandwidth, STEP as f64);
+    assert!(found[0].snr > 11.0 && found[0].snr < 15.0, "{}", found[0].snr);
+
+    // Half the amplitude falls under the threshold.
+    let psd = spectrum(&mut tones(&[100], 0.0012));
+    assert!(detector.detect(psd.start, psd.step, &psd.power()).is_empty());
+}
@@ -0,0 +1,208 @@
+// Tests of ISM band decoding for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::f64::consts::PI;
+
+use rtlsdr::ism::{Receiver, SAMPLE_RATE};
+use rtlsdr::ism::analyze::Segmenter;
+use rtlsdr::ism::pulse::{PulseConfig, PulseDetector};
+use rtlsdr::json::Value;
+
+/// Carrier amplitude, out of 127.5.
+const AMP: f64 = 60.0;
+
+/// u8 I/Q built up from stretches of carrier and silence, with a little
+/// noise throughout.
+struct Signal {
+    iq: Vec<u8>,
+    phase: f64,
+    seed: u32
+}
+
+impl Signal {
+    /// A signal starting with enough silence for the noise floor.
+    fn new() -> Signal {
+        let mut s = Signal { iq: Vec::new(), phase: 0.0, seed: 1 };
+        s.off(20_000.0);
+        s
+    }
+
+    /// Uniform noise of up to two steps either way.
+    fn noise(&mut self) -> f64 {
+        self.seed = self.seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (self.seed >> 16) as f64 / 65536.0 * 4.0 - 2.0
+    }
+
+    /// `us` microseconds of carrier at `amp` and `freq` (in Hz).
+    fn tone(&mut self, amp: f64, freq: f64, us: f64) {
+        let n = (us * SAMPLE_RATE as f64 / 1e6).round() as usize;
+        for _ in 0..n {
+            self.phase += 2.0 * PI * freq / SAMPLE_RATE as f64;
+            let i = 127.5 + amp * self.phase.cos() + self.noise();
+            let q = 127.5 + amp * self.phase.sin() + self.noise();
+            self.iq.push(i.round().clamp(0.0, 255.0) as u8);
+            self.iq.push(q.round().clamp(0.0, 255.0) as u8);
+        }
+    }
+
+    fn on(&mut self, us: f64) {
+        self.tone(AMP, 0.0, us);
+    }
+
+    fn off(&mut self, us: f64) {
+        self.tone(0.0, 0.0, us);
+    }
+}
+
+/// The bits of `bytes`, most significant first, up to `len` of them.
+fn bits(bytes: &[u8], len: usize) -> Vec<bool> {
+    (0..len).map(|i| bytes[i / 8] & 0x80 >> (i % 8) != 0).collect()
+}
+
+/// Run `signal` through a receiver, flushing at the end.
+fn receive(signal: &Signal) -> Vec<Value> {
+    let mut receiver = Receiver::new(SAMPLE_RATE);
+    let mut events = Vec::new();
+    for block in signal.iq.chunks(65536) {
+        events.extend(receiver.process_u8(block));
+    }
+    events.extend(receiver.flush());
+    events
+}
+
+/// The one event from `model`, checking there are no others.
+fn only<'a>(events: &'a [Value], model: &str) -> &'a Value {
+    assert_eq!(events.len(), 1, "{:?}",
+               events.iter().map(|e| e.to_compact()).collect::<Vec<_>>());
+    let event = &events[0];
+    assert_eq!(event.get("model").and_then(Value::as_str), Some(model));
+    event
+}
+
+fn number(event: &Value, key: &str) -> f64 {
+    event.get(key).and_then(Value::as_f64).expect(key)
+}
+
+fn text<'a>(event: &'a Value, key: &str) -> &'a str {
+    event.get(key).and_then(Value::as_str).expect(key)
+}
+
+/// A Nexus message, ID 0xa5 on channel 2 with a good battery, at 23.5C
+/// and 55%, repeated 12 times and followed by `tail` microseconds of
+/// silence.
+fn nexus(tail: f64) -> Signal {
+    let message = bits(&[0xa5, 0x90, 0xeb, 0xf3, 0x70], 36);
+    let mut s = Signal::new();
+    for _ in 0..12 {
+        for &b in &message {
+            s.on(500.0);
+            s.off(if b { 2000.0 } else { 1000.0 });
+        }
+        s.on(500.0);
+        s.off(4000.0);
+    }
+    s.off(tail);
+    s
+}
+
+#[test]
+fn decodes_nexus() {
+    let events = receive(&nexus(50_000.0));
+    let event = only(&events, "Nexus-TH");
+    assert_eq!(number(event, "id"), 165.0);
+    assert_eq!(number(event, "channel"), 2.0);
+    assert_eq!(number(event, "battery_ok"), 1.0);
+    assert_eq!(number(event, "temperature_C"), 23.5);
+    assert_eq!(number(event, "humidity"), 55.0);
+    assert_eq!(text(event, "mod"), "ASK");
+}
+
+#[test]
+fn flushes_at_end() {
+    // Ending before the train's final gap is long enough to end it.
+    let signal = nexus(1000.0);
+    let mut receiver = Receiver::new(SAMPLE_RATE);
+    assert!(receiver.process_u8(&signal.iq).is_empty());
+    only(&receiver.flush(), "Nexus-TH");
+    assert!(receiver.flush().is_empty());
+}
+
+#[test]
+fn segmenter_finishes_at_end() {
+    let signal = nexus(1000.0);
+    let mut segmenter = Segmenter::new(SAMPLE_RATE, PulseConfig::new());
+    assert!(segmenter.process_u8(&signal.iq).is_empty());
+    let burst = segmenter.finish().expect("no burst");
+    // Twelve rows of 79.5ms, less the last row's final gap.
+    assert!((burst.duration() - 950_000.0).abs() < 10_000.0,
+            "{}us", burst.duration());
+    assert!(segmenter.finish().is_none());
+}
+
+#[test]
+fn decodes_ev1527() {
+    let period = 350.0;
+    let message = bits(&[0x5a, 0x3c, 0x18], 24);
+    let mut s = Signal::new();
+    for _ in 0..5 {
+        s.on(period);
+        s.off(31.0 * period);
+        for &b in &message {
+            let (on, off) = if b { (3.0, 1.0) } else { (1.0, 3.0) };
+            s.on(on * period);
+            s.off(off * period);
+        }
+    }
+    s.off(50_000.0);
+    let events = receive(&s);
+    let event = only(&events, "EV1527");
+    assert_eq!(text(event, "id"), "5a3c1");
+    assert_eq!(number(event, "button"), 8.0);
+}
+
+#[test]
+fn decodes_citroen() {
+    // State 0, ID 0x12345678, flags 2, repeat 1, 218.2kPa, 25C, battery
+    // 0x30 and the check byte.
+    let mut message = vec![0x00, 0x12, 0x34, 0x56, 0x78, 0x21, 0xa0, 0x4b,
+                           0x30];
+    message.push(message[1..].iter().fold(0, |x, &b| x ^ b));
+    let mut symbols = bits(&[0x55, 0x55, 0x55, 0x56], 32);
+    for b in bits(&message, 80) {
+        symbols.extend([!b, b]);
+    }
+    symbols.extend([false, true, false, true]);
+
+    let mut s = Signal::new();
+    for &mark in &symbols {
+        s.tone(AMP, if mark { 40_000.0 } else { -40_000.0 }, 52.0);
+    }
+    s.off(50_000.0);
+    let events = receive(&s);
+    let event = only(&events, "Citroen");
+    assert_eq!(text(event, "id"), "12345678");
+    assert_eq!(number(event, "flags"), 2.0);
+    assert_eq!(number(event, "repeat"), 1.0);
+    assert_eq!(number(event, "pressure_kPa"), 218.2);
+    assert_eq!(number(event, "temperature_C"), 25.0);
+    assert_eq!(text(event, "mod"), "FSK");
+}
+
+#[test]
+fn steady_carrier_ends_train() {
+    let mut s = Signal::new();
+    for _ in 0..3 {
+        s.on(500.0);
+        s.off(1000.0);
+    }
+    // Far longer than any pulse, and never switched off.
+    s.on(1_000_000.0);
+    let mut detector = PulseDetector::new(SAMPLE_RATE);
+    let trains = detector.process_u8(&s.iq);
+    assert_eq!(trains.len(), 1);
+    assert_eq!(trains[0].len(), 3);
+    assert_eq!(trains[0].gaps.len(), 3);
+}
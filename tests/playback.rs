@@ -0,0 +1,75 @@
+// Tests of recorded sample playback for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::path::PathBuf;
+
+use rtlsdr::playback::FileSource;
+use rtlsdr::source::Source;
+
+/// A path for a scratch file unique to this test run.
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rtlsdr-{}-{}", std::process::id(),
+                                      name))
+}
+
+#[test]
+fn loops_on_whole_samples() {
+    // Three samples of I bytes 0x10.. and Q bytes 0x80.., then an odd byte.
+    let path = scratch("odd.cu8");
+    std::fs::write(&path, [0x10, 0x80, 0x11, 0x81, 0x12, 0x82, 0x13])
+        .unwrap();
+    let mut source = FileSource::open_raw(&path, 1_000_000, 100_000_000)
+        .unwrap();
+    assert_eq!(source.len(), 3);
+    source.set_looping(true);
+    // Reads which straddle the end, by a different amount each pass.
+    for len in [4, 10, 2, 8, 6] {
+        let buf = source.read_sync(len).unwrap();
+        assert_eq!(buf.len(), len);
+        for iq in buf.chunks(2) {
+            assert!(iq[0] < 0x80 && iq[1] >= 0x80, "{:?}", buf);
+            assert_eq!(iq[1] - iq[0], 0x70);
+        }
+    }
+
+    // Without looping, the odd byte is never returned.
+    source.set_looping(false);
+    source.seek(0).unwrap();
+    assert_eq!(source.read_sync(100).unwrap().len(), 6);
+    assert!(source.read_sync(100).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Write a SigMF recording with `rate` as its sample rate, returning its
+/// base path.
+fn sigmf(name: &str, rate: &str) -> PathBuf {
+    let base = scratch(name);
+    let meta = format!("{{\"global\": {{\"core:datatype\": \"cu8\", \
+                        \"core:sample_rate\": {}, \
+                        \"core:version\": \"1.0.0\"}}, \
+                        \"captures\": [{{\"core:sample_start\": 0, \
+                        \"core:frequency\": 433920000}}], \
+                        \"annotations\": []}}", rate);
+    std::fs::write(base.with_extension("sigmf-meta"), meta).unwrap();
+    std::fs::write(base.with_extension("sigmf-data"), [127u8; 64]).unwrap();
+    base
+}
+
+#[test]
+fn sigmf_sample_rate() {
+    let base = sigmf("whole", "250000");
+    let mut source = FileSource::open_sigmf(&base).unwrap();
+    assert_eq!(source.get_sample_rate().unwrap(), 250_000);
+    assert_eq!(source.get_center_freq().unwrap(), 433_920_000);
+    for rate in ["250000.5", "0", "-1", "1e12"] {
+        let base = sigmf("fraction", rate);
+        assert!(FileSource::open_sigmf(&base).is_err(), "{}", rate);
+    }
+    for ext in ["sigmf-meta", "sigmf-data"] {
+        let _ = std::fs::remove_file(base.with_extension(ext));
+        let _ = std::fs::remove_file(scratch("whole").with_extension(ext));
+    }
+}
@@ -0,0 +1,75 @@
+// Tests of sample rate conversion for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::f64::consts::PI;
+
+use rtlsdr::dsp::resample::{FractionalResampler, Resampler};
+
+const IN_RATE: usize = 2_400_000;
+const OUT_RATE: usize = 48_000;
+
+/// Tones which must come through within 1dB, and ones which must be at
+/// least 60dB down, all in Hz at the 48kHz output.
+const PASS: [f64; 3] = [1000.0, 5000.0, 12_000.0];
+const STOP: [f64; 6] = [30_000.0, 38_400.0, 60_000.0, 144_000.0, 500_000.0,
+                        1_100_000.0];
+
+/// Level (in dB) at the output of `resample` of a unit tone at `freq`,
+/// fed in uneven blocks at `rate`.
+fn level<F>(mut resample: F, rate: usize, freq: f64) -> f64
+    where F: FnMut(&[f32]) -> Vec<f32> {
+    let input: Vec<f32> = (0..rate / 5)
+        .map(|n| (2.0 * PI * freq * n as f64 / rate as f64).cos() as f32)
+        .collect();
+    let mut out = Vec::new();
+    for block in input.chunks(4099) {
+        out.extend(resample(block));
+    }
+    // Leave out the filter's start up.
+    let settled = &out[out.len() / 4..];
+    let power = settled.iter().map(|&x| (x as f64).powi(2)).sum::<f64>()
+                / settled.len() as f64;
+    10.0 * (2.0 * power).log10()
+}
+
+/// Check the passband and stopband of a 2.4MS/s to 48kS/s conversion.
+fn check<F>(mut make: impl FnMut() -> F)
+    where F: FnMut(&[f32]) -> Vec<f32> {
+    for freq in PASS {
+        let db = level(make(), IN_RATE, freq);
+        assert!(db.abs() < 1.0, "{}Hz at {:.1}dB", freq, db);
+    }
+    for freq in STOP {
+        let db = level(make(), IN_RATE, freq);
+        assert!(db < -60.0, "{}Hz at {:.1}dB", freq, db);
+    }
+}
+
+#[test]
+fn rational_stopband() {
+    check(|| {
+        let mut r = Resampler::<f32>::between(IN_RATE, OUT_RATE);
+        assert_eq!(r.ratio(), (1, 50));
+        move |x: &[f32]| r.process(x)
+    });
+}
+
+#[test]
+fn fractional_stopband() {
+    check(|| {
+        let mut r = FractionalResampler::<f32>::between(IN_RATE as f64,
+                                                       OUT_RATE as f64);
+        move |x: &[f32]| r.process(x)
+    });
+}
+
+#[test]
+fn fractional_tenth() {
+    // A tone at 0.8 times the output rate, which would alias to 0.2.
+    let mut r = FractionalResampler::<f32>::new(0.1);
+    let db = level(|x| r.process(x), 1_000_000, 80_000.0);
+    assert!(db < -60.0, "{:.1}dB", db);
+}
@@ -0,0 +1,33 @@
+// Tests of sample conversion for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use rtlsdr::samples::IqPairs;
+
+#[test]
+fn pairs_across_blocks() {
+    let bytes: Vec<u8> = (0..20).collect();
+    let expected: Vec<(u8, u8)> = bytes.chunks(2).map(|p| (p[0], p[1]))
+                                       .collect();
+    // Split at every possible pair of points, including empty blocks.
+    for a in 0..=bytes.len() {
+        for b in a..=bytes.len() {
+            let mut iq = IqPairs::new();
+            let mut got = Vec::new();
+            for block in [&bytes[..a], &bytes[a..b], &bytes[b..]] {
+                got.extend(iq.pairs(block));
+            }
+            assert_eq!(got, expected, "split at {} and {}", a, b);
+        }
+    }
+}
+
+#[test]
+fn reset_drops_held_byte() {
+    let mut iq = IqPairs::new();
+    assert_eq!(iq.pairs(&[1, 2, 3]).collect::<Vec<_>>(), [(1, 2)]);
+    iq.reset();
+    assert_eq!(iq.pairs(&[4, 5]).collect::<Vec<_>>(), [(4, 5)]);
+}
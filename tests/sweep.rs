@@ -0,0 +1,117 @@
+// Tests of wideband power sweeps for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use rtlsdr::dsp::Complex;
+use rtlsdr::source::SimulatedSource;
+use rtlsdr::sweep::{HopPlan, Scanner, Spectrum};
+
+const RATE: u32 = 2_048_000;
+
+/// Index of the strongest bin.
+fn peak(spectrum: &Spectrum) -> usize {
+    (0..spectrum.power.len())
+        .max_by(|&a, &b| spectrum.power[a].total_cmp(&spectrum.power[b]))
+        .unwrap()
+}
+
+#[test]
+fn plan_covers_range() {
+    let (start, stop) = (88_000_000, 108_000_000);
+    let plan = HopPlan::new(start, stop, 5000.0, RATE, 0.25).unwrap();
+    assert_eq!(plan.fft_size, 512);
+    let bin = plan.bin_width();
+    assert_eq!(bin, 4000.0);
+    assert!((plan.hop_low(&plan.hops[0]) - start as f64).abs() < 1.0);
+    // Each hop starts where the last one ended.
+    for w in plan.hops.windows(2) {
+        let end = plan.hop_low(&w[0]) + w[0].bins as f64 * bin;
+        assert!((plan.hop_low(&w[1]) - end).abs() < 1.0);
+        assert!(w[1].center > w[0].center);
+    }
+    let last = plan.hops.last().unwrap();
+    let end = plan.hop_low(last) + last.bins as f64 * bin;
+    assert!(end >= stop as f64 && end < stop as f64 + bin);
+    let total: usize = plan.hops.iter().map(|h| h.bins).sum();
+    assert_eq!(total, ((stop - start) as f64 / bin).ceil() as usize);
+}
+
+#[test]
+fn plan_trims_edges() {
+    let plan = HopPlan::new(400_000_000, 430_000_000, 1000.0, RATE, 0.25)
+        .unwrap();
+    let n = plan.fft_size;
+    for hop in &plan.hops {
+        // A quarter is cropped, an eighth from each edge, centred on the
+        // capture.
+        assert_eq!(hop.first_bin, n / 8);
+        assert!(hop.bins <= n * 3 / 4);
+        assert!(hop.first_bin + hop.bins <= n - n / 8);
+        let low = plan.hop_low(hop) - hop.center as f64;
+        assert!((low + 0.375 * RATE as f64).abs() < 1.0);
+    }
+    // Only the last hop is cut short.
+    let full = plan.hops[..plan.hops.len() - 1].iter()
+                   .all(|h| h.bins == n * 3 / 4);
+    assert!(full);
+}
+
+#[test]
+fn plan_rejects_bad_ranges() {
+    assert!(HopPlan::new(100, 100, 1000.0, RATE, 0.25).is_err());
+    assert!(HopPlan::new(200, 100, 1000.0, RATE, 0.25).is_err());
+    assert!(HopPlan::new(100, 200, 0.0, RATE, 0.25).is_err());
+    assert!(HopPlan::new(100, 200, 1000.0, RATE, 1.0).is_err());
+}
+
+#[test]
+fn average_power_needs_one_fft() {
+    let plan = HopPlan::new(100_000_000, 101_000_000, 8000.0, RATE, 0.25)
+        .unwrap();
+    let scanner = Scanner::new(plan, 4);
+    let n = scanner.plan().fft_size;
+    assert!(scanner.average_power(&[]).is_err());
+    assert!(scanner.average_power(&vec![Complex::default(); n - 1]).is_err());
+    // A full scale tone in the centre of bin 8 reads 0dB.
+    let step = 8.0 / n as f32 * 2.0 * std::f32::consts::PI;
+    let tone: Vec<Complex> = (0..2 * n)
+        .map(|i| Complex::expj(step * i as f32))
+        .collect();
+    let power = scanner.average_power(&tone).unwrap();
+    assert!((10.0 * power[8].log10()).abs() < 0.1);
+}
+
+#[test]
+fn stitch_finds_tones() {
+    let (start, stop) = (100_000_000, 110_000_000);
+    let plan = HopPlan::new(start, stop, 10_000.0, RATE, 0.25).unwrap();
+    let bin = plan.bin_width();
+    let bins: usize = plan.hops.iter().map(|h| h.bins).sum();
+    // One tone mid-hop, one by the boundary between the first two hops.
+    let boundary = plan.hop_low(&plan.hops[1]) as u64;
+    let tones = [104_321_000, boundary + 2 * bin as u64];
+    let mut source = SimulatedSource::new();
+    source.set_noise(0.001);
+    for &f in tones.iter() {
+        let mut scanner = Scanner::new(plan.clone(), 8);
+        scanner.set_settle(0);
+        source.clear_signals();
+        source.add_signal(f, 0.5);
+        let spectrum = scanner.sweep(&mut source).unwrap().stitch();
+        assert_eq!(spectrum.power.len(), bins);
+        assert_eq!(spectrum.step, bin);
+        assert!((spectrum.start - start as f64).abs() < 1.0);
+        let expected = ((f as f64 - spectrum.start) / bin).round() as usize;
+        let found = peak(&spectrum);
+        assert!(found.abs_diff(expected) <= 1, "tone at {} found in bin {} \
+                 not {}", f, found, expected);
+        // Nothing else comes near.
+        let p = spectrum.power[found];
+        let others = spectrum.power.iter().enumerate()
+            .filter(|&(i, _)| i.abs_diff(found) > 3)
+            .all(|(_, &q)| q < p - 40.0);
+        assert!(others);
+    }
+}
@@ -0,0 +1,174 @@
+// Tests of the rtl_tcp client for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rtlsdr::DirectSampling;
+use rtlsdr::source::Source;
+use rtlsdr::tcp::Header;
+use rtlsdr::tcp::client::TcpDevice;
+use rtlsdr::tuning::tuner_gains;
+
+/// Accept one connection on a free port, send it `header` and hand it to
+/// `serve` on another thread.
+fn server<T, F>(header: &[u8], serve: F) -> (SocketAddr, JoinHandle<T>)
+    where T: Send + 'static, F: FnOnce(TcpStream) -> T + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let header = header.to_vec();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(&header).unwrap();
+        serve(stream)
+    });
+    (addr, handle)
+}
+
+/// Header bytes for `tuner` with `gains` gains.
+fn header(tuner: u32, gains: u32) -> [u8; 12] {
+    Header { tuner_type: tuner, gain_count: gains }.to_bytes()
+}
+
+#[test]
+fn reads_header() {
+    let bytes = [b'R', b'T', b'L', b'0', 0, 0, 0, 1, 0, 0, 0, 14];
+    let (addr, handle) = server(&bytes, |_| ());
+    let mut dev = TcpDevice::connect(addr).unwrap();
+    handle.join().unwrap();
+    assert_eq!(dev.peer_addr(), addr);
+    assert_eq!(dev.header(), Header { tuner_type: 1, gain_count: 14 });
+    assert_eq!(dev.get_tuner_type(), (1, "E4000".to_string()));
+    assert_eq!(dev.get_tuner_gains().unwrap(), tuner_gains(1));
+}
+
+#[test]
+fn rejects_other_servers() {
+    let (addr, handle) = server(b"HTTP/1.1 200", |_| ());
+    assert!(TcpDevice::connect(addr).is_err());
+    handle.join().unwrap();
+}
+
+#[test]
+fn checks_gain_count() {
+    // An R820T offers 29 gains; any other count means a different list.
+    let (addr, handle) = server(&header(5, 12), |_| ());
+    let mut dev = TcpDevice::connect(addr).unwrap();
+    handle.join().unwrap();
+    assert_eq!(dev.get_tuner_type().1, "R820T");
+    assert!(dev.get_tuner_gains().is_err());
+
+    let (addr, handle) = server(&header(99, 3), |_| ());
+    let mut dev = TcpDevice::connect(addr).unwrap();
+    handle.join().unwrap();
+    assert_eq!(dev.get_tuner_type(), (99, "Unknown".to_string()));
+    assert!(dev.get_tuner_gains().is_err());
+}
+
+#[test]
+fn sends_commands() {
+    let (addr, handle) = server(&header(5, 29), |mut stream| {
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        received
+    });
+    let mut dev = TcpDevice::connect(addr).unwrap();
+    assert!(dev.get_center_freq().is_err());
+    assert!(dev.get_sample_rate().is_err());
+
+    dev.set_center_freq(100_000_000).unwrap();
+    // Too high for the protocol, so nothing is sent.
+    assert!(dev.set_center_freq(5_000_000_000).is_err());
+    dev.set_sample_rate(2_048_000).unwrap();
+    dev.set_tuner_gain_mode(true).unwrap();
+    dev.set_tuner_gain(496).unwrap();
+    dev.set_freq_correction(-5).unwrap();
+    dev.set_tuner_if_gain(2, -30).unwrap();
+    dev.set_test_mode(true).unwrap();
+    dev.set_agc_mode(true).unwrap();
+    dev.set_direct_sampling(DirectSampling::Q).unwrap();
+    dev.set_offset_tuning(true).unwrap();
+    dev.set_xtal_freq(28_800_000, 28_800_001).unwrap();
+    dev.set_bias_tee(true).unwrap();
+    dev.set_tuner_gain_mode(false).unwrap();
+
+    assert_eq!(dev.get_center_freq().unwrap(), 100_000_000);
+    assert_eq!(dev.get_sample_rate().unwrap(), 2_048_000);
+    assert_eq!(dev.get_freq_correction(), -5);
+    assert_eq!(dev.get_direct_sampling().unwrap(), DirectSampling::Q);
+    assert!(dev.get_offset_tuning().unwrap());
+    // Back to automatic gain.
+    assert_eq!(dev.get_tuner_gain(), 0);
+    dev.close().unwrap();
+
+    let expected: Vec<[u8; 5]> = vec![
+        [0x01, 0x05, 0xf5, 0xe1, 0x00],
+        [0x02, 0x00, 0x1f, 0x40, 0x00],
+        [0x03, 0x00, 0x00, 0x00, 0x01],
+        [0x04, 0x00, 0x00, 0x01, 0xf0],
+        [0x05, 0xff, 0xff, 0xff, 0xfb],
+        [0x06, 0x00, 0x02, 0xff, 0xe2],
+        [0x07, 0x00, 0x00, 0x00, 0x01],
+        [0x08, 0x00, 0x00, 0x00, 0x01],
+        [0x09, 0x00, 0x00, 0x00, 0x02],
+        [0x0a, 0x00, 0x00, 0x00, 0x01],
+        [0x0b, 0x01, 0xb7, 0x74, 0x00],
+        [0x0c, 0x01, 0xb7, 0x74, 0x01],
+        [0x0e, 0x00, 0x00, 0x00, 0x01],
+        [0x03, 0x00, 0x00, 0x00, 0x00]
+    ];
+    let received = handle.join().unwrap();
+    assert_eq!(received, expected.concat());
+}
+
+#[test]
+fn reads_across_short_writes() {
+    let data: Vec<u8> = (0..3000).map(|i| (i * 7 % 251) as u8).collect();
+    let sent = data.clone();
+    let (addr, handle) = server(&header(5, 29), move |mut stream| {
+        stream.set_nodelay(true).unwrap();
+        for piece in sent.chunks(333) {
+            stream.write_all(piece).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+    });
+    let mut dev = TcpDevice::connect(addr).unwrap();
+    dev.set_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(dev.read_sync(1000).unwrap(), &data[..1000]);
+    assert_eq!(dev.read_sync(1).unwrap(), &data[1000..1001]);
+    let iq = dev.read_iq(1998).unwrap();
+    assert_eq!(iq.len(), 999);
+    handle.join().unwrap();
+    // The server has gone, so a further read fails rather than blocking.
+    assert!(dev.read_sync(2).is_err());
+}
+
+#[test]
+fn reset_discards_settle_time() {
+    let old = vec![0u8; 300];
+    let new: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+    let sent = new.clone();
+    let (addr, handle) = server(&header(5, 29), move |mut stream| {
+        let mut command = [0u8; 5];
+        stream.read_exact(&mut command).unwrap();
+        stream.write_all(&old).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        stream.write_all(&sent).unwrap();
+        stream.read_to_end(&mut Vec::new()).unwrap();
+    });
+    let mut dev = TcpDevice::connect(addr).unwrap();
+    dev.set_timeout(Some(Duration::from_secs(5))).unwrap();
+    dev.set_sample_rate(1000).unwrap();
+    // 20ms at 1kHz is 20 samples, or 40 bytes.
+    dev.set_settle_time(Duration::from_millis(20));
+    thread::sleep(Duration::from_millis(100));
+    dev.reset_buffer().unwrap();
+    assert_eq!(dev.read_sync(100).unwrap(), &new[40..140]);
+    dev.close().unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,141 @@
+// Tests of the rtl_tcp server for the RTL-SDR crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+extern crate rtlsdr;
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rtlsdr::source::{SimulatedSource, Source};
+use rtlsdr::tcp::{Command, Header};
+use rtlsdr::tcp::server::{DropPolicy, Server, ServerOptions, SessionStats};
+use rtlsdr::tuning::tuner_gains;
+
+/// Bytes per block, not a multiple of 256 so that blocks of the test mode
+/// counter can be told apart.
+const BLOCK: usize = 1000;
+
+/// Serve one session of `source` on a free port, returning its address
+/// and a thread which ends with the session.
+fn serve(mut source: SimulatedSource, options: ServerOptions)
+         -> (SocketAddr, JoinHandle<(SessionStats, SimulatedSource)>) {
+    let server = Server::bind("127.0.0.1:0", options).unwrap();
+    let addr = server.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let stats = server.serve_client(&mut source).unwrap();
+        (stats, source)
+    });
+    (addr, handle)
+}
+
+/// Connect and read the header.
+fn connect(addr: SocketAddr) -> (TcpStream, Header) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut b = [0u8; 12];
+    stream.read_exact(&mut b).unwrap();
+    (stream, Header::from_bytes(&b).expect("bad header"))
+}
+
+/// A source streaming the test mode counter.
+fn counter() -> SimulatedSource {
+    let mut source = SimulatedSource::new();
+    source.set_test_mode(true).unwrap();
+    source
+}
+
+/// Options with small blocks and a short queue.
+fn options(drop_policy: DropPolicy) -> ServerOptions {
+    ServerOptions { block_size: BLOCK, max_queued: 4, drop_policy }
+}
+
+/// Stop reading for a while, so the server has to drop blocks, then read
+/// some more and check each block arrived whole.
+fn stall(drop_policy: DropPolicy) -> SessionStats {
+    let (addr, handle) = serve(counter(), options(drop_policy));
+    let (mut stream, _) = connect(addr);
+    thread::sleep(Duration::from_millis(300));
+    let mut buf = vec![0u8; 100 * BLOCK];
+    stream.read_exact(&mut buf).unwrap();
+    for block in buf.chunks(BLOCK) {
+        for w in block.windows(2) {
+            assert_eq!(w[1], w[0].wrapping_add(1), "partial block");
+        }
+    }
+    drop(stream);
+    handle.join().unwrap().0
+}
+
+#[test]
+fn sends_header() {
+    let (addr, handle) = serve(SimulatedSource::new(),
+                               ServerOptions::default());
+    let (stream, header) = connect(addr);
+    assert_eq!(header, Header { tuner_type: 5,
+                                gain_count: tuner_gains(5).len() as u32 });
+    drop(stream);
+    handle.join().unwrap();
+}
+
+#[test]
+fn applies_commands() {
+    let (addr, handle) = serve(SimulatedSource::new(),
+                               options(DropPolicy::Oldest));
+    let (mut stream, _) = connect(addr);
+    let commands = [
+        Command::SetFrequency(433_920_000),
+        Command::SetSampleRate(1_024_000),
+        Command::SetFreqCorrection(-7),
+        Command::SetGainMode(true),
+        Command::SetGain(496),
+        Command::SetGainByIndex(3),
+        Command::SetTestMode(true),
+        // Neither of these can be carried out on a Source.
+        Command::SetRtlXtal(28_800_000),
+        Command::Unknown(0x7f, 1)
+    ];
+    for c in commands.iter() {
+        stream.write_all(&c.to_bytes()).unwrap();
+    }
+    // The session ends once the server has read every command.
+    stream.shutdown(Shutdown::Write).unwrap();
+    let _ = stream.read_to_end(&mut Vec::new());
+
+    let (stats, mut source) = handle.join().unwrap();
+    assert_eq!(stats.commands, commands.len() as u64);
+    assert_eq!(stats.command_errors, 2);
+    assert_eq!(source.get_center_freq().unwrap(), 433_920_000);
+    assert_eq!(source.get_sample_rate().unwrap(), 1_024_000);
+    assert_eq!(source.get_freq_correction(), -7);
+    let buf = source.read_sync(4).unwrap();
+    assert!(buf.windows(2).all(|w| w[1] == w[0].wrapping_add(1)));
+}
+
+#[test]
+fn drops_oldest() {
+    let stats = stall(DropPolicy::Oldest);
+    assert!(stats.blocks_dropped > 0);
+    assert!(!stats.overflowed);
+    assert!(stats.bytes_sent >= 100 * BLOCK as u64);
+}
+
+#[test]
+fn drops_newest() {
+    let stats = stall(DropPolicy::Newest);
+    assert!(stats.blocks_dropped > 0);
+    assert!(!stats.overflowed);
+    assert!(stats.bytes_sent >= 100 * BLOCK as u64);
+}
+
+#[test]
+fn disconnects_slow_client() {
+    let (addr, handle) = serve(counter(), options(DropPolicy::Disconnect));
+    let (stream, _) = connect(addr);
+    // The server gives up on its own while the client isn't reading.
+    let (stats, _) = handle.join().unwrap();
+    assert!(stats.overflowed);
+    assert_eq!(stats.blocks_dropped, 1);
+    drop(stream);
+}